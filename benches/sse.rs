@@ -0,0 +1,76 @@
+//! Decoding a long streamed answer: `cargo bench --bench sse`.
+
+use bytes::Bytes;
+use copilot_client::{ChatStreamChunk, sse::SseDecoder};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+/// A streamed answer of `deltas` chunks, as the chat completions endpoint sends it.
+fn body(deltas: usize) -> Vec<u8> {
+    let mut body = String::new();
+    for i in 0..deltas {
+        body.push_str(&format!(
+            "data: {{\"id\":\"chatcmpl-1\",\"created\":1700000000,\"model\":\"gpt-4o\",\
+             \"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"token {i} \"}}}}]}}\n\n"
+        ));
+    }
+    body.push_str("data: [DONE]\n\n");
+    body.into_bytes()
+}
+
+/// Splits `body` into chunks of `size` bytes, which cut events at arbitrary positions.
+fn chunks(body: &[u8], size: usize) -> Vec<Bytes> {
+    body.chunks(size).map(Bytes::copy_from_slice).collect()
+}
+
+fn decode(c: &mut Criterion) {
+    let body = body(2000);
+    let mut group = c.benchmark_group("sse");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    for size in [64, 1024, 16 * 1024] {
+        let chunks = chunks(&body, size);
+        group.bench_with_input(
+            BenchmarkId::new("push_bytes", size),
+            &chunks,
+            |b, chunks| {
+                b.iter(|| {
+                    let mut decoder = SseDecoder::new();
+                    let mut events = Vec::new();
+                    for chunk in chunks {
+                        decoder.push_bytes(chunk.clone(), &mut events);
+                    }
+                    black_box(events)
+                });
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("push", size), &chunks, |b, chunks| {
+            b.iter(|| {
+                let mut decoder = SseDecoder::new();
+                let mut events = Vec::new();
+                for chunk in chunks {
+                    events.extend(decoder.push(chunk));
+                }
+                black_box(events)
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("parse", size), &chunks, |b, chunks| {
+            b.iter(|| {
+                let mut decoder = SseDecoder::new();
+                let mut events = Vec::new();
+                for chunk in chunks {
+                    decoder.push_bytes(chunk.clone(), &mut events);
+                }
+                let parsed: Vec<ChatStreamChunk> = events
+                    .iter()
+                    .filter(|data| data.as_ref() != b"[DONE]")
+                    .map(|data| serde_json::from_slice(data).unwrap())
+                    .collect();
+                black_box(parsed)
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+fn main() {
+    // Regenerate the C header for the `ffi` feature.
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+        let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+            .expect("cbindgen.toml is valid");
+        cbindgen::Builder::new()
+            .with_crate(&crate_dir)
+            .with_config(config)
+            .generate()
+            .expect("the ffi module can be translated to C")
+            .write_to_file(format!("{crate_dir}/include/copilot_client.h"));
+    }
+}
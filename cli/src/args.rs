@@ -0,0 +1,361 @@
+//! Command-line argument definitions.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+/// Default model used by `copilot chat`.
+pub const DEFAULT_MODEL: &str = "gpt-4o";
+
+/// Default editor version reported to the Copilot API.
+pub const DEFAULT_EDITOR_VERSION: &str = "Neovim/0.9.0";
+
+/// Exit codes, listed in `--help`.
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  other error
+  2  invalid arguments
+  3  authentication failed (missing, invalid, or unauthorized token)
+  4  rate limited
+  5  model not found";
+
+/// Command-line client for the GitHub Copilot API.
+#[derive(Debug, Parser)]
+#[command(
+    name = "copilot",
+    version,
+    about,
+    after_help = EXIT_CODES,
+    arg_required_else_help = true
+)]
+pub struct Cli {
+    /// Editor version reported to the Copilot API. Defaults to the `editor_version` config value,
+    /// then to `Neovim/0.9.0`.
+    #[arg(long, global = true)]
+    pub editor_version: Option<String>,
+
+    /// Auth profile to use instead of the active one.
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Output format. Ignored by `completions` and `tui`.
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Do not print progress and status messages to stderr. Errors are still reported.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Serve JSON-RPC requests on stdin and stdout instead of running a command (methods:
+    /// chat, chatStream, models, embeddings, cancel).
+    #[arg(long)]
+    pub stdio: bool,
+
+    /// Serve msgpack-RPC requests from Neovim on stdin and stdout instead of running a command.
+    #[cfg(feature = "nvim")]
+    #[arg(long, conflicts_with = "stdio")]
+    pub nvim: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// How command output is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable text; chat replies are rendered when stdout is a terminal.
+    Text,
+    /// Machine-readable JSON, including token usage for chat replies.
+    Json,
+    /// Unrendered Markdown, with tables for listings.
+    #[value(alias = "md")]
+    Markdown,
+    /// A standalone HTML page. Only `export` supports it; other commands print text.
+    Html,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Send a prompt to a chat model and print the reply.
+    Chat(ChatArgs),
+    /// List the models available to your account.
+    Models(ModelsArgs),
+    /// List the Copilot agents available to your account.
+    Agents,
+    /// Print embeddings for one or more input strings.
+    Embed(EmbedArgs),
+    /// Generate a commit message for the staged changes.
+    Commit(CommitArgs),
+    /// Review a diff and print comments.
+    Review(ReviewArgs),
+    /// Show or change default settings and auth profiles.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Print a shell completion script.
+    Completions(CompletionsArgs),
+    /// Open a full-screen chat client.
+    Tui(TuiArgs),
+    /// Build a semantic search index of a directory.
+    Index(IndexArgs),
+    /// Search the index for chunks related to a query.
+    Search(SearchArgs),
+    /// List, resume, and delete saved chat sessions.
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+    /// Write a saved session as a Markdown (default), JSON, or HTML transcript.
+    Export(ExportArgs),
+    /// Print a Copilot API bearer token for use by other tools.
+    Token(TokenArgs),
+    /// Sign in to GitHub and inspect credentials.
+    #[command(subcommand)]
+    Auth(AuthCommand),
+    /// Show premium request quota and the token usage and costs recorded by this CLI.
+    Usage,
+    /// Check the GitHub token, the Copilot token exchange, and the connection to the API.
+    Doctor,
+    /// Set by `--stdio`.
+    #[command(skip)]
+    Stdio,
+    /// Set by `--nvim`.
+    #[cfg(feature = "nvim")]
+    #[command(skip)]
+    Nvim,
+}
+
+#[derive(Debug, Args)]
+pub struct ChatArgs {
+    /// The prompt to send. Multiple words are joined with spaces. Input piped to stdin is
+    /// attached below the prompt as a fenced block.
+    pub prompt: Vec<String>,
+
+    /// Attach a file or an http(s) URL as fenced, language-tagged context, or a PNG, JPEG, GIF,
+    /// or WebP image for models with vision. Can be repeated; large files are truncated so that
+    /// all attachments share the model's prompt budget.
+    #[arg(short, long = "file", value_name = "PATH|URL")]
+    pub files: Vec<String>,
+
+    /// Start an interactive session. A prompt given on the command line is sent first.
+    #[arg(short, long)]
+    pub interactive: bool,
+
+    /// Model to use. Defaults to the `model` config value, then to `gpt-4o`.
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Sampling temperature. Defaults to the `temperature` config value.
+    #[arg(short, long)]
+    pub temperature: Option<f64>,
+
+    /// Stop generating at this sequence, e.g. "```" to end the reply after one code block. Can
+    /// be repeated.
+    #[arg(long, value_name = "SEQUENCE")]
+    pub stop: Vec<String>,
+
+    /// Seed for best-effort reproducible sampling. With `--format json`, compare the
+    /// `system_fingerprint` of the responses to tell backend changes apart.
+    #[arg(long)]
+    pub seed: Option<i64>,
+
+    /// System prompt sent before the user message.
+    #[arg(short, long)]
+    pub system: Option<String>,
+
+    /// Natural language to answer in, e.g. "Japanese". Defaults to the `language` config value.
+    #[arg(long)]
+    pub language: Option<String>,
+
+    /// Print only the reply text, without rendering. Overrides `--format`.
+    #[arg(long, conflicts_with = "interactive")]
+    pub raw: bool,
+
+    /// Print the request JSON and headers (token redacted) instead of sending it. Uses the model
+    /// list cached by the last command that talked to the API.
+    #[arg(long, conflicts_with = "interactive")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ModelsArgs {
+    /// Choose a chat model interactively and save it as the default model.
+    #[arg(long)]
+    pub pick: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct IndexArgs {
+    /// Directory to index.
+    #[arg(default_value = ".")]
+    pub dir: String,
+
+    /// Where to write the index. Defaults to `.copilot-index.json` in the directory.
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// The search query. Multiple words are joined with spaces.
+    #[arg(required = true)]
+    pub query: Vec<String>,
+
+    /// Index file to search. Defaults to the nearest `.copilot-index.json` in the current
+    /// directory or its parents.
+    #[arg(short, long)]
+    pub index: Option<String>,
+
+    /// Maximum number of results.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub limit: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct TuiArgs {
+    /// Model to use. Defaults to the `model` config value, then to `gpt-4o`.
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Session to resume, by id or unique id prefix. Starts a new session otherwise.
+    #[arg(long)]
+    pub session: Option<String>,
+
+    /// System prompt for a new session.
+    #[arg(short, long)]
+    pub system: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct EmbedArgs {
+    /// Input strings to embed.
+    #[arg(required = true)]
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CommitArgs {
+    /// Model to use. Defaults to the `model` config value, then to `gpt-4o`.
+    #[arg(short, long)]
+    pub model: Option<String>,
+
+    /// Run `git commit` with the generated message instead of printing it.
+    #[arg(short, long)]
+    pub commit: bool,
+
+    /// Like `--commit`, but open the editor to adjust the message first.
+    #[arg(short, long)]
+    pub edit: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ReviewArgs {
+    /// Revision range to review (e.g. `main..HEAD`). Defaults to uncommitted changes.
+    pub range: Option<String>,
+
+    /// Model to use. Defaults to the `model` config value, then to `gpt-4o`.
+    #[arg(short, long)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the script for. Model ids are completed from the list cached by the
+    /// last command that contacted the API; regenerate the script to pick up new models.
+    pub shell: Shell,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SessionsCommand {
+    /// List saved sessions, most recently updated first.
+    List,
+    /// Continue a saved session in interactive mode, saving after every reply.
+    Resume {
+        /// Session id or unique id prefix. Defaults to the most recently updated session.
+        id: Option<String>,
+    },
+    /// Delete a saved session.
+    Delete {
+        /// Session id or unique id prefix.
+        id: String,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Session id or unique id prefix.
+    pub id: String,
+
+    /// File to write to instead of stdout.
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct TokenArgs {
+    /// Print the underlying GitHub OAuth token instead.
+    #[arg(long)]
+    pub github: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuthCommand {
+    /// Sign in with the GitHub device flow and store the token in the selected profile (or
+    /// the active one, or `default`).
+    Login,
+    /// Show the active account and check that a Copilot token can be obtained.
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the value of a setting (`model`, `temperature`, `editor_version`, or `language`).
+    Get {
+        /// Setting to print.
+        key: String,
+    },
+    /// Change a setting.
+    Set {
+        /// Setting to change.
+        key: String,
+        /// New value.
+        value: String,
+    },
+    /// Reset a setting to its built-in default.
+    Unset {
+        /// Setting to reset.
+        key: String,
+    },
+    /// Print all settings and profiles.
+    List,
+    /// Print the location of the config file.
+    Path,
+    /// Manage named auth profiles.
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileCommand {
+    /// List profiles, marking the active one.
+    List,
+    /// Add or update a profile.
+    Add {
+        /// Profile name.
+        name: String,
+        /// GitHub token for the profile. Without it, the token is discovered from the
+        /// environment or editor configuration when the profile is used.
+        #[arg(long, conflicts_with = "from_env")]
+        token: Option<String>,
+        /// Store the token currently discovered from the environment or editor configuration.
+        #[arg(long)]
+        from_env: bool,
+    },
+    /// Make a profile the active one.
+    Use {
+        /// Profile name.
+        name: String,
+    },
+    /// Delete a profile.
+    Remove {
+        /// Profile name.
+        name: String,
+    },
+}
@@ -0,0 +1,161 @@
+//! `copilot auth` and `copilot token`: device-flow sign-in, credential status, and tokens.
+
+use crate::{
+    args::{AuthCommand, Format, TokenArgs},
+    output,
+};
+use copilot_client::{
+    auth::{self, DevicePoll, GITHUB_HOST},
+    config::{Config, Profile},
+};
+use serde_json::json;
+use std::{
+    error::Error,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Profile that receives the credentials when none is selected or active.
+const DEFAULT_PROFILE: &str = "default";
+
+/// Runs a `copilot auth` subcommand. `profile` is the profile selected with `--profile`.
+pub async fn run(
+    command: AuthCommand,
+    profile: Option<String>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        AuthCommand::Login => login(profile, format).await,
+        AuthCommand::Status => status(profile, format).await,
+    }
+}
+
+/// Runs `copilot token`: prints a fresh Copilot token (or the GitHub token) to stdout. In text
+/// format the expiry goes to stderr so that the output can be captured directly.
+pub async fn token(
+    args: TokenArgs,
+    profile: Option<String>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let github_token = Config::load()?.github_token(profile.as_deref())?;
+    if args.github {
+        if format == Format::Json {
+            return output::json(&json!({ "token": github_token }));
+        }
+        println!("{github_token}");
+        return Ok(());
+    }
+    let token = auth::exchange_token(&github_token).await?;
+    if format == Format::Json {
+        return output::json(&token);
+    }
+    println!("{}", token.token);
+    output::notice(format_args!(
+        "expires at {} (in {} min)",
+        token.expires_at,
+        minutes_until(token.expires_at)
+    ));
+    Ok(())
+}
+
+async fn login(profile: Option<String>, format: Format) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::load()?;
+    let name = profile
+        .or_else(|| config.active_profile.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+    let code = auth::request_device_code().await?;
+    // Instructions go to stderr so they do not mix with `--format json` output.
+    eprintln!(
+        "Open {} and enter the code {}",
+        code.verification_uri, code.user_code
+    );
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = code.interval.max(1);
+    let token = loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        match auth::poll_device_token(&code.device_code).await? {
+            DevicePoll::Pending => {}
+            DevicePoll::SlowDown(extra) => interval += extra,
+            DevicePoll::Complete(token) => break token,
+        }
+        if Instant::now() >= deadline {
+            return Err("the device code expired; run `copilot auth login` again".into());
+        }
+    };
+
+    let login = auth::github_user(&token).await.ok().map(|user| user.login);
+    config.profiles.insert(
+        name.clone(),
+        Profile {
+            github_token: Some(token),
+            login: login.clone(),
+        },
+    );
+    if config.active_profile.is_none() {
+        config.active_profile = Some(name.clone());
+    }
+    config.save()?;
+
+    if format == Format::Json {
+        return output::json(&json!({ "profile": name, "login": login, "host": GITHUB_HOST }));
+    }
+    match login {
+        Some(login) => println!("Logged in to {GITHUB_HOST} as {login} (profile {name})"),
+        None => println!("Logged in to {GITHUB_HOST} (profile {name})"),
+    }
+    Ok(())
+}
+
+async fn status(profile: Option<String>, format: Format) -> Result<(), Box<dyn Error>> {
+    let config = Config::load()?;
+    let name = profile.or_else(|| config.active_profile.clone());
+    let stored = config
+        .profile(name.as_deref())?
+        .is_some_and(|p| p.github_token.is_some());
+    let source = if stored { "profile" } else { "environment" };
+    let github_token = config.github_token(name.as_deref())?;
+    let login = auth::github_user(&github_token)
+        .await
+        .map(|user| user.login)
+        .map_err(|e| e.to_string());
+    let exchange = auth::exchange_token(&github_token).await;
+
+    if format == Format::Json {
+        output::json(&json!({
+            "host": GITHUB_HOST,
+            "profile": name,
+            "token_source": source,
+            "login": login.as_ref().ok(),
+            "copilot_token": {
+                "ok": exchange.is_ok(),
+                "expires_at": exchange.as_ref().ok().map(|t| t.expires_at),
+                "error": exchange.as_ref().err().map(ToString::to_string),
+            },
+        }))?;
+    } else {
+        println!("Host:          {GITHUB_HOST}");
+        println!("Profile:       {}", name.as_deref().unwrap_or("(none)"));
+        println!("Token source:  {source}");
+        match &login {
+            Ok(login) => println!("Account:       {login}"),
+            Err(e) => println!("Account:       unknown ({e})"),
+        }
+        match &exchange {
+            Ok(token) => println!(
+                "Copilot token: ok (expires in {} min)",
+                minutes_until(token.expires_at)
+            ),
+            Err(e) => println!("Copilot token: failed ({e})"),
+        }
+    }
+    // Fail with the exchange error so that the exit code reflects its cause.
+    exchange.map(|_| ()).map_err(Into::into)
+}
+
+/// Returns the whole minutes from now until the Unix timestamp `expires_at`.
+fn minutes_until(expires_at: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    expires_at.saturating_sub(now) / 60
+}
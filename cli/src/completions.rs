@@ -0,0 +1,67 @@
+//! `copilot completions`: shell completion scripts, with model ids taken from a local cache.
+
+use crate::args::{Cli, CompletionsArgs};
+use clap::{CommandFactory, builder::PossibleValuesParser};
+use copilot_client::{Model, get_config_path};
+use std::{error::Error, fs, io, path::PathBuf};
+
+/// Location of the model list cached by the last command that talked to the API.
+fn cache_path() -> Option<PathBuf> {
+    let config_dir = get_config_path().ok()?;
+    Some(
+        PathBuf::from(config_dir)
+            .join("copilot-client")
+            .join("models.json"),
+    )
+}
+
+/// Caches `models` for completion. Failures are ignored since the cache is only a convenience.
+pub fn cache_models(models: &[Model]) {
+    let Some(path) = cache_path() else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(models) else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, json);
+}
+
+/// Returns the cached models, or an empty list if nothing has been cached yet.
+pub fn cached_models() -> Vec<Model> {
+    cache_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the ids of the cached models, or an empty list if nothing has been cached yet.
+fn cached_model_ids() -> Vec<String> {
+    let mut ids: Vec<String> = cached_models().into_iter().map(|m| m.id).collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Prints the completion script for the requested shell.
+pub fn run(args: CompletionsArgs) -> Result<(), Box<dyn Error>> {
+    let mut command = Cli::command();
+    let ids = cached_model_ids();
+    if !ids.is_empty() {
+        // Offer the cached ids for every `--model` option. This only affects the generated
+        // script; parsing still accepts any model id.
+        command = command.mut_subcommands(|sub| {
+            if sub.get_arguments().any(|arg| arg.get_id() == "model") {
+                sub.mut_arg("model", |arg| {
+                    arg.value_parser(PossibleValuesParser::new(ids.clone()))
+                })
+            } else {
+                sub
+            }
+        });
+    }
+    clap_complete::generate(args.shell, &mut command, "copilot", &mut io::stdout());
+    Ok(())
+}
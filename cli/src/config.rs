@@ -0,0 +1,144 @@
+//! `copilot config`: default settings and auth profiles.
+
+use crate::{
+    args::{ConfigCommand, Format, ProfileCommand},
+    output,
+};
+use copilot_client::{
+    config::{Config, KEYS, Profile},
+    get_github_token,
+};
+use std::error::Error;
+
+/// Runs a `copilot config` subcommand.
+pub fn run(command: ConfigCommand, format: Format) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::load()?;
+    match command {
+        ConfigCommand::Get { key } => {
+            let value = config.get(&key)?;
+            if format == Format::Json {
+                return output::json(&value);
+            }
+            if let Some(value) = value {
+                println!("{value}");
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            config.set(&key, &value)?;
+            config.save()?;
+        }
+        ConfigCommand::Unset { key } => {
+            config.unset(&key)?;
+            config.save()?;
+        }
+        ConfigCommand::List => {
+            if format == Format::Json {
+                // Profiles are left out since they may contain tokens.
+                return output::json(&serde_json::json!({
+                    "defaults": config.defaults,
+                    "active_profile": config.active_profile,
+                }));
+            }
+            let mut rows = Vec::new();
+            for key in KEYS {
+                if let Some(value) = config.get(key)? {
+                    rows.push(vec![key.to_string(), value]);
+                }
+            }
+            if let Some(profile) = &config.active_profile {
+                rows.push(vec!["profile".to_string(), profile.clone()]);
+            }
+            if format == Format::Markdown {
+                output::table(format, &["Key", "Value"], &rows);
+            } else {
+                for row in rows {
+                    println!("{} = {}", row[0], row[1]);
+                }
+            }
+        }
+        ConfigCommand::Path => {
+            let path = Config::default_path()?;
+            if format == Format::Json {
+                return output::json(&path);
+            }
+            println!("{}", path.display());
+        }
+        ConfigCommand::Profile(command) => profile(&mut config, command, format)?,
+    }
+    Ok(())
+}
+
+fn profile(
+    config: &mut Config,
+    command: ProfileCommand,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        ProfileCommand::List => return list_profiles(config, format),
+        ProfileCommand::Add {
+            name,
+            token,
+            from_env,
+        } => {
+            let github_token = if from_env {
+                Some(get_github_token()?)
+            } else {
+                token
+            };
+            let profile = Profile {
+                github_token,
+                ..Profile::default()
+            };
+            config.profiles.insert(name.clone(), profile);
+            // The first profile becomes the active one.
+            if config.active_profile.is_none() {
+                config.active_profile = Some(name);
+            }
+        }
+        ProfileCommand::Use { name } => config.use_profile(&name)?,
+        ProfileCommand::Remove { name } => {
+            if !config.remove_profile(&name) {
+                return Err(format!("unknown profile '{name}'").into());
+            }
+        }
+    }
+    config.save()?;
+    Ok(())
+}
+
+fn list_profiles(config: &Config, format: Format) -> Result<(), Box<dyn Error>> {
+    let profiles = config.profiles.iter().map(|(name, profile)| {
+        let active = config.active_profile.as_deref() == Some(name);
+        (name, active, profile.github_token.is_some())
+    });
+    match format {
+        Format::Json => {
+            let profiles: Vec<_> = profiles
+                .map(|(name, active, stored)| {
+                    serde_json::json!({ "name": name, "active": active, "stored_token": stored })
+                })
+                .collect();
+            output::json(&profiles)?;
+        }
+        Format::Markdown => {
+            let rows: Vec<Vec<String>> = profiles
+                .map(|(name, active, stored)| {
+                    vec![name.clone(), active.to_string(), stored.to_string()]
+                })
+                .collect();
+            output::table(format, &["Profile", "Active", "Stored token"], &rows);
+        }
+        Format::Text | Format::Html => {
+            for (name, active, stored) in profiles {
+                let marker = if active { '*' } else { ' ' };
+                let source = if stored {
+                    "stored token"
+                } else {
+                    "token from environment"
+                };
+                println!("{marker} {name}\t{source}");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,31 @@
+//! Context attached to prompts from outside the command line: piped stdin and the prompt
+//! budget that attachments share.
+
+use copilot_client::CopilotClient;
+pub use copilot_client::context::fenced;
+use std::io::{self, IsTerminal, Read};
+
+/// Prompt budget used when the model does not report its limits.
+const DEFAULT_PROMPT_BUDGET: u32 = 16_384;
+
+/// Reads all of stdin if it is piped or redirected. Returns `None` for an interactive terminal or
+/// empty input.
+pub fn read_piped_stdin() -> io::Result<Option<String>> {
+    let mut stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+    let mut content = String::new();
+    stdin.read_to_string(&mut content)?;
+    Ok((!content.trim().is_empty()).then_some(content))
+}
+
+/// Returns the number of prompt tokens `model` accepts.
+pub fn prompt_budget(client: &CopilotClient, model: &str) -> u32 {
+    client
+        .models()
+        .iter()
+        .find(|m| m.id == model)
+        .and_then(|m| m.prompt_token_limit().or_else(|| m.context_window()))
+        .unwrap_or(DEFAULT_PROMPT_BUDGET)
+}
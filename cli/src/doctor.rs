@@ -0,0 +1,69 @@
+//! `copilot doctor`: checks the credentials and the connection to the Copilot API.
+
+use crate::{args::Format, output};
+use copilot_client::{
+    CopilotClient,
+    config::Config,
+    health::{HealthCheck, HealthReport},
+};
+use serde_json::json;
+use std::error::Error;
+
+/// Runs `copilot doctor`, failing if any check fails.
+pub async fn run(
+    config: &Config,
+    profile: Option<&str>,
+    editor_version: String,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let github_token = config.github_token(profile);
+    let report = match &github_token {
+        Ok(token) => Some(
+            CopilotClient::with_models(token.clone(), editor_version, Vec::new())
+                .health_check()
+                .await,
+        ),
+        Err(_) => None,
+    };
+    let healthy = report.as_ref().is_some_and(HealthReport::is_healthy);
+
+    if format == Format::Json {
+        output::json(&json!({
+            "healthy": healthy,
+            "github_token": {
+                "ok": github_token.is_ok(),
+                "error": github_token.as_ref().err().map(ToString::to_string),
+            },
+            "auth": report.as_ref().map(|r| &r.auth),
+            "api": report.as_ref().and_then(|r| r.api.as_ref()),
+            "model_count": report.as_ref().and_then(|r| r.model_count),
+        }))?;
+    } else {
+        match &github_token {
+            Ok(_) => println!("GitHub token:  found"),
+            Err(e) => println!("GitHub token:  missing ({e})"),
+        }
+        let auth = report.as_ref().map(|r| &r.auth);
+        println!("Copilot token: {}", describe(auth, None));
+        let api = report.as_ref().and_then(|r| r.api.as_ref());
+        let models = report.as_ref().and_then(|r| r.model_count);
+        println!("API:           {}", describe(api, models));
+    }
+    if healthy {
+        Ok(())
+    } else {
+        Err("health check failed".into())
+    }
+}
+
+/// Describes the outcome of a check, or that it was skipped because an earlier one failed.
+fn describe(check: Option<&HealthCheck>, models: Option<usize>) -> String {
+    match check {
+        None => "skipped".to_string(),
+        Some(check) => match (&check.error, models) {
+            (Some(e), _) => format!("failed after {} ms ({e})", check.latency_ms),
+            (None, Some(models)) => format!("ok, {models} models ({} ms)", check.latency_ms),
+            (None, None) => format!("ok ({} ms)", check.latency_ms),
+        },
+    }
+}
@@ -0,0 +1,52 @@
+//! Thin wrappers around the `git` executable.
+
+use std::{
+    error::Error,
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+};
+
+/// Runs `git` with `args` and returns its stdout.
+fn git(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args.join(" "), stderr.trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Creates a commit with `message`, optionally opening the editor to amend it first.
+pub fn commit(message: &str, edit: bool) -> Result<(), Box<dyn Error>> {
+    if edit {
+        // The editor needs the terminal on stdin, so pass the message through a file.
+        let git_dir = git(&["rev-parse", "--git-dir"])?;
+        let path = Path::new(git_dir.trim()).join("COPILOT_COMMIT_MSG");
+        fs::write(&path, message)?;
+        let status = Command::new("git")
+            .args(["commit", "--edit", "-F"])
+            .arg(&path)
+            .status();
+        let _ = fs::remove_file(&path);
+        return check_status(status?);
+    }
+    let mut child = Command::new("git")
+        .args(["commit", "-F", "-"])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("failed to open git stdin")?
+        .write_all(message.as_bytes())?;
+    check_status(child.wait()?)
+}
+
+fn check_status(status: ExitStatus) -> Result<(), Box<dyn Error>> {
+    if !status.success() {
+        return Err(format!("git commit exited with {status}").into());
+    }
+    Ok(())
+}
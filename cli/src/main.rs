@@ -0,0 +1,352 @@
+//! `copilot`: a command-line client for the GitHub Copilot API built on `copilot-client`.
+
+mod args;
+mod auth;
+mod completions;
+mod config;
+mod context;
+mod doctor;
+mod git;
+mod models;
+#[cfg(feature = "nvim")]
+mod nvim;
+mod output;
+mod printer;
+mod repl;
+mod review;
+mod rpc;
+mod search;
+mod sessions;
+mod tui;
+mod usage;
+
+use args::{
+    ChatArgs, Cli, Command, DEFAULT_EDITOR_VERSION, DEFAULT_MODEL, EmbedArgs, Format,
+    SessionsCommand, TuiArgs,
+};
+use clap::{CommandFactory, Parser, error::ErrorKind};
+use copilot_client::{
+    ChatOptions, CopilotClient, CopilotError, Message,
+    attachment::{self, Attachment, ResolvedAttachment},
+    config::Config,
+    conversation::Conversation,
+    prompt::estimate_message_tokens,
+    session::SessionStore,
+};
+use printer::StreamPrinter;
+use std::{error::Error, process::ExitCode};
+
+/// Exit code for authentication failures.
+const EXIT_AUTH: u8 = 3;
+
+/// Exit code for rate-limited requests.
+const EXIT_RATE_LIMITED: u8 = 4;
+
+/// Exit code for requests naming a model that does not exist.
+const EXIT_MODEL_NOT_FOUND: u8 = 5;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+    // `--stdio` and `--nvim` stand in for a subcommand.
+    let mode = cli.stdio.then_some(Command::Stdio);
+    #[cfg(feature = "nvim")]
+    let mode = if cli.nvim { Some(Command::Nvim) } else { mode };
+    cli.command = match (cli.command.take(), mode) {
+        (None, Some(mode)) => Some(mode),
+        (Some(_), Some(_)) => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "server modes cannot be used with a command",
+            )
+            .exit(),
+        (None, None) => Cli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "a command or --stdio is required",
+            )
+            .exit(),
+        (command, None) => command,
+    };
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            exit_code(e.as_ref())
+        }
+    }
+}
+
+/// Maps an error to the exit codes documented in `--help`.
+fn exit_code(error: &(dyn Error + 'static)) -> ExitCode {
+    let Some(error) = error.downcast_ref::<CopilotError>() else {
+        return ExitCode::FAILURE;
+    };
+    if error.is_auth_error() {
+        ExitCode::from(EXIT_AUTH)
+    } else if error.is_rate_limited() {
+        ExitCode::from(EXIT_RATE_LIMITED)
+    } else if error.is_model_not_found() {
+        ExitCode::from(EXIT_MODEL_NOT_FOUND)
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let format = cli.format;
+    output::set_quiet(cli.quiet);
+    // These commands must work before any credentials are set up.
+    let command = match cli.command.expect("set in main") {
+        Command::Config(command) => return config::run(command, format),
+        Command::Completions(args) => return completions::run(args),
+        Command::Auth(command) => return auth::run(command, cli.profile, format).await,
+        Command::Token(args) => return auth::token(args, cli.profile, format).await,
+        Command::Usage => return usage::run(cli.profile, format).await,
+        Command::Export(args) => return sessions::export(args, format),
+        Command::Sessions(command) if !matches!(command, SessionsCommand::Resume { .. }) => {
+            return sessions::run(command, format);
+        }
+        command => command,
+    };
+    let config = Config::load()?;
+    let editor_version = cli
+        .editor_version
+        .or_else(|| config.defaults.editor_version.clone())
+        .unwrap_or_else(|| DEFAULT_EDITOR_VERSION.to_string());
+    if matches!(command, Command::Doctor) {
+        return doctor::run(&config, cli.profile.as_deref(), editor_version, format).await;
+    }
+    let tracker = usage::load_tracker();
+    let recorded = tracker.totals().requests;
+    let client = if matches!(&command, Command::Chat(args) if args.dry_run) {
+        // Nothing is sent, so neither a token nor a model list from the API is needed.
+        let models = completions::cached_models();
+        if models.is_empty() {
+            return Err("--dry-run needs the cached model list; run `copilot models` first".into());
+        }
+        CopilotClient::with_models(String::new(), editor_version, models)
+    } else {
+        let github_token = config.github_token(cli.profile.as_deref())?;
+        let client = CopilotClient::new_with_models(github_token, editor_version)
+            .await?
+            .with_usage_tracker(tracker.clone());
+        tracker.set_model_multipliers(client.models());
+        completions::cache_models(client.models());
+        client
+    };
+    let client = match &config.defaults.language {
+        Some(language) => client.with_response_language(language),
+        None => client,
+    };
+    let model = |model: Option<String>| {
+        model
+            .or_else(|| config.defaults.model.clone())
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string())
+    };
+    let result = match command {
+        Command::Chat(mut args) => {
+            args.temperature = args.temperature.or(config.defaults.temperature);
+            let model = model(args.model.take());
+            chat(&client, &model, args, format).await
+        }
+        Command::Tui(mut args) => {
+            let model = model(args.model.take());
+            tui(&client, &model, args, config.defaults.temperature).await
+        }
+        Command::Index(args) => search::index(&client, args, format).await,
+        Command::Search(args) => search::search(&client, args, format).await,
+        Command::Sessions(SessionsCommand::Resume { id }) => {
+            sessions::resume(&client, id, format).await
+        }
+        Command::Models(args) => models::run(&client, args, format),
+        Command::Stdio => rpc::run(&client, model(None)).await,
+        #[cfg(feature = "nvim")]
+        Command::Nvim => nvim::run(&client, model(None)).await,
+        Command::Agents => agents(&client, format).await,
+        Command::Embed(args) => embed(&client, args, format).await,
+        Command::Commit(mut args) => {
+            let model = model(args.model.take());
+            review::commit(&client, &model, args, format).await
+        }
+        Command::Review(mut args) => {
+            let model = model(args.model.take());
+            review::review(&client, &model, args, format).await
+        }
+        Command::Config(_)
+        | Command::Completions(_)
+        | Command::Auth(_)
+        | Command::Token(_)
+        | Command::Usage
+        | Command::Doctor
+        | Command::Export(_)
+        | Command::Sessions(_) => {
+            unreachable!("handled above")
+        }
+    };
+    // Keep the totals shown by `copilot usage`, even if the command failed after some requests.
+    if tracker.totals().requests != recorded {
+        usage::save_tracker(&tracker);
+    }
+    result
+}
+
+async fn chat(
+    client: &CopilotClient,
+    model: &str,
+    args: ChatArgs,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let mut options = ChatOptions::default();
+    if let Some(temperature) = args.temperature {
+        options.temperature = temperature;
+    }
+    options.stop = args.stop;
+    options.seed = args.seed;
+    options.response_language = args.language;
+    let mut conversation = Conversation::new(client.clone(), model).with_options(options);
+    if let Some(system) = args.system {
+        conversation = conversation.with_system_prompt(system);
+    }
+    let mut attachments: Vec<Attachment> = args
+        .files
+        .iter()
+        .map(|arg| Attachment::from_arg(arg))
+        .collect();
+    let mut message = Message::user(args.prompt.join(" "));
+    if args.interactive {
+        if format == Format::Json {
+            return Err("--format json is not supported in interactive mode".into());
+        }
+        if !attachments.is_empty() {
+            if message.content.is_empty() {
+                return Err("--file needs a prompt to attach to in interactive mode".into());
+            }
+            let resolved = client.resolve_attachments(&attachments).await?;
+            attach_context(&conversation, &mut message, &resolved);
+        }
+        let initial = (!message.content.is_empty()).then_some(message);
+        return repl::run(conversation, initial, None).await;
+    }
+
+    if let Some(stdin) = context::read_piped_stdin()? {
+        attachments.push(Attachment::text(stdin));
+    }
+    if !attachments.is_empty() {
+        let resolved = client.resolve_attachments(&attachments).await?;
+        attach_context(&conversation, &mut message, &resolved);
+    }
+    if message.content.trim().is_empty() {
+        return Err("no prompt given; pass one as an argument or pipe input to stdin".into());
+    }
+
+    if args.dry_run {
+        let mut messages = conversation.request_messages();
+        messages.push(message);
+        // Match the request below: JSON output is requested in one piece, the rest streams.
+        let stream = args.raw || format != Format::Json;
+        let preview = client.preview_chat_request(
+            messages,
+            model.to_string(),
+            conversation.options(),
+            stream,
+        )?;
+        return output::json(&preview);
+    }
+    if format == Format::Json && !args.raw {
+        // Request the reply in one piece so the full response, including usage, can be printed.
+        let mut messages = conversation.request_messages();
+        messages.push(message);
+        let response = client
+            .chat_completion_with_options(messages, model.to_string(), conversation.options())
+            .await?;
+        return output::json(&response);
+    }
+
+    let mut printer = if args.raw || format == Format::Markdown {
+        StreamPrinter::plain()
+    } else {
+        StreamPrinter::new()
+    };
+    let result = conversation
+        .send_message_streaming(message, |delta| {
+            let _ = printer.write(delta);
+        })
+        .await;
+    printer.finish()?;
+    result?;
+    Ok(())
+}
+
+/// Attaches `attachments` to `message`, fitting them into the prompt budget of the
+/// conversation's model.
+fn attach_context(
+    conversation: &Conversation,
+    message: &mut Message,
+    attachments: &[ResolvedAttachment],
+) {
+    let budget = context::prompt_budget(conversation.client(), conversation.model());
+    let reserved: u32 = conversation
+        .request_messages()
+        .iter()
+        .map(estimate_message_tokens)
+        .sum();
+    attachment::attach(message, attachments, budget.saturating_sub(reserved));
+}
+
+async fn tui(
+    client: &CopilotClient,
+    model: &str,
+    args: TuiArgs,
+    temperature: Option<f64>,
+) -> Result<(), Box<dyn Error>> {
+    let conversation = match args.session {
+        Some(prefix) => {
+            let store = SessionStore::default_location()?;
+            store.load(&store.resolve_id(&prefix)?, client.clone())?
+        }
+        None => {
+            let mut options = ChatOptions::default();
+            if let Some(temperature) = temperature {
+                options.temperature = temperature;
+            }
+            let mut conversation = Conversation::new(client.clone(), model).with_options(options);
+            conversation.set_system_prompt(args.system);
+            conversation
+        }
+    };
+    tui::run(conversation).await
+}
+
+async fn agents(client: &CopilotClient, format: Format) -> Result<(), Box<dyn Error>> {
+    let agents = client.get_agents().await?;
+    if format == Format::Json {
+        return output::json(&agents);
+    }
+    let rows: Vec<Vec<String>> = agents
+        .into_iter()
+        .map(|agent| vec![agent.id, agent.name, agent.description.unwrap_or_default()])
+        .collect();
+    output::table(format, &["ID", "Name", "Description"], &rows);
+    Ok(())
+}
+
+async fn embed(
+    client: &CopilotClient,
+    args: EmbedArgs,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let embeddings = client.get_embeddings(args.input).await?;
+    if format == Format::Json {
+        return output::json(&embeddings);
+    }
+    let rows: Vec<Vec<String>> = embeddings
+        .iter()
+        .map(|e| {
+            let values: Vec<String> = e.embedding.iter().map(ToString::to_string).collect();
+            vec![e.index.to_string(), values.join(",")]
+        })
+        .collect();
+    output::table(format, &["Index", "Embedding"], &rows);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! `copilot models`: listing models and choosing the default one.
+
+use crate::{
+    args::{DEFAULT_MODEL, Format, ModelsArgs},
+    output,
+};
+use copilot_client::{CopilotClient, Model, config::Config};
+use rustyline::{DefaultEditor, error::ReadlineError};
+use std::error::Error;
+
+/// Runs `copilot models`.
+pub fn run(client: &CopilotClient, args: ModelsArgs, format: Format) -> Result<(), Box<dyn Error>> {
+    if args.pick {
+        return pick(client, format);
+    }
+    if format == Format::Json {
+        return output::json(&client.models());
+    }
+    let rows: Vec<Vec<String>> = client
+        .models()
+        .iter()
+        .map(|model| {
+            vec![
+                model.id.clone(),
+                model.name.clone(),
+                multiplier(model),
+                status(model),
+            ]
+        })
+        .collect();
+    output::table(format, &["ID", "Name", "Premium", "Status"], &rows);
+    Ok(())
+}
+
+/// Formats the premium requests a chat completion with the model consumes, e.g. `0.33x`.
+fn multiplier(model: &Model) -> String {
+    match model.premium_multiplier() {
+        Some(multiplier) => format!("{multiplier}x"),
+        None => "-".to_string(),
+    }
+}
+
+/// Describes whether the model can be used, e.g. `enabled, preview`.
+fn status(model: &Model) -> String {
+    let state = match &model.policy {
+        Some(policy) if !model.is_enabled() => format!("{:?}", policy.state).to_lowercase(),
+        _ => "enabled".to_string(),
+    };
+    if model.is_preview() {
+        format!("{state}, preview")
+    } else {
+        state
+    }
+}
+
+/// Lists the chat models and saves the one the user selects as the default model.
+fn pick(client: &CopilotClient, format: Format) -> Result<(), Box<dyn Error>> {
+    let models: Vec<&Model> = client
+        .models()
+        .iter()
+        .filter(|m| m.is_chat_model())
+        .collect();
+    if models.is_empty() {
+        return Err("no chat models are available".into());
+    }
+    let mut config = Config::load()?;
+    let current = config.defaults.model.as_deref().unwrap_or(DEFAULT_MODEL);
+
+    // The menu goes to stderr so that only the result is printed to stdout.
+    for (i, model) in models.iter().enumerate() {
+        let marker = if model.id == current { '*' } else { ' ' };
+        eprintln!(
+            "{marker}{:>3}. {:<28} {:<32} {:>6}  {}",
+            i + 1,
+            model.id,
+            model.name,
+            context_size(model),
+            model.supported_features().join(", "),
+        );
+    }
+    let mut editor = DefaultEditor::new()?;
+    let selected = loop {
+        let line = match editor.readline("Select a model (number or id, empty to cancel): ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let choice = line.trim();
+        if choice.is_empty() {
+            return Ok(());
+        }
+        let model = choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| models.get(i))
+            .or_else(|| models.iter().find(|m| m.id == choice));
+        match model {
+            Some(model) => break *model,
+            None => eprintln!("No model matches '{choice}'."),
+        }
+    };
+
+    config.set("model", &selected.id)?;
+    config.save()?;
+    if format == Format::Json {
+        return output::json(selected);
+    }
+    println!("Default model set to {}", selected.id);
+    Ok(())
+}
+
+/// Formats the model's context window (or prompt limit) compactly, e.g. `128k`.
+fn context_size(model: &Model) -> String {
+    match model
+        .context_window()
+        .or_else(|| model.prompt_token_limit())
+    {
+        Some(tokens) if tokens >= 1000 => format!("{}k", tokens / 1000),
+        Some(tokens) => tokens.to_string(),
+        None => "-".to_string(),
+    }
+}
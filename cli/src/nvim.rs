@@ -0,0 +1,248 @@
+//! `copilot --nvim`: a msgpack-RPC backend for Neovim plugins.
+//!
+//! Neovim starts the server with `jobstart({ "copilot", "--nvim" }, { rpc = true })` and calls
+//! the methods of [`crate::rpc`] with a single table argument:
+//!
+//! - `vim.rpcrequest(chan, "chat", { messages = ... })` blocks until the reply is complete.
+//! - `vim.rpcnotify(chan, "chatStream", { id = 1, messages = ... })` returns at once. The server
+//!   then fires `User CopilotClientDelta` autocommands with `data = { id, content }` for each
+//!   piece of content and a final `User CopilotClientDone` with `data = { id, result }` or
+//!   `data = { id, error }`. Any method can be called this way.
+//! - `vim.rpcnotify(chan, "cancel", { id = 1 })` cancels a notification-started call, which then
+//!   finishes with an error.
+
+use crate::rpc::{self, RpcError};
+use copilot_client::CopilotClient;
+use rmpv::Value as Msgpack;
+use serde_json::{Map, Number, Value, json};
+use std::{
+    collections::HashMap,
+    error::Error,
+    io,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::mpsc::{self, UnboundedSender},
+    task::AbortHandle,
+};
+
+const REQUEST: u64 = 0;
+const RESPONSE: u64 = 1;
+const NOTIFICATION: u64 = 2;
+
+/// Fires a `User` autocommand in Neovim; called with the pattern and the event data.
+const FIRE_EVENT: &str = "local pattern, data = ...; \
+vim.api.nvim_exec_autocmds('User', { pattern = pattern, data = data })";
+
+/// Calls started by notifications, by the JSON encoding of their id.
+type Pending = Arc<Mutex<HashMap<String, AbortHandle>>>;
+
+/// Serves Neovim until it closes the channel. `model` is used when a call names none.
+pub async fn run(client: &CopilotClient, model: String) -> Result<(), Box<dyn Error>> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Msgpack>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let mut buf = Vec::new();
+            if rmpv::encode::write_value(&mut buf, &message).is_err()
+                || stdout.write_all(&buf).await.is_err()
+                || stdout.flush().await.is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    // The decoder reads synchronously, so it runs on a blocking thread.
+    let (input_tx, mut input) = mpsc::unbounded_channel::<Msgpack>();
+    tokio::task::spawn_blocking(move || {
+        let mut stdin = io::stdin().lock();
+        while let Ok(message) = rmpv::decode::read_value(&mut stdin) {
+            if input_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let pending: Pending = Arc::default();
+    while let Some(message) = input.recv().await {
+        let Msgpack::Array(parts) = message else {
+            continue;
+        };
+        match parts.first().and_then(Msgpack::as_u64) {
+            Some(REQUEST) if parts.len() == 4 => {
+                let msgid = parts[1].clone();
+                let method = parts[2].as_str().unwrap_or_default().to_string();
+                let params = first_param(&parts[3]);
+                let (client, model, tx) = (client.clone(), model.clone(), tx.clone());
+                tokio::spawn(async move {
+                    let id = to_json(&msgid);
+                    let on_delta = |delta: &str| delta_event(&tx, &id, delta);
+                    let result = rpc::call(&client, &model, &method, params, &on_delta).await;
+                    let (error, result) = match result {
+                        Ok(result) => (Msgpack::Nil, from_json(&result)),
+                        Err(e) => (error_value(&e), Msgpack::Nil),
+                    };
+                    let response = vec![RESPONSE.into(), msgid, error, result];
+                    let _ = tx.send(Msgpack::Array(response));
+                });
+            }
+            Some(NOTIFICATION) if parts.len() == 3 => {
+                let method = parts[1].as_str().unwrap_or_default();
+                let params = first_param(&parts[2]);
+                let id = params.get("id").cloned().unwrap_or(Value::Null);
+                if method == "cancel" {
+                    cancel(&pending, &id, &tx);
+                } else {
+                    notification(client, &model, method, params, id, &pending, &tx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    drop(tx);
+    writer.await?;
+    Ok(())
+}
+
+/// Starts a call requested by a notification; its result is delivered as an event.
+fn notification(
+    client: &CopilotClient,
+    model: &str,
+    method: &str,
+    params: Value,
+    id: Value,
+    pending: &Pending,
+    tx: &UnboundedSender<Msgpack>,
+) {
+    let key = id.to_string();
+    let (client, model, method, tx) = (
+        client.clone(),
+        model.to_string(),
+        method.to_string(),
+        tx.clone(),
+    );
+    // Hold the lock until the task is registered so that it cannot finish before.
+    let mut calls = pending.lock().unwrap_or_else(|e| e.into_inner());
+    let task = {
+        let (pending, key) = (pending.clone(), key.clone());
+        tokio::spawn(async move {
+            let on_delta = |delta: &str| delta_event(&tx, &id, delta);
+            let result = rpc::call(&client, &model, &method, params, &on_delta).await;
+            pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&key);
+            let data = match result {
+                Ok(result) => json!({ "id": id, "result": result }),
+                Err(e) => json!({ "id": id, "error": e.message }),
+            };
+            event(&tx, "CopilotClientDone", &data);
+        })
+    };
+    calls.insert(key, task.abort_handle());
+}
+
+/// Aborts the call started with `id` and reports it as cancelled.
+fn cancel(pending: &Pending, id: &Value, tx: &UnboundedSender<Msgpack>) {
+    let removed = pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id.to_string());
+    if let Some(task) = removed {
+        task.abort();
+        event(
+            tx,
+            "CopilotClientDone",
+            &json!({ "id": id, "error": "request cancelled" }),
+        );
+    }
+}
+
+fn delta_event(tx: &UnboundedSender<Msgpack>, id: &Value, delta: &str) {
+    event(
+        tx,
+        "CopilotClientDelta",
+        &json!({ "id": id, "content": delta }),
+    );
+}
+
+/// Sends a notification that makes Neovim fire `User <pattern>` with `data`.
+fn event(tx: &UnboundedSender<Msgpack>, pattern: &str, data: &Value) {
+    let args = Msgpack::Array(vec![pattern.into(), from_json(data)]);
+    let params = Msgpack::Array(vec![FIRE_EVENT.into(), args]);
+    let _ = tx.send(Msgpack::Array(vec![
+        NOTIFICATION.into(),
+        "nvim_exec_lua".into(),
+        params,
+    ]));
+}
+
+/// Neovim reports errors as `[type, message]`; type 0 is an exception.
+fn error_value(error: &RpcError) -> Msgpack {
+    Msgpack::Array(vec![0.into(), error.message.as_str().into()])
+}
+
+/// Returns the table argument of a call, or `null` if there is none.
+fn first_param(params: &Msgpack) -> Value {
+    match params {
+        Msgpack::Array(args) => args.first().map_or(Value::Null, to_json),
+        other => to_json(other),
+    }
+}
+
+fn to_json(value: &Msgpack) -> Value {
+    match value {
+        Msgpack::Nil => Value::Null,
+        Msgpack::Boolean(b) => Value::Bool(*b),
+        Msgpack::Integer(i) => i
+            .as_i64()
+            .map(Value::from)
+            .or_else(|| i.as_u64().map(Value::from))
+            .unwrap_or(Value::Null),
+        Msgpack::F32(f) => Number::from_f64(f64::from(*f)).map_or(Value::Null, Value::Number),
+        Msgpack::F64(f) => Number::from_f64(*f).map_or(Value::Null, Value::Number),
+        Msgpack::String(s) => Value::String(s.as_str().unwrap_or_default().to_string()),
+        Msgpack::Binary(bytes) => Value::String(String::from_utf8_lossy(bytes).into_owned()),
+        Msgpack::Array(items) => Value::Array(items.iter().map(to_json).collect()),
+        Msgpack::Map(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Msgpack::String(s) => s.as_str().unwrap_or_default().to_string(),
+                        other => other.to_string(),
+                    };
+                    (key, to_json(v))
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Msgpack::Ext(_, _) => Value::Null,
+    }
+}
+
+fn from_json(value: &Value) -> Msgpack {
+    match value {
+        Value::Null => Msgpack::Nil,
+        Value::Bool(b) => Msgpack::Boolean(*b),
+        Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                i.into()
+            } else if let Some(u) = n.as_u64() {
+                u.into()
+            } else {
+                n.as_f64().unwrap_or_default().into()
+            }
+        }
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => Msgpack::Array(items.iter().map(from_json).collect()),
+        Value::Object(entries) => Msgpack::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.as_str().into(), from_json(v)))
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,50 @@
+//! Formatting of command output according to `--format`.
+
+use crate::args::Format;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppresses [`notice`] output for the rest of the process (`--quiet`).
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Prints a progress or status message to stderr unless `--quiet` is set.
+pub fn notice(message: fmt::Arguments<'_>) {
+    if !QUIET.load(Ordering::Relaxed) {
+        eprintln!("{message}");
+    }
+}
+
+/// Prints `value` as pretty-printed JSON.
+pub fn json(value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Prints rows as tab-separated text, or as a Markdown table with `headers` for
+/// [`Format::Markdown`]. Empty trailing cells are omitted from text output.
+pub fn table(format: Format, headers: &[&str], rows: &[Vec<String>]) {
+    if format != Format::Markdown {
+        for row in rows {
+            let end = row
+                .iter()
+                .rposition(|cell| !cell.is_empty())
+                .map_or(0, |i| i + 1);
+            println!("{}", row[..end].join("\t"));
+        }
+        return;
+    }
+    println!("| {} |", headers.join(" | "));
+    println!("|{}", " --- |".repeat(headers.len()));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        println!("| {} |", cells.join(" | "));
+    }
+}
@@ -0,0 +1,63 @@
+//! Incremental printing of streamed replies to stdout.
+
+use copilot_client::render::MarkdownRenderer;
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+};
+
+/// Writes streamed text to stdout, rendering Markdown when stdout is a terminal.
+pub struct StreamPrinter {
+    renderer: Option<MarkdownRenderer>,
+    ends_with_newline: bool,
+}
+
+impl StreamPrinter {
+    pub fn new() -> Self {
+        StreamPrinter {
+            renderer: use_color().then(MarkdownRenderer::new),
+            ends_with_newline: true,
+        }
+    }
+
+    /// Creates a printer that writes the text unrendered, even to a terminal.
+    pub fn plain() -> Self {
+        StreamPrinter {
+            renderer: None,
+            ends_with_newline: true,
+        }
+    }
+
+    /// Writes a delta and flushes stdout so it appears immediately.
+    pub fn write(&mut self, delta: &str) -> io::Result<()> {
+        if delta.is_empty() {
+            return Ok(());
+        }
+        self.ends_with_newline = delta.ends_with('\n');
+        let mut out = io::stdout().lock();
+        match &mut self.renderer {
+            Some(renderer) => out.write_all(renderer.push(delta).as_bytes())?,
+            None => out.write_all(delta.as_bytes())?,
+        }
+        out.flush()
+    }
+
+    /// Flushes any buffered partial line and terminates the output with a newline.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        if let Some(renderer) = &mut self.renderer {
+            out.write_all(renderer.finish().as_bytes())?;
+        }
+        if !self.ends_with_newline {
+            writeln!(out)?;
+            self.ends_with_newline = true;
+        }
+        out.flush()
+    }
+}
+
+/// Returns `true` if output should be rendered with ANSI styling: stdout is a terminal and the
+/// user has not opted out via `NO_COLOR`.
+fn use_color() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
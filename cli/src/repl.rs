@@ -0,0 +1,191 @@
+//! Interactive chat mode (`copilot chat -i`).
+
+use crate::printer::StreamPrinter;
+use copilot_client::{
+    CopilotError, Message, conversation::Conversation, get_config_path, session::SessionStore,
+};
+use rustyline::{DefaultEditor, error::ReadlineError};
+use std::{error::Error, path::PathBuf};
+
+const HELP: &str = "\
+Commands:
+  /model [id]      Show or switch the model
+  /system [text]   Show or replace the system prompt (`/system clear` removes it)
+  /save [path]     Save the conversation to a file, or to the session store without a path
+  /clear           Forget the conversation history
+  /help            Show this help
+  /exit            Leave the REPL (Ctrl-D also works)
+
+End a line with `\\` to continue on the next line, or enter a block between two `\"\"\"` lines.";
+
+/// Outcome of handling a slash command.
+enum Flow {
+    Continue,
+    Exit,
+}
+
+/// Runs the REPL until the user exits. `initial` is sent as the first prompt, if given. With a
+/// `store`, the conversation is saved to it after every completed turn.
+pub async fn run(
+    mut conversation: Conversation,
+    initial: Option<Message>,
+    store: Option<SessionStore>,
+) -> Result<(), Box<dyn Error>> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file is expected on first use.
+        let _ = editor.load_history(path);
+    }
+    println!(
+        "Chatting with {}. Type /help for commands.",
+        conversation.model()
+    );
+
+    if let Some(prompt) = initial {
+        send(&mut conversation, prompt, store.as_ref()).await;
+    }
+    while let Some(input) = read_input(&mut editor)? {
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(input)?;
+        if input.starts_with('/') {
+            match command(&mut conversation, input) {
+                Ok(Flow::Continue) => continue,
+                Ok(Flow::Exit) => break,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    continue;
+                }
+            }
+        }
+        send(&mut conversation, Message::user(input), store.as_ref()).await;
+    }
+
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+/// Sends a message and streams the reply, then saves the conversation to `store`, if given.
+/// Errors are reported without leaving the REPL.
+async fn send(conversation: &mut Conversation, message: Message, store: Option<&SessionStore>) {
+    let mut printer = StreamPrinter::new();
+    let result = conversation
+        .send_message_streaming(message, |delta| {
+            let _ = printer.write(delta);
+        })
+        .await;
+    let _ = printer.finish();
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+    } else if let Some(store) = store
+        && let Err(e) = store.save(conversation)
+    {
+        eprintln!("error: failed to save session: {e}");
+    }
+}
+
+/// Reads one prompt, joining continuation lines. Returns `None` on end of input.
+fn read_input(editor: &mut DefaultEditor) -> Result<Option<String>, ReadlineError> {
+    let first = match editor.readline(">> ") {
+        Ok(line) => line,
+        Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+        Err(ReadlineError::Eof) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    if first.trim() == "\"\"\"" {
+        let mut lines = Vec::new();
+        loop {
+            match editor.readline(".. ") {
+                Ok(line) if line.trim() == "\"\"\"" => break,
+                Ok(line) => lines.push(line),
+                Err(ReadlineError::Eof) => break,
+                Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+                Err(e) => return Err(e),
+            }
+        }
+        return Ok(Some(lines.join("\n")));
+    }
+
+    let mut text = first;
+    while text.ends_with('\\') {
+        text.pop();
+        text.push('\n');
+        match editor.readline(".. ") {
+            Ok(line) => text.push_str(&line),
+            Err(ReadlineError::Eof) => break,
+            Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(text))
+}
+
+/// Handles a slash command.
+fn command(conversation: &mut Conversation, input: &str) -> Result<Flow, CopilotError> {
+    let (name, arg) = match input.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+    match name {
+        "/model" if arg.is_empty() => println!("{}", conversation.model()),
+        "/model" => {
+            if !conversation.client().has_model(arg) {
+                return Err(CopilotError::InvalidModel(arg.to_string()));
+            }
+            conversation.set_model(arg);
+            println!("Switched to {arg}.");
+        }
+        "/system" if arg.is_empty() => match conversation.system_prompt() {
+            Some(system) => println!("{system}"),
+            None => println!("(no system prompt)"),
+        },
+        "/system" if arg == "clear" => {
+            conversation.set_system_prompt(None);
+            println!("System prompt removed.");
+        }
+        "/system" => {
+            conversation.set_system_prompt(Some(arg.to_string()));
+            println!("System prompt updated.");
+        }
+        "/save" if arg.is_empty() => {
+            let store = SessionStore::default_location()?;
+            let info = store.save(conversation)?;
+            println!("Saved session {}.", info.id);
+        }
+        "/save" => {
+            conversation.save(arg)?;
+            println!("Saved to {arg}.");
+        }
+        "/clear" => {
+            conversation.clear();
+            println!("History cleared.");
+        }
+        "/help" => println!("{HELP}"),
+        "/exit" | "/quit" => return Ok(Flow::Exit),
+        other => {
+            return Err(CopilotError::Other(format!(
+                "Unknown command {other}; type /help for a list"
+            )));
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+/// Location of the persistent REPL history.
+fn history_path() -> Option<PathBuf> {
+    let config_dir = get_config_path().ok()?;
+    Some(
+        PathBuf::from(config_dir)
+            .join("copilot-client")
+            .join("history.txt"),
+    )
+}
@@ -0,0 +1,147 @@
+//! Git-aware commands: `copilot commit` and `copilot review`.
+
+use crate::{
+    args::{CommitArgs, Format, ReviewArgs},
+    context, git, output,
+};
+use copilot_client::{
+    ChatOptions, CopilotClient, Message,
+    context::git::{GitRepo, diff_context},
+    postprocess::strip_code_fence,
+    prompt::estimate_tokens,
+};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+const COMMIT_PROMPT: &str = "You write git commit messages. Given a staged diff, reply with \
+a commit message only: a summary line of at most 72 characters in the imperative mood, then a \
+blank line and a short body explaining what changed and why, wrapped at 72 characters. Omit \
+the body for trivial changes. Do not wrap the message in code fences.";
+
+const REVIEW_PROMPT: &str = "You are an experienced code reviewer. Review the diff you are \
+given and point out bugs, risky changes, and clear improvements; skip praise and nitpicks \
+about formatting. Reply with only a JSON array of objects with the fields \"file\" (path), \
+\"line\" (line number in the new file, or null), \"severity\" (\"error\", \"warning\", or \
+\"info\"), and \"comment\". Reply with [] if there is nothing to report.";
+
+/// A single review comment returned by the model.
+#[derive(Debug, Serialize, Deserialize)]
+struct ReviewComment {
+    file: String,
+    #[serde(default)]
+    line: Option<u32>,
+    #[serde(default = "default_severity")]
+    severity: String,
+    comment: String,
+}
+
+fn default_severity() -> String {
+    "info".to_string()
+}
+
+/// JSON output of `copilot review`.
+#[derive(Debug, Serialize)]
+struct ReviewOutput {
+    comments: Vec<ReviewComment>,
+    /// The raw reply, if the model did not follow the requested format.
+    unparsed: Option<String>,
+}
+
+/// Generates a commit message for the staged changes and optionally commits them.
+pub async fn commit(
+    client: &CopilotClient,
+    model: &str,
+    args: CommitArgs,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let diff = GitRepo::new(".").staged_diff()?;
+    if diff.trim().is_empty() {
+        return Err("nothing staged; stage changes with `git add` first".into());
+    }
+    let reply = ask(client, model, COMMIT_PROMPT, &diff).await?;
+    let message = strip_code_fence(&reply).trim().to_string();
+    let committed = args.commit || args.edit;
+    if committed {
+        git::commit(&message, args.edit)?;
+    }
+    if format == Format::Json {
+        return output::json(&serde_json::json!({
+            "message": message,
+            "committed": committed,
+        }));
+    }
+    if !committed {
+        println!("{message}");
+    }
+    Ok(())
+}
+
+/// Reviews a diff and prints the model's comments.
+pub async fn review(
+    client: &CopilotClient,
+    model: &str,
+    args: ReviewArgs,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let diff = GitRepo::new(".").diff(args.range.as_deref())?;
+    if diff.trim().is_empty() {
+        return Err("the diff is empty; nothing to review".into());
+    }
+    let reply = ask(client, model, REVIEW_PROMPT, &diff).await?;
+    let (comments, unparsed) =
+        match serde_json::from_str::<Vec<ReviewComment>>(strip_code_fence(&reply).trim()) {
+            Ok(comments) => (comments, None),
+            // Fall back to the raw reply if the model did not follow the format.
+            Err(_) => (Vec::new(), Some(reply.trim().to_string())),
+        };
+    if format == Format::Json {
+        return output::json(&ReviewOutput { comments, unparsed });
+    }
+    if let Some(reply) = unparsed {
+        println!("{reply}");
+        return Ok(());
+    }
+    if comments.is_empty() {
+        println!("No issues found.");
+    }
+    for c in comments {
+        let location = match c.line {
+            Some(line) => format!("{}:{line}", c.file),
+            None => c.file,
+        };
+        match format {
+            Format::Markdown => println!("- `{location}` **{}**: {}", c.severity, c.comment),
+            _ => println!("{location}: [{}] {}", c.severity, c.comment),
+        }
+    }
+    Ok(())
+}
+
+/// Sends `diff` with a task-specific system prompt and returns the reply.
+async fn ask(
+    client: &CopilotClient,
+    model: &str,
+    system: &str,
+    diff: &str,
+) -> Result<String, Box<dyn Error>> {
+    let budget = context::prompt_budget(client, model)
+        .saturating_sub(estimate_tokens(system))
+        .saturating_sub(16);
+    let messages = vec![
+        Message::system(system),
+        Message::user(diff_context(diff, budget)),
+    ];
+    let options = ChatOptions {
+        temperature: 0.2,
+        ..ChatOptions::default()
+    };
+    let response = client
+        .chat_completion_with_options(messages, model.to_string(), &options)
+        .await?;
+    Ok(response
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content)
+        .unwrap_or_default())
+}
@@ -0,0 +1,264 @@
+//! `copilot --stdio`: JSON-RPC 2.0 over stdin and stdout.
+//!
+//! Every message is a single line of JSON. Requests are handled concurrently, so responses may
+//! arrive out of order; match them by `id`. Methods:
+//!
+//! - `models`: the available models.
+//! - `chat` (`messages`, optional `model` and `options`): the complete chat response. Messages
+//!   use the OpenAI chat format and options are the fields of `ChatOptions`.
+//! - `chatStream` (same parameters): sends `chatStream/delta` notifications with the request
+//!   `id` and the new `content`, then answers with the full `content`, `finish_reason`, and
+//!   `usage`.
+//! - `embeddings` (`input`, an array of strings): the embeddings.
+//! - `cancel` (`id`): cancels a pending request, which then fails with code -32800.
+//!
+//! The server exits once stdin is closed and the pending requests are answered.
+
+use copilot_client::{ChatOptions, CopilotClient, CopilotError, Message, openai};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::mpsc::{self, UnboundedSender},
+    task::AbortHandle,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Pending requests by the JSON encoding of their id.
+type Pending = Arc<Mutex<HashMap<String, (AbortHandle, Value)>>>;
+
+#[derive(Debug, Deserialize)]
+struct ChatParams {
+    messages: Value,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    options: ChatOptions,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingsParams {
+    input: Vec<String>,
+}
+
+/// A failed call, sent as a JSON-RPC error object.
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<CopilotError> for RpcError {
+    fn from(e: CopilotError) -> Self {
+        RpcError {
+            code: SERVER_ERROR,
+            data: e.status().map(|status| json!({ "status": status })),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Serves requests from stdin until it is closed. `model` is used when a request names none.
+pub async fn run(client: &CopilotClient, model: String) -> Result<(), Box<dyn Error>> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    // A single writer keeps concurrent responses from interleaving.
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let line = format!("{message}\n");
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let pending: Pending = Arc::default();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                let error = RpcError::new(PARSE_ERROR, e.to_string());
+                let _ = tx.send(response(Value::Null, Err(error)));
+                continue;
+            }
+        };
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            let error = RpcError::new(INVALID_REQUEST, "missing method");
+            let _ = tx.send(response(id.unwrap_or(Value::Null), Err(error)));
+            continue;
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        if method == "cancel" {
+            let cancelled = cancel(&pending, &params, &tx);
+            if let Some(id) = id {
+                let _ = tx.send(response(id, Ok(json!({ "cancelled": cancelled }))));
+            }
+            continue;
+        }
+        // Every other method returns a result, so notifications are ignored.
+        let Some(id) = id else {
+            continue;
+        };
+
+        let key = id.to_string();
+        let (client, model, method, tx) = (
+            client.clone(),
+            model.clone(),
+            method.to_string(),
+            tx.clone(),
+        );
+        // Hold the lock until the task is registered so that it cannot finish before.
+        let mut requests = pending.lock().unwrap_or_else(|e| e.into_inner());
+        let task = {
+            let (pending, id, key) = (pending.clone(), id.clone(), key.clone());
+            tokio::spawn(async move {
+                let on_delta = |delta: &str| {
+                    let _ = tx.send(json!({
+                        "jsonrpc": "2.0",
+                        "method": "chatStream/delta",
+                        "params": { "id": id, "content": delta },
+                    }));
+                };
+                let result = call(&client, &model, &method, params, &on_delta).await;
+                pending
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&key);
+                let _ = tx.send(response(id, result));
+            })
+        };
+        requests.insert(key, (task.abort_handle(), id));
+    }
+
+    // The writer stops once every request task has dropped its sender.
+    drop(tx);
+    writer.await?;
+    Ok(())
+}
+
+/// Aborts the request named by `params.id` and answers it with a cancellation error. Returns
+/// whether the request was still pending.
+fn cancel(pending: &Pending, params: &Value, tx: &UnboundedSender<Value>) -> bool {
+    let Some(target) = params.get("id") else {
+        return false;
+    };
+    let removed = pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&target.to_string());
+    let Some((task, id)) = removed else {
+        return false;
+    };
+    task.abort();
+    let error = RpcError::new(REQUEST_CANCELLED, "request cancelled");
+    let _ = tx.send(response(id, Err(error)));
+    true
+}
+
+/// Runs `method` with `params`. `chatStream` reports each piece of content to `on_delta`.
+pub async fn call(
+    client: &CopilotClient,
+    model: &str,
+    method: &str,
+    params: Value,
+    on_delta: &(dyn Fn(&str) + Send + Sync),
+) -> Result<Value, RpcError> {
+    match method {
+        "models" => Ok(json!(client.models())),
+        "chat" => {
+            let (messages, model, options) = chat_params(params, model)?;
+            let response = client
+                .chat_completion_with_options(messages, model, &options)
+                .await?;
+            Ok(json!(response))
+        }
+        "chatStream" => {
+            let (messages, model, options) = chat_params(params, model)?;
+            let mut stream = client
+                .chat_completion_stream(messages, model, &options)
+                .await?;
+            let (mut content, mut finish_reason, mut usage) = (String::new(), None, None);
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                if let Some(delta) = chunk.content() {
+                    content.push_str(delta);
+                    on_delta(delta);
+                }
+                if let Some(reason) = chunk.choices.iter().find_map(|c| c.finish_reason.clone()) {
+                    finish_reason = Some(reason);
+                }
+                if chunk.usage.is_some() {
+                    usage = chunk.usage;
+                }
+            }
+            Ok(json!({
+                "content": content,
+                "finish_reason": finish_reason,
+                "usage": usage,
+            }))
+        }
+        "embeddings" => {
+            let params: EmbeddingsParams = parse_params(params)?;
+            Ok(json!(client.get_embeddings(params.input).await?))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method '{method}'"),
+        )),
+    }
+}
+
+fn chat_params(
+    params: Value,
+    default_model: &str,
+) -> Result<(Vec<Message>, String, ChatOptions), RpcError> {
+    let params: ChatParams = parse_params(params)?;
+    let messages = openai::messages_from_openai(&params.messages)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+    let model = params.model.unwrap_or_else(|| default_model.to_string());
+    Ok((messages, model, params.options))
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => {
+            let mut error_obj = json!({ "code": error.code, "message": error.message });
+            if let Some(data) = error.data {
+                error_obj["data"] = data;
+            }
+            json!({ "jsonrpc": "2.0", "id": id, "error": error_obj })
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! `copilot index` and `copilot search`: semantic search over a workspace.
+
+use crate::{
+    args::{Format, IndexArgs, SearchArgs},
+    context, output,
+};
+use copilot_client::{
+    CopilotClient,
+    index::{INDEX_FILE_NAME, VectorIndex},
+};
+use serde_json::json;
+use std::{error::Error, path::PathBuf};
+
+/// Number of lines of each result shown in text output.
+const PREVIEW_LINES: usize = 3;
+
+/// Builds the index of a directory and saves it.
+pub async fn index(
+    client: &CopilotClient,
+    args: IndexArgs,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let output_path = args
+        .output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(&args.dir).join(INDEX_FILE_NAME));
+    output::notice(format_args!("Indexing {}...", args.dir));
+    let index = VectorIndex::build(client, &args.dir).await?;
+    index.save(&output_path)?;
+    if format == Format::Json {
+        return output::json(&json!({
+            "path": output_path,
+            "chunks": index.len(),
+            "model": index.model,
+        }));
+    }
+    println!(
+        "Indexed {} chunks into {}",
+        index.len(),
+        output_path.display()
+    );
+    Ok(())
+}
+
+/// Searches the index and prints the best matches with their scores.
+pub async fn search(
+    client: &CopilotClient,
+    args: SearchArgs,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let path = match args.index {
+        Some(path) => PathBuf::from(path),
+        None => VectorIndex::find(std::env::current_dir()?)
+            .ok_or("no index found; build one with `copilot index`")?,
+    };
+    let index = VectorIndex::load(&path)?;
+    let hits = index
+        .search_text(client, &args.query.join(" "), args.limit)
+        .await?;
+
+    match format {
+        Format::Json => {
+            let hits: Vec<_> = hits
+                .iter()
+                .map(|hit| {
+                    json!({
+                        "path": hit.entry.chunk.path,
+                        "start_line": hit.entry.chunk.start_line,
+                        "end_line": hit.entry.chunk.end_line,
+                        "score": hit.score,
+                        "text": hit.entry.chunk.text,
+                    })
+                })
+                .collect();
+            output::json(&hits)?;
+        }
+        Format::Markdown => {
+            for hit in hits {
+                let chunk = &hit.entry.chunk;
+                println!(
+                    "### `{}:{}-{}` ({:.3})\n",
+                    chunk.path, chunk.start_line, chunk.end_line, hit.score
+                );
+                println!("{}\n", context::fenced(&chunk.text, ""));
+            }
+        }
+        Format::Text | Format::Html => {
+            for hit in hits {
+                let chunk = &hit.entry.chunk;
+                println!(
+                    "{:.3}  {}:{}-{}",
+                    hit.score, chunk.path, chunk.start_line, chunk.end_line
+                );
+                for line in chunk
+                    .text
+                    .lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .take(PREVIEW_LINES)
+                {
+                    println!("       {}", line.trim_end());
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! `copilot sessions`: managing conversations saved in the session store.
+
+use crate::{
+    args::{ExportArgs, Format, SessionsCommand},
+    output, repl,
+};
+use copilot_client::{CopilotClient, session::SessionStore};
+use std::{
+    error::Error,
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Runs `copilot sessions list` or `copilot sessions delete`, which need no client.
+pub fn run(command: SessionsCommand, format: Format) -> Result<(), Box<dyn Error>> {
+    let store = SessionStore::default_location()?;
+    match command {
+        SessionsCommand::List => {
+            let sessions = store.list()?;
+            if format == Format::Json {
+                return output::json(&sessions);
+            }
+            let now = now();
+            let rows: Vec<Vec<String>> = sessions
+                .into_iter()
+                .map(|s| {
+                    vec![
+                        s.id,
+                        age(now, s.updated_at),
+                        s.message_count.to_string(),
+                        s.model,
+                        s.title.unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            output::table(
+                format,
+                &["ID", "Updated", "Messages", "Model", "Title"],
+                &rows,
+            );
+        }
+        SessionsCommand::Delete { id } => {
+            let id = store.resolve_id(&id)?;
+            store.delete(&id)?;
+            if format == Format::Json {
+                return output::json(&serde_json::json!({ "deleted": id }));
+            }
+            println!("Deleted session {id}.");
+        }
+        SessionsCommand::Resume { .. } => unreachable!("resume needs a client"),
+    }
+    Ok(())
+}
+
+/// Runs `copilot sessions resume`: continues a saved session in the REPL, saving it after every
+/// reply. Without an id, the most recently updated session is resumed.
+pub async fn resume(
+    client: &CopilotClient,
+    id: Option<String>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    if format == Format::Json {
+        return Err("--format json is not supported in interactive mode".into());
+    }
+    let store = SessionStore::default_location()?;
+    let conversation = match id {
+        Some(prefix) => store.load(&store.resolve_id(&prefix)?, client.clone())?,
+        None => store
+            .resume_latest(client.clone())?
+            .ok_or("there are no saved sessions")?,
+    };
+    println!(
+        "Resuming {} ({} messages).",
+        conversation.title().unwrap_or(conversation.id()),
+        conversation.messages().len()
+    );
+    repl::run(conversation, None, Some(store)).await
+}
+
+/// Runs `copilot export`: writes a saved session as a transcript in the selected format.
+pub fn export(args: ExportArgs, format: Format) -> Result<(), Box<dyn Error>> {
+    let store = SessionStore::default_location()?;
+    let record = store.load_record(&store.resolve_id(&args.id)?)?;
+    let transcript = match format {
+        Format::Json => serde_json::to_string_pretty(&record)? + "\n",
+        Format::Html => record.to_html(),
+        Format::Markdown | Format::Text => record.to_markdown(),
+    };
+    match args.output {
+        Some(path) => fs::write(path, transcript)?,
+        None => print!("{transcript}"),
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Formats the time elapsed since `timestamp` compactly, e.g. `5m ago` or `3d ago`.
+fn age(now: u64, timestamp: u64) -> String {
+    let secs = now.saturating_sub(timestamp);
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
@@ -0,0 +1,427 @@
+//! Full-screen terminal chat client (`copilot tui`).
+//!
+//! Replies are streamed from a background task while the event loop keeps redrawing, so the
+//! history stays scrollable during generation. Every completed turn is saved to the session
+//! store, which makes switching sessions lossless.
+
+use copilot_client::{
+    CopilotError, Model,
+    conversation::Conversation,
+    session::{SessionInfo, SessionStore},
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+use std::{error::Error, time::Duration};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+const HELP: &str = "Enter send · Alt-Enter newline · PgUp/PgDn scroll · Ctrl-O model · \
+Ctrl-S sessions · Ctrl-C quit";
+
+/// How often the event loop wakes up to apply streamed text while no key is pressed.
+const TICK: Duration = Duration::from_millis(30);
+
+/// Progress of an in-flight request, sent from the streaming task.
+enum Update {
+    Delta(String),
+    Done(Box<Conversation>, Result<(), CopilotError>),
+}
+
+/// A popup list shown over the chat.
+enum Picker {
+    /// Only chat models are listed; embeddings models cannot answer.
+    Model(ListState, Vec<Model>),
+    /// `None` stands for a new, empty session.
+    Session(ListState, Vec<Option<SessionInfo>>),
+}
+
+/// A reply being generated.
+struct Pending {
+    prompt: String,
+    reply: String,
+}
+
+struct App {
+    conversation: Conversation,
+    store: Option<SessionStore>,
+    input: String,
+    pending: Option<Pending>,
+    picker: Option<Picker>,
+    /// Number of lines scrolled up from the bottom of the history.
+    scroll: usize,
+    status: String,
+    quit: bool,
+    tx: UnboundedSender<Update>,
+}
+
+/// Runs the TUI until the user quits.
+pub async fn run(conversation: Conversation) -> Result<(), Box<dyn Error>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let store = SessionStore::default_location().ok();
+    let mut app = App {
+        conversation,
+        status: match &store {
+            Some(_) => HELP.to_string(),
+            None => format!("Sessions are unavailable. {HELP}"),
+        },
+        store,
+        input: String::new(),
+        pending: None,
+        picker: None,
+        scroll: 0,
+        quit: false,
+        tx,
+    };
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal, &mut rx).await;
+    ratatui::restore();
+    result
+}
+
+impl App {
+    async fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        rx: &mut UnboundedReceiver<Update>,
+    ) -> Result<(), Box<dyn Error>> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(TICK)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.on_key(key);
+            }
+            while let Ok(update) = rx.try_recv() {
+                self.on_update(update);
+            }
+            // Let the streaming task make progress between frames.
+            tokio::task::yield_now().await;
+        }
+        Ok(())
+    }
+
+    fn on_update(&mut self, update: Update) {
+        match update {
+            Update::Delta(delta) => {
+                if let Some(pending) = &mut self.pending {
+                    pending.reply.push_str(&delta);
+                }
+            }
+            Update::Done(conversation, result) => {
+                self.pending = None;
+                match result {
+                    Ok(()) => {
+                        self.conversation = *conversation;
+                        self.status = self.save();
+                    }
+                    Err(e) => self.status = format!("error: {e}"),
+                }
+            }
+        }
+    }
+
+    /// Saves the conversation to the session store and returns a status message.
+    fn save(&self) -> String {
+        match &self.store {
+            Some(store) => match store.save(&self.conversation) {
+                Ok(info) => format!("Saved session {}", short_id(&info.id)),
+                Err(e) => format!("error: failed to save session: {e}"),
+            },
+            None => HELP.to_string(),
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if self.picker.is_some() {
+            self.on_picker_key(key);
+            return;
+        }
+        match key.code {
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_add(10),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(10),
+            // Switching model or session mid-reply would discard the reply.
+            KeyCode::Char('o') if ctrl && self.pending.is_none() => self.open_model_picker(),
+            KeyCode::Char('s') if ctrl && self.pending.is_none() => self.open_session_picker(),
+            KeyCode::Char('u') if ctrl => self.input.clear(),
+            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => self.input.push('\n'),
+            KeyCode::Enter => self.submit(),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) if !ctrl => self.input.push(c),
+            KeyCode::Esc if self.input.is_empty() && self.pending.is_none() => self.quit = true,
+            _ => {}
+        }
+    }
+
+    fn submit(&mut self) {
+        let prompt = self.input.trim().to_string();
+        if prompt.is_empty() || self.pending.is_some() {
+            return;
+        }
+        self.input.clear();
+        self.scroll = 0;
+        self.pending = Some(Pending {
+            prompt: prompt.clone(),
+            reply: String::new(),
+        });
+        self.status = format!("Waiting for {}…", self.conversation.model());
+        let mut conversation = self.conversation.clone();
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            let result = conversation
+                .send_streaming(prompt, |delta| {
+                    let _ = tx.send(Update::Delta(delta.to_string()));
+                })
+                .await
+                .map(|_| ());
+            let _ = tx.send(Update::Done(Box::new(conversation), result));
+        });
+    }
+
+    fn open_model_picker(&mut self) {
+        let models: Vec<Model> = self
+            .conversation
+            .client()
+            .models()
+            .iter()
+            .filter(|m| m.is_chat_model())
+            .cloned()
+            .collect();
+        let current = models
+            .iter()
+            .position(|m| m.id == self.conversation.model());
+        self.picker = Some(Picker::Model(
+            ListState::default().with_selected(current.or(Some(0))),
+            models,
+        ));
+    }
+
+    fn open_session_picker(&mut self) {
+        let Some(store) = &self.store else {
+            self.status = "Sessions are unavailable".to_string();
+            return;
+        };
+        match store.list() {
+            Ok(sessions) => {
+                let current = sessions
+                    .iter()
+                    .position(|s| s.id == self.conversation.id())
+                    .map_or(0, |i| i + 1);
+                let entries = std::iter::once(None)
+                    .chain(sessions.into_iter().map(Some))
+                    .collect();
+                self.picker = Some(Picker::Session(
+                    ListState::default().with_selected(Some(current)),
+                    entries,
+                ));
+            }
+            Err(e) => self.status = format!("error: {e}"),
+        }
+    }
+
+    fn on_picker_key(&mut self, key: KeyEvent) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        let (state, len) = match picker {
+            Picker::Model(state, models) => (state, models.len()),
+            Picker::Session(state, entries) => (state, entries.len()),
+        };
+        match key.code {
+            KeyCode::Esc => self.picker = None,
+            KeyCode::Up => state.select_previous(),
+            KeyCode::Down if state.selected().is_some_and(|i| i + 1 < len) => state.select_next(),
+            KeyCode::Enter => {
+                let selected = state.selected();
+                if let (Some(picker), Some(index)) = (self.picker.take(), selected) {
+                    self.choose(picker, index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn choose(&mut self, picker: Picker, index: usize) {
+        match picker {
+            Picker::Model(_, models) => {
+                if let Some(model) = models.get(index) {
+                    let id = model.id.clone();
+                    self.status = format!("Switched to {id}");
+                    self.conversation.set_model(id);
+                }
+            }
+            Picker::Session(_, mut entries) => {
+                let Some(entry) = entries.get_mut(index).map(Option::take) else {
+                    return;
+                };
+                let client = self.conversation.client().clone();
+                let loaded = match (entry, &self.store) {
+                    (Some(info), Some(store)) => store.load(&info.id, client),
+                    _ => {
+                        // Start afresh, keeping the model, system prompt, and options.
+                        let mut fresh = Conversation::new(client, self.conversation.model())
+                            .with_options(self.conversation.options().clone());
+                        fresh.set_system_prompt(
+                            self.conversation.system_prompt().map(str::to_string),
+                        );
+                        Ok(fresh)
+                    }
+                };
+                match loaded {
+                    Ok(conversation) => {
+                        self.status = format!("Session {}", short_id(conversation.id()));
+                        self.conversation = conversation;
+                        self.scroll = 0;
+                    }
+                    Err(e) => self.status = format!("error: {e}"),
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let input_height = (self.input.lines().count().max(1) as u16).min(6) + 2;
+        let [history_area, input_area, status_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(input_height),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_history(frame, history_area);
+
+        let input = Paragraph::new(self.input.as_str())
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title(format!(" {} ", self.conversation.model())));
+        frame.render_widget(input, input_area);
+        if self.picker.is_none() {
+            let last = self.input.split('\n').next_back().unwrap_or("");
+            let rows = self.input.matches('\n').count() as u16;
+            frame.set_cursor_position((
+                (input_area.x + 1 + last.chars().count() as u16)
+                    .min(input_area.right().saturating_sub(2)),
+                (input_area.y + 1 + rows).min(input_area.bottom().saturating_sub(2)),
+            ));
+        }
+
+        frame.render_widget(Paragraph::new(self.status.as_str()).dim(), status_area);
+
+        match &mut self.picker {
+            Some(Picker::Model(state, models)) => {
+                let items: Vec<ListItem> = models
+                    .iter()
+                    .map(|m| ListItem::new(format!("{}  {}", m.id, m.name)))
+                    .collect();
+                draw_picker(frame, " Model ", items, state);
+            }
+            Some(Picker::Session(state, entries)) => {
+                let items: Vec<ListItem> = entries
+                    .iter()
+                    .map(|entry| match entry {
+                        Some(info) => ListItem::new(format!(
+                            "{}  {}  ({} messages, {})",
+                            short_id(&info.id),
+                            info.title.as_deref().unwrap_or("Untitled"),
+                            info.message_count,
+                            info.model,
+                        )),
+                        None => ListItem::new("+ New session"),
+                    })
+                    .collect();
+                draw_picker(frame, " Session ", items, state);
+            }
+            None => {}
+        }
+    }
+
+    fn draw_history(&mut self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Some(system) = self.conversation.system_prompt() {
+            push_message(&mut lines, "system", system);
+        }
+        for message in self.conversation.messages() {
+            push_message(&mut lines, &message.role, &message.content);
+        }
+        if let Some(pending) = &self.pending {
+            push_message(&mut lines, "user", &pending.prompt);
+            push_message(&mut lines, "assistant", &format!("{}▌", pending.reply));
+        }
+
+        let title = match self.conversation.title() {
+            Some(title) => format!(" {title} "),
+            None => " copilot ".to_string(),
+        };
+        let block = Block::bordered().title(title);
+        let inner = block.inner(area);
+        let paragraph = Paragraph::new(Text::from(lines)).wrap(Wrap { trim: false });
+        let total = paragraph.line_count(inner.width);
+        let max_top = total.saturating_sub(inner.height as usize);
+        // Keep the offset in range so scrolling back down responds immediately.
+        self.scroll = self.scroll.min(max_top);
+        let top = (max_top - self.scroll).min(u16::MAX as usize) as u16;
+        frame.render_widget(paragraph.block(block).scroll((top, 0)), area);
+    }
+}
+
+/// Appends a message with a role heading, styling Markdown headings and code blocks.
+fn push_message(lines: &mut Vec<Line<'static>>, role: &str, content: &str) {
+    let (heading, color) = match role {
+        "user" => ("You", Color::Cyan),
+        "assistant" => ("Assistant", Color::Green),
+        "system" => ("System", Color::Magenta),
+        other => (other, Color::Yellow),
+    };
+    lines.push(Line::from(Span::styled(
+        heading.to_string(),
+        Style::new().fg(color).add_modifier(Modifier::BOLD),
+    )));
+    let mut in_code = false;
+    for line in content.lines() {
+        let fence = line.trim_start().starts_with("```");
+        let style = if fence || in_code {
+            Style::new().fg(Color::Yellow)
+        } else if line.starts_with('#') {
+            Style::new().add_modifier(Modifier::BOLD)
+        } else {
+            Style::new()
+        };
+        if fence {
+            in_code = !in_code;
+        }
+        lines.push(Line::styled(line.to_string(), style));
+    }
+    lines.push(Line::default());
+}
+
+fn draw_picker(frame: &mut Frame, title: &str, items: Vec<ListItem>, state: &mut ListState) {
+    let area = frame.area();
+    let width = area.width.saturating_sub(8).min(80);
+    let height = (items.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let list = List::new(items)
+        .block(Block::bordered().title(title.to_string()))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_widget(Clear, popup);
+    frame.render_stateful_widget(list, popup, state);
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(8)]
+}
@@ -0,0 +1,208 @@
+//! `copilot usage`: account quotas and the usage and costs recorded locally by the CLI.
+
+use crate::{args::Format, output};
+use copilot_client::{
+    auth::{self, CopilotUser, QuotaSnapshot},
+    config::Config,
+    get_config_path,
+    pricing::PriceTable,
+    usage::UsageTracker,
+};
+use serde_json::json;
+use std::{error::Error, fs, path::PathBuf, sync::Arc};
+
+/// Location of the usage accumulated by every command that talked to the API.
+fn usage_path() -> Option<PathBuf> {
+    Some(usage_dir()?.join("usage.json"))
+}
+
+/// Location of the per-request usage history, one JSON record per line.
+fn history_path() -> Option<PathBuf> {
+    Some(usage_dir()?.join("usage.jsonl"))
+}
+
+/// Location of an optional price table replacing the built-in one, as JSON.
+fn prices_path() -> Option<PathBuf> {
+    Some(usage_dir()?.join("prices.json"))
+}
+
+fn usage_dir() -> Option<PathBuf> {
+    let config_dir = get_config_path().ok()?;
+    Some(PathBuf::from(config_dir).join("copilot-client"))
+}
+
+/// Loads the recorded usage, or an empty tracker if nothing has been recorded yet. Every
+/// further request is also appended to the usage history, and priced with `prices.json` if it
+/// exists, or else with the built-in price table.
+pub fn load_tracker() -> Arc<UsageTracker> {
+    let tracker = usage_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| UsageTracker::from_json(&json).ok())
+        .unwrap_or_default();
+    let prices = prices_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(PriceTable::builtin);
+    tracker.set_price_table(prices);
+    let tracker = match history_path() {
+        Some(path) => tracker.with_history_file(path),
+        None => tracker,
+    };
+    Arc::new(tracker)
+}
+
+/// Saves the recorded usage. Failures are ignored since the totals are informational.
+pub fn save_tracker(tracker: &UsageTracker) {
+    let Some(path) = usage_path() else {
+        return;
+    };
+    let Ok(json) = tracker.to_json() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let _ = fs::write(path, json);
+}
+
+/// Runs `copilot usage`. The local totals are shown even if the quota cannot be fetched.
+pub async fn run(profile: Option<String>, format: Format) -> Result<(), Box<dyn Error>> {
+    let github_token = Config::load()?.github_token(profile.as_deref())?;
+    let user = auth::copilot_user(&github_token).await;
+    let tracker = load_tracker();
+
+    if format == Format::Json {
+        return output::json(&json!({
+            "plan": user.as_ref().ok().and_then(|u| u.copilot_plan.as_ref()),
+            "quota_reset_date": user.as_ref().ok().and_then(|u| u.quota_reset_date.as_ref()),
+            "quotas": user.as_ref().ok().map(|u| &u.quota_snapshots),
+            "quota_error": user.as_ref().err().map(ToString::to_string),
+            "local": {
+                "models": tracker.snapshot(),
+                "totals": tracker.totals(),
+                "costs": tracker.costs(),
+                "cost": tracker.cost(),
+                "history": tracker.history_file(),
+            },
+        }));
+    }
+
+    match &user {
+        Ok(user) => print_quotas(user),
+        Err(e) => output::notice(format_args!("Could not fetch the quota: {e}")),
+    }
+    let snapshot = tracker.snapshot();
+    if snapshot.is_empty() {
+        println!("No usage recorded yet.");
+        return Ok(());
+    }
+    let costs = tracker.costs();
+    let mut rows: Vec<Vec<String>> = snapshot
+        .iter()
+        .map(|(model, usage)| {
+            let cost = costs.get(model).copied().unwrap_or_default();
+            vec![
+                model.clone(),
+                usage.requests.to_string(),
+                usage.prompt_tokens.to_string(),
+                usage.cached_tokens.to_string(),
+                usage.completion_tokens.to_string(),
+                usage.total_tokens.to_string(),
+                format_units(usage.premium_requests),
+                format_dollars(cost.premium_cost),
+                format_dollars(cost.token_cost),
+            ]
+        })
+        .collect();
+    let totals = tracker.totals();
+    let cost = tracker.cost();
+    rows.push(vec![
+        "total".to_string(),
+        totals.requests.to_string(),
+        totals.prompt_tokens.to_string(),
+        totals.cached_tokens.to_string(),
+        totals.completion_tokens.to_string(),
+        totals.total_tokens.to_string(),
+        format_units(totals.premium_requests),
+        format_dollars(cost.premium_cost),
+        format_dollars(cost.token_cost),
+    ]);
+    if user.is_ok() {
+        println!();
+    }
+    output::table(
+        format,
+        &[
+            "Model",
+            "Requests",
+            "Prompt tokens",
+            "Cached tokens",
+            "Completion tokens",
+            "Total tokens",
+            "Premium requests",
+            "Premium cost",
+            "Token cost",
+        ],
+        &rows,
+    );
+    Ok(())
+}
+
+fn print_quotas(user: &CopilotUser) {
+    println!(
+        "Plan:              {}",
+        user.copilot_plan.as_deref().unwrap_or("unknown")
+    );
+    match user.premium_requests() {
+        Some(quota) => println!("Premium requests:  {}", describe(quota)),
+        None => println!("Premium requests:  no quota reported"),
+    }
+    for (kind, quota) in &user.quota_snapshots {
+        if kind != "premium_interactions" {
+            println!(
+                "{:<19}{}",
+                format!("{}:", capitalize(kind)),
+                describe(quota)
+            );
+        }
+    }
+    if let Some(date) = &user.quota_reset_date {
+        println!("Resets:            {date}");
+    }
+}
+
+/// Describes a quota as used and remaining units.
+fn describe(quota: &QuotaSnapshot) -> String {
+    if quota.unlimited {
+        return "unlimited".to_string();
+    }
+    let mut text = format!(
+        "{} of {} used, {} remaining ({:.0}%)",
+        format_units(quota.used()),
+        format_units(quota.entitlement),
+        format_units(quota.remaining),
+        quota.percent_remaining
+    );
+    if quota.overage_count > 0.0 {
+        text.push_str(&format!(", {} over", format_units(quota.overage_count)));
+    }
+    text
+}
+
+/// Formats fractional premium-request units without trailing zeros.
+fn format_units(units: f64) -> String {
+    let text = format!("{units:.2}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn format_dollars(dollars: f64) -> String {
+    format!("${dollars:.2}")
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
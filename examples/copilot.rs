@@ -22,14 +22,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
     // In this sample, the system prompt tells the assistant it is highly capable,
     // and the user asks: "Can you explain how to send an HTTP request in Rust?"
     let messages = vec![
-        Message {
-            role: "system".to_string(),
-            content: "You are a highly skilled assistant.".to_string(),
-        },
-        Message {
-            role: "user".to_string(),
-            content: "Can you explain how to send an HTTP request in Rust?".to_string(),
-        },
+        Message::system("You are a highly skilled assistant."),
+        Message::user("Can you explain how to send an HTTP request in Rust?"),
     ];
 
     // If the specified model ID is not found in the client's internal model list,
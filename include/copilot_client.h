@@ -0,0 +1,91 @@
+/* C bindings for copilot-client. Generated by cbindgen; do not edit. */
+
+#ifndef COPILOT_CLIENT_H
+#define COPILOT_CLIENT_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ A client together with the runtime its requests run on. Opaque to C.
+ */
+typedef struct CopilotHandle CopilotHandle;
+
+/*
+ Receives each piece of a streamed reply, together with the `user_data` given to
+ [`copilot_chat`]. The string is only valid during the call. May be `NULL`.
+ */
+typedef void (*CopilotDeltaCallback)(const char *delta, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Creates a client and fetches the available models.
+
+ `github_token` may be `NULL` to discover the token from the environment. Returns `NULL` on
+ failure. Release the client with [`copilot_client_free`].
+
+ # Safety
+
+ `github_token` must be `NULL` or a valid C string, and `editor_version` a valid C string.
+ */
+struct CopilotHandle *copilot_client_new(const char *github_token, const char *editor_version);
+
+/*
+ Releases a client created with [`copilot_client_new`]. `NULL` is ignored.
+
+ # Safety
+
+ `client` must be `NULL` or a pointer returned by [`copilot_client_new`] that has not been
+ freed.
+ */
+void copilot_client_free(struct CopilotHandle *client);
+
+/*
+ Returns the available models as a JSON array, or `NULL` on failure.
+
+ # Safety
+
+ `client` must be a valid pointer returned by [`copilot_client_new`].
+ */
+char *copilot_models(const struct CopilotHandle *client);
+
+/*
+ Sends a chat request and returns the reply text, or `NULL` on failure.
+
+ `messages_json` is a JSON array of messages in the OpenAI chat format. `options_json` may be
+ `NULL` or a JSON object with the fields of [`ChatOptions`]. If `on_delta` is given, the reply
+ is streamed and each piece is passed to it with `user_data`.
+
+ # Safety
+
+ `client` must be a valid pointer returned by [`copilot_client_new`], `model` and
+ `messages_json` valid C strings, and `options_json` `NULL` or a valid C string.
+ */
+char *copilot_chat(const struct CopilotHandle *client, const char *model, const char *messages_json, const char *options_json, CopilotDeltaCallback on_delta, void *user_data);
+
+/*
+ Releases a string returned by this library. `NULL` is ignored.
+
+ # Safety
+
+ `s` must be `NULL` or a string returned by this library that has not been freed.
+ */
+void copilot_string_free(char *s);
+
+/*
+ Returns the message of the last failure on this thread, or `NULL` if there was none. The
+ string is owned by the library and valid until the next call on this thread.
+ */
+const char *copilot_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* COPILOT_CLIENT_H */
@@ -0,0 +1,147 @@
+//! The [`CopilotApi`] trait, an abstraction over [`CopilotClient`] requests.
+//!
+//! Code that takes a `&dyn CopilotApi` or an `impl CopilotApi` instead of a concrete client
+//! can be unit tested against a mock:
+//!
+//! ```
+//! use copilot_client::{
+//!     ChatOptions, ChatResponse, ChatStream, CopilotError, Embedding, Message, Model,
+//!     api::{ApiFuture, CopilotApi},
+//! };
+//!
+//! struct Echo;
+//!
+//! impl CopilotApi for Echo {
+//!     fn get_models(&self) -> ApiFuture<'_, Vec<Model>> {
+//!         Box::pin(async { Ok(Vec::new()) })
+//!     }
+//!
+//!     fn chat_completion_with_options<'a>(
+//!         &'a self,
+//!         messages: Vec<Message>,
+//!         _model_id: String,
+//!         _options: &'a ChatOptions,
+//!     ) -> ApiFuture<'a, ChatResponse> {
+//!         let reply = messages.last().map(|m| m.content.clone()).unwrap_or_default();
+//!         Box::pin(async move {
+//!             Ok(serde_json::from_value(serde_json::json!({
+//!                 "choices": [{ "message": { "role": "assistant", "content": reply } }]
+//!             }))
+//!             .unwrap())
+//!         })
+//!     }
+//!
+//!     fn chat_completion_stream<'a>(
+//!         &'a self,
+//!         _messages: Vec<Message>,
+//!         _model_id: String,
+//!         _options: &'a ChatOptions,
+//!     ) -> ApiFuture<'a, ChatStream> {
+//!         Box::pin(async { Ok(ChatStream::from_chunks(Vec::new())) })
+//!     }
+//!
+//!     fn get_embeddings(&self, _inputs: Vec<String>) -> ApiFuture<'_, Vec<Embedding>> {
+//!         Box::pin(async { Err(CopilotError::Other("not supported".to_string())) })
+//!     }
+//! }
+//!
+//! async fn greet(api: &dyn CopilotApi) -> Result<String, CopilotError> {
+//!     let response = api
+//!         .chat_completion(vec![Message::user("Hello")], "gpt-4o".to_string())
+//!         .await?;
+//!     Ok(response.choices[0].message.content.clone())
+//! }
+//! ```
+
+use crate::{
+    ChatOptions, ChatResponse, ChatStream, CopilotClient, CopilotError, Embedding, Message, Model,
+};
+use std::{future::Future, pin::Pin};
+
+/// The future returned by [`CopilotApi`] methods. It is `Send` except on `wasm32`.
+#[cfg(not(target_arch = "wasm32"))]
+pub type ApiFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, CopilotError>> + Send + 'a>>;
+/// The future returned by [`CopilotApi`] methods. It is `Send` except on `wasm32`.
+#[cfg(target_arch = "wasm32")]
+pub type ApiFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, CopilotError>> + 'a>>;
+
+/// The requests of a [`CopilotClient`], as a trait that mocks can implement.
+///
+/// The methods behave like the [`CopilotClient`] methods of the same names.
+pub trait CopilotApi: Send + Sync {
+    /// See [`CopilotClient::get_models`].
+    fn get_models(&self) -> ApiFuture<'_, Vec<Model>>;
+
+    /// See [`CopilotClient::chat_completion`]. Calls
+    /// [`chat_completion_with_options`](CopilotApi::chat_completion_with_options) with default
+    /// options unless overridden.
+    fn chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model_id: String,
+    ) -> ApiFuture<'_, ChatResponse> {
+        Box::pin(async move {
+            self.chat_completion_with_options(messages, model_id, &ChatOptions::default())
+                .await
+        })
+    }
+
+    /// See [`CopilotClient::chat_completion_with_options`].
+    fn chat_completion_with_options<'a>(
+        &'a self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &'a ChatOptions,
+    ) -> ApiFuture<'a, ChatResponse>;
+
+    /// See [`CopilotClient::chat_completion_stream`].
+    fn chat_completion_stream<'a>(
+        &'a self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &'a ChatOptions,
+    ) -> ApiFuture<'a, ChatStream>;
+
+    /// See [`CopilotClient::get_embeddings`].
+    fn get_embeddings(&self, inputs: Vec<String>) -> ApiFuture<'_, Vec<Embedding>>;
+}
+
+impl CopilotApi for CopilotClient {
+    fn get_models(&self) -> ApiFuture<'_, Vec<Model>> {
+        Box::pin(CopilotClient::get_models(self))
+    }
+
+    fn chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model_id: String,
+    ) -> ApiFuture<'_, ChatResponse> {
+        Box::pin(CopilotClient::chat_completion(self, messages, model_id))
+    }
+
+    fn chat_completion_with_options<'a>(
+        &'a self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &'a ChatOptions,
+    ) -> ApiFuture<'a, ChatResponse> {
+        Box::pin(CopilotClient::chat_completion_with_options(
+            self, messages, model_id, options,
+        ))
+    }
+
+    fn chat_completion_stream<'a>(
+        &'a self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &'a ChatOptions,
+    ) -> ApiFuture<'a, ChatStream> {
+        Box::pin(CopilotClient::chat_completion_stream(
+            self, messages, model_id, options,
+        ))
+    }
+
+    fn get_embeddings(&self, inputs: Vec<String>) -> ApiFuture<'_, Vec<Embedding>> {
+        Box::pin(CopilotClient::get_embeddings(self, inputs))
+    }
+}
@@ -0,0 +1,177 @@
+//! Conversions between this crate's types and those of the `async-openai` crate, enabled with
+//! the `async-openai` feature.
+//!
+//! Requests, messages, and streamed chunks convert both ways with `TryFrom`, so code built on
+//! `async-openai` can keep constructing its requests and reading its responses while the calls
+//! go to Copilot:
+//!
+//! ```ignore
+//! let request: ChatRequest = openai_request.try_into()?;
+//! let response = client
+//!     .chat_completion(request.messages, request.model.clone())
+//!     .await?;
+//! let openai_response = chat_response_to_async_openai(&response, &request.model)?;
+//! ```
+//!
+//! Copilot responses carry no id, timestamp, or model, so converting them into `async-openai`
+//! responses takes the model and fills in the rest.
+
+use crate::{
+    ChatRequest, ChatResponse, CopilotError, Embedding, Message, conversation::generate_id, openai,
+    stream::ChatStreamChunk, unix_time,
+};
+use ::async_openai::types::{
+    chat::{
+        ChatCompletionRequestMessage, CreateChatCompletionRequest, CreateChatCompletionResponse,
+        CreateChatCompletionStreamResponse,
+    },
+    embeddings::{self, CreateEmbeddingResponse, EmbeddingUsage},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+impl TryFrom<CreateChatCompletionRequest> for ChatRequest<'static> {
+    type Error = CopilotError;
+
+    fn try_from(request: CreateChatCompletionRequest) -> Result<Self, Self::Error> {
+        openai::request_from_openai(&to_value(&request)?)
+    }
+}
+
+impl TryFrom<ChatRequest<'_>> for CreateChatCompletionRequest {
+    type Error = CopilotError;
+
+    fn try_from(request: ChatRequest<'_>) -> Result<Self, Self::Error> {
+        from_value(openai::request_to_openai(&request))
+    }
+}
+
+impl TryFrom<ChatCompletionRequestMessage> for Message {
+    type Error = CopilotError;
+
+    fn try_from(message: ChatCompletionRequestMessage) -> Result<Self, Self::Error> {
+        openai::message_from_openai(&to_value(&message)?)
+    }
+}
+
+impl TryFrom<Message> for ChatCompletionRequestMessage {
+    type Error = CopilotError;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        from_value(openai::message_to_openai(&message))
+    }
+}
+
+impl TryFrom<CreateChatCompletionResponse> for ChatResponse {
+    type Error = CopilotError;
+
+    fn try_from(response: CreateChatCompletionResponse) -> Result<Self, Self::Error> {
+        from_value(to_value(&response)?)
+    }
+}
+
+impl TryFrom<CreateChatCompletionStreamResponse> for ChatStreamChunk {
+    type Error = CopilotError;
+
+    fn try_from(chunk: CreateChatCompletionStreamResponse) -> Result<Self, Self::Error> {
+        from_value(to_value(&chunk)?)
+    }
+}
+
+impl From<Embedding> for embeddings::Embedding {
+    fn from(embedding: Embedding) -> Self {
+        embeddings::Embedding {
+            index: embedding.index as u32,
+            object: "embedding".to_string(),
+            embedding: embedding.embedding.into_iter().map(|v| v as f32).collect(),
+        }
+    }
+}
+
+impl From<embeddings::Embedding> for Embedding {
+    fn from(embedding: embeddings::Embedding) -> Self {
+        Embedding {
+            index: embedding.index as usize,
+            embedding: embedding.embedding.into_iter().map(f64::from).collect(),
+        }
+    }
+}
+
+/// Converts a chat response into an `async-openai` response for `model`.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if a choice cannot be represented, for example because of an
+/// unknown finish reason.
+pub fn chat_response_to_async_openai(
+    response: &ChatResponse,
+    model: &str,
+) -> Result<CreateChatCompletionResponse, CopilotError> {
+    let choices: Vec<Value> = response
+        .choices
+        .iter()
+        .enumerate()
+        .map(|(index, choice)| {
+            json!({
+                "index": index,
+                "message": openai::message_to_openai(&choice.message),
+                "finish_reason": choice.finish_reason,
+                "logprobs": choice.logprobs,
+            })
+        })
+        .collect();
+    from_value(json!({
+        "id": format!("chatcmpl-{}", generate_id()),
+        "object": "chat.completion",
+        "created": unix_time().as_secs(),
+        "model": model,
+        "choices": choices,
+        "usage": response.usage,
+        "system_fingerprint": response.system_fingerprint,
+    }))
+}
+
+/// Converts a streamed chunk into an `async-openai` chunk for `model`.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the chunk cannot be represented, for example because of an
+/// unknown finish reason.
+pub fn chat_chunk_to_async_openai(
+    chunk: &ChatStreamChunk,
+    model: &str,
+) -> Result<CreateChatCompletionStreamResponse, CopilotError> {
+    from_value(json!({
+        "id": format!("chatcmpl-{}", generate_id()),
+        "object": "chat.completion.chunk",
+        "created": unix_time().as_secs(),
+        "model": model,
+        "choices": chunk.choices,
+        "usage": chunk.usage,
+    }))
+}
+
+/// Converts embeddings into an `async-openai` embeddings response for `model`. Copilot does not
+/// report token usage for embeddings, so the usage is zero.
+pub fn embeddings_to_async_openai(
+    embeddings: Vec<Embedding>,
+    model: &str,
+) -> CreateEmbeddingResponse {
+    CreateEmbeddingResponse {
+        object: "list".to_string(),
+        model: model.to_string(),
+        data: embeddings.into_iter().map(Into::into).collect(),
+        usage: EmbeddingUsage {
+            prompt_tokens: 0,
+            total_tokens: 0,
+        },
+    }
+}
+
+fn to_value(value: &impl Serialize) -> Result<Value, CopilotError> {
+    serde_json::to_value(value).map_err(|e| CopilotError::Other(e.to_string()))
+}
+
+fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, CopilotError> {
+    serde_json::from_value(value).map_err(|e| CopilotError::Other(e.to_string()))
+}
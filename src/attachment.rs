@@ -0,0 +1,340 @@
+//! Attachments.
+//!
+//! An [`Attachment`] is context to send with a prompt: a file, a web page, raw text, or an
+//! image. [`CopilotClient::resolve_attachments`] reads the files, fetches the URLs, and encodes
+//! the images, and [`attach`] adds the results to a message: text as labelled, fenced blocks
+//! that share a token budget, and images as image parts. [`CopilotClient::chat_with_attachments`]
+//! does both for the last message of a chat completion:
+//!
+//! ```no_run
+//! use copilot_client::{ChatOptions, Message, attachment::Attachment};
+//!
+//! # async fn run(client: copilot_client::CopilotClient) -> Result<(), copilot_client::CopilotError> {
+//! let attachments = [
+//!     Attachment::file("src/lib.rs"),
+//!     Attachment::url("https://docs.rs/regex/latest/regex/"),
+//!     Attachment::image("screenshot.png"),
+//! ];
+//! let response = client
+//!     .chat_with_attachments(
+//!         vec![Message::user("Why does the screenshot show an error?")],
+//!         &attachments,
+//!         "gpt-4o".into(),
+//!         &ChatOptions::default(),
+//!     )
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "fetch")]
+use crate::tools::fetch;
+use crate::{
+    ChatOptions, ChatResponse, CopilotClient, CopilotError, ImageUrl, Message,
+    context::{allocate, decode, fenced, fit, language},
+    prompt::{estimate_message_tokens, estimate_tokens},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Prompt budget used when the model does not report its limits.
+const DEFAULT_PROMPT_BUDGET: u32 = 16_384;
+
+/// Context to attach to a prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attachment {
+    /// A file, read as text, or as an image if its extension is that of an image format.
+    File(PathBuf),
+    /// A resource fetched over HTTP, read as text, or as an image if its content type is that
+    /// of an image format. Fetching needs the `fetch` feature.
+    Url(String),
+    /// Text, with an optional label such as where it came from.
+    Text {
+        /// Shown above the text.
+        label: Option<String>,
+        /// The text.
+        content: String,
+    },
+    /// An image file.
+    Image(PathBuf),
+}
+
+impl Attachment {
+    /// Attaches the file at `path`.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Attachment::File(path.into())
+    }
+
+    /// Attaches the resource at `url`.
+    pub fn url(url: impl Into<String>) -> Self {
+        Attachment::Url(url.into())
+    }
+
+    /// Attaches unlabelled text, such as input piped to a command.
+    pub fn text(content: impl Into<String>) -> Self {
+        Attachment::Text {
+            label: None,
+            content: content.into(),
+        }
+    }
+
+    /// Attaches text shown under `label`.
+    pub fn labeled_text(label: impl Into<String>, content: impl Into<String>) -> Self {
+        Attachment::Text {
+            label: Some(label.into()),
+            content: content.into(),
+        }
+    }
+
+    /// Attaches the image file at `path`.
+    pub fn image(path: impl Into<PathBuf>) -> Self {
+        Attachment::Image(path.into())
+    }
+
+    /// Reads a command-line argument: `http://` and `https://` URLs are attached as URLs, and
+    /// anything else as a file.
+    pub fn from_arg(arg: &str) -> Self {
+        if arg.starts_with("https://") || arg.starts_with("http://") {
+            Attachment::url(arg)
+        } else {
+            Attachment::file(arg)
+        }
+    }
+}
+
+/// An [`Attachment`] that has been read, ready to [`attach`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedAttachment {
+    /// Text, sent as a fenced block.
+    Text {
+        /// Shown above the block, such as the file path or URL.
+        label: Option<String>,
+        /// Language tag of the code fence.
+        language: String,
+        /// The text.
+        content: String,
+    },
+    /// An image, sent as an image part of the message.
+    Image {
+        /// The file path or URL of the image.
+        label: String,
+        /// The encoded image.
+        image: ImageUrl,
+    },
+}
+
+impl ResolvedAttachment {
+    /// Tokens used by the label and fence lines of a text block.
+    fn overhead(&self) -> u32 {
+        match self {
+            ResolvedAttachment::Text { label, .. } => {
+                label.as_deref().map_or(0, |l| estimate_tokens(l) + 2) + 4
+            }
+            ResolvedAttachment::Image { label, .. } => estimate_tokens(label) + 6,
+        }
+    }
+
+    /// Tokens of the text, or 0 for an image.
+    fn tokens(&self) -> u32 {
+        match self {
+            ResolvedAttachment::Text { content, .. } => estimate_tokens(content),
+            ResolvedAttachment::Image { .. } => 0,
+        }
+    }
+}
+
+/// Returns the MIME type of an image file from its extension, for the formats models accept:
+/// PNG, JPEG, GIF, and WebP.
+pub fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => return None,
+    })
+}
+
+/// Adds `attachments` to `message`: text as fenced blocks after the content, each labelled
+/// with its origin, truncated so that the message stays within `budget` tokens, and images as
+/// image parts, named in the text.
+///
+/// The tokens are shared fairly: blocks smaller than an equal share keep their full content,
+/// and what they leave unused is split among the larger ones.
+pub fn attach(message: &mut Message, attachments: &[ResolvedAttachment], budget: u32) {
+    let overhead: u32 = attachments.iter().map(ResolvedAttachment::overhead).sum();
+    let available = budget
+        .saturating_sub(estimate_tokens(&message.content))
+        .saturating_sub(overhead);
+    let sizes: Vec<u32> = attachments.iter().map(ResolvedAttachment::tokens).collect();
+    let shares = allocate(&sizes, available);
+    let mut parts: Vec<String> = Vec::with_capacity(attachments.len() + 1);
+    if !message.content.is_empty() {
+        parts.push(std::mem::take(&mut message.content));
+    }
+    for (attachment, share) in attachments.iter().zip(shares) {
+        match attachment {
+            ResolvedAttachment::Text {
+                label,
+                language,
+                content,
+            } => {
+                let block = fenced(&fit(content, share), language);
+                parts.push(match label {
+                    Some(label) => format!("`{label}`:\n{block}"),
+                    None => block,
+                });
+            }
+            ResolvedAttachment::Image { label, image } => {
+                message.images.push(image.clone());
+                parts.push(format!(
+                    "`{label}`: attached image {}",
+                    message.images.len()
+                ));
+            }
+        }
+    }
+    message.content = parts.join("\n\n");
+}
+
+impl CopilotClient {
+    /// Reads `attachment`: reads a file, fetches a URL, or encodes an image.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a file cannot be read or is neither text nor a supported
+    /// image, or a URL cannot be fetched or the `fetch` feature is off.
+    pub async fn resolve_attachment(
+        &self,
+        attachment: &Attachment,
+    ) -> Result<ResolvedAttachment, CopilotError> {
+        match attachment {
+            Attachment::File(path) => match image_mime_type(path) {
+                Some(_) => resolve_image(path),
+                None => {
+                    let bytes = read(path)?;
+                    let content = decode(&bytes).ok_or_else(|| {
+                        CopilotError::Other(format!("{} is not a text file", path.display()))
+                    })?;
+                    Ok(ResolvedAttachment::Text {
+                        label: Some(path.display().to_string()),
+                        language: language(path).to_string(),
+                        content,
+                    })
+                }
+            },
+            Attachment::Image(path) => resolve_image(path),
+            Attachment::Text { label, content } => Ok(ResolvedAttachment::Text {
+                label: label.clone(),
+                language: String::new(),
+                content: content.clone(),
+            }),
+            #[cfg(feature = "fetch")]
+            Attachment::Url(url) => self.fetch_attachment(url).await,
+            #[cfg(not(feature = "fetch"))]
+            Attachment::Url(url) => Err(CopilotError::Other(format!(
+                "Fetching {url} needs the `fetch` feature"
+            ))),
+        }
+    }
+
+    /// Reads `attachments` in order, like [`CopilotClient::resolve_attachment`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of [`CopilotClient::resolve_attachment`].
+    pub async fn resolve_attachments(
+        &self,
+        attachments: &[Attachment],
+    ) -> Result<Vec<ResolvedAttachment>, CopilotError> {
+        let mut resolved = Vec::with_capacity(attachments.len());
+        for attachment in attachments {
+            resolved.push(self.resolve_attachment(attachment).await?);
+        }
+        Ok(resolved)
+    }
+
+    /// Reads `attachments`, [attaches](attach) them to the last message of `messages` within
+    /// the model's prompt budget, and sends the chat completion request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if there are no messages, an attachment cannot be read, or the
+    /// request fails.
+    pub async fn chat_with_attachments(
+        &self,
+        mut messages: Vec<Message>,
+        attachments: &[Attachment],
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ChatResponse, CopilotError> {
+        let resolved = self.resolve_attachments(attachments).await?;
+        let (last, earlier) = messages
+            .split_last_mut()
+            .ok_or_else(|| CopilotError::Other("No message to attach to".to_string()))?;
+        let budget = self
+            .models()
+            .iter()
+            .find(|m| m.id == model_id)
+            .and_then(|m| m.prompt_token_limit().or_else(|| m.context_window()))
+            .unwrap_or(DEFAULT_PROMPT_BUDGET);
+        let reserved: u32 = earlier.iter().map(estimate_message_tokens).sum();
+        attach(last, &resolved, budget.saturating_sub(reserved));
+        self.chat_completion_with_options(messages, model_id, options)
+            .await
+    }
+
+    #[cfg(feature = "fetch")]
+    async fn fetch_attachment(&self, url: &str) -> Result<ResolvedAttachment, CopilotError> {
+        let (content_type, bytes) = fetch::get(self, url, fetch::DEFAULT_MAX_BYTES).await?;
+        if content_type.starts_with("image/") {
+            return Ok(ResolvedAttachment::Image {
+                label: url.to_string(),
+                image: ImageUrl::from_bytes(&content_type, &bytes),
+            });
+        }
+        let content = decode(&bytes)
+            .ok_or_else(|| CopilotError::Other(format!("{url} is not a text document")))?;
+        let (language, content) = match content_type.as_str() {
+            "text/html" | "application/xhtml+xml" => {
+                ("markdown", fetch::html_to_markdown(&content))
+            }
+            "text/markdown" => ("markdown", content),
+            "application/json" => ("json", content),
+            "application/xml" | "text/xml" => ("xml", content),
+            _ => {
+                let url = url.split(['?', '#']).next().unwrap_or(url);
+                let path = url
+                    .split_once("://")
+                    .map_or(url, |(_, rest)| rest.find('/').map_or("", |i| &rest[i..]));
+                (language(Path::new(path)), content)
+            }
+        };
+        Ok(ResolvedAttachment::Text {
+            label: Some(url.to_string()),
+            language: language.to_string(),
+            content,
+        })
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CopilotError> {
+    fs::read(path)
+        .map_err(|e| CopilotError::Other(format!("Failed to read {}: {e}", path.display())))
+}
+
+fn resolve_image(path: &Path) -> Result<ResolvedAttachment, CopilotError> {
+    let mime_type = image_mime_type(path).ok_or_else(|| {
+        CopilotError::Other(format!(
+            "{} is not a PNG, JPEG, GIF, or WebP image",
+            path.display()
+        ))
+    })?;
+    Ok(ResolvedAttachment::Image {
+        label: path.display().to_string(),
+        image: ImageUrl::from_bytes(mime_type, &read(path)?),
+    })
+}
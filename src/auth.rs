@@ -0,0 +1,249 @@
+//! GitHub authentication: the OAuth device flow, Copilot token exchange, and account quotas.
+//!
+//! The device flow is exposed as two steps so that callers control how they wait between polls:
+//!
+//! 1. [`request_device_code`] returns a code for the user to enter at the verification URL.
+//! 2. [`poll_device_token`] is called every [`DeviceCode::interval`] seconds until it returns
+//!    [`DevicePoll::Complete`] with the GitHub token.
+//!
+//! The functions here send their requests with the default transport and need the `reqwest`
+//! feature.
+
+#[cfg(feature = "reqwest")]
+use crate::transport::ReqwestTransport;
+use crate::{
+    CopilotError, CopilotTokenResponse, send,
+    transport::{HttpRequest, HttpTransport, Method},
+};
+use http::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue, USER_AGENT};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::BTreeMap;
+
+/// OAuth client id of the GitHub Copilot editor integrations.
+pub const COPILOT_CLIENT_ID: &str = "Iv1.b507a08c87ecfe98";
+
+/// The host the device flow and token exchange run against.
+pub const GITHUB_HOST: &str = "github.com";
+
+#[cfg(feature = "reqwest")]
+const DEVICE_CODE_URL: &str = "https://github.com/login/device/code";
+#[cfg(feature = "reqwest")]
+const ACCESS_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const COPILOT_TOKEN_URL: &str = "https://api.github.com/copilot_internal/v2/token";
+#[cfg(feature = "reqwest")]
+const USER_URL: &str = "https://api.github.com/user";
+#[cfg(feature = "reqwest")]
+const COPILOT_USER_URL: &str = "https://api.github.com/copilot_internal/user";
+
+/// A pending device authorization.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCode {
+    /// Code identifying this authorization when polling.
+    pub device_code: String,
+    /// Code the user enters at `verification_uri`.
+    pub user_code: String,
+    /// Page where the user authorizes the device.
+    pub verification_uri: String,
+    /// Seconds until the codes expire.
+    pub expires_in: u64,
+    /// Minimum number of seconds between polls.
+    pub interval: u64,
+}
+
+/// Result of polling for the device authorization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DevicePoll {
+    /// The user has not finished authorizing yet.
+    Pending,
+    /// Polling too often; wait this many additional seconds before the next poll.
+    SlowDown(u64),
+    /// Authorization succeeded; contains the GitHub OAuth token.
+    Complete(String),
+}
+
+/// Response of the access token endpoint, which reports pending states as errors.
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Deserialize)]
+struct AccessTokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+    interval: Option<u64>,
+}
+
+/// The GitHub account a token belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitHubUser {
+    /// The account's login name.
+    pub login: String,
+    /// The account's display name, if set.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// The Copilot subscription of an account and its current quotas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopilotUser {
+    /// The Copilot plan, such as `individual` or `business`.
+    #[serde(default)]
+    pub copilot_plan: Option<String>,
+    /// Date (`YYYY-MM-DD`) when the monthly quotas reset.
+    #[serde(default)]
+    pub quota_reset_date: Option<String>,
+    /// Quotas keyed by kind, such as `chat`, `completions`, and `premium_interactions`.
+    #[serde(default)]
+    pub quota_snapshots: BTreeMap<String, QuotaSnapshot>,
+}
+
+impl CopilotUser {
+    /// Returns the premium request quota, if the account has one.
+    pub fn premium_requests(&self) -> Option<&QuotaSnapshot> {
+        self.quota_snapshots.get("premium_interactions")
+    }
+}
+
+/// The state of one quota.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuotaSnapshot {
+    /// Units included in the plan for the current period.
+    #[serde(default)]
+    pub entitlement: f64,
+    /// Units left in the current period.
+    #[serde(default)]
+    pub remaining: f64,
+    /// Percentage of the entitlement left.
+    #[serde(default)]
+    pub percent_remaining: f64,
+    /// Whether the quota is unlimited, in which case the other fields are meaningless.
+    #[serde(default)]
+    pub unlimited: bool,
+    /// Units used beyond the entitlement.
+    #[serde(default)]
+    pub overage_count: f64,
+    /// Whether usage beyond the entitlement is allowed.
+    #[serde(default)]
+    pub overage_permitted: bool,
+}
+
+impl QuotaSnapshot {
+    /// Returns the units used in the current period.
+    pub fn used(&self) -> f64 {
+        (self.entitlement - self.remaining).max(0.0) + self.overage_count
+    }
+}
+
+/// Starts the device authorization flow.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+#[cfg(feature = "reqwest")]
+pub async fn request_device_code() -> Result<DeviceCode, CopilotError> {
+    let request = HttpRequest::new(Method::POST, DEVICE_CODE_URL)
+        .headers(accept_json())
+        .form(&[("client_id", COPILOT_CLIENT_ID), ("scope", "read:user")])?;
+    send(&ReqwestTransport::default(), request)
+        .await?
+        .json()
+        .await
+}
+
+/// Polls once for the result of the device authorization started with `device_code`.
+///
+/// # Errors
+///
+/// Returns a `CopilotError::TokenError` if the user denied the request or the code expired, and
+/// other `CopilotError`s if the HTTP request fails.
+#[cfg(feature = "reqwest")]
+pub async fn poll_device_token(device_code: &str) -> Result<DevicePoll, CopilotError> {
+    let request = HttpRequest::new(Method::POST, ACCESS_TOKEN_URL)
+        .headers(accept_json())
+        .form(&[
+            ("client_id", COPILOT_CLIENT_ID),
+            ("device_code", device_code),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ])?;
+    let response: AccessTokenResponse = send(&ReqwestTransport::default(), request)
+        .await?
+        .json()
+        .await?;
+    if let Some(token) = response.access_token {
+        return Ok(DevicePoll::Complete(token));
+    }
+    match response.error.as_deref() {
+        Some("authorization_pending") => Ok(DevicePoll::Pending),
+        // GitHub asks for at least five more seconds between polls.
+        Some("slow_down") => Ok(DevicePoll::SlowDown(response.interval.unwrap_or(5))),
+        _ => Err(CopilotError::TokenError(
+            response
+                .error_description
+                .or(response.error)
+                .unwrap_or_else(|| "Device authorization failed".into()),
+        )),
+    }
+}
+
+/// Exchanges a GitHub token for a short-lived Copilot token.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the HTTP request fails (for example because the account has no
+/// Copilot access) or the response cannot be parsed.
+#[cfg(feature = "reqwest")]
+pub async fn exchange_token(github_token: &str) -> Result<CopilotTokenResponse, CopilotError> {
+    request_copilot_token(&ReqwestTransport::default(), github_token).await
+}
+
+pub(crate) async fn request_copilot_token(
+    transport: &dyn HttpTransport,
+    github_token: &str,
+) -> Result<CopilotTokenResponse, CopilotError> {
+    github_get(transport, COPILOT_TOKEN_URL, github_token).await
+}
+
+/// Looks up the GitHub account that `github_token` belongs to.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+#[cfg(feature = "reqwest")]
+pub async fn github_user(github_token: &str) -> Result<GitHubUser, CopilotError> {
+    github_get(&ReqwestTransport::default(), USER_URL, github_token).await
+}
+
+/// Looks up the Copilot plan and quotas of the account that `github_token` belongs to.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+#[cfg(feature = "reqwest")]
+pub async fn copilot_user(github_token: &str) -> Result<CopilotUser, CopilotError> {
+    github_get(&ReqwestTransport::default(), COPILOT_USER_URL, github_token).await
+}
+
+/// Sends an authenticated GET request to the GitHub API and decodes the JSON response.
+async fn github_get<T: DeserializeOwned>(
+    transport: &dyn HttpTransport,
+    url: &str,
+    github_token: &str,
+) -> Result<T, CopilotError> {
+    let request = HttpRequest::new(Method::GET, url).headers(github_headers(github_token)?);
+    send(transport, request).await?.json().await
+}
+
+fn accept_json() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+    headers
+}
+
+fn github_headers(github_token: &str) -> Result<HeaderMap, CopilotError> {
+    let mut headers = accept_json();
+    headers.insert(USER_AGENT, HeaderValue::from_static("CopilotChat.nvim"));
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Token {github_token}"))
+            .map_err(|e| CopilotError::Other(e.to_string()))?,
+    );
+    Ok(headers)
+}
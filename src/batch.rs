@@ -0,0 +1,247 @@
+//! Running many independent chat completions.
+//!
+//! [`CopilotClient::chat_completion_batch`] sends a list of requests with bounded parallelism
+//! and returns their results in the order of the requests, which suits dataset labeling and
+//! evaluation jobs:
+//!
+//! ```no_run
+//! use copilot_client::{Message, batch::BatchRequest};
+//!
+//! # async fn run(client: copilot_client::CopilotClient) {
+//! let requests = ["positive", "negative", "neutral"]
+//!     .iter()
+//!     .map(|label| BatchRequest::new(vec![Message::user(format!("Write a {label} review"))], "gpt-4o"))
+//!     .collect();
+//! for result in client.chat_completion_batch(requests, 4).await {
+//!     match result {
+//!         Ok(response) => println!("{:?}", response.choices[0].message.content),
+//!         Err(e) => eprintln!("failed: {e}"),
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Failed requests are not retried unless the client has a [`BatchRetry`], which waits with
+//! exponential backoff and jitter between attempts. The client has no timer of its own, so the
+//! policy is given the sleep function of the application's runtime:
+//!
+//! ```
+//! use copilot_client::{CopilotClient, batch::BatchRetry};
+//! use std::time::Duration;
+//!
+//! # fn run(client: CopilotClient) {
+//! let client = client.with_batch_retry(
+//!     BatchRetry::new(tokio::time::sleep)
+//!         .with_max_attempts(4)
+//!         .with_base_delay(Duration::from_millis(500)),
+//! );
+//! # }
+//! ```
+
+use crate::{ChatOptions, ChatResponse, CopilotClient, CopilotError, Message, hooks::RetryEvent};
+use futures_util::{StreamExt, stream};
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+
+/// The default number of times a request of a batch is sent before its error is returned, if
+/// the client has a [`BatchRetry`].
+pub const BATCH_ATTEMPTS: u32 = 3;
+
+/// The default delay before the first retry of a batch request.
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// The default longest delay between attempts of a batch request.
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A function that sleeps for a duration on the application's runtime.
+#[cfg(not(target_arch = "wasm32"))]
+type Sleep = Arc<dyn Fn(Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+#[cfg(target_arch = "wasm32")]
+type Sleep = Arc<dyn Fn(Duration) -> Pin<Box<dyn Future<Output = ()>>> + Send + Sync>;
+
+/// How [`CopilotClient::chat_completion_batch`] retries requests that fail with a
+/// [transient](CopilotError::is_transient) error. See the [module documentation](self).
+///
+/// The delay before the `n`th retry is between half and all of the base delay times `2^(n-1)`,
+/// capped at the maximum delay, so that requests that failed together do not all retry
+/// together.
+#[derive(Clone)]
+pub struct BatchRetry {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    sleep: Sleep,
+}
+
+impl fmt::Debug for BatchRetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchRetry")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BatchRetry {
+    /// Creates a policy that sends each request up to [`BATCH_ATTEMPTS`] times, waiting with
+    /// `sleep`, such as `tokio::time::sleep`, between attempts.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new<F>(sleep: impl Fn(Duration) -> F + Send + Sync + 'static) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self::with_sleep(Arc::new(move |delay| Box::pin(sleep(delay))))
+    }
+
+    /// Creates a policy that sends each request up to [`BATCH_ATTEMPTS`] times, waiting with
+    /// `sleep` between attempts.
+    #[cfg(target_arch = "wasm32")]
+    pub fn new<F>(sleep: impl Fn(Duration) -> F + Send + Sync + 'static) -> Self
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        Self::with_sleep(Arc::new(move |delay| Box::pin(sleep(delay))))
+    }
+
+    fn with_sleep(sleep: Sleep) -> Self {
+        BatchRetry {
+            max_attempts: BATCH_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            sleep,
+        }
+    }
+
+    /// Sends each request at most `attempts` times in all. Defaults to [`BATCH_ATTEMPTS`].
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Sets the delay before the first retry. Defaults to [`DEFAULT_BASE_DELAY`].
+    pub fn with_base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Sets the longest delay between attempts. Defaults to [`DEFAULT_MAX_DELAY`].
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Returns the delay before retry number `retry`, counted from 1, with jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let cap = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(retry);
+        let fraction = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+        cap.div_f64(2.0).mul_f64(1.0 + fraction)
+    }
+}
+
+/// One chat completion of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchRequest {
+    /// The chat messages to send.
+    pub messages: Vec<Message>,
+    /// The identifier of the model to use.
+    pub model: String,
+    /// Sampling and length options for the request.
+    pub options: ChatOptions,
+}
+
+impl BatchRequest {
+    /// Creates a request with the default options.
+    pub fn new(messages: Vec<Message>, model: impl Into<String>) -> Self {
+        BatchRequest {
+            messages,
+            model: model.into(),
+            options: ChatOptions::default(),
+        }
+    }
+
+    /// Sets the options of the request.
+    pub fn with_options(mut self, options: ChatOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl CopilotClient {
+    /// Sends `requests` with at most `max_concurrency` in flight at a time and returns their
+    /// results in the order of the requests. One failed request does not stop the others.
+    ///
+    /// If the client has a [`BatchRetry`], a request that fails with a
+    /// [transient](CopilotError::is_transient) error is sent again after a backoff delay, up to
+    /// the policy's number of attempts. Without one, failed requests are not retried.
+    /// Rate-limited requests are not retried either; lower `max_concurrency` instead.
+    #[tracing::instrument(
+        name = "copilot.chat_completion_batch",
+        skip_all,
+        fields(requests = requests.len(), max_concurrency)
+    )]
+    pub async fn chat_completion_batch(
+        &self,
+        requests: Vec<BatchRequest>,
+        max_concurrency: usize,
+    ) -> Vec<Result<ChatResponse, CopilotError>> {
+        stream::iter(requests)
+            .map(|request| async move { self.send_batch_request(&request).await })
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn send_batch_request(
+        &self,
+        request: &BatchRequest,
+    ) -> Result<ChatResponse, CopilotError> {
+        let mut attempt = 1;
+        let max_attempts = self
+            .batch_retry
+            .as_ref()
+            .map_or(1, |retry| retry.max_attempts);
+        loop {
+            let result = self
+                .chat_completion_with_options(
+                    &request.messages,
+                    request.model.clone(),
+                    &request.options,
+                )
+                .await;
+            match result {
+                Err(e) if e.is_transient() && attempt < max_attempts => {
+                    let delay = self
+                        .batch_retry
+                        .as_ref()
+                        .map_or(Duration::ZERO, |retry| retry.delay(attempt));
+                    tracing::debug!(attempt, ?delay, error = %e, "Retrying batch request");
+                    if let Some(retry) = &self.batch_retry {
+                        (retry.sleep)(delay).await;
+                    }
+                    attempt += 1;
+                    if let Some(hooks) = &self.hooks {
+                        hooks.retry(&RetryEvent {
+                            model: request.model.clone(),
+                            next_model: request.model.clone(),
+                            attempt,
+                            reason: e.to_string(),
+                            tags: request.options.tags.clone(),
+                        });
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! A synchronous client, enabled with the `blocking` feature.
+//!
+//! [`CopilotClientBlocking`] wraps a [`CopilotClient`] and runs each request to completion on
+//! an internal runtime, for command-line tools and build scripts that are not async:
+//!
+//! ```no_run
+//! use copilot_client::{Message, blocking::CopilotClientBlocking};
+//!
+//! let client = CopilotClientBlocking::from_env_with_models("Neovim/0.9.0".to_string())?;
+//! let response = client.chat_completion(vec![Message::user("Hello")], "gpt-4o".to_string())?;
+//! for chunk in client.chat_completion_stream(
+//!     vec![Message::user("Tell me a story")],
+//!     "gpt-4o".to_string(),
+//!     &Default::default(),
+//! )? {
+//!     print!("{}", chunk?.content().unwrap_or_default());
+//! }
+//! # Ok::<(), copilot_client::CopilotError>(())
+//! ```
+//!
+//! Like `reqwest::blocking`, the methods panic when called from within an async runtime.
+
+use crate::{
+    Agent, ChatOptions, ChatResponse, ChatStream, ChatStreamChunk, CopilotClient, CopilotError,
+    Embedding, Message, Model, RequestPreview, usage::UsageTracker,
+};
+use futures_util::StreamExt;
+use std::{fmt, sync::Arc};
+use tokio::runtime::{Builder, Runtime};
+
+/// A synchronous counterpart of [`CopilotClient`] with the same methods.
+///
+/// Cloning is cheap: clones share the client and the runtime.
+#[derive(Clone)]
+pub struct CopilotClientBlocking {
+    inner: CopilotClient,
+    runtime: Arc<Runtime>,
+}
+
+impl fmt::Debug for CopilotClientBlocking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopilotClientBlocking")
+            .finish_non_exhaustive()
+    }
+}
+
+impl CopilotClientBlocking {
+    /// Blocking version of [`CopilotClient::from_env_with_models`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the token retrieval or model fetching fails.
+    pub fn from_env_with_models(editor_version: String) -> Result<Self, CopilotError> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(CopilotClient::from_env_with_models(editor_version))?;
+        Ok(CopilotClientBlocking { inner, runtime })
+    }
+
+    /// Blocking version of [`CopilotClient::new_with_models`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the model fetching fails.
+    pub fn new_with_models(
+        github_token: String,
+        editor_version: String,
+    ) -> Result<Self, CopilotError> {
+        let runtime = runtime()?;
+        let inner =
+            runtime.block_on(CopilotClient::new_with_models(github_token, editor_version))?;
+        Ok(CopilotClientBlocking { inner, runtime })
+    }
+
+    /// Blocking version of [`CopilotClient::with_models`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the runtime cannot be created.
+    pub fn with_models(
+        github_token: String,
+        editor_version: String,
+        models: Vec<Model>,
+    ) -> Result<Self, CopilotError> {
+        Self::from_async(CopilotClient::with_models(
+            github_token,
+            editor_version,
+            models,
+        ))
+    }
+
+    /// Wraps an existing async client.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the runtime cannot be created.
+    pub fn from_async(client: CopilotClient) -> Result<Self, CopilotError> {
+        Ok(CopilotClientBlocking {
+            inner: client,
+            runtime: runtime()?,
+        })
+    }
+
+    /// Returns the wrapped async client.
+    pub fn as_async(&self) -> &CopilotClient {
+        &self.inner
+    }
+
+    /// See [`CopilotClient::with_usage_tracker`].
+    pub fn with_usage_tracker(mut self, tracker: Arc<UsageTracker>) -> Self {
+        self.inner = self.inner.with_usage_tracker(tracker);
+        self
+    }
+
+    /// See [`CopilotClient::usage_tracker`].
+    pub fn usage_tracker(&self) -> Option<&Arc<UsageTracker>> {
+        self.inner.usage_tracker()
+    }
+
+    /// See [`CopilotClient::models`].
+    pub fn models(&self) -> &[Model] {
+        self.inner.models()
+    }
+
+    /// See [`CopilotClient::has_model`].
+    pub fn has_model(&self, model_id: &str) -> bool {
+        self.inner.has_model(model_id)
+    }
+
+    /// Blocking version of [`CopilotClient::get_agents`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+    pub fn get_agents(&self) -> Result<Vec<Agent>, CopilotError> {
+        self.runtime.block_on(self.inner.get_agents())
+    }
+
+    /// Blocking version of [`CopilotClient::get_models`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+    pub fn get_models(&self) -> Result<Vec<Model>, CopilotError> {
+        self.runtime.block_on(self.inner.get_models())
+    }
+
+    /// Blocking version of [`CopilotClient::warm_up`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the token exchange or the model fetch fails.
+    pub fn warm_up(&mut self) -> Result<(), CopilotError> {
+        self.runtime.block_on(self.inner.warm_up())
+    }
+
+    /// Blocking version of [`CopilotClient::chat_completion`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available,
+    /// or another `CopilotError` if the HTTP request or response parsing fails.
+    pub fn chat_completion(
+        &self,
+        messages: Vec<Message>,
+        model_id: String,
+    ) -> Result<ChatResponse, CopilotError> {
+        self.runtime
+            .block_on(self.inner.chat_completion(messages, model_id))
+    }
+
+    /// Blocking version of [`CopilotClient::chat_completion_with_options`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available,
+    /// or another `CopilotError` if the HTTP request or response parsing fails.
+    pub fn chat_completion_with_options(
+        &self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ChatResponse, CopilotError> {
+        self.runtime.block_on(
+            self.inner
+                .chat_completion_with_options(messages, model_id, options),
+        )
+    }
+
+    /// Blocking version of [`CopilotClient::chat_completion_stream`]. The returned iterator
+    /// blocks until each chunk arrives.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available,
+    /// or another `CopilotError` if the request cannot be sent. Errors that occur while the
+    /// response is streaming are yielded by the returned iterator.
+    pub fn chat_completion_stream(
+        &self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ChatStreamBlocking, CopilotError> {
+        let stream = self.runtime.block_on(
+            self.inner
+                .chat_completion_stream(messages, model_id, options),
+        )?;
+        Ok(ChatStreamBlocking {
+            stream,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// See [`CopilotClient::preview_chat_request`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available.
+    pub fn preview_chat_request(
+        &self,
+        messages: Vec<Message>,
+        model_id: String,
+        options: &ChatOptions,
+        stream: bool,
+    ) -> Result<RequestPreview, CopilotError> {
+        self.inner
+            .preview_chat_request(messages, model_id, options, stream)
+    }
+
+    /// Blocking version of [`CopilotClient::get_embeddings`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+    pub fn get_embeddings(&self, inputs: Vec<String>) -> Result<Vec<Embedding>, CopilotError> {
+        self.runtime.block_on(self.inner.get_embeddings(inputs))
+    }
+}
+
+/// An iterator over the chunks of a streamed chat completion, returned by
+/// [`CopilotClientBlocking::chat_completion_stream`].
+pub struct ChatStreamBlocking {
+    stream: ChatStream,
+    runtime: Arc<Runtime>,
+}
+
+impl fmt::Debug for ChatStreamBlocking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChatStreamBlocking").finish_non_exhaustive()
+    }
+}
+
+impl ChatStreamBlocking {
+    /// Blocking version of [`ChatStream::collect_content`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered while streaming.
+    pub fn collect_content(self) -> Result<String, CopilotError> {
+        self.runtime.block_on(self.stream.collect_content())
+    }
+}
+
+impl Iterator for ChatStreamBlocking {
+    type Item = Result<ChatStreamChunk, CopilotError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+fn runtime() -> Result<Arc<Runtime>, CopilotError> {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map(Arc::new)
+        .map_err(|e| CopilotError::Other(e.to_string()))
+}
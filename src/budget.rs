@@ -0,0 +1,453 @@
+//! Client-side request and token budgets.
+//!
+//! A [`Budget`] attached with [`CopilotClient::with_budget`] limits the number of requests and
+//! tokens a client may use per minute, hour, or day. Once a limit is reached, chat completion
+//! and embeddings requests fail with [`CopilotError::BudgetExceeded`] without being sent, until
+//! enough of the window has passed, so that a runaway loop in unattended automation cannot use
+//! up a month of premium requests:
+//!
+//! ```
+//! use copilot_client::{
+//!     CopilotClient, CopilotError,
+//!     budget::{Budget, BudgetWindow},
+//! };
+//! use std::sync::Arc;
+//!
+//! # async fn run(client: CopilotClient) -> Result<(), CopilotError> {
+//! let budget = Budget::new()
+//!     .with_request_limit(BudgetWindow::Minute, 20)
+//!     .with_request_limit(BudgetWindow::Day, 300)
+//!     .with_token_limit(BudgetWindow::Hour, 500_000);
+//! let client = client.with_budget(Arc::new(budget));
+//! if let Err(CopilotError::BudgetExceeded(exceeded)) =
+//!     client.chat_completion(Vec::new(), "gpt-4o".into()).await
+//! {
+//!     eprintln!("{exceeded}; pausing for {:?}", exceeded.retry_after);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Windows slide: a limit per hour counts the requests and tokens of the last 60 minutes.
+//! Every request sent counts, whether it succeeds or not, including fallback attempts and
+//! retries; responses answered from the [response cache](crate::cache) do not. Token usage is
+//! only known once a response arrives, so a request is allowed while the tokens of the window
+//! are below the limit, and the last one may exceed it by its own usage.
+//!
+//! The budget is kept in memory and shared by every client it is attached to.
+//!
+//! To be warned before requests start failing, register a callback with
+//! [`Budget::on_threshold`]. It is called with a [`BudgetAlert`] whenever the usage of a
+//! window reaches the given fraction of its limit:
+//!
+//! ```
+//! use copilot_client::budget::{Budget, BudgetWindow};
+//!
+//! let budget = Budget::new()
+//!     .with_request_limit(BudgetWindow::Day, 300)
+//!     .on_threshold(0.8, |alert| {
+//!         eprintln!("{} of {} {} per {} used", alert.used, alert.limit, alert.kind, alert.window);
+//!     });
+//! ```
+//!
+//! [`CopilotClient::with_budget`]: crate::CopilotClient::with_budget
+//! [`CopilotError::BudgetExceeded`]: crate::CopilotError::BudgetExceeded
+
+use crate::{
+    TokenUsage,
+    clock::{Clock, SystemClock},
+};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// The period a budget limit applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BudgetWindow {
+    /// The last 60 seconds.
+    Minute,
+    /// The last 60 minutes.
+    Hour,
+    /// The last 24 hours.
+    Day,
+}
+
+impl BudgetWindow {
+    /// Returns the length of the window.
+    pub fn duration(self) -> Duration {
+        Duration::from_secs(match self {
+            BudgetWindow::Minute => 60,
+            BudgetWindow::Hour => 3600,
+            BudgetWindow::Day => 86_400,
+        })
+    }
+}
+
+impl fmt::Display for BudgetWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BudgetWindow::Minute => "minute",
+            BudgetWindow::Hour => "hour",
+            BudgetWindow::Day => "day",
+        })
+    }
+}
+
+/// What a budget limit counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BudgetKind {
+    /// Chat completion and embeddings requests.
+    Requests,
+    /// Total tokens reported by the API.
+    Tokens,
+}
+
+impl fmt::Display for BudgetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BudgetKind::Requests => "requests",
+            BudgetKind::Tokens => "tokens",
+        })
+    }
+}
+
+/// The limit a request was refused by, returned in [`CopilotError::BudgetExceeded`].
+///
+/// [`CopilotError::BudgetExceeded`]: crate::CopilotError::BudgetExceeded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// What the limit counts.
+    pub kind: BudgetKind,
+    /// The period the limit applies to.
+    pub window: BudgetWindow,
+    /// The limit.
+    pub limit: u64,
+    /// The requests or tokens used in the window.
+    pub used: u64,
+    /// The time until the limit allows another request.
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Budget of {} {} per {} exceeded ({} used); retry in {}s",
+            self.limit,
+            self.kind,
+            self.window,
+            self.used,
+            self.retry_after.as_secs_f64().ceil()
+        )
+    }
+}
+
+/// Usage reaching a fraction of a limit, passed to the callbacks of [`Budget::on_threshold`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetAlert {
+    /// What the limit counts.
+    pub kind: BudgetKind,
+    /// The period the limit applies to.
+    pub window: BudgetWindow,
+    /// The limit.
+    pub limit: u64,
+    /// The requests or tokens used in the window.
+    pub used: u64,
+    /// The fraction of the limit that was reached.
+    pub threshold: f64,
+}
+
+/// The requests and tokens used in a window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BudgetUsage {
+    /// Requests sent.
+    pub requests: u64,
+    /// Total tokens reported by the API.
+    pub tokens: u64,
+}
+
+/// Limits on the requests and tokens used per minute, hour, and day. See the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct Budget {
+    limits: Vec<(BudgetKind, BudgetWindow, u64)>,
+    alerts: Vec<Alert>,
+    clock: Arc<dyn Clock>,
+    /// Requests and token usage of the longest window, oldest first.
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+/// A callback of [`Budget::on_threshold`].
+struct Alert {
+    threshold: f64,
+    callback: Arc<dyn Fn(&BudgetAlert) + Send + Sync>,
+}
+
+impl fmt::Debug for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alert")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A request sent, or the tokens of a response, at a point in time.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    at: Duration,
+    requests: u64,
+    tokens: u64,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Budget {
+    /// Creates a budget without limits.
+    pub fn new() -> Self {
+        Budget {
+            limits: Vec::new(),
+            alerts: Vec::new(),
+            clock: Arc::new(SystemClock),
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Allows at most `limit` requests per `window`, replacing an earlier request limit for
+    /// the same window.
+    pub fn with_request_limit(self, window: BudgetWindow, limit: u64) -> Self {
+        self.with_limit(BudgetKind::Requests, window, limit)
+    }
+
+    /// Allows requests while fewer than `limit` tokens were used in the last `window`,
+    /// replacing an earlier token limit for the same window.
+    pub fn with_token_limit(self, window: BudgetWindow, limit: u64) -> Self {
+        self.with_limit(BudgetKind::Tokens, window, limit)
+    }
+
+    fn with_limit(mut self, kind: BudgetKind, window: BudgetWindow, limit: u64) -> Self {
+        self.limits.retain(|&(k, w, _)| (k, w) != (kind, window));
+        self.limits.push((kind, window, limit));
+        self.limits.sort_unstable();
+        self
+    }
+
+    /// Calls `alert` whenever a request or response brings the usage of a window to
+    /// `threshold` times its limit, such as `0.8` for 80%, for every limit. The usage has to
+    /// fall below the threshold again, as the window moves on, before the callback is called
+    /// again for the same limit. Callbacks run synchronously on the task making the request.
+    pub fn on_threshold(
+        mut self,
+        threshold: f64,
+        alert: impl Fn(&BudgetAlert) + Send + Sync + 'static,
+    ) -> Self {
+        self.alerts.push(Alert {
+            threshold,
+            callback: Arc::new(alert),
+        });
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system time.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the limit on `kind` per `window`, if any.
+    pub fn limit(&self, kind: BudgetKind, window: BudgetWindow) -> Option<u64> {
+        self.limits
+            .iter()
+            .find(|&&(k, w, _)| (k, w) == (kind, window))
+            .map(|&(.., limit)| limit)
+    }
+
+    /// Returns the requests and tokens used in the last `window`. Only usage within the
+    /// longest window with a limit is kept.
+    pub fn usage(&self, window: BudgetWindow) -> BudgetUsage {
+        let now = self.clock.now();
+        let mut entries = self.lock();
+        self.prune(&mut entries, now);
+        in_window(&entries, window, now).fold(BudgetUsage::default(), |usage, entry| BudgetUsage {
+            requests: usage.requests + entry.requests,
+            tokens: usage.tokens + entry.tokens,
+        })
+    }
+
+    /// Forgets all usage. Limits are kept.
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    /// Counts a request about to be sent, or refuses it if a limit is reached.
+    pub(crate) fn acquire(&self) -> Result<(), BudgetExceeded> {
+        let now = self.clock.now();
+        let mut entries = self.lock();
+        self.prune(&mut entries, now);
+        for &(kind, window, limit) in &self.limits {
+            if let Some(exceeded) = exceeded(&entries, kind, window, limit, now) {
+                return Err(exceeded);
+            }
+        }
+        let alerts = self.push(
+            entries,
+            Entry {
+                at: now,
+                requests: 1,
+                tokens: 0,
+            },
+        );
+        self.fire(&alerts);
+        Ok(())
+    }
+
+    /// Counts the tokens of a response.
+    pub(crate) fn record_tokens(&self, usage: Option<&TokenUsage>) {
+        let Some(usage) = usage.filter(|usage| usage.total_tokens > 0) else {
+            return;
+        };
+        let now = self.clock.now();
+        let mut entries = self.lock();
+        self.prune(&mut entries, now);
+        let alerts = self.push(
+            entries,
+            Entry {
+                at: now,
+                requests: 0,
+                tokens: u64::from(usage.total_tokens),
+            },
+        );
+        self.fire(&alerts);
+    }
+
+    /// Adds `entry` and releases the lock, returning the thresholds it made usage reach, with
+    /// the indexes of their callbacks.
+    fn push(
+        &self,
+        mut entries: MutexGuard<'_, VecDeque<Entry>>,
+        entry: Entry,
+    ) -> Vec<(usize, BudgetAlert)> {
+        if self.alerts.is_empty() {
+            entries.push_back(entry);
+            return Vec::new();
+        }
+        let before: Vec<u64> = self
+            .limits
+            .iter()
+            .map(|&(kind, window, _)| used(&entries, kind, window, entry.at))
+            .collect();
+        entries.push_back(entry);
+        drop(entries);
+        let mut reached = Vec::new();
+        for (&(kind, window, limit), before) in self.limits.iter().zip(before) {
+            let after = before + amount(&entry, kind);
+            for (index, alert) in self.alerts.iter().enumerate() {
+                let level = alert.threshold * limit as f64;
+                if (before as f64) < level && after as f64 >= level {
+                    reached.push((
+                        index,
+                        BudgetAlert {
+                            kind,
+                            window,
+                            limit,
+                            used: after,
+                            threshold: alert.threshold,
+                        },
+                    ));
+                }
+            }
+        }
+        reached
+    }
+
+    /// Calls the callbacks for `alerts`, outside the lock so that they can query the budget.
+    fn fire(&self, alerts: &[(usize, BudgetAlert)]) {
+        for (index, reached) in alerts {
+            (self.alerts[*index].callback)(reached);
+        }
+    }
+
+    /// Drops entries older than the longest window with a limit.
+    fn prune(&self, entries: &mut VecDeque<Entry>, now: Duration) {
+        let longest = self
+            .limits
+            .iter()
+            .map(|&(_, window, _)| window.duration())
+            .max()
+            .unwrap_or_default();
+        while entries
+            .front()
+            .is_some_and(|entry| entry.at + longest <= now)
+        {
+            entries.pop_front();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Entry>> {
+        // A panic while holding the lock cannot leave the entries in an invalid state.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the entries within `window` of `now`, oldest first.
+fn in_window(
+    entries: &VecDeque<Entry>,
+    window: BudgetWindow,
+    now: Duration,
+) -> impl Iterator<Item = &Entry> {
+    entries
+        .iter()
+        .skip_while(move |entry| entry.at + window.duration() <= now)
+}
+
+/// Returns the requests or tokens, as `kind` says, used within `window` of `now`.
+fn used(entries: &VecDeque<Entry>, kind: BudgetKind, window: BudgetWindow, now: Duration) -> u64 {
+    in_window(entries, window, now)
+        .map(|entry| amount(entry, kind))
+        .sum()
+}
+
+fn amount(entry: &Entry, kind: BudgetKind) -> u64 {
+    match kind {
+        BudgetKind::Requests => entry.requests,
+        BudgetKind::Tokens => entry.tokens,
+    }
+}
+
+/// Returns how `limit` on `kind` per `window` is exceeded, if it is.
+fn exceeded(
+    entries: &VecDeque<Entry>,
+    kind: BudgetKind,
+    window: BudgetWindow,
+    limit: u64,
+    now: Duration,
+) -> Option<BudgetExceeded> {
+    let used = used(entries, kind, window, now);
+    if used < limit {
+        return None;
+    }
+    // Another request is allowed once enough of the oldest usage has left the window to bring
+    // the total below the limit.
+    let mut remaining = used;
+    let retry_after = in_window(entries, window, now)
+        .find(|entry| {
+            remaining -= amount(entry, kind);
+            remaining < limit
+        })
+        .map_or(Duration::ZERO, |entry| {
+            (entry.at + window.duration()).saturating_sub(now)
+        });
+    Some(BudgetExceeded {
+        kind,
+        window,
+        limit,
+        used,
+        retry_after,
+    })
+}
@@ -0,0 +1,205 @@
+//! Caching of chat completion and embeddings responses.
+//!
+//! A [`ResponseCache`] attached with [`CopilotClient::with_response_cache`] answers a request
+//! that was already made with the stored response instead of sending it again, so re-running a
+//! batch script over the same prompts costs no quota:
+//!
+//! ```no_run
+//! use copilot_client::{CopilotClient, cache::ResponseCache};
+//! use std::{sync::Arc, time::Duration};
+//!
+//! # fn run(client: CopilotClient) {
+//! let cache = ResponseCache::new(Duration::from_secs(24 * 3600), 1000).with_dir(".copilot-cache");
+//! let client = client.with_response_cache(Arc::new(cache));
+//! # }
+//! ```
+//!
+//! Requests are identified by the endpoint and the full request body, including the model,
+//! the messages, and the sampling options. Streamed chat completions are not cached. Cached
+//! responses are not reported to the usage tracker, since they cost nothing.
+//!
+//! [`CopilotClient::with_response_cache`]: crate::CopilotClient::with_response_cache
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex, time::Duration};
+
+/// A cache of API responses in memory and, optionally, on disk.
+///
+/// Entries expire after the time to live and the oldest entries are evicted beyond the maximum
+/// number of entries, both in memory and on disk. Disk errors are ignored: the cache is an
+/// optimization and never fails a request.
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    dir: Option<PathBuf>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+/// A stored response. The full key is kept to tell apart keys with the same hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    /// Unix timestamp of when the response was stored.
+    created_at: u64,
+    response: Value,
+}
+
+impl ResponseCache {
+    /// Creates an in-memory cache whose entries live for `ttl` and which holds at most
+    /// `max_entries` responses.
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        ResponseCache {
+            ttl,
+            max_entries,
+            dir: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Also stores responses as files in `dir`, so that they survive the process. Responses
+    /// found there are loaded into memory when first requested.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Returns the number of responses held in memory.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no responses are held in memory.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all responses, including those on disk.
+    pub fn clear(&self) {
+        self.lock().clear();
+        if let Some(dir) = &self.dir
+            && let Ok(files) = fs::read_dir(dir)
+        {
+            for file in files.flatten() {
+                if file.path().extension().is_some_and(|ext| ext == "json") {
+                    let _ = fs::remove_file(file.path());
+                }
+            }
+        }
+    }
+
+    /// Returns the response stored for `key` if it has not expired by `now`, the time since the
+    /// Unix epoch.
+    pub(crate) fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+        now: Duration,
+    ) -> Option<T> {
+        let hash = hash(key);
+        let cached = self.lock().get(&hash).cloned();
+        let entry = cached.or_else(|| self.read_file(&hash))?;
+        if entry.key != key {
+            return None;
+        }
+        if now.as_secs() >= entry.created_at.saturating_add(self.ttl.as_secs()) {
+            self.remove(&hash);
+            return None;
+        }
+        let response = serde_json::from_value(entry.response.clone()).ok()?;
+        self.insert(hash, entry);
+        Some(response)
+    }
+
+    /// Stores `response` for `key` at `now`, the time since the Unix epoch.
+    pub(crate) fn put<T: Serialize>(&self, key: &str, response: &T, now: Duration) {
+        let Ok(response) = serde_json::to_value(response) else {
+            return;
+        };
+        let entry = Entry {
+            key: key.to_string(),
+            created_at: now.as_secs(),
+            response,
+        };
+        let hash = hash(key);
+        if let Some(path) = self.path(&hash)
+            && let Ok(json) = serde_json::to_string(&entry)
+        {
+            let _ = fs::create_dir_all(path.parent().unwrap_or(&path))
+                .and_then(|_| fs::write(&path, json));
+        }
+        self.insert(hash, entry);
+        self.prune_dir();
+    }
+
+    fn insert(&self, hash: String, entry: Entry) {
+        let mut entries = self.lock();
+        entries.insert(hash, entry);
+        while entries.len() > self.max_entries {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.created_at)
+                .map(|(hash, _)| hash.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&self, hash: &str) {
+        self.lock().remove(hash);
+        if let Some(path) = self.path(hash) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn read_file(&self, hash: &str) -> Option<Entry> {
+        let json = fs::read_to_string(self.path(hash)?).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Deletes the oldest files beyond the maximum number of entries.
+    fn prune_dir(&self) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+        let Ok(files) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut files: Vec<_> = files
+            .flatten()
+            .filter(|file| file.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|file| Some((file.metadata().ok()?.modified().ok()?, file.path())))
+            .collect();
+        if files.len() <= self.max_entries {
+            return;
+        }
+        files.sort();
+        for (_, path) in &files[..files.len() - self.max_entries] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn path(&self, hash: &str) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{hash}.json")))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the key for a request to `endpoint` with `body`.
+pub(crate) fn key(endpoint: &str, body: &impl Serialize) -> Option<String> {
+    Some(format!("{endpoint} {}", serde_json::to_string(body).ok()?))
+}
+
+/// Hashes `key` with 64-bit FNV-1a, which unlike the standard library's hasher is stable
+/// across Rust versions, so that file names stay valid.
+fn hash(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
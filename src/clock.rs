@@ -0,0 +1,75 @@
+//! The source of the current time for token expiry.
+//!
+//! [`CopilotClient`](crate::CopilotClient) caches the Copilot token until shortly before it
+//! expires, reading the time from a [`Clock`]. The default [`SystemClock`] reads the system
+//! time; tests substitute a [`ManualClock`] with
+//! [`CopilotClient::with_clock`](crate::CopilotClient::with_clock) to step past the expiry
+//! without sleeping:
+//!
+//! ```no_run
+//! use copilot_client::{CopilotClient, clock::ManualClock};
+//! use std::time::Duration;
+//!
+//! # async fn run(client: CopilotClient) -> Result<(), copilot_client::CopilotError> {
+//! let clock = ManualClock::new(Duration::from_secs(1_700_000_000));
+//! let client = client.with_clock(clock.clone());
+//! client.get_models().await?; // fetches a token
+//! clock.advance(Duration::from_secs(3600));
+//! client.get_models().await?; // the token has expired and is fetched again
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::unix_time;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A source of the current time.
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Returns the time elapsed since the Unix epoch.
+    fn now(&self) -> Duration;
+}
+
+/// The system time. It is read with `Date.now()` on `wasm32`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        unix_time()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    /// Creates a clock showing `now`, the time since the Unix epoch.
+    pub fn new(now: Duration) -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Sets the time to `now`.
+    pub fn set(&self, now: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    /// Moves the time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,206 @@
+//! Extraction of fenced code blocks from replies.
+//!
+//! [`extract_code_blocks`] finds the fenced code blocks of a Markdown reply with their language
+//! and position, so tools that apply generated code need not parse fences themselves. Text that
+//! is still being streamed may end inside a block, which is then returned as incomplete. To pick
+//! up blocks while a reply streams in, feed the chunks to a [`CodeBlockExtractor`]:
+//!
+//! ```
+//! use copilot_client::code::CodeBlockExtractor;
+//!
+//! let mut extractor = CodeBlockExtractor::new();
+//! assert!(extractor.push("Run:\n```sh\necho ").is_empty());
+//! let blocks = extractor.push("hi\n```\nDone.");
+//! assert_eq!(blocks[0].language.as_deref(), Some("sh"));
+//! assert_eq!(blocks[0].content, "echo hi");
+//! ```
+//!
+//! Fences follow CommonMark: three or more backticks or tildes, indented by at most three
+//! spaces, closed by a fence of the same character that is at least as long.
+
+use crate::Message;
+use std::ops::Range;
+
+/// A fenced code block of a Markdown text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The first word of the info string after the opening fence, such as `"rust"`.
+    pub language: Option<String>,
+    /// The code between the fences, without the newline before the closing fence.
+    pub content: String,
+    /// The byte range of the block in the text, from the opening fence to the end of the
+    /// closing fence.
+    pub range: Range<usize>,
+    /// The line of the opening fence, counted from 1.
+    pub line: usize,
+    /// `false` if the text ends before the closing fence.
+    pub complete: bool,
+}
+
+/// Returns the fenced code blocks of `text` in order. A block left open at the end of `text` is
+/// returned with `complete` set to `false`.
+pub fn extract_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut scanner = Scanner::default();
+    let mut blocks: Vec<CodeBlock> = lines(text, 0)
+        .filter_map(|(start, line)| scanner.line(start, line))
+        .collect();
+    blocks.extend(scanner.unterminated(text.len()));
+    blocks
+}
+
+impl Message {
+    /// Returns the fenced code blocks of the content. See [`extract_code_blocks`].
+    pub fn code_blocks(&self) -> Vec<CodeBlock> {
+        extract_code_blocks(&self.content)
+    }
+}
+
+/// Extracts code blocks from text that arrives in pieces, such as the deltas of a streamed
+/// reply. Positions refer to the concatenation of all pieces.
+#[derive(Debug, Default)]
+pub struct CodeBlockExtractor {
+    text: String,
+    /// The length of the complete lines already scanned.
+    scanned: usize,
+    scanner: Scanner,
+}
+
+impl CodeBlockExtractor {
+    /// Creates an extractor that has seen no text.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `text` and returns the blocks it closed.
+    pub fn push(&mut self, text: &str) -> Vec<CodeBlock> {
+        self.text.push_str(text);
+        let complete = self.text.rfind('\n').map_or(0, |newline| newline + 1);
+        if complete <= self.scanned {
+            return Vec::new();
+        }
+        let blocks = lines(&self.text[self.scanned..complete], self.scanned)
+            .filter_map(|(start, line)| self.scanner.line(start, line))
+            .collect();
+        self.scanned = complete;
+        blocks
+    }
+
+    /// Returns the block the text seen so far ends in, if any.
+    pub fn partial(&self) -> Option<CodeBlock> {
+        let mut scanner = self.scanner.clone();
+        if let Some(block) = lines(&self.text[self.scanned..], self.scanned)
+            .find_map(|(start, line)| scanner.line(start, line))
+        {
+            return Some(block);
+        }
+        scanner.unterminated(self.text.len())
+    }
+
+    /// Returns the block closed or left open by the text after the last newline, once no more
+    /// text will arrive.
+    pub fn finish(self) -> Option<CodeBlock> {
+        self.partial()
+    }
+
+    /// Returns all text pushed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Splits `text` into lines with their newlines, paired with their offsets plus `offset`.
+fn lines(text: &str, offset: usize) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(offset, |start, line| {
+        let line_start = *start;
+        *start += line.len();
+        Some((line_start, line))
+    })
+}
+
+/// A code block whose closing fence has not been seen yet.
+#[derive(Debug, Clone)]
+struct OpenBlock {
+    fence: char,
+    fence_len: usize,
+    indent: usize,
+    language: Option<String>,
+    content: String,
+    start: usize,
+    line: usize,
+}
+
+/// Line-by-line fence recognition.
+#[derive(Debug, Clone, Default)]
+struct Scanner {
+    lines: usize,
+    open: Option<OpenBlock>,
+}
+
+impl Scanner {
+    /// Scans the line starting at byte `start` and returns the block it closes, if any.
+    fn line(&mut self, start: usize, line: &str) -> Option<CodeBlock> {
+        self.lines += 1;
+        let text = line.trim_end_matches(['\n', '\r']);
+        let Some(open) = &mut self.open else {
+            if let Some((indent, fence, fence_len, info)) = opening_fence(text) {
+                self.open = Some(OpenBlock {
+                    fence,
+                    fence_len,
+                    indent,
+                    language: info.split_whitespace().next().map(str::to_string),
+                    content: String::new(),
+                    start,
+                    line: self.lines,
+                });
+            }
+            return None;
+        };
+        if closes(text, open.fence, open.fence_len) {
+            let open = self.open.take()?;
+            return Some(CodeBlock {
+                language: open.language,
+                content: open.content,
+                range: open.start..start + text.len(),
+                line: open.line,
+                complete: true,
+            });
+        }
+        if self.lines > open.line + 1 {
+            open.content.push('\n');
+        }
+        let indent = text.len() - text.trim_start_matches(' ').len();
+        open.content.push_str(&text[indent.min(open.indent)..]);
+        None
+    }
+
+    /// Returns the block left open at the end of the text, `end` bytes long.
+    fn unterminated(&self, end: usize) -> Option<CodeBlock> {
+        let open = self.open.clone()?;
+        Some(CodeBlock {
+            language: open.language,
+            content: open.content,
+            range: open.start..end,
+            line: open.line,
+            complete: false,
+        })
+    }
+}
+
+/// Parses an opening fence into its indentation, character, length, and info string.
+fn opening_fence(line: &str) -> Option<(usize, char, usize, &str)> {
+    let rest = line.trim_start_matches(' ');
+    let indent = line.len() - rest.len();
+    let fence = rest.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let fence_len = rest.len() - rest.trim_start_matches(fence).len();
+    let info = rest[fence_len..].trim();
+    (indent <= 3 && fence_len >= 3 && !(fence == '`' && info.contains('`')))
+        .then_some((indent, fence, fence_len, info))
+}
+
+/// Returns `true` if `line` is a fence closing a block opened with `fence_len` `fence`s.
+fn closes(line: &str, fence: char, fence_len: usize) -> bool {
+    let rest = line.trim_start_matches(' ');
+    let indent = line.len() - rest.len();
+    let len = rest.len() - rest.trim_start_matches(fence).len();
+    indent <= 3 && len >= fence_len && rest[len..].trim().is_empty()
+}
@@ -0,0 +1,262 @@
+//! Persistent configuration: default request settings and named auth profiles.
+//!
+//! The configuration is stored as JSON in `<config dir>/copilot-client/config.json` (see
+//! [`get_config_path`]). Each profile can hold its own GitHub token, which lets users switch
+//! between accounts without juggling environment variables.
+
+use crate::{CopilotError, REDACTED, get_config_path, get_github_token};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Keys accepted by [`Config::get`], [`Config::set`], and [`Config::unset`].
+pub const KEYS: &[&str] = &["model", "temperature", "editor_version", "language"];
+
+/// Default settings applied when a request does not specify them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Defaults {
+    /// Default chat model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Default sampling temperature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Editor version reported to the API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor_version: Option<String>,
+    /// Natural language to answer in, such as `Japanese`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// A named set of credentials. The token is left out of `Debug` output.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    /// The GitHub OAuth token used to obtain Copilot tokens. When absent, the token is
+    /// discovered from the environment or the editor configuration files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_token: Option<String>,
+    /// The GitHub login the stored token belongs to, recorded at sign-in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login: Option<String>,
+}
+
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profile")
+            .field(
+                "github_token",
+                &self.github_token.as_ref().map(|_| REDACTED),
+            )
+            .field("login", &self.login)
+            .finish()
+    }
+}
+
+/// The contents of the configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Default request settings.
+    #[serde(default)]
+    pub defaults: Defaults,
+    /// Named auth profiles.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// The profile used when none is selected explicitly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+}
+
+impl Config {
+    /// Returns the default location of the configuration file.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the configuration directory cannot be determined.
+    pub fn default_path() -> Result<PathBuf, CopilotError> {
+        let config_dir = get_config_path().map_err(|e| CopilotError::Other(e.to_string()))?;
+        Ok(Path::new(&config_dir)
+            .join("copilot-client")
+            .join("config.json"))
+    }
+
+    /// Loads the configuration from the default location. A missing file yields the default
+    /// configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file exists but cannot be read or parsed.
+    pub fn load() -> Result<Self, CopilotError> {
+        Self::load_from(Self::default_path()?)
+    }
+
+    /// Loads the configuration from `path`. A missing file yields the default configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file exists but cannot be read or parsed.
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                CopilotError::Other(format!(
+                    "Invalid config file {}: {e}",
+                    path.as_ref().display()
+                ))
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(CopilotError::Other(e.to_string())),
+        }
+    }
+
+    /// Saves the configuration to the default location.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be written.
+    pub fn save(&self) -> Result<(), CopilotError> {
+        self.save_to(Self::default_path()?)
+    }
+
+    /// Saves the configuration to `path`, creating parent directories as needed.
+    ///
+    /// On Unix the file is only readable by the current user, since profiles may contain tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be written.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), CopilotError> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| CopilotError::Other(e.to_string()))?;
+        }
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| CopilotError::Other(e.to_string()))?;
+        fs::write(path, json).map_err(|e| CopilotError::Other(e.to_string()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| CopilotError::Other(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value of a default setting as a string, if set.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `key` is not one of [`KEYS`].
+    pub fn get(&self, key: &str) -> Result<Option<String>, CopilotError> {
+        match key {
+            "model" => Ok(self.defaults.model.clone()),
+            "temperature" => Ok(self.defaults.temperature.map(|t| t.to_string())),
+            "editor_version" => Ok(self.defaults.editor_version.clone()),
+            "language" => Ok(self.defaults.language.clone()),
+            _ => Err(unknown_key(key)),
+        }
+    }
+
+    /// Sets a default setting from its string representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `key` is unknown or `value` is invalid for it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), CopilotError> {
+        match key {
+            "model" => self.defaults.model = Some(value.to_string()),
+            "temperature" => {
+                let temperature: f64 = value
+                    .parse()
+                    .map_err(|_| CopilotError::Other(format!("Invalid temperature '{value}'")))?;
+                if !(0.0..=2.0).contains(&temperature) {
+                    return Err(CopilotError::Other(
+                        "Temperature must be between 0 and 2".into(),
+                    ));
+                }
+                self.defaults.temperature = Some(temperature);
+            }
+            "editor_version" => self.defaults.editor_version = Some(value.to_string()),
+            "language" => self.defaults.language = Some(value.to_string()),
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// Clears a default setting.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `key` is not one of [`KEYS`].
+    pub fn unset(&mut self, key: &str) -> Result<(), CopilotError> {
+        match key {
+            "model" => self.defaults.model = None,
+            "temperature" => self.defaults.temperature = None,
+            "editor_version" => self.defaults.editor_version = None,
+            "language" => self.defaults.language = None,
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    /// Returns the profile named `name`, or the active profile when `name` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a profile was named (explicitly or as the active profile) but
+    /// does not exist.
+    pub fn profile(&self, name: Option<&str>) -> Result<Option<&Profile>, CopilotError> {
+        match name.or(self.active_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .map(Some)
+                .ok_or_else(|| CopilotError::Other(format!("Unknown profile '{name}'"))),
+            None => Ok(None),
+        }
+    }
+
+    /// Makes `name` the active profile.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the profile does not exist.
+    pub fn use_profile(&mut self, name: &str) -> Result<(), CopilotError> {
+        if !self.profiles.contains_key(name) {
+            return Err(CopilotError::Other(format!("Unknown profile '{name}'")));
+        }
+        self.active_profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Removes the profile `name`, deactivating it if it was active. Returns `false` if it did
+    /// not exist.
+    pub fn remove_profile(&mut self, name: &str) -> bool {
+        if self.active_profile.as_deref() == Some(name) {
+            self.active_profile = None;
+        }
+        self.profiles.remove(name).is_some()
+    }
+
+    /// Resolves the GitHub token for the profile `name` (or the active profile), falling back to
+    /// [`get_github_token`] when the profile has no token of its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::TokenError` if no token can be found.
+    pub fn github_token(&self, name: Option<&str>) -> Result<String, CopilotError> {
+        if let Some(token) = self.profile(name)?.and_then(|p| p.github_token.clone()) {
+            return Ok(token);
+        }
+        get_github_token().map_err(|e| CopilotError::TokenError(e.to_string()))
+    }
+}
+
+fn unknown_key(key: &str) -> CopilotError {
+    CopilotError::Other(format!(
+        "Unknown config key '{key}' (expected one of: {})",
+        KEYS.join(", ")
+    ))
+}
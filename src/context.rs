@@ -0,0 +1,99 @@
+//! Prompt-ready context.
+//!
+//! Helpers that turn source text into fenced blocks for prompts and fit them into a token
+//! budget, shared by the context builders in the submodules: [`git`] produces diffs and blame
+//! excerpts of a repository, [`editor`] assembles the state of an editor, and `files`, with the
+//! `files` feature, gathers the files of a workspace that may be uploaded.
+
+pub mod editor;
+#[cfg(feature = "files")]
+pub mod files;
+pub mod git;
+
+use crate::prompt::{estimate_tokens, truncate_to_tokens};
+use std::path::Path;
+
+/// Tokens kept free for the notice appended to truncated content.
+const NOTICE_TOKENS: u32 = 16;
+
+/// Number of leading bytes checked for a NUL byte to detect binary files, as git does.
+const BINARY_CHECK_BYTES: usize = 8000;
+
+/// Wraps `content` in a Markdown code fence tagged with `lang`, using a fence longer than any
+/// backtick run inside the content.
+pub fn fenced(content: &str, lang: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{lang}\n{}\n{fence}", content.trim_end_matches('\n'))
+}
+
+/// Truncates `content` to roughly `budget` tokens, appending a notice when anything was cut.
+pub fn fit(content: &str, budget: u32) -> String {
+    let tokens = estimate_tokens(content);
+    if tokens <= budget {
+        return content.to_string();
+    }
+    let kept = truncate_to_tokens(content, budget.saturating_sub(NOTICE_TOKENS));
+    let dropped = tokens - estimate_tokens(kept);
+    format!("{kept}\n[... truncated {dropped} tokens to fit the context window ...]")
+}
+
+/// Splits `budget` among items of the given sizes, smallest first, so that no item gets more
+/// than it needs and the rest is shared equally.
+pub fn allocate(sizes: &[u32], budget: u32) -> Vec<u32> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| sizes[i]);
+    let mut shares = vec![0; sizes.len()];
+    let mut remaining = budget;
+    for (n, &i) in order.iter().enumerate() {
+        let left = (sizes.len() - n) as u32;
+        let share = sizes[i].min(remaining / left);
+        shares[i] = share;
+        remaining -= share;
+    }
+    shares
+}
+
+/// Returns `true` if `bytes` look like the content of a binary file, that is if a NUL byte
+/// occurs near the start.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0)
+}
+
+/// Decodes `bytes` as text, or returns `None` for binary or non-UTF-8 content.
+pub(crate) fn decode(bytes: &[u8]) -> Option<String> {
+    if is_binary(bytes) {
+        return None;
+    }
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Returns the code fence language for a file, based on its name or extension.
+pub fn language(path: &Path) -> &str {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some("Makefile" | "makefile" | "GNUmakefile") => return "makefile",
+        Some("Dockerfile") => return "dockerfile",
+        _ => {}
+    }
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return "";
+    };
+    match ext {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" | "mts" => "typescript",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "rb" => "ruby",
+        "sh" | "bash" | "zsh" => "bash",
+        "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "kt" | "kts" => "kotlin",
+        "md" => "markdown",
+        "yml" => "yaml",
+        "txt" => "",
+        other => other,
+    }
+}
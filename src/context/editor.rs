@@ -0,0 +1,308 @@
+//! Context from an editor.
+//!
+//! A [`ContextBuilder`] gives editor integrations a standard way to ground the model in what
+//! the user is looking at: the active file and the selection in it, the diagnostics the editor
+//! shows, the symbols of the active file, and other open files. [`ContextBuilder::build`]
+//! formats them as Markdown within a token budget:
+//!
+//! ```
+//! use copilot_client::context::editor::{ContextBuilder, Diagnostic, Severity, Symbol};
+//!
+//! let context = ContextBuilder::new(4_000)
+//!     .active_file("src/main.rs", "fn main() {\n    let x: u32 = -1;\n}\n")
+//!     .selection(2..=2)
+//!     .diagnostic(Diagnostic::new("src/main.rs", 2, Severity::Error, "cannot apply unary `-` to `u32`"))
+//!     .symbol(Symbol::new("main", "function", 1))
+//!     .build();
+//! assert!(context.starts_with("Selected lines 2-2 of `src/main.rs`:\n```rust\n    let x: u32 = -1;\n```"));
+//! ```
+//!
+//! When everything does not fit, the parts are kept in order of importance: the selection,
+//! diagnostics, symbols, and then the files, which share what is left of the budget. The lists
+//! of diagnostics and symbols take at most half of the budget left for them, so that some of
+//! the files remain.
+
+use super::{allocate, fenced, fit, language};
+use crate::prompt::estimate_tokens;
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::RangeInclusive, path::Path};
+
+/// Tokens used by the label and fence lines of a file block.
+const BLOCK_OVERHEAD_TOKENS: u32 = 8;
+
+/// The severity of a [`Diagnostic`], in the order of the Language Server Protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// An error.
+    Error,
+    /// A warning.
+    Warning,
+    /// An informational message.
+    Information,
+    /// A hint, such as a suggested simplification.
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Information => "info",
+            Severity::Hint => "hint",
+        })
+    }
+}
+
+/// A compiler or linter message shown by the editor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// The file the diagnostic is in.
+    pub path: String,
+    /// The line of the diagnostic, counted from 1.
+    pub line: u32,
+    /// The severity.
+    pub severity: Severity,
+    /// The message.
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic.
+    pub fn new(
+        path: impl Into<String>,
+        line: u32,
+        severity: Severity,
+        message: impl Into<String>,
+    ) -> Self {
+        Diagnostic {
+            path: path.into(),
+            line,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// A symbol of the active file, such as a function or a type, as reported by the editor's
+/// outline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    /// The name of the symbol.
+    pub name: String,
+    /// The kind of the symbol, such as `"function"` or `"struct"`.
+    pub kind: String,
+    /// The line the symbol is defined on, counted from 1.
+    pub line: u32,
+}
+
+impl Symbol {
+    /// Creates a symbol.
+    pub fn new(name: impl Into<String>, kind: impl Into<String>, line: u32) -> Self {
+        Symbol {
+            name: name.into(),
+            kind: kind.into(),
+            line,
+        }
+    }
+}
+
+/// A file and its content.
+#[derive(Debug, Clone)]
+struct File {
+    path: String,
+    content: String,
+}
+
+impl File {
+    fn block(&self, label: &str, budget: u32) -> String {
+        format!(
+            "{label}:\n{}",
+            fenced(&fit(&self.content, budget), language(Path::new(&self.path)))
+        )
+    }
+}
+
+/// Assembles editor state into a token-budgeted context block.
+#[derive(Debug, Clone)]
+pub struct ContextBuilder {
+    budget: u32,
+    active: Option<File>,
+    selection: Option<RangeInclusive<u32>>,
+    open_files: Vec<File>,
+    diagnostics: Vec<Diagnostic>,
+    symbols: Vec<Symbol>,
+}
+
+impl ContextBuilder {
+    /// Creates a builder for a context of at most about `budget` tokens.
+    pub fn new(budget: u32) -> Self {
+        ContextBuilder {
+            budget,
+            active: None,
+            selection: None,
+            open_files: Vec::new(),
+            diagnostics: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    /// Sets the file the user is editing.
+    pub fn active_file(mut self, path: impl Into<String>, content: impl Into<String>) -> Self {
+        self.active = Some(File {
+            path: path.into(),
+            content: content.into(),
+        });
+        self
+    }
+
+    /// Sets the selected lines of the active file, counted from 1.
+    pub fn selection(mut self, lines: RangeInclusive<u32>) -> Self {
+        self.selection = Some(lines);
+        self
+    }
+
+    /// Adds another open file. Files added first are considered more relevant.
+    pub fn open_file(mut self, path: impl Into<String>, content: impl Into<String>) -> Self {
+        self.open_files.push(File {
+            path: path.into(),
+            content: content.into(),
+        });
+        self
+    }
+
+    /// Adds a diagnostic.
+    pub fn diagnostic(mut self, diagnostic: Diagnostic) -> Self {
+        self.diagnostics.push(diagnostic);
+        self
+    }
+
+    /// Adds diagnostics.
+    pub fn diagnostics(mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) -> Self {
+        self.diagnostics.extend(diagnostics);
+        self
+    }
+
+    /// Adds a symbol of the active file.
+    pub fn symbol(mut self, symbol: Symbol) -> Self {
+        self.symbols.push(symbol);
+        self
+    }
+
+    /// Adds symbols of the active file.
+    pub fn symbols(mut self, symbols: impl IntoIterator<Item = Symbol>) -> Self {
+        self.symbols.extend(symbols);
+        self
+    }
+
+    /// Formats the context as Markdown sections separated by blank lines. Parts that do not fit
+    /// the budget are truncated with a notice, or left out.
+    pub fn build(&self) -> String {
+        let mut remaining = self.budget;
+        let mut sections = Vec::new();
+        let mut push = |section: String, remaining: &mut u32| {
+            *remaining = remaining.saturating_sub(estimate_tokens(&section));
+            sections.push(section);
+        };
+
+        if let (Some(active), Some(lines)) = (&self.active, &self.selection) {
+            let selected = File {
+                path: active.path.clone(),
+                content: select_lines(&active.content, lines),
+            };
+            let label = format!(
+                "Selected lines {}-{} of `{}`",
+                lines.start(),
+                lines.end(),
+                active.path
+            );
+            let budget = remaining.saturating_sub(BLOCK_OVERHEAD_TOKENS);
+            push(selected.block(&label, budget), &mut remaining);
+        }
+
+        let mut diagnostics = self.diagnostics.clone();
+        diagnostics.sort_by_key(|d| d.severity);
+        let diagnostics = diagnostics
+            .iter()
+            .map(|d| format!("- `{}:{}` {}: {}", d.path, d.line, d.severity, d.message));
+        if let Some(section) = list("Diagnostics", diagnostics, remaining / 2) {
+            push(section, &mut remaining);
+        }
+
+        if let Some(active) = &self.active {
+            let symbols = self
+                .symbols
+                .iter()
+                .map(|s| format!("- {} `{}` (line {})", s.kind, s.name, s.line));
+            let title = format!("Symbols in `{}`", active.path);
+            if let Some(section) = list(&title, symbols, remaining / 2) {
+                push(section, &mut remaining);
+            }
+        }
+
+        let files: Vec<(&File, String)> = self
+            .active
+            .iter()
+            .map(|file| (file, format!("Active file `{}`", file.path)))
+            .chain(
+                self.open_files
+                    .iter()
+                    .map(|file| (file, format!("Open file `{}`", file.path))),
+            )
+            .collect();
+        let overhead = files.len() as u32 * BLOCK_OVERHEAD_TOKENS;
+        let sizes: Vec<u32> = files
+            .iter()
+            .map(|(file, _)| estimate_tokens(&file.content))
+            .collect();
+        let shares = allocate(&sizes, remaining.saturating_sub(overhead));
+        for ((file, label), share) in files.into_iter().zip(shares) {
+            if share > 0 || file.content.is_empty() {
+                sections.push(file.block(&label, share));
+            }
+        }
+        sections.join("\n\n")
+    }
+}
+
+/// Returns `lines` of `content`, counted from 1.
+fn select_lines(content: &str, lines: &RangeInclusive<u32>) -> String {
+    let start = lines.start().saturating_sub(1) as usize;
+    let count = (lines.end() + 1).saturating_sub(*lines.start()).max(1) as usize;
+    content
+        .lines()
+        .skip(start)
+        .take(count)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats `items` as a list under `title`, keeping as many as fit in `budget` tokens and
+/// noting how many were left out. Returns `None` for an empty list.
+fn list(title: &str, items: impl ExactSizeIterator<Item = String>, budget: u32) -> Option<String> {
+    let total = items.len();
+    if total == 0 {
+        return None;
+    }
+    let mut section = format!("{title}:");
+    let mut used = estimate_tokens(&section);
+    let mut kept = 0;
+    for item in items {
+        let tokens = estimate_tokens(&item) + 1;
+        if used + tokens > budget {
+            break;
+        }
+        section.push('\n');
+        section.push_str(&item);
+        used += tokens;
+        kept += 1;
+    }
+    if kept == 0 {
+        return None;
+    }
+    if kept < total {
+        section.push_str(&format!("\n- ... and {} more", total - kept));
+    }
+    Some(section)
+}
@@ -0,0 +1,191 @@
+//! Files of a workspace.
+//!
+//! A [`FileCollector`] gathers the text files under a directory for context or indexing while
+//! keeping out what should never be uploaded: files matched by `.gitignore` (along with
+//! `.git/info/exclude` and the global git excludes), by a `.copilotignore` or `.aiexclude` file,
+//! hidden files, common build and dependency directories, large files, and binary files:
+//!
+//! ```no_run
+//! use copilot_client::context::files::FileCollector;
+//!
+//! # fn run() -> Result<(), copilot_client::CopilotError> {
+//! for file in FileCollector::new(".").with_max_file_bytes(64 * 1024).collect()? {
+//!     println!("{} ({} bytes)", file.relative, file.content.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `.copilotignore` and `.aiexclude` use the `.gitignore` syntax, apply to the directory they
+//! are in and its subdirectories, and are respected even outside a git repository.
+
+use super::is_binary;
+use crate::CopilotError;
+use ignore::{Walk, WalkBuilder};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Names of the ignore files read in addition to `.gitignore`.
+pub const IGNORE_FILE_NAMES: &[&str] = &[".copilotignore", ".aiexclude"];
+
+/// Files larger than this are skipped unless [`FileCollector::with_max_file_bytes`] says
+/// otherwise.
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 512 * 1024;
+
+/// Directories that are skipped even when no ignore file lists them.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist", "build", "vendor"];
+
+/// A text file collected by a [`FileCollector`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceFile {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The path relative to the collected directory, with `/` separators.
+    pub relative: String,
+    /// The content of the file.
+    pub content: String,
+}
+
+/// Gathers the files under a directory, honoring ignore files and size and binary filters.
+#[derive(Debug, Clone)]
+pub struct FileCollector {
+    root: PathBuf,
+    max_file_bytes: u64,
+    hidden: bool,
+    gitignore: bool,
+}
+
+impl FileCollector {
+    /// Creates a collector for the files under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileCollector {
+            root: root.into(),
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            hidden: false,
+            gitignore: true,
+        }
+    }
+
+    /// Returns the directory the files are collected from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Skips files larger than `bytes`. Defaults to [`DEFAULT_MAX_FILE_BYTES`].
+    pub fn with_max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    /// Includes hidden files and directories, whose names start with a dot. `.git` is skipped
+    /// regardless.
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Sets whether `.gitignore` and the git excludes are honored. `.copilotignore` and
+    /// `.aiexclude` are honored regardless.
+    pub fn with_gitignore(mut self, gitignore: bool) -> Self {
+        self.gitignore = gitignore;
+        self
+    }
+
+    /// Returns the paths of the files that are not ignored and not too large, sorted. Binary
+    /// files are only detected by [`FileCollector::collect`], which reads them.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a directory or an ignore file cannot be read.
+    pub fn paths(&self) -> Result<Vec<PathBuf>, CopilotError> {
+        let mut paths = Vec::new();
+        for entry in self.walk(None) {
+            let entry = entry.map_err(|e| {
+                CopilotError::Other(format!("Failed to read {}: {e}", self.root.display()))
+            })?;
+            if entry.file_type().is_some_and(|t| t.is_file()) {
+                paths.push(entry.into_path());
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Returns `true` if the file at `relative`, a path relative to the root, would be
+    /// collected: it exists and is neither ignored nor too large. Only the directories leading
+    /// to the file are walked.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a directory or an ignore file cannot be read.
+    pub fn includes(&self, relative: impl AsRef<Path>) -> Result<bool, CopilotError> {
+        let target = self.root.join(relative);
+        for entry in self.walk(Some(target.clone())) {
+            let entry = entry.map_err(|e| {
+                CopilotError::Other(format!("Failed to read {}: {e}", self.root.display()))
+            })?;
+            if entry.path() == target {
+                return Ok(entry.file_type().is_some_and(|t| t.is_file()));
+            }
+        }
+        Ok(false)
+    }
+
+    /// Walks the root, or only the directories leading to `target` if given.
+    fn walk(&self, target: Option<PathBuf>) -> Walk {
+        let mut builder = WalkBuilder::new(&self.root);
+        builder
+            .hidden(!self.hidden)
+            .git_ignore(self.gitignore)
+            .git_global(self.gitignore)
+            .git_exclude(self.gitignore)
+            .require_git(false)
+            .max_filesize(Some(self.max_file_bytes))
+            .filter_entry(move |entry| {
+                let name = entry.file_name().to_string_lossy();
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                let skipped = is_dir && (name == ".git" || SKIPPED_DIRS.contains(&name.as_ref()));
+                !skipped && target.as_ref().is_none_or(|t| t.starts_with(entry.path()))
+            });
+        for name in IGNORE_FILE_NAMES {
+            builder.add_custom_ignore_filename(name);
+        }
+        builder.build()
+    }
+
+    /// Reads the files that are not ignored, not too large, and not binary, sorted by path.
+    /// Files that are not valid UTF-8 or cannot be read are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a directory or an ignore file cannot be read.
+    pub fn collect(&self) -> Result<Vec<WorkspaceFile>, CopilotError> {
+        let mut files = Vec::new();
+        for path in self.paths()? {
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            if is_binary(&bytes) {
+                continue;
+            }
+            let Ok(content) = String::from_utf8(bytes) else {
+                continue;
+            };
+            let relative = path
+                .strip_prefix(&self.root)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push(WorkspaceFile {
+                path,
+                relative,
+                content,
+            });
+        }
+        Ok(files)
+    }
+}
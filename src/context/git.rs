@@ -0,0 +1,169 @@
+//! Context from a git repository.
+//!
+//! [`GitRepo`] reads staged and unstaged diffs, diffs between refs, and blame excerpts by
+//! running the `git` executable, and [`diff_context`] and [`blame_context`] turn them into
+//! fenced prompt blocks that fit a token budget:
+//!
+//! ```no_run
+//! use copilot_client::context::git::{GitRepo, diff_context};
+//!
+//! # fn run() -> Result<(), copilot_client::CopilotError> {
+//! let repo = GitRepo::new(".");
+//! let prompt = diff_context(&repo.staged_diff()?, 8_000);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Diffs are budgeted per file: files smaller than an equal share of the budget are kept whole,
+//! and the rest of the budget is split among the larger ones, so that one huge file does not
+//! push every other change out of the prompt.
+
+use super::{allocate, fenced, fit};
+use crate::{CopilotError, prompt::estimate_tokens};
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Tokens used by the fence lines and label of a block.
+const BLOCK_OVERHEAD_TOKENS: u32 = 8;
+
+/// A git working tree.
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    dir: PathBuf,
+}
+
+impl GitRepo {
+    /// Opens the repository containing `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        GitRepo { dir: dir.into() }
+    }
+
+    /// Returns the directory git runs in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Runs `git` with `args` in the repository and returns its stdout.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `git` cannot be run or fails.
+    pub fn git(&self, args: &[&str]) -> Result<String, CopilotError> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .output()
+            .map_err(|e| CopilotError::Other(format!("Failed to run git: {e}")))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(CopilotError::Other(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                stderr.trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Returns the diff of the staged changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `git` fails.
+    pub fn staged_diff(&self) -> Result<String, CopilotError> {
+        self.git(&["diff", "--staged", "--no-color"])
+    }
+
+    /// Returns the diff of the changes that are not staged.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `git` fails.
+    pub fn unstaged_diff(&self) -> Result<String, CopilotError> {
+        self.git(&["diff", "--no-color"])
+    }
+
+    /// Returns the diff for `range` (e.g. `main..HEAD`, or a single ref to compare the working
+    /// tree against), or of all uncommitted changes against `HEAD` when no range is given.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `git` fails, for example because a ref does not exist.
+    pub fn diff(&self, range: Option<&str>) -> Result<String, CopilotError> {
+        self.git(&["diff", "--no-color", range.unwrap_or("HEAD")])
+    }
+
+    /// Returns `git blame` output for `lines` (counted from 1) of `path`, with the abbreviated
+    /// commit, author, and date of every line.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `git` fails, for example because the file is not tracked or
+    /// has fewer lines.
+    pub fn blame(
+        &self,
+        path: impl AsRef<Path>,
+        lines: RangeInclusive<u32>,
+    ) -> Result<String, CopilotError> {
+        let range = format!("{},{}", lines.start(), lines.end());
+        let path = path.as_ref().to_string_lossy();
+        self.git(&["blame", "--date=short", "-L", &range, "--", &path])
+    }
+}
+
+/// Fences `diff` as a `diff` block of at most about `budget` tokens, sharing the budget fairly
+/// among the changed files. Truncated files end with a notice of how much was cut.
+pub fn diff_context(diff: &str, budget: u32) -> String {
+    let files = split_files(diff);
+    let sizes: Vec<u32> = files.iter().map(|file| estimate_tokens(file)).collect();
+    let available = budget.saturating_sub(BLOCK_OVERHEAD_TOKENS);
+    let shares = allocate(&sizes, available);
+    let fitted: Vec<String> = files
+        .iter()
+        .zip(shares)
+        .map(|(file, share)| fit(file.trim_end_matches('\n'), share))
+        .collect();
+    fenced(&fitted.join("\n"), "diff")
+}
+
+/// Fences the `blame` output for `lines` of `path` as a block of at most about `budget` tokens,
+/// labelled with the path and the line range.
+pub fn blame_context(
+    path: impl AsRef<Path>,
+    lines: RangeInclusive<u32>,
+    blame: &str,
+    budget: u32,
+) -> String {
+    format!(
+        "`{}` lines {}-{} (git blame):\n{}",
+        path.as_ref().display(),
+        lines.start(),
+        lines.end(),
+        fenced(
+            &fit(blame, budget.saturating_sub(BLOCK_OVERHEAD_TOKENS)),
+            ""
+        )
+    )
+}
+
+/// Splits a diff into the sections of the files it changes.
+fn split_files(diff: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = diff
+        .match_indices("diff --git ")
+        .map(|(i, _)| i)
+        .filter(|&i| i == 0 || diff.as_bytes()[i - 1] == b'\n')
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&diff.len()]))
+        .map(|(&start, &end)| &diff[start..end])
+        .filter(|file| !file.is_empty())
+        .collect()
+}
@@ -0,0 +1,699 @@
+//! Stateful multi-turn conversations.
+//!
+//! A [`Conversation`] owns the system prompt, message history, model, and [`ChatOptions`] of a
+//! chat session. Each call to [`Conversation::send`] appends the user turn and the assistant's
+//! reply to the history, so callers don't have to rebuild the message list for every turn.
+//!
+//! Conversations can be saved to and loaded from a stable JSON format (see
+//! [`ConversationRecord`]) and exported as Markdown transcripts. A conversation can also be
+//! forked at any turn with [`Conversation::fork`] to explore alternative follow-ups; the branch
+//! remembers where it came from. [`Conversation::generate_title`] asks a small model for a short
+//! title, which is stored with the conversation.
+//!
+//! Cross-cutting behavior such as injecting context, redacting secrets, or logging turns can be
+//! attached with [`ConversationHook`]s instead of wrapping every call site.
+
+use crate::{ChatOptions, CopilotClient, CopilotError, Message, prompt, unix_time};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Version of the JSON format written by [`Conversation::save`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Model preferred by [`Conversation::generate_title`] when it is available.
+pub const DEFAULT_TITLE_MODEL: &str = "gpt-4o-mini";
+
+/// Maximum number of tokens of each message of the first exchange sent for title generation.
+const TITLE_EXCERPT_TOKENS: u32 = 500;
+
+const TITLE_PROMPT: &str = "Write a short title (at most six words) that summarizes the \
+following conversation. Reply with the title only, without quotes or trailing punctuation.";
+
+/// The serialized form of a [`Conversation`].
+///
+/// This is the stable on-disk format used by [`Conversation::save`] and [`Conversation::load`].
+/// New fields are only ever added with defaults, so older files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationRecord {
+    /// Format version, see [`FORMAT_VERSION`].
+    pub version: u32,
+    /// Unique identifier of the conversation. Files without an id get a fresh one on load.
+    #[serde(default)]
+    pub id: String,
+    /// Where this conversation was forked from, if it is a branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<BranchOrigin>,
+    /// A short human-readable title, if one has been set or generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The model identifier.
+    pub model: String,
+    /// The system prompt, if any.
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// The message history, excluding the system prompt.
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    /// The request options.
+    #[serde(default)]
+    pub options: ChatOptions,
+}
+
+impl ConversationRecord {
+    /// Renders the conversation as a human-readable Markdown transcript.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title.as_deref().unwrap_or("Conversation"));
+        out.push_str(&format!("**Model:** `{}`\n", self.model));
+        if let Some(parent) = &self.parent {
+            out.push_str(&format!(
+                "**Forked from:** `{}` at message {}\n",
+                parent.conversation_id, parent.message_index
+            ));
+        }
+        if let Some(system) = &self.system_prompt {
+            out.push_str(&format!("\n## System\n\n{}\n", system.trim_end()));
+        }
+        for message in &self.messages {
+            out.push_str(&format!(
+                "\n## {}\n\n{}\n",
+                role_heading(&message.role),
+                message.content.trim_end()
+            ));
+        }
+        out
+    }
+
+    /// Renders the conversation as a standalone HTML page, with message text shown verbatim.
+    pub fn to_html(&self) -> String {
+        let title = escape_html(self.title.as_deref().unwrap_or("Conversation"));
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+        );
+        out.push_str(&format!(
+            "<p class=\"meta\">Model: <code>{}</code></p>\n",
+            escape_html(&self.model)
+        ));
+        if let Some(parent) = &self.parent {
+            out.push_str(&format!(
+                "<p class=\"meta\">Forked from <code>{}</code> at message {}</p>\n",
+                escape_html(&parent.conversation_id),
+                parent.message_index
+            ));
+        }
+        let system = self.system_prompt.as_deref().map(|s| ("system", s));
+        let messages = self
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()));
+        for (role, content) in system.into_iter().chain(messages) {
+            out.push_str(&format!(
+                "<section class=\"{}\">\n<h2>{}</h2>\n<div class=\"content\">{}</div>\n</section>\n",
+                escape_html(role),
+                escape_html(&role_heading(role)),
+                escape_html(content.trim_end())
+            ));
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Identifies the conversation and turn a branch was forked from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchOrigin {
+    /// The id of the parent conversation.
+    pub conversation_id: String,
+    /// Number of parent messages the branch started with.
+    pub message_index: usize,
+}
+
+/// Middleware invoked around every [`Conversation::send`].
+///
+/// Both methods have no-op defaults, so implementations only override what they need. Returning
+/// an error aborts the turn and leaves the history unchanged.
+pub trait ConversationHook: Send + Sync {
+    /// Called with the full list of messages (system prompt, history, and the new user message)
+    /// right before they are sent. Changes affect only the outgoing request, not the stored
+    /// history.
+    fn before_send(&self, messages: &mut Vec<Message>) -> Result<(), CopilotError> {
+        let _ = messages;
+        Ok(())
+    }
+
+    /// Called with the assistant's reply before it is appended to the history and returned.
+    fn after_receive(&self, reply: &mut Message) -> Result<(), CopilotError> {
+        let _ = reply;
+        Ok(())
+    }
+}
+
+/// Adapts a closure into a [`ConversationHook::before_send`] hook.
+struct BeforeSend<F>(F);
+
+impl<F> ConversationHook for BeforeSend<F>
+where
+    F: Fn(&mut Vec<Message>) -> Result<(), CopilotError> + Send + Sync,
+{
+    fn before_send(&self, messages: &mut Vec<Message>) -> Result<(), CopilotError> {
+        (self.0)(messages)
+    }
+}
+
+/// Adapts a closure into a [`ConversationHook::after_receive`] hook.
+struct AfterReceive<F>(F);
+
+impl<F> ConversationHook for AfterReceive<F>
+where
+    F: Fn(&mut Message) -> Result<(), CopilotError> + Send + Sync,
+{
+    fn after_receive(&self, reply: &mut Message) -> Result<(), CopilotError> {
+        (self.0)(reply)
+    }
+}
+
+/// A multi-turn chat session with a single model.
+///
+/// ```no_run
+/// # use copilot_client::{CopilotClient, conversation::Conversation};
+/// # async fn run(client: CopilotClient) -> Result<(), copilot_client::CopilotError> {
+/// let mut conversation = Conversation::new(client, "gpt-4o")
+///     .with_system_prompt("You are a concise assistant.");
+/// let reply = conversation.send("What is a lifetime in Rust?").await?;
+/// println!("{}", reply.content);
+/// let reply = conversation.send("Give me an example.").await?;
+/// println!("{}", reply.content);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Conversation {
+    client: CopilotClient,
+    id: String,
+    parent: Option<BranchOrigin>,
+    title: Option<String>,
+    system_prompt: Option<String>,
+    messages: Vec<Message>,
+    model: String,
+    options: ChatOptions,
+    hooks: Vec<Arc<dyn ConversationHook>>,
+}
+
+impl fmt::Debug for Conversation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Conversation")
+            .field("id", &self.id)
+            .field("parent", &self.parent)
+            .field("title", &self.title)
+            .field("system_prompt", &self.system_prompt)
+            .field("messages", &self.messages)
+            .field("model", &self.model)
+            .field("options", &self.options)
+            .field("hooks", &self.hooks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Conversation {
+    /// Creates an empty conversation using `model`.
+    pub fn new(client: CopilotClient, model: impl Into<String>) -> Self {
+        Conversation {
+            client,
+            id: generate_id(),
+            parent: None,
+            title: None,
+            system_prompt: None,
+            messages: Vec::new(),
+            model: model.into(),
+            options: ChatOptions::default(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Sets the system prompt sent at the start of every request.
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Sets the options used for every request.
+    pub fn with_options(mut self, options: ChatOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Registers a hook. Hooks run in registration order and are carried over to forks, but are
+    /// not saved.
+    pub fn with_hook(mut self, hook: impl ConversationHook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Registers a closure run on the outgoing messages before every send.
+    pub fn on_before_send<F>(self, f: F) -> Self
+    where
+        F: Fn(&mut Vec<Message>) -> Result<(), CopilotError> + Send + Sync + 'static,
+    {
+        self.with_hook(BeforeSend(f))
+    }
+
+    /// Registers a closure run on every assistant reply.
+    pub fn on_after_receive<F>(self, f: F) -> Self
+    where
+        F: Fn(&mut Message) -> Result<(), CopilotError> + Send + Sync + 'static,
+    {
+        self.with_hook(AfterReceive(f))
+    }
+
+    /// Returns the unique identifier of the conversation.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns where this conversation was forked from, if it is a branch.
+    pub fn parent(&self) -> Option<&BranchOrigin> {
+        self.parent.as_ref()
+    }
+
+    /// Returns the title of the conversation, if any.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Sets or clears the title of the conversation.
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
+    /// Returns the client used to send requests.
+    pub fn client(&self) -> &CopilotClient {
+        &self.client
+    }
+
+    /// Returns the system prompt, if any.
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    /// Replaces the system prompt. Pass `None` to remove it.
+    pub fn set_system_prompt(&mut self, prompt: Option<String>) {
+        self.system_prompt = prompt;
+    }
+
+    /// Returns the model identifier.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Switches the model used for subsequent turns.
+    pub fn set_model(&mut self, model: impl Into<String>) {
+        self.model = model.into();
+    }
+
+    /// Returns the request options.
+    pub fn options(&self) -> &ChatOptions {
+        &self.options
+    }
+
+    /// Returns the request options for modification.
+    pub fn options_mut(&mut self) -> &mut ChatOptions {
+        &mut self.options
+    }
+
+    /// Returns the message history, excluding the system prompt.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Appends a message to the history without sending anything.
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Removes all messages from the history. The system prompt is kept.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+
+    /// Creates a branch that starts with the first `at` messages of this conversation.
+    ///
+    /// The branch gets a new id and records this conversation as its parent; the model,
+    /// system prompt, and options are copied. To "edit and resend" a user message, fork at that
+    /// message's index and send the edited text on the branch. If `at` is greater than the
+    /// history length, the whole history is kept.
+    pub fn fork(&self, at: usize) -> Conversation {
+        let at = at.min(self.messages.len());
+        Conversation {
+            client: self.client.clone(),
+            id: generate_id(),
+            parent: Some(BranchOrigin {
+                conversation_id: self.id.clone(),
+                message_index: at,
+            }),
+            title: self.title.clone(),
+            system_prompt: self.system_prompt.clone(),
+            messages: self.messages[..at].to_vec(),
+            model: self.model.clone(),
+            options: self.options.clone(),
+            hooks: self.hooks.clone(),
+        }
+    }
+
+    /// Returns the full list of messages that would be sent, including the system prompt.
+    pub fn request_messages(&self) -> Vec<Message> {
+        let mut messages = Vec::with_capacity(self.messages.len() + 1);
+        if let Some(system) = &self.system_prompt {
+            messages.push(Message::system(system.clone()));
+        }
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
+    /// Returns the serializable state of the conversation.
+    pub fn to_record(&self) -> ConversationRecord {
+        ConversationRecord {
+            version: FORMAT_VERSION,
+            id: self.id.clone(),
+            parent: self.parent.clone(),
+            title: self.title.clone(),
+            model: self.model.clone(),
+            system_prompt: self.system_prompt.clone(),
+            messages: self.messages.clone(),
+            options: self.options.clone(),
+        }
+    }
+
+    /// Restores a conversation from its serialized state.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the record was written by a newer, unsupported format version.
+    pub fn from_record(
+        client: CopilotClient,
+        record: ConversationRecord,
+    ) -> Result<Self, CopilotError> {
+        if record.version > FORMAT_VERSION {
+            return Err(CopilotError::Other(format!(
+                "Unsupported conversation format version {} (expected at most {FORMAT_VERSION})",
+                record.version
+            )));
+        }
+        let id = if record.id.is_empty() {
+            generate_id()
+        } else {
+            record.id
+        };
+        Ok(Conversation {
+            client,
+            id,
+            parent: record.parent,
+            title: record.title,
+            system_prompt: record.system_prompt,
+            messages: record.messages,
+            model: record.model,
+            options: record.options,
+            hooks: Vec::new(),
+        })
+    }
+
+    /// Saves the conversation to `path` as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if serialization or writing the file fails.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CopilotError> {
+        let json = serde_json::to_string_pretty(&self.to_record())
+            .map_err(|e| CopilotError::Other(e.to_string()))?;
+        fs::write(path, json).map_err(|e| CopilotError::Other(e.to_string()))
+    }
+
+    /// Loads a conversation previously written by [`Conversation::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be read or parsed.
+    pub fn load(client: CopilotClient, path: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        let content = fs::read_to_string(path).map_err(|e| CopilotError::Other(e.to_string()))?;
+        let record: ConversationRecord =
+            serde_json::from_str(&content).map_err(|e| CopilotError::Other(e.to_string()))?;
+        Self::from_record(client, record)
+    }
+
+    /// Renders the conversation as a human-readable Markdown transcript.
+    pub fn to_markdown(&self) -> String {
+        self.to_record().to_markdown()
+    }
+
+    /// Renders the conversation as a standalone HTML page.
+    pub fn to_html(&self) -> String {
+        self.to_record().to_html()
+    }
+
+    /// Generates a short title from the first exchange and stores it on the conversation.
+    ///
+    /// Uses [`DEFAULT_TITLE_MODEL`] if the client has it, and the conversation's own model
+    /// otherwise. See [`Conversation::generate_title_with`] to choose the model explicitly.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the conversation is empty or the request fails.
+    pub async fn generate_title(&mut self) -> Result<String, CopilotError> {
+        let model = if self.client.has_model(DEFAULT_TITLE_MODEL) {
+            DEFAULT_TITLE_MODEL.to_string()
+        } else {
+            self.model.clone()
+        };
+        self.generate_title_with(model).await
+    }
+
+    /// Generates a short title from the first exchange using `model` and stores it on the
+    /// conversation.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the conversation is empty or the request fails.
+    pub async fn generate_title_with(
+        &mut self,
+        model: impl Into<String>,
+    ) -> Result<String, CopilotError> {
+        let excerpt = self
+            .messages
+            .iter()
+            .take(2)
+            .map(|m| {
+                let content = prompt::truncate_to_tokens(&m.content, TITLE_EXCERPT_TOKENS);
+                format!("{}: {}", role_heading(&m.role), content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if excerpt.is_empty() {
+            return Err(CopilotError::Other(
+                "Cannot generate a title for an empty conversation".into(),
+            ));
+        }
+        let options = ChatOptions {
+            temperature: 0.2,
+            max_tokens: Some(20),
+            ..ChatOptions::default()
+        };
+        let messages = vec![Message::system(TITLE_PROMPT), Message::user(excerpt)];
+        let response = self
+            .client
+            .chat_completion_with_options(messages, model.into(), &options)
+            .await?;
+        let raw = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .unwrap_or_default();
+        let title = clean_title(&raw);
+        if title.is_empty() {
+            return Err(CopilotError::Other("Model returned an empty title".into()));
+        }
+        self.title = Some(title.clone());
+        Ok(title)
+    }
+
+    /// Sends a user message and returns the assistant's reply.
+    ///
+    /// On success both the user message and the reply are appended to the history. On failure
+    /// the history is left unchanged, so the call can simply be retried. Registered
+    /// [`ConversationHook`]s run before the request is sent and after the reply is received.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a hook fails, the request fails, or the response contains no
+    /// choices.
+    pub async fn send(&mut self, content: impl Into<String>) -> Result<Message, CopilotError> {
+        self.send_message(Message::user(content)).await
+    }
+
+    /// Sends a user message built by the caller, for example with
+    /// [attachments](crate::attachment::attach), and returns the assistant's reply. Behaves
+    /// like [`Conversation::send`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a hook fails, the request fails, or the response contains no
+    /// choices.
+    pub async fn send_message(&mut self, user: Message) -> Result<Message, CopilotError> {
+        let messages = self.prepare(&user)?;
+        let response = self
+            .client
+            .chat_completion_with_options(messages, self.model.clone(), &self.options)
+            .await?;
+        let reply = response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .ok_or_else(|| CopilotError::Other("Chat response contained no choices".into()))?;
+        self.complete(user, reply)
+    }
+
+    /// Sends a user message, streaming the reply through `on_delta` as it is generated.
+    ///
+    /// Behaves like [`Conversation::send`]: the turn is only appended to the history once the
+    /// stream has completed successfully, and hooks see the complete reply.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a hook fails or the request fails before or during
+    /// streaming.
+    pub async fn send_streaming<F>(
+        &mut self,
+        content: impl Into<String>,
+        on_delta: F,
+    ) -> Result<Message, CopilotError>
+    where
+        F: FnMut(&str),
+    {
+        self.send_message_streaming(Message::user(content), on_delta)
+            .await
+    }
+
+    /// Sends a user message built by the caller, streaming the reply through `on_delta`.
+    /// Behaves like [`Conversation::send_streaming`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a hook fails or the request fails before or during
+    /// streaming.
+    pub async fn send_message_streaming<F>(
+        &mut self,
+        user: Message,
+        mut on_delta: F,
+    ) -> Result<Message, CopilotError>
+    where
+        F: FnMut(&str),
+    {
+        let messages = self.prepare(&user)?;
+        let mut stream = self
+            .client
+            .chat_completion_stream(messages, self.model.clone(), &self.options)
+            .await?;
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            if let Some(delta) = chunk?.content()
+                && !delta.is_empty()
+            {
+                on_delta(delta);
+                text.push_str(delta);
+            }
+        }
+        self.complete(user, Message::assistant(text))
+    }
+
+    /// Builds the outgoing messages for a new user turn and runs the before-send hooks.
+    fn prepare(&self, user: &Message) -> Result<Vec<Message>, CopilotError> {
+        let mut messages = self.request_messages();
+        messages.push(user.clone());
+        for hook in &self.hooks {
+            hook.before_send(&mut messages)?;
+        }
+        Ok(messages)
+    }
+
+    /// Runs the after-receive hooks and appends the completed turn to the history.
+    fn complete(&mut self, user: Message, mut reply: Message) -> Result<Message, CopilotError> {
+        for hook in &self.hooks {
+            hook.after_receive(&mut reply)?;
+        }
+        self.messages.push(user);
+        self.messages.push(reply.clone());
+        Ok(reply)
+    }
+}
+
+/// Stylesheet embedded in HTML transcripts.
+const HTML_STYLE: &str = "body{font-family:sans-serif;max-width:50em;margin:2em auto;padding:0 1em}\
+.meta{color:#666}section{border-left:4px solid #ccc;padding-left:1em;margin:1.5em 0}\
+section.user{border-color:#0969da}section.assistant{border-color:#1a7f37}\
+section.system{border-color:#8250df}.content{white-space:pre-wrap;font-family:monospace}";
+
+/// Escapes the characters that are significant in HTML text and attributes.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Returns the Markdown heading used for a message role.
+fn role_heading(role: &str) -> String {
+    let mut chars = role.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Normalizes a model-generated title: first line only, without quotes or trailing punctuation.
+fn clean_title(raw: &str) -> String {
+    let line = raw.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    line.trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '*' || c == '`')
+        .trim_end_matches(['.', '!', '?', ':'])
+        .trim()
+        .to_string()
+}
+
+/// Generates an identifier that is unique across processes and calls.
+///
+/// The id combines the current time in milliseconds, the process id, and a per-process counter.
+pub(crate) fn generate_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let millis = unix_time().as_millis();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{millis:x}-{:x}-{count:x}", process_id())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn process_id() -> u32 {
+    std::process::id()
+}
+
+/// Browser tabs have no process id, so a random number tells them apart instead.
+#[cfg(target_arch = "wasm32")]
+fn process_id() -> u32 {
+    (js_sys::Math::random() * f64::from(u32::MAX)) as u32
+}
@@ -0,0 +1,149 @@
+//! Coalescing of identical concurrent requests.
+//!
+//! With [`CopilotClient::with_deduplication`](crate::CopilotClient::with_deduplication), a
+//! request that is identical to one still in flight does not go upstream: it waits for the
+//! first one and receives a copy of its response or error. No task is spawned; the first caller
+//! drives the request, and if it is dropped before the response arrives, one of the waiting
+//! callers sends the request in its place.
+
+use crate::CopilotError;
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::{Future, poll_fn},
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+/// The requests in flight, by cache key.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    calls: Mutex<HashMap<String, Arc<Call>>>,
+}
+
+#[derive(Debug, Default)]
+struct Call {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+enum State {
+    /// The request is in flight; the wakers belong to the callers waiting for it.
+    Pending(Vec<Waker>),
+    /// The request finished with the boxed `Result<T, CopilotError>`.
+    Done(Box<dyn Any + Send + Sync>),
+    /// The caller driving the request was dropped before it finished.
+    Abandoned,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State::Pending(Vec::new())
+    }
+}
+
+impl InFlight {
+    /// Runs `request` unless a request with the same `key` is in flight, in which case its
+    /// result is awaited and cloned instead.
+    pub(crate) async fn run<T, F>(&self, key: String, request: F) -> Result<T, CopilotError>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: Future<Output = Result<T, CopilotError>>,
+    {
+        let mut request = Some(request);
+        loop {
+            let (call, leader) = {
+                let mut calls = lock(&self.calls);
+                match calls.get(&key) {
+                    Some(call) => (call.clone(), false),
+                    None => {
+                        let call = Arc::new(Call::default());
+                        calls.insert(key.clone(), call.clone());
+                        (call, true)
+                    }
+                }
+            };
+
+            if leader {
+                let guard = Leader {
+                    in_flight: self,
+                    key: &key,
+                    call: &call,
+                };
+                // The request is only taken by the leader, which returns.
+                let result = match request.take() {
+                    Some(request) => request.await,
+                    None => unreachable!("a deduplicated request is sent at most once"),
+                };
+                call.finish(State::Done(Box::new(result.clone())));
+                drop(guard);
+                return result;
+            }
+
+            tracing::debug!("Waiting for an identical request in flight");
+            if let Some(result) = wait::<T>(&call).await {
+                return result;
+            }
+            // The leader was dropped; try again, possibly as the leader.
+        }
+    }
+}
+
+/// Waits until `call` finishes. Returns `None` if it was abandoned.
+async fn wait<T: Clone + 'static>(call: &Call) -> Option<Result<T, CopilotError>> {
+    poll_fn(|cx| match &mut *lock(&call.state) {
+        State::Pending(wakers) => {
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+        State::Done(result) => {
+            Poll::Ready(result.downcast_ref::<Result<T, CopilotError>>().cloned())
+        }
+        State::Abandoned => Poll::Ready(None),
+    })
+    .await
+}
+
+/// Removes a call from the requests in flight and wakes its waiters when the caller driving it
+/// finishes or is dropped.
+struct Leader<'a> {
+    in_flight: &'a InFlight,
+    key: &'a str,
+    call: &'a Arc<Call>,
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        let mut calls = lock(&self.in_flight.calls);
+        if calls
+            .get(self.key)
+            .is_some_and(|call| Arc::ptr_eq(call, self.call))
+        {
+            calls.remove(self.key);
+        }
+        drop(calls);
+
+        // `run` finishes the call before dropping the guard, so a pending call was abandoned.
+        self.call.finish(State::Abandoned);
+    }
+}
+
+impl Call {
+    /// Stores the final `state` and wakes the waiters, unless the call already finished.
+    fn finish(&self, state: State) {
+        let mut current = lock(&self.state);
+        let State::Pending(wakers) = &mut *current else {
+            return;
+        };
+        let wakers = std::mem::take(wakers);
+        *current = state;
+        drop(current);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,339 @@
+//! Incremental decoding of embeddings responses into `f32` vectors.
+//!
+//! An embeddings response for a large batch is mostly decimal numbers, several times the size
+//! of the vectors they encode. [`CopilotClient::get_embeddings_f32`] decodes each embedding as
+//! soon as its bytes have arrived, into a vector allocated for the requested dimensions, and
+//! drops its text, so neither the whole body nor `f64` copies of the vectors are held at once.
+
+use crate::{
+    CopilotClient, CopilotError, EMBEDDING_DIMENSIONS, EMBEDDINGS_URL, EmbeddingRequest,
+    TokenUsage, record_usage,
+    transport::{HttpRequest, Method},
+    unix_time,
+};
+use futures_util::StreamExt;
+use serde::{
+    Deserialize,
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+};
+use std::fmt;
+use tracing::{Span, field::Empty};
+
+impl CopilotClient {
+    /// Like [`CopilotClient::get_embeddings`], but returns the vectors in `f32` precision, in the
+    /// order of `inputs`, decoding the response as it arrives. This keeps the peak memory of
+    /// large batches, such as those of an indexing job, several times lower.
+    ///
+    /// Responses are not cached or deduplicated.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the HTTP request fails, the response cannot be parsed, or it
+    /// does not contain one embedding per input.
+    #[tracing::instrument(
+        name = "copilot.get_embeddings",
+        skip_all,
+        fields(
+            model = self.embedding_model(),
+            inputs = inputs.len(),
+            prompt_tokens = Empty,
+            completion_tokens = Empty,
+            total_tokens = Empty,
+        ),
+        err(Display, level = "debug"),
+    )]
+    pub async fn get_embeddings_f32(
+        &self,
+        inputs: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, CopilotError> {
+        #[cfg(feature = "otel")]
+        let mut operation = crate::otel::Operation::embeddings(self.embedding_model());
+        let result = async {
+            let count = inputs.len();
+            let mut inputs = inputs;
+            if let Some(scanner) = &self.secret_scanner {
+                scanner.apply_to_inputs(&mut inputs)?;
+            }
+            let request_body = EmbeddingRequest {
+                dimensions: EMBEDDING_DIMENSIONS,
+                input: inputs,
+                model: self.embedding_model().to_string(),
+            };
+            let headers = self.get_headers().await?;
+            let request = HttpRequest::new(Method::POST, EMBEDDINGS_URL)
+                .headers(headers)
+                .json(&request_body)?;
+            self.acquire_budget()?;
+            let start = unix_time();
+            let mut body = self.send_request(request).await?.into_body();
+            let mut decoder = EmbeddingsDecoder::new(count, EMBEDDING_DIMENSIONS as usize);
+            while let Some(chunk) = body.next().await {
+                decoder.push(&chunk?)?;
+            }
+            let (embeddings, usage) = decoder.finish()?;
+            record_usage(&Span::current(), &request_body.model, usage.as_ref());
+            if let Some(tracker) = &self.usage_tracker {
+                tracker.record_embeddings_timed(
+                    &request_body.model,
+                    usage.as_ref(),
+                    Some(unix_time().saturating_sub(start)),
+                );
+            }
+            if let Some(budget) = &self.budget {
+                budget.record_tokens(usage.as_ref());
+            }
+            Ok((embeddings, usage))
+        }
+        .await;
+        #[cfg(feature = "otel")]
+        match &result {
+            Ok((_, usage)) => {
+                operation.record_usage(usage.as_ref());
+                operation.end(None);
+            }
+            Err(e) => operation.end(Some(e)),
+        }
+        result.map(|(embeddings, _)| embeddings)
+    }
+}
+
+/// A top-level value of the response that is being collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    /// An element of the `data` array.
+    Embedding,
+    /// The `usage` object.
+    Usage,
+}
+
+/// Scans the body of an embeddings response chunk by chunk, collecting the text of one element
+/// of `data` at a time and decoding it as soon as it is complete.
+#[derive(Debug)]
+struct EmbeddingsDecoder {
+    /// The number of embeddings expected.
+    count: usize,
+    dimensions: usize,
+    /// The depth of nested objects and arrays at the current position.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Whether the next string in the top-level object is a key.
+    expect_key: bool,
+    reading_key: bool,
+    /// The last key read in the top-level object.
+    key: Vec<u8>,
+    in_data: bool,
+    capture: Option<Capture>,
+    /// The text of the value being captured.
+    buffer: Vec<u8>,
+    embeddings: Vec<(usize, Vec<f32>)>,
+    usage: Option<TokenUsage>,
+    /// Whether the top-level object was closed.
+    done: bool,
+}
+
+impl EmbeddingsDecoder {
+    fn new(count: usize, dimensions: usize) -> Self {
+        EmbeddingsDecoder {
+            count,
+            dimensions,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            expect_key: false,
+            reading_key: false,
+            key: Vec::new(),
+            in_data: false,
+            capture: None,
+            buffer: Vec::new(),
+            embeddings: Vec::with_capacity(count),
+            usage: None,
+            done: false,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) -> Result<(), CopilotError> {
+        for &byte in chunk {
+            if self.capture.is_some() {
+                self.buffer.push(byte);
+            }
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                    self.reading_key = false;
+                } else if self.reading_key {
+                    self.key.push(byte);
+                }
+                continue;
+            }
+            match byte {
+                b'"' => {
+                    self.in_string = true;
+                    if self.depth == 1 && self.expect_key {
+                        self.expect_key = false;
+                        self.reading_key = true;
+                        self.key.clear();
+                    }
+                }
+                b'{' | b'[' => {
+                    let capture = match (self.depth, byte) {
+                        (1, b'[') if self.key == b"data" => {
+                            self.in_data = true;
+                            None
+                        }
+                        (1, b'{') if self.key == b"usage" => Some(Capture::Usage),
+                        (2, b'{') if self.in_data && self.capture.is_none() => {
+                            Some(Capture::Embedding)
+                        }
+                        _ => None,
+                    };
+                    if capture.is_some() {
+                        self.capture = capture;
+                        self.buffer.clear();
+                        self.buffer.push(byte);
+                    }
+                    self.depth += 1;
+                    if self.depth == 1 {
+                        self.expect_key = true;
+                    }
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.checked_sub(1).ok_or_else(invalid)?;
+                    match (self.depth, self.capture) {
+                        (2, Some(Capture::Embedding)) => {
+                            self.capture = None;
+                            self.embeddings.push(self.decode_embedding()?);
+                        }
+                        (1, Some(Capture::Usage)) => {
+                            self.capture = None;
+                            self.usage = serde_json::from_slice(&self.buffer).ok();
+                        }
+                        (1, _) if byte == b']' => self.in_data = false,
+                        (0, _) => self.done = true,
+                        _ => {}
+                    }
+                }
+                b',' if self.depth == 1 => self.expect_key = true,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_embedding(&self) -> Result<(usize, Vec<f32>), CopilotError> {
+        let mut deserializer = serde_json::Deserializer::from_slice(&self.buffer);
+        EmbeddingSeed(self.dimensions)
+            .deserialize(&mut deserializer)
+            .map_err(|e| CopilotError::Other(format!("Invalid embeddings response: {e}")))
+    }
+
+    /// Returns the vectors in input order and the usage.
+    fn finish(mut self) -> Result<(Vec<Vec<f32>>, Option<TokenUsage>), CopilotError> {
+        if !self.done {
+            return Err(invalid());
+        }
+        self.embeddings.sort_by_key(|(index, _)| *index);
+        if self.embeddings.len() != self.count
+            || self
+                .embeddings
+                .iter()
+                .enumerate()
+                .any(|(i, (index, _))| i != *index)
+        {
+            return Err(CopilotError::Other(format!(
+                "Expected {} embeddings, got {}",
+                self.count,
+                self.embeddings.len()
+            )));
+        }
+        let embeddings = self.embeddings.into_iter().map(|(_, vector)| vector);
+        Ok((embeddings.collect(), self.usage))
+    }
+}
+
+fn invalid() -> CopilotError {
+    CopilotError::Other("Invalid embeddings response: unbalanced JSON".into())
+}
+
+/// Decodes an element of `data` into its index and vector.
+struct EmbeddingSeed(usize);
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Index,
+    Embedding,
+    #[serde(other)]
+    Other,
+}
+
+impl<'de> DeserializeSeed<'de> for EmbeddingSeed {
+    type Value = (usize, Vec<f32>);
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EmbeddingSeed {
+    type Value = (usize, Vec<f32>);
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an embedding object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut index = None;
+        let mut vector = None;
+        while let Some(key) = map.next_key::<Field>()? {
+            match key {
+                Field::Index => index = Some(map.next_value()?),
+                Field::Embedding => vector = Some(map.next_value_seed(VectorSeed(self.0))?),
+                Field::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok((
+            index.ok_or_else(|| de::Error::missing_field("index"))?,
+            vector.ok_or_else(|| de::Error::missing_field("embedding"))?,
+        ))
+    }
+}
+
+/// Decodes a vector into a buffer allocated for the expected dimensions.
+struct VectorSeed(usize);
+
+impl<'de> DeserializeSeed<'de> for VectorSeed {
+    type Value = Vec<f32>;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for VectorSeed {
+    type Value = Vec<f32>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of numbers")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut vector = Vec::with_capacity(self.0);
+        while let Some(value) = seq.next_element::<f32>()? {
+            vector.push(value);
+        }
+        Ok(vector)
+    }
+}
@@ -0,0 +1,163 @@
+//! Falling back to other models when a chat completion fails.
+//!
+//! A [`FallbackPolicy`] attached with [`CopilotClient::with_fallback`] maps a model to the models
+//! to try instead, in order. When a chat completion for that model is rate limited, fails with a
+//! server error, or is stopped by the content filter, the client sends the same request to the
+//! next model of the chain and returns the first result that does not call for a fallback, or
+//! the result of the last model. [`ChatResponse::model`] and [`ChatStream::model`] report the
+//! model that answered.
+//!
+//! ```
+//! use copilot_client::fallback::FallbackPolicy;
+//!
+//! let policy = FallbackPolicy::new()
+//!     .with_chain("gpt-4o", ["gpt-4o-mini"])
+//!     .on_content_filter(false);
+//! assert_eq!(policy.fallbacks("gpt-4o"), ["gpt-4o-mini"]);
+//! ```
+//!
+//! [`CopilotClient::with_fallback`]: crate::CopilotClient::with_fallback
+//! [`ChatStream::model`]: crate::ChatStream::model
+
+use crate::{
+    ChatResponse, CopilotError,
+    hooks::{Hooks, RetryEvent},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+};
+
+/// Which models to try when a chat completion fails, and on which failures.
+///
+/// All failure kinds trigger a fallback by default.
+#[derive(Debug, Clone)]
+pub struct FallbackPolicy {
+    chains: HashMap<String, Vec<String>>,
+    on_rate_limit: bool,
+    on_server_error: bool,
+    on_content_filter: bool,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FallbackPolicy {
+    /// Creates a policy without fallback chains.
+    pub fn new() -> Self {
+        FallbackPolicy {
+            chains: HashMap::new(),
+            on_rate_limit: true,
+            on_server_error: true,
+            on_content_filter: true,
+        }
+    }
+
+    /// Tries `fallbacks`, in order, when a request for `model` fails. Replaces an earlier chain
+    /// for `model`. The fallback models' own chains are not followed.
+    pub fn with_chain(
+        mut self,
+        model: impl Into<String>,
+        fallbacks: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.chains.insert(
+            model.into(),
+            fallbacks.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    /// Sets whether rate-limited requests (status 429) fall back. Defaults to `true`.
+    pub fn on_rate_limit(mut self, enabled: bool) -> Self {
+        self.on_rate_limit = enabled;
+        self
+    }
+
+    /// Sets whether server errors (status 5xx) and dropped connections fall back. Defaults to
+    /// `true`.
+    pub fn on_server_error(mut self, enabled: bool) -> Self {
+        self.on_server_error = enabled;
+        self
+    }
+
+    /// Sets whether requests rejected or responses stopped by the content filter fall back.
+    /// Defaults to `true`.
+    pub fn on_content_filter(mut self, enabled: bool) -> Self {
+        self.on_content_filter = enabled;
+        self
+    }
+
+    /// Returns the models tried after `model`, in order.
+    pub fn fallbacks(&self, model: &str) -> &[String] {
+        self.chains.get(model).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns `true` if a request failing with `error` should be sent to the next model.
+    pub fn falls_back_on(&self, error: &CopilotError) -> bool {
+        (self.on_rate_limit && error.is_rate_limited())
+            || (self.on_server_error
+                && (matches!(error, CopilotError::HttpError(_))
+                    || matches!(error.status(), Some(500..=599))))
+            || (self.on_content_filter && error.is_content_filtered())
+    }
+
+    /// Returns `true` if `response` should be replaced by that of the next model because the
+    /// content filter stopped one of its choices.
+    pub fn falls_back_on_response(&self, response: &ChatResponse) -> bool {
+        self.on_content_filter
+            && response
+                .choices
+                .iter()
+                .any(|choice| choice.finish_reason.as_deref() == Some("content_filter"))
+    }
+
+    /// Sends a request with `send`, first for `model_id` and then for its fallbacks, until a
+    /// result does not call for a fallback. Each fallback is reported to the retry hooks.
+    pub(crate) async fn run<T, F, Fut>(
+        &self,
+        model_id: String,
+        hooks: Option<&Hooks>,
+        tags: &BTreeMap<String, String>,
+        falls_back_on_response: impl Fn(&T) -> bool,
+        mut send: F,
+    ) -> Result<T, CopilotError>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<T, CopilotError>>,
+    {
+        let mut fallbacks = self.fallbacks(&model_id).to_vec().into_iter();
+        let mut model = model_id;
+        let mut attempt = 1;
+        loop {
+            let result = send(model.clone()).await;
+            let Some(next) = fallbacks.next() else {
+                return result;
+            };
+            let reason = match &result {
+                Ok(response) if falls_back_on_response(response) => {
+                    tracing::debug!(%model, fallback = %next, "Content filtered, falling back");
+                    "Content filtered".to_string()
+                }
+                Err(e) if self.falls_back_on(e) => {
+                    tracing::debug!(%model, fallback = %next, error = %e, "Falling back");
+                    e.to_string()
+                }
+                _ => return result,
+            };
+            attempt += 1;
+            if let Some(hooks) = hooks {
+                hooks.retry(&RetryEvent {
+                    model,
+                    next_model: next.clone(),
+                    attempt,
+                    reason,
+                    tags: tags.clone(),
+                });
+            }
+            model = next;
+        }
+    }
+}
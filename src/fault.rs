@@ -0,0 +1,251 @@
+//! Fault injection for resilience tests, enabled with the `test-util` feature.
+//!
+//! A [`FaultTransport`] wraps another transport and disturbs the requests it is told to: it can
+//! delay them, answer them with an error status, fail them as if the connection was reset, cut
+//! off their response bodies, or slip a malformed event into a streamed response. Together with
+//! [`MockCopilot`](crate::test_util::MockCopilot), this exercises the error handling of code
+//! built on the client without a flaky network:
+//!
+//! ```
+//! use copilot_client::{
+//!     CopilotClient, Message,
+//!     fault::{Fault, FaultTransport},
+//!     test_util::{self, MockCopilot},
+//! };
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), copilot_client::CopilotError> {
+//! let mock = MockCopilot::start().await;
+//! mock.mock_chat(test_util::chat_response("Hi there")).await;
+//! let faults = FaultTransport::new(mock.transport());
+//! let client = CopilotClient::new_with_transport(
+//!     test_util::GITHUB_TOKEN.to_string(),
+//!     "test-editor/1.0".to_string(),
+//!     faults.clone(),
+//! )
+//! .await?;
+//!
+//! faults.inject("/chat/completions", Fault::rate_limited(Some(1)));
+//! let messages = vec![Message::user("Hello")];
+//! let error = client
+//!     .chat_completion(messages.clone(), test_util::MODEL.to_string())
+//!     .await
+//!     .unwrap_err();
+//! assert!(error.is_rate_limited());
+//! // The fault applied once; the next request goes through.
+//! client.chat_completion(messages, test_util::MODEL.to_string()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    CopilotError,
+    transport::{
+        BoxStream, HeaderMap, HttpRequest, HttpResponse, HttpTransport, StatusCode, TransportFuture,
+    },
+};
+use bytes::Bytes;
+use futures_util::{StreamExt, stream};
+use http::header::{CONTENT_TYPE, HeaderValue, RETRY_AFTER};
+use serde_json::json;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A disturbance applied to a request by a [`FaultTransport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Waits before sending the request.
+    Delay(Duration),
+    /// Answers with an error status and a JSON error body, without sending the request.
+    Status {
+        /// The HTTP status code.
+        status: u16,
+        /// The value of the `Retry-After` header, in seconds.
+        retry_after: Option<u64>,
+    },
+    /// Fails with `CopilotError::HttpError`, as if the connection was reset before a response
+    /// arrived.
+    ConnectionReset,
+    /// Sends the request, then fails the response body with `CopilotError::HttpError` after
+    /// `after_bytes` bytes, as if the connection was reset mid-stream.
+    TruncateBody {
+        /// The number of body bytes delivered before the failure.
+        after_bytes: usize,
+    },
+    /// Sends the request and puts an event that is not valid JSON before the response body,
+    /// which a [`ChatStream`](crate::ChatStream) yields as an error.
+    MalformedEvent,
+}
+
+impl Fault {
+    /// A `429 Too Many Requests` answer, optionally asking to retry after `retry_after` seconds.
+    pub fn rate_limited(retry_after: Option<u64>) -> Self {
+        Fault::Status {
+            status: 429,
+            retry_after,
+        }
+    }
+}
+
+/// A transport that injects [`Fault`]s into the requests it sends through an inner transport.
+///
+/// Faults are registered for an endpoint, matched as a substring of the request URL, and apply
+/// once or, with [`FaultTransport::inject_always`], to every matching request. Clones share
+/// the registered faults, so a test can keep one to inject faults into a client built with
+/// another.
+#[derive(Debug)]
+pub struct FaultTransport<T> {
+    inner: Arc<T>,
+    rules: Arc<Mutex<Vec<Rule>>>,
+}
+
+impl<T> Clone for FaultTransport<T> {
+    fn clone(&self) -> Self {
+        FaultTransport {
+            inner: self.inner.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    endpoint: String,
+    fault: Fault,
+    once: bool,
+}
+
+impl<T: HttpTransport> FaultTransport<T> {
+    /// Creates a transport that passes requests to `inner` until faults are injected.
+    pub fn new(inner: T) -> Self {
+        FaultTransport {
+            inner: Arc::new(inner),
+            rules: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Applies `fault` to the next request to `endpoint`, such as `"/chat/completions"`.
+    /// Faults injected for the same request apply in the order they were injected.
+    pub fn inject(&self, endpoint: &str, fault: Fault) {
+        self.push(endpoint, fault, true);
+    }
+
+    /// Applies `fault` to every request to `endpoint` until [`FaultTransport::clear`] is
+    /// called.
+    pub fn inject_always(&self, endpoint: &str, fault: Fault) {
+        self.push(endpoint, fault, false);
+    }
+
+    /// Removes all injected faults.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn push(&self, endpoint: &str, fault: Fault, once: bool) {
+        self.lock().push(Rule {
+            endpoint: endpoint.to_string(),
+            fault,
+            once,
+        });
+    }
+
+    /// Removes and returns the faults for `url`, keeping those that apply to every request.
+    fn take(&self, url: &str) -> Vec<Fault> {
+        let mut rules = self.lock();
+        let mut faults = Vec::new();
+        rules.retain(|rule| {
+            if !url.contains(&rule.endpoint) {
+                return true;
+            }
+            faults.push(rule.fault.clone());
+            !rule.once
+        });
+        faults
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Rule>> {
+        self.rules.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: HttpTransport> HttpTransport for FaultTransport<T> {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        let faults = self.take(&request.url);
+        Box::pin(async move {
+            let mut truncate = None;
+            let mut malformed = false;
+            for fault in faults {
+                match fault {
+                    Fault::Delay(duration) => tokio::time::sleep(duration).await,
+                    Fault::Status {
+                        status,
+                        retry_after,
+                    } => return Ok(status_response(status, retry_after)),
+                    Fault::ConnectionReset => return Err(connection_reset()),
+                    Fault::TruncateBody { after_bytes } => truncate = Some(after_bytes),
+                    Fault::MalformedEvent => malformed = true,
+                }
+            }
+            let response = self.inner.send(request).await?;
+            if truncate.is_none() && !malformed {
+                return Ok(response);
+            }
+            let status = response.status;
+            let headers = response.headers.clone();
+            let mut body = response.into_body();
+            if malformed {
+                let event = Bytes::from_static(b"data: {\"choices\": [\n\n");
+                body = Box::pin(stream::once(async { Ok(event) }).chain(body));
+            }
+            if let Some(after_bytes) = truncate {
+                body = truncate_body(body, after_bytes);
+            }
+            Ok(HttpResponse::new(status, headers, body))
+        })
+    }
+}
+
+fn connection_reset() -> CopilotError {
+    CopilotError::HttpError("connection reset (injected fault)".to_string())
+}
+
+fn status_response(status: u16, retry_after: Option<u64>) -> HttpResponse {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    if let Some(seconds) = retry_after {
+        headers.insert(RETRY_AFTER, HeaderValue::from(seconds));
+    }
+    let body = json!({ "error": { "message": "injected fault" } }).to_string();
+    HttpResponse::from_bytes(status, headers, body)
+}
+
+/// Passes the first `after_bytes` bytes of `body` and then fails.
+fn truncate_body(
+    body: BoxStream<Result<Bytes, CopilotError>>,
+    after_bytes: usize,
+) -> BoxStream<Result<Bytes, CopilotError>> {
+    Box::pin(stream::unfold(
+        Some((body, after_bytes)),
+        |state| async move {
+            let (mut body, remaining) = state?;
+            if remaining == 0 {
+                return Some((Err(connection_reset()), None));
+            }
+            match body.next().await {
+                Some(Ok(mut bytes)) => {
+                    if bytes.len() > remaining {
+                        bytes.truncate(remaining);
+                    }
+                    let remaining = remaining - bytes.len();
+                    Some((Ok(bytes), Some((body, remaining))))
+                }
+                // Errors and the end of the body pass through.
+                Some(Err(e)) => Some((Err(e), None)),
+                None => None,
+            }
+        },
+    ))
+}
@@ -0,0 +1,213 @@
+//! C bindings, enabled with the `ffi` feature.
+//!
+//! The declarations are in `include/copilot_client.h`, which is regenerated whenever the crate
+//! is built with the feature. Build a shared or static library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! All strings are NUL-terminated UTF-8. Strings returned by these functions belong to the
+//! caller and must be released with [`copilot_string_free`]. Functions that fail return `NULL`
+//! and set a message that [`copilot_last_error`] returns on the same thread.
+//!
+//! Calls block the calling thread until the request completes; stream callbacks run on that
+//! thread as well.
+
+use crate::{ChatOptions, CopilotClient, CopilotError, get_github_token, openai};
+use futures_util::StreamExt;
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_void},
+    ptr,
+};
+use tokio::runtime::{Builder, Runtime};
+
+/// A client together with the runtime its requests run on. Opaque to C.
+pub struct CopilotHandle {
+    runtime: Runtime,
+    client: CopilotClient,
+}
+
+/// Receives each piece of a streamed reply, together with the `user_data` given to
+/// [`copilot_chat`]. The string is only valid during the call. May be `NULL`.
+pub type CopilotDeltaCallback = Option<extern "C" fn(delta: *const c_char, user_data: *mut c_void)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Creates a client and fetches the available models.
+///
+/// `github_token` may be `NULL` to discover the token from the environment. Returns `NULL` on
+/// failure. Release the client with [`copilot_client_free`].
+///
+/// # Safety
+///
+/// `github_token` must be `NULL` or a valid C string, and `editor_version` a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn copilot_client_new(
+    github_token: *const c_char,
+    editor_version: *const c_char,
+) -> *mut CopilotHandle {
+    let result = (|| {
+        let editor_version = unsafe { read_str(editor_version) }?
+            .ok_or_else(|| CopilotError::Other("editor_version must not be NULL".into()))?;
+        let github_token = match unsafe { read_str(github_token) }? {
+            Some(token) => token,
+            None => get_github_token().map_err(|e| CopilotError::TokenError(e.to_string()))?,
+        };
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| CopilotError::Other(e.to_string()))?;
+        let client =
+            runtime.block_on(CopilotClient::new_with_models(github_token, editor_version))?;
+        Ok(CopilotHandle { runtime, client })
+    })();
+    match result {
+        Ok(handle) => Box::into_raw(Box::new(handle)),
+        Err(e) => fail(e),
+    }
+}
+
+/// Releases a client created with [`copilot_client_new`]. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `client` must be `NULL` or a pointer returned by [`copilot_client_new`] that has not been
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn copilot_client_free(client: *mut CopilotHandle) {
+    if !client.is_null() {
+        drop(unsafe { Box::from_raw(client) });
+    }
+}
+
+/// Returns the available models as a JSON array, or `NULL` on failure.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`copilot_client_new`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn copilot_models(client: *const CopilotHandle) -> *mut c_char {
+    let Some(handle) = (unsafe { client.as_ref() }) else {
+        return fail(CopilotError::Other("client must not be NULL".into()));
+    };
+    match serde_json::to_string(handle.client.models()) {
+        Ok(json) => into_c_string(json),
+        Err(e) => fail(CopilotError::Other(e.to_string())),
+    }
+}
+
+/// Sends a chat request and returns the reply text, or `NULL` on failure.
+///
+/// `messages_json` is a JSON array of messages in the OpenAI chat format. `options_json` may be
+/// `NULL` or a JSON object with the fields of [`ChatOptions`]. If `on_delta` is given, the reply
+/// is streamed and each piece is passed to it with `user_data`.
+///
+/// # Safety
+///
+/// `client` must be a valid pointer returned by [`copilot_client_new`], `model` and
+/// `messages_json` valid C strings, and `options_json` `NULL` or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn copilot_chat(
+    client: *const CopilotHandle,
+    model: *const c_char,
+    messages_json: *const c_char,
+    options_json: *const c_char,
+    on_delta: CopilotDeltaCallback,
+    user_data: *mut c_void,
+) -> *mut c_char {
+    let result = (|| {
+        let handle = unsafe { client.as_ref() }
+            .ok_or_else(|| CopilotError::Other("client must not be NULL".into()))?;
+        let model = unsafe { read_str(model) }?
+            .ok_or_else(|| CopilotError::Other("model must not be NULL".into()))?;
+        let messages = unsafe { read_str(messages_json) }?
+            .ok_or_else(|| CopilotError::Other("messages_json must not be NULL".into()))?;
+        let messages = serde_json::from_str(&messages)
+            .map_err(|e| CopilotError::Other(e.to_string()))
+            .and_then(|value| openai::messages_from_openai(&value))?;
+        let options = match unsafe { read_str(options_json) }? {
+            Some(json) => {
+                serde_json::from_str(&json).map_err(|e| CopilotError::Other(e.to_string()))?
+            }
+            None => ChatOptions::default(),
+        };
+
+        let client = &handle.client;
+        handle.runtime.block_on(async {
+            let Some(on_delta) = on_delta else {
+                let response = client
+                    .chat_completion_with_options(messages, model, &options)
+                    .await?;
+                return Ok(response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .map(|c| c.message.content)
+                    .unwrap_or_default());
+            };
+            let mut stream = client
+                .chat_completion_stream(messages, model, &options)
+                .await?;
+            let mut content = String::new();
+            while let Some(chunk) = stream.next().await {
+                if let Some(delta) = chunk?.content() {
+                    content.push_str(delta);
+                    // Content with interior NULs cannot be passed on; it is still returned.
+                    if let Ok(delta) = CString::new(delta) {
+                        on_delta(delta.as_ptr(), user_data);
+                    }
+                }
+            }
+            Ok(content)
+        })
+    })();
+    match result {
+        Ok(content) => into_c_string(content),
+        Err(e) => fail(e),
+    }
+}
+
+/// Releases a string returned by this library. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `s` must be `NULL` or a string returned by this library that has not been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn copilot_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Returns the message of the last failure on this thread, or `NULL` if there was none. The
+/// string is owned by the library and valid until the next call on this thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn copilot_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Reads a C string, returning `None` for `NULL`.
+unsafe fn read_str(s: *const c_char) -> Result<Option<String>, CopilotError> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map(|s| Some(s.to_string()))
+        .map_err(|e| CopilotError::Other(e.to_string()))
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(e) => fail(CopilotError::Other(e.to_string())),
+    }
+}
+
+/// Records `error` for [`copilot_last_error`] and returns `NULL`.
+fn fail<T>(error: CopilotError) -> *mut T {
+    let message = CString::new(error.to_string().replace('\0', " ")).ok();
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+    ptr::null_mut()
+}
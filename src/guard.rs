@@ -0,0 +1,210 @@
+//! Output guardrails.
+//!
+//! [`Guardrails`] check the text of each choice as it is generated and can cut a reply short or
+//! reject it, for applications that must enforce output policies. Attached to a client with
+//! [`CopilotClient::with_guardrails`], they apply to every chat completion: complete responses
+//! are checked once, and streamed responses after every chunk, so that a stream is ended as soon
+//! as a guard stops it and the rest of the reply is never generated.
+//!
+//! ```
+//! use copilot_client::guard::{Guardrails, Verdict};
+//! use regex::Regex;
+//!
+//! let guardrails = Guardrails::new()
+//!     .max_length(4_000)
+//!     .stop_on_regex(Regex::new(r"(?m)^-----BEGIN").unwrap())
+//!     .with_guard(|text: &str| {
+//!         if text.contains("internal-only") {
+//!             Verdict::Reject("mentions internal data".to_string())
+//!         } else {
+//!             Verdict::Continue
+//!         }
+//!     });
+//! ```
+//!
+//! [`CopilotClient::with_guardrails`]: crate::CopilotClient::with_guardrails
+
+use crate::{ChatResponse, CopilotError, stream::ChatStream};
+use futures_util::{StreamExt, stream};
+use regex::Regex;
+use std::{fmt, sync::Arc};
+
+/// What a guard decides about the text of a choice generated so far.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The text is acceptable so far.
+    Continue,
+    /// End the reply after the given number of bytes of the text. The reply ends with the
+    /// finish reason `"stop"`.
+    Truncate(usize),
+    /// Reject the reply for the given reason.
+    Reject(String),
+}
+
+/// A check on generated text.
+///
+/// Guards see the whole text of a choice generated so far, which grows with each chunk of a
+/// streamed response, and must decide the same way for the same text. Closures taking a `&str`
+/// and returning a [`Verdict`] are guards.
+pub trait OutputGuard: Send + Sync {
+    /// Inspects the text generated so far.
+    fn check(&self, text: &str) -> Verdict;
+}
+
+impl<F> OutputGuard for F
+where
+    F: Fn(&str) -> Verdict + Send + Sync,
+{
+    fn check(&self, text: &str) -> Verdict {
+        self(text)
+    }
+}
+
+/// A set of guards applied to chat completion output in order. The first verdict other than
+/// [`Verdict::Continue`] wins.
+#[derive(Clone, Default)]
+pub struct Guardrails {
+    guards: Vec<Arc<dyn OutputGuard>>,
+}
+
+impl fmt::Debug for Guardrails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guardrails")
+            .field("guards", &self.guards.len())
+            .finish()
+    }
+}
+
+impl Guardrails {
+    /// Creates an empty set of guards.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a guard.
+    pub fn with_guard(mut self, guard: impl OutputGuard + 'static) -> Self {
+        self.guards.push(Arc::new(guard));
+        self
+    }
+
+    /// Ends replies after `max_chars` characters.
+    pub fn max_length(self, max_chars: usize) -> Self {
+        self.with_guard(move |text: &str| match text.char_indices().nth(max_chars) {
+            Some((end, _)) => Verdict::Truncate(end),
+            None => Verdict::Continue,
+        })
+    }
+
+    /// Ends replies where `pattern` first matches, leaving out the match.
+    pub fn stop_on_regex(self, pattern: Regex) -> Self {
+        self.with_guard(move |text: &str| match pattern.find(text) {
+            Some(found) => Verdict::Truncate(found.start()),
+            None => Verdict::Continue,
+        })
+    }
+
+    /// Returns the first verdict on `text` other than [`Verdict::Continue`]. Truncation points
+    /// inside a character are moved back to its start.
+    pub fn check(&self, text: &str) -> Verdict {
+        let verdict = self
+            .guards
+            .iter()
+            .map(|guard| guard.check(text))
+            .find(|verdict| *verdict != Verdict::Continue)
+            .unwrap_or(Verdict::Continue);
+        match verdict {
+            Verdict::Truncate(end) => {
+                let mut end = end.min(text.len());
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                Verdict::Truncate(end)
+            }
+            verdict => verdict,
+        }
+    }
+
+    /// Applies the guards to the content of every choice of `response`, truncating choices in
+    /// place.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a guard rejects a choice.
+    pub fn apply(&self, response: &mut ChatResponse) -> Result<(), CopilotError> {
+        for choice in &mut response.choices {
+            match self.check(&choice.message.content) {
+                Verdict::Continue => {}
+                Verdict::Truncate(end) => {
+                    choice.message.content.truncate(end);
+                    choice.finish_reason = Some("stop".to_string());
+                }
+                Verdict::Reject(reason) => return Err(rejected(&reason)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rejected(reason: &str) -> CopilotError {
+    CopilotError::Other(format!("Reply rejected by a guardrail: {reason}"))
+}
+
+/// The text of each choice of a guarded stream generated so far.
+struct GuardState {
+    guardrails: Guardrails,
+    texts: Vec<String>,
+    done: bool,
+}
+
+impl ChatStream {
+    /// Applies `guardrails` to the content of every choice after each chunk. When a guard
+    /// truncates a choice, the chunk is cut at that point, given the finish reason `"stop"`,
+    /// and the stream ends; when a guard rejects a choice, the stream yields an error and ends.
+    /// Ending the stream drops the response, so the server stops generating.
+    pub fn with_guardrails(self, guardrails: Guardrails) -> Self {
+        let state = GuardState {
+            guardrails,
+            texts: Vec::new(),
+            done: false,
+        };
+        self.map_inner(|inner| {
+            Box::pin(stream::unfold(
+                (inner, state),
+                |(mut inner, mut state)| async move {
+                    if state.done {
+                        return None;
+                    }
+                    let mut chunk = match inner.next().await? {
+                        Ok(chunk) => chunk,
+                        Err(e) => return Some((Err(e), (inner, state))),
+                    };
+                    for choice in &mut chunk.choices {
+                        let Some(delta) = choice.delta.content.as_mut() else {
+                            continue;
+                        };
+                        let index = choice.index as usize;
+                        if state.texts.len() <= index {
+                            state.texts.resize(index + 1, String::new());
+                        }
+                        let text = &mut state.texts[index];
+                        let start = text.len();
+                        text.push_str(delta);
+                        match state.guardrails.check(text) {
+                            Verdict::Continue => {}
+                            Verdict::Truncate(end) => {
+                                delta.truncate(end.saturating_sub(start));
+                                choice.finish_reason = Some("stop".to_string());
+                                state.done = true;
+                            }
+                            Verdict::Reject(reason) => {
+                                state.done = true;
+                                return Some((Err(rejected(&reason)), (inner, state)));
+                            }
+                        }
+                    }
+                    Some((Ok(chunk), (inner, state)))
+                },
+            ))
+        })
+    }
+}
@@ -0,0 +1,110 @@
+//! Health checks of the credentials and the Copilot API.
+//!
+//! [`CopilotClient::health_check`] exchanges the GitHub token for a Copilot token and lists the
+//! models with it, and reports how each step went instead of failing, which suits diagnostic
+//! output and readiness probes:
+//!
+//! ```no_run
+//! # async fn run(client: copilot_client::CopilotClient) {
+//! let report = client.health_check().await;
+//! if !report.is_healthy() {
+//!     eprintln!("Copilot is unavailable: {report:?}");
+//! }
+//! # }
+//! ```
+
+use crate::{
+    CopilotClient, CopilotError, MODELS_URL, ModelsResponse, request_id,
+    transport::{HttpRequest, Method},
+    unix_time,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The outcome of [`CopilotClient::health_check`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Exchanging the GitHub token for a Copilot token.
+    pub auth: HealthCheck,
+    /// Listing the models with the Copilot token, or `None` if authentication failed.
+    pub api: Option<HealthCheck>,
+    /// The number of models listed, if the API answered.
+    pub model_count: Option<usize>,
+}
+
+impl HealthReport {
+    /// Returns `true` if authentication succeeded and the API answered.
+    pub fn is_healthy(&self) -> bool {
+        self.auth.is_ok() && self.api.as_ref().is_some_and(HealthCheck::is_ok)
+    }
+}
+
+/// The outcome of one step of a health check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// Milliseconds until the step succeeded or failed.
+    pub latency_ms: u64,
+    /// The HTTP status of the error response, if the step failed with one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// The error message, if the step failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HealthCheck {
+    /// Returns `true` if the step succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    fn new<T>(latency_ms: u64, result: &Result<T, CopilotError>) -> Self {
+        HealthCheck {
+            latency_ms,
+            status: result.as_ref().err().and_then(CopilotError::status),
+            error: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+}
+
+impl CopilotClient {
+    /// Checks that the GitHub token can be exchanged for a Copilot token and that the API
+    /// answers a model listing with it. The exchange is made even if a token is cached.
+    /// Failures are reported in the [`HealthReport`] rather than returned as errors.
+    ///
+    /// The client's model list is not updated.
+    #[tracing::instrument(name = "copilot.health_check", skip_all)]
+    pub async fn health_check(&self) -> HealthReport {
+        let start = unix_time();
+        let headers = self.refresh_copilot_token().await;
+        let auth = HealthCheck::new(elapsed_ms(start), &headers);
+        let Ok(headers) = headers else {
+            return HealthReport {
+                auth,
+                api: None,
+                model_count: None,
+            };
+        };
+
+        let start = unix_time();
+        let models = async {
+            let mut headers = headers;
+            request_id::set(&mut headers, None);
+            let request = HttpRequest::new(Method::GET, MODELS_URL).headers(headers);
+            self.send_request(request)
+                .await?
+                .json::<ModelsResponse>()
+                .await
+        }
+        .await;
+        HealthReport {
+            auth,
+            api: Some(HealthCheck::new(elapsed_ms(start), &models)),
+            model_count: models.ok().map(|models| models.data.len()),
+        }
+    }
+}
+
+fn elapsed_ms(start: Duration) -> u64 {
+    unix_time().saturating_sub(start).as_millis() as u64
+}
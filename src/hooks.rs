@@ -0,0 +1,178 @@
+//! Callbacks on request lifecycle events.
+//!
+//! [`Hooks`] attached with [`CopilotClient::with_hooks`] are called when the client sends a
+//! request, receives a response or fails to, retries a request, and refreshes its Copilot
+//! token, so that applications can log, audit, or show progress without wrapping every call.
+//! Hooks receive metadata only: credentials in headers are redacted, and bodies are reduced to
+//! their length.
+//!
+//! ```
+//! use copilot_client::hooks::Hooks;
+//!
+//! let hooks = Hooks::new()
+//!     .on_request(|event| eprintln!("-> {} {}", event.method, event.url))
+//!     .on_response(|event| eprintln!("<- {:?} in {:?}", event.status, event.latency))
+//!     .on_retry(|event| eprintln!("retrying with {}: {}", event.next_model, event.reason));
+//! ```
+//!
+//! Hooks run synchronously on the task making the request, so they should return quickly;
+//! send events to a channel for slow work.
+//!
+//! [`CopilotClient::with_hooks`]: crate::CopilotClient::with_hooks
+
+use crate::{redacted_headers, request_id, transport::HttpRequest};
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+type Hook<E> = Arc<dyn Fn(&E) + Send + Sync>;
+
+/// A request about to be sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestEvent {
+    /// The HTTP method.
+    pub method: String,
+    /// The URL.
+    pub url: String,
+    /// The headers, with credentials and values marked as sensitive redacted.
+    pub headers: BTreeMap<String, String>,
+    /// The length of the body in bytes.
+    pub body_len: usize,
+    /// The client's ID for the request, from its `X-Request-Id` header.
+    pub request_id: Option<String>,
+    /// The [tags](crate::ChatOptions::tags) of the request.
+    pub tags: BTreeMap<String, String>,
+}
+
+impl RequestEvent {
+    pub(crate) fn new(request: &HttpRequest, tags: &BTreeMap<String, String>) -> Self {
+        RequestEvent {
+            method: request.method.to_string(),
+            url: request.url.clone(),
+            headers: redacted_headers(&request.headers),
+            body_len: request.body.as_ref().map_or(0, |body| body.len()),
+            request_id: request_id::client_id(&request.headers),
+            tags: tags.clone(),
+        }
+    }
+}
+
+/// The outcome of a request, reported once the response headers have arrived or the request
+/// has failed. Streamed responses are reported before their body is read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseEvent {
+    /// The HTTP method of the request.
+    pub method: String,
+    /// The URL of the request.
+    pub url: String,
+    /// The HTTP status, or `None` if no response was received.
+    pub status: Option<u16>,
+    /// The time until the response headers arrived or the request failed.
+    pub latency: Duration,
+    /// The error the request failed with, including error statuses, if any.
+    pub error: Option<String>,
+    /// The client's ID for the request, from its `X-Request-Id` header.
+    pub request_id: Option<String>,
+    /// The ID GitHub assigned to the request, from the `X-GitHub-Request-Id` header.
+    pub server_request_id: Option<String>,
+    /// The [tags](crate::ChatOptions::tags) of the request.
+    pub tags: BTreeMap<String, String>,
+}
+
+/// A chat completion sent again after a failure: retried by
+/// [`CopilotClient::chat_completion_batch`] or sent to a fallback model.
+///
+/// [`CopilotClient::chat_completion_batch`]: crate::CopilotClient::chat_completion_batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryEvent {
+    /// The model of the failed attempt.
+    pub model: String,
+    /// The model of the next attempt, the same as `model` unless it is a fallback.
+    pub next_model: String,
+    /// The number of the next attempt, counted from 1.
+    pub attempt: u32,
+    /// Why the previous attempt failed.
+    pub reason: String,
+    /// The [tags](crate::ChatOptions::tags) of the request.
+    pub tags: BTreeMap<String, String>,
+}
+
+/// A Copilot token exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenRefreshEvent {
+    /// The Unix timestamp at which the new token expires, if the exchange succeeded.
+    pub expires_at: Option<u64>,
+    /// The error the exchange failed with, if any.
+    pub error: Option<String>,
+}
+
+/// Callbacks on request lifecycle events. Several callbacks can be registered for each event;
+/// they are called in the order they were added.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    request: Vec<Hook<RequestEvent>>,
+    response: Vec<Hook<ResponseEvent>>,
+    retry: Vec<Hook<RetryEvent>>,
+    token_refresh: Vec<Hook<TokenRefreshEvent>>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("request", &self.request.len())
+            .field("response", &self.response.len())
+            .field("retry", &self.retry.len())
+            .field("token_refresh", &self.token_refresh.len())
+            .finish()
+    }
+}
+
+impl Hooks {
+    /// Creates an empty set of hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `hook` before each API request is sent, including requests made by tools through
+    /// the client, but not the Copilot token exchange.
+    pub fn on_request(mut self, hook: impl Fn(&RequestEvent) + Send + Sync + 'static) -> Self {
+        self.request.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` when a request sent after [`Hooks::on_request`] receives its response
+    /// headers or fails.
+    pub fn on_response(mut self, hook: impl Fn(&ResponseEvent) + Send + Sync + 'static) -> Self {
+        self.response.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` before a failed chat completion is sent again.
+    pub fn on_retry(mut self, hook: impl Fn(&RetryEvent) + Send + Sync + 'static) -> Self {
+        self.retry.push(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` after each Copilot token exchange, successful or not.
+    pub fn on_token_refresh(
+        mut self,
+        hook: impl Fn(&TokenRefreshEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.token_refresh.push(Arc::new(hook));
+        self
+    }
+
+    pub(crate) fn request(&self, event: &RequestEvent) {
+        self.request.iter().for_each(|hook| hook(event));
+    }
+
+    pub(crate) fn response(&self, event: &ResponseEvent) {
+        self.response.iter().for_each(|hook| hook(event));
+    }
+
+    pub(crate) fn retry(&self, event: &RetryEvent) {
+        self.retry.iter().for_each(|hook| hook(event));
+    }
+
+    pub(crate) fn token_refresh(&self, event: &TokenRefreshEvent) {
+        self.token_refresh.iter().for_each(|hook| hook(event));
+    }
+}
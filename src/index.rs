@@ -0,0 +1,314 @@
+//! A local vector index of a workspace for semantic search.
+//!
+//! `VectorIndex::build`, with the `files` feature, splits the text files under a directory into
+//! line-based chunks, embeds them with [`CopilotClient::get_embeddings`], and keeps the vectors
+//! in memory. [`VectorIndex::add_chunks`] indexes chunks from anywhere else. The index can be
+//! saved as JSON (by convention in [`INDEX_FILE_NAME`] at the workspace root) and queried with
+//! [`VectorIndex::search_text`].
+
+#[cfg(feature = "files")]
+use crate::context::files::FileCollector;
+use crate::{CopilotClient, CopilotError, DEFAULT_EMBEDDING_MODEL, prompt::truncate_to_tokens};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Conventional file name of a saved index, relative to the indexed directory.
+pub const INDEX_FILE_NAME: &str = ".copilot-index.json";
+
+/// Version of the saved index format.
+const FORMAT_VERSION: u32 = 1;
+
+/// Maximum number of lines per chunk.
+const CHUNK_LINES: usize = 60;
+
+/// Maximum number of tokens per chunk; longer chunks are truncated before embedding.
+const CHUNK_TOKENS: u32 = 2_000;
+
+/// Number of chunks embedded per request.
+const BATCH_SIZE: usize = 32;
+
+/// A span of lines from a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Path of the file, relative to the indexed directory.
+    pub path: String,
+    /// First line of the chunk (1-based).
+    pub start_line: usize,
+    /// Last line of the chunk (inclusive).
+    pub end_line: usize,
+    /// The chunk's text.
+    pub text: String,
+}
+
+/// A chunk together with its embedding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// The indexed chunk.
+    #[serde(flatten)]
+    pub chunk: Chunk,
+    /// The chunk's embedding vector.
+    pub embedding: Vec<f32>,
+}
+
+/// A search result.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchHit<'a> {
+    /// The matching entry.
+    pub entry: &'a IndexEntry,
+    /// Cosine similarity between the query and the entry, between -1 and 1.
+    pub score: f32,
+}
+
+/// An in-memory collection of embedded chunks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorIndex {
+    /// Version of the format the index was saved in.
+    pub version: u32,
+    /// The embedding model the vectors were produced with.
+    pub model: String,
+    /// The indexed chunks.
+    pub entries: Vec<IndexEntry>,
+}
+
+impl Default for VectorIndex {
+    fn default() -> Self {
+        VectorIndex {
+            version: FORMAT_VERSION,
+            model: DEFAULT_EMBEDDING_MODEL.to_string(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl VectorIndex {
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of indexed chunks.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing has been indexed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Indexes the text files under `root`.
+    ///
+    /// The files are gathered by a [`FileCollector`] with its defaults, so files ignored by
+    /// `.gitignore`, `.copilotignore`, or `.aiexclude`, hidden files and directories, common build
+    /// and dependency directories, binary files, and files over 512 KiB are skipped. Use
+    /// [`VectorIndex::build_from`] to collect them differently. Needs the `files` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the directory cannot be read or an embeddings request fails.
+    #[cfg(feature = "files")]
+    pub async fn build(
+        client: &CopilotClient,
+        root: impl AsRef<Path>,
+    ) -> Result<Self, CopilotError> {
+        Self::build_from(client, &FileCollector::new(root.as_ref())).await
+    }
+
+    /// Indexes the files gathered by `files`. Needs the `files` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the files cannot be collected or an embeddings request fails.
+    #[cfg(feature = "files")]
+    pub async fn build_from(
+        client: &CopilotClient,
+        files: &FileCollector,
+    ) -> Result<Self, CopilotError> {
+        let chunks = files
+            .collect()?
+            .iter()
+            .flat_map(|file| chunk_text(&file.relative, &file.content))
+            .collect();
+        let mut index = Self::new();
+        index.add_chunks(client, chunks).await?;
+        Ok(index)
+    }
+
+    /// Embeds `chunks` with the client's [embedding model](CopilotClient::embedding_model) and
+    /// adds them to the index. An empty index takes the client's model.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if an embeddings request fails, or if the index holds vectors
+    /// of a different embedding model.
+    pub async fn add_chunks(
+        &mut self,
+        client: &CopilotClient,
+        chunks: Vec<Chunk>,
+    ) -> Result<(), CopilotError> {
+        if self.is_empty() {
+            self.model = client.embedding_model().to_string();
+        } else {
+            self.check_model(client)?;
+        }
+        let mut chunks = chunks.into_iter().peekable();
+        while chunks.peek().is_some() {
+            let batch: Vec<Chunk> = chunks.by_ref().take(BATCH_SIZE).collect();
+            let inputs = batch
+                .iter()
+                .map(|c| truncate_to_tokens(&c.text, CHUNK_TOKENS).to_string())
+                .collect();
+            let embeddings = client.get_embeddings_f32(inputs).await?;
+            self.entries.extend(
+                batch
+                    .into_iter()
+                    .zip(embeddings)
+                    .map(|(chunk, embedding)| IndexEntry { chunk, embedding }),
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the `limit` entries most similar to `query`, best first.
+    pub fn search(&self, query: &[f32], limit: usize) -> Vec<SearchHit<'_>> {
+        let mut hits: Vec<SearchHit<'_>> = self
+            .entries
+            .iter()
+            .map(|entry| SearchHit {
+                entry,
+                score: cosine_similarity(query, &entry.embedding),
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+        hits
+    }
+
+    /// Embeds `query` and returns the `limit` most similar entries, best first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the embeddings request fails, or if the index holds vectors
+    /// of a different embedding model than the client's.
+    pub async fn search_text(
+        &self,
+        client: &CopilotClient,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit<'_>>, CopilotError> {
+        if !self.is_empty() {
+            self.check_model(client)?;
+        }
+        let query = client
+            .get_embeddings_f32(vec![query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| CopilotError::Other("No embedding returned for the query".into()))?;
+        Ok(self.search(&query, limit))
+    }
+
+    /// Fails if the client embeds with another model than the index's vectors were produced
+    /// with, since vectors of different models cannot be compared.
+    fn check_model(&self, client: &CopilotClient) -> Result<(), CopilotError> {
+        let model = client.embedding_model();
+        if model != self.model {
+            return Err(CopilotError::Other(format!(
+                "The index was built with embedding model {}, but the client embeds with {model}; \
+                 rebuild the index",
+                self.model
+            )));
+        }
+        Ok(())
+    }
+
+    /// Saves the index as JSON to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CopilotError> {
+        let json = serde_json::to_string(self).map_err(|e| CopilotError::Other(e.to_string()))?;
+        fs::write(path, json).map_err(io_error)
+    }
+
+    /// Loads an index saved with [`VectorIndex::save`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be read, is not a valid index, or was saved
+    /// by a newer version of this crate.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        let content = fs::read_to_string(path).map_err(io_error)?;
+        let index: Self =
+            serde_json::from_str(&content).map_err(|e| CopilotError::Other(e.to_string()))?;
+        if index.version > FORMAT_VERSION {
+            return Err(CopilotError::Other(format!(
+                "Unsupported index format version {}",
+                index.version
+            )));
+        }
+        Ok(index)
+    }
+
+    /// Looks for [`INDEX_FILE_NAME`] in `dir` and its ancestors.
+    pub fn find(dir: impl AsRef<Path>) -> Option<PathBuf> {
+        dir.as_ref()
+            .ancestors()
+            .map(|d| d.join(INDEX_FILE_NAME))
+            .find(|p| p.is_file())
+    }
+}
+
+/// Splits `content` into chunks of at most 60 lines, preferring to break at blank lines in the
+/// second half of a chunk so that functions and paragraphs stay together.
+pub fn chunk_text(path: &str, content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = (start + CHUNK_LINES).min(lines.len());
+        if end < lines.len()
+            && let Some(blank) = (start + CHUNK_LINES / 2..end)
+                .rev()
+                .find(|&i| lines[i].trim().is_empty())
+        {
+            end = blank + 1;
+        }
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                path: path.to_string(),
+                start_line: start + 1,
+                end_line: end,
+                text,
+            });
+        }
+        start = end;
+    }
+    chunks
+}
+
+/// Returns the cosine similarity of two vectors, or 0 if either is zero or their lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+fn io_error(e: io::Error) -> CopilotError {
+    CopilotError::Other(e.to_string())
+}
@@ -0,0 +1,59 @@
+//! Interceptors that change outgoing requests.
+//!
+//! An [`Interceptor`] sees every request the client sends, after the client has built it and
+//! before it reaches the transport, and can change its URL, headers, or body, or stop it with
+//! an error. This includes the requests of tools that use the client, such as
+//! [`FetchTool`](crate::tools::fetch::FetchTool). Interceptors added with
+//! [`CopilotClient::with_interceptor`] run in the order they were added, so that one request
+//! policy, such as the audit headers of an enterprise proxy, covers chat completions,
+//! embeddings, and model listings alike:
+//!
+//! ```
+//! use copilot_client::{
+//!     CopilotClient, CopilotError,
+//!     transport::{HeaderValue, HttpRequest},
+//! };
+//!
+//! let client = CopilotClient::with_models("token".into(), "editor/1.0".into(), Vec::new())
+//!     .with_interceptor(|request: &mut HttpRequest| {
+//!         request
+//!             .headers
+//!             .insert("X-Audit-User", HeaderValue::from_static("build-bot"));
+//!         Ok(())
+//!     })
+//!     .with_interceptor(|request: &mut HttpRequest| {
+//!         if request.url.contains("/embeddings") {
+//!             return Err(CopilotError::Other("Embeddings are disabled".into()));
+//!         }
+//!         Ok(())
+//!     });
+//! ```
+//!
+//! Interceptors run before [`Hooks::on_request`](crate::hooks::Hooks::on_request), which sees
+//! the changed request. The Copilot token exchange is not intercepted. To wrap the transport
+//! itself, for example to retry or to change responses, use
+//! [`CopilotClient::with_transport`] or, with the `tower` feature, a tower layer.
+//!
+//! [`CopilotClient::with_interceptor`]: crate::CopilotClient::with_interceptor
+//! [`CopilotClient::with_transport`]: crate::CopilotClient::with_transport
+
+use crate::{CopilotError, transport::HttpRequest};
+
+/// Changes outgoing requests before they are sent.
+pub trait Interceptor: Send + Sync {
+    /// Changes `request` in place.
+    ///
+    /// # Errors
+    ///
+    /// An error stops the request, and the client method that made it returns the error.
+    fn intercept(&self, request: &mut HttpRequest) -> Result<(), CopilotError>;
+}
+
+impl<F> Interceptor for F
+where
+    F: Fn(&mut HttpRequest) -> Result<(), CopilotError> + Send + Sync,
+{
+    fn intercept(&self, request: &mut HttpRequest) -> Result<(), CopilotError> {
+        self(request)
+    }
+}
@@ -0,0 +1,273 @@
+//! langchain-rust integration, enabled with the `langchain` feature.
+//!
+//! [`CopilotLLM`] implements langchain-rust's `LLM` trait and [`CopilotEmbedder`] its
+//! `Embedder` trait, so chains, agents, and vector stores built with langchain-rust can run on
+//! Copilot models:
+//!
+//! ```ignore
+//! use copilot_client::{CopilotClient, langchain::CopilotLLM};
+//! use langchain_rust::language_models::llm::LLM;
+//!
+//! let client = CopilotClient::from_env_with_models(editor_version).await?;
+//! let llm = CopilotLLM::new(client, "gpt-4o");
+//! let answer = llm.invoke("Hello").await?;
+//! ```
+//!
+//! rig ships its own Copilot provider and no longer exposes `CompletionModel` or
+//! `EmbeddingModel` traits to implement, so there is no rig adapter.
+
+use crate::{
+    ChatOptions, ChatResponse, CopilotClient, CopilotError, Message, TokenUsage, Tool, ToolCall,
+};
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use http::StatusCode;
+use langchain_rust::{
+    embedding::{Embedder, EmbedderError},
+    language_models::{
+        GenerateResult, LLMError, TokenUsage as LangchainTokenUsage, llm::LLM, options::CallOptions,
+    },
+    schemas::{self, FunctionCallBehavior, MessageType, StreamData},
+};
+use serde_json::json;
+use std::{fmt, pin::Pin};
+
+/// A langchain-rust `LLM` that sends chat requests for one model through a [`CopilotClient`].
+///
+/// Call options added by chains with `add_options` are merged into the options given with
+/// [`CopilotLLM::with_options`]. Stop words, seeds, and penalties are not supported by the
+/// client and are ignored.
+#[derive(Clone)]
+pub struct CopilotLLM {
+    client: CopilotClient,
+    model: String,
+    options: CallOptions,
+}
+
+impl fmt::Debug for CopilotLLM {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopilotLLM")
+            .field("model", &self.model)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CopilotLLM {
+    /// Creates an LLM for `model` with default options.
+    pub fn new(client: CopilotClient, model: impl Into<String>) -> Self {
+        CopilotLLM {
+            client,
+            model: model.into(),
+            options: CallOptions::default(),
+        }
+    }
+
+    /// Sets the call options used for every request.
+    pub fn with_options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn chat_options(&self) -> ChatOptions {
+        let mut options = ChatOptions::default();
+        if let Some(n) = self.options.n.or(self.options.candidate_count) {
+            options.n = n as u32;
+        }
+        if let Some(top_p) = self.options.top_p {
+            options.top_p = f64::from(top_p);
+        }
+        if let Some(temperature) = self.options.temperature {
+            options.temperature = f64::from(temperature);
+        }
+        options.max_tokens = self.options.max_tokens;
+        options.stop = self.options.stop_words.clone().unwrap_or_default();
+        options.tools = self.options.functions.as_ref().map(|functions| {
+            functions
+                .iter()
+                .map(|f| Tool::function(&f.name, &f.description, f.parameters.clone()))
+                .collect()
+        });
+        options.tool_choice = self
+            .options
+            .function_call_behavior
+            .as_ref()
+            .map(|behavior| match behavior {
+                FunctionCallBehavior::None => json!("none"),
+                FunctionCallBehavior::Auto => json!("auto"),
+                FunctionCallBehavior::Named(name) => {
+                    json!({ "type": "function", "function": { "name": name } })
+                }
+            });
+        options
+    }
+}
+
+#[async_trait]
+impl LLM for CopilotLLM {
+    /// Sends the messages and returns the reply. When the model calls tools, the generation is
+    /// the JSON-encoded tool calls, as with langchain-rust's OpenAI LLM.
+    ///
+    /// With a streaming function in the options, the reply is streamed and the function is
+    /// called with each JSON-encoded choice delta.
+    async fn generate(&self, messages: &[schemas::Message]) -> Result<GenerateResult, LLMError> {
+        let messages = into_messages(messages)?;
+        let options = self.chat_options();
+        let Some(func) = &self.options.streaming_func else {
+            let response = self
+                .client
+                .chat_completion_with_options(messages, self.model.clone(), &options)
+                .await
+                .map_err(into_llm_error)?;
+            return Ok(generate_result(response));
+        };
+
+        let mut stream = self
+            .client
+            .chat_completion_stream(messages, self.model.clone(), &options)
+            .await
+            .map_err(into_llm_error)?;
+        let mut result = GenerateResult::default();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(into_llm_error)?;
+            if let Some(usage) = &chunk.usage {
+                result.tokens = Some(token_usage(usage));
+            }
+            for choice in &chunk.choices {
+                let _ = (func.lock().await)(serde_json::to_string(choice)?).await;
+                if let Some(content) = &choice.delta.content {
+                    result.generation.push_str(content);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    async fn stream(
+        &self,
+        messages: &[schemas::Message],
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamData, LLMError>> + Send>>, LLMError> {
+        let stream = self
+            .client
+            .chat_completion_stream(
+                into_messages(messages)?,
+                self.model.clone(),
+                &self.chat_options(),
+            )
+            .await
+            .map_err(into_llm_error)?;
+        Ok(Box::pin(stream.map(|chunk| {
+            let chunk = chunk.map_err(into_llm_error)?;
+            let content = chunk.content().unwrap_or_default().to_string();
+            let tokens = chunk.usage.as_ref().map(token_usage);
+            Ok(StreamData::new(
+                serde_json::to_value(&chunk)?,
+                tokens,
+                content,
+            ))
+        })))
+    }
+
+    fn add_options(&mut self, options: CallOptions) {
+        self.options.merge_options(options);
+    }
+}
+
+/// A langchain-rust `Embedder` backed by [`CopilotClient::get_embeddings`].
+#[derive(Clone)]
+pub struct CopilotEmbedder {
+    client: CopilotClient,
+}
+
+impl fmt::Debug for CopilotEmbedder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopilotEmbedder").finish_non_exhaustive()
+    }
+}
+
+impl CopilotEmbedder {
+    /// Creates an embedder that requests embeddings through `client`.
+    pub fn new(client: CopilotClient) -> Self {
+        CopilotEmbedder { client }
+    }
+}
+
+#[async_trait]
+impl Embedder for CopilotEmbedder {
+    async fn embed_documents(&self, documents: &[String]) -> Result<Vec<Vec<f64>>, EmbedderError> {
+        let mut embeddings = self
+            .client
+            .get_embeddings(documents.to_vec())
+            .await
+            .map_err(into_embedder_error)?;
+        embeddings.sort_by_key(|e| e.index);
+        Ok(embeddings.into_iter().map(|e| e.embedding).collect())
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f64>, EmbedderError> {
+        let mut embeddings = self.embed_documents(&[text.to_string()]).await?;
+        Ok(embeddings.pop().unwrap_or_default())
+    }
+}
+
+/// Converts langchain-rust messages into chat messages. Images are not supported and dropped.
+fn into_messages(messages: &[schemas::Message]) -> Result<Vec<Message>, LLMError> {
+    messages
+        .iter()
+        .map(|m| {
+            Ok(match m.message_type {
+                MessageType::SystemMessage => Message::system(&m.content),
+                MessageType::HumanMessage => Message::user(&m.content),
+                MessageType::AIMessage => Message {
+                    tool_calls: m
+                        .tool_calls
+                        .clone()
+                        .map(serde_json::from_value::<Vec<ToolCall>>)
+                        .transpose()?,
+                    ..Message::assistant(&m.content)
+                },
+                MessageType::ToolMessage => {
+                    Message::tool_result(m.id.clone().unwrap_or_default(), &m.content)
+                }
+            })
+        })
+        .collect()
+}
+
+fn generate_result(response: ChatResponse) -> GenerateResult {
+    let generation = response
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| match choice.message.tool_calls {
+            Some(tool_calls) => serde_json::to_string(&tool_calls).unwrap_or_default(),
+            None => choice.message.content,
+        })
+        .unwrap_or_default();
+    GenerateResult {
+        tokens: response.usage.as_ref().map(token_usage),
+        generation,
+    }
+}
+
+fn token_usage(usage: &TokenUsage) -> LangchainTokenUsage {
+    LangchainTokenUsage {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        total_tokens: usage.total_tokens,
+    }
+}
+
+fn into_llm_error(error: CopilotError) -> LLMError {
+    LLMError::OtherError(error.to_string())
+}
+
+/// Reports errors as HTTP errors, with status 500 for errors that did not come from the API.
+fn into_embedder_error(error: CopilotError) -> EmbedderError {
+    EmbedderError::HttpError {
+        status_code: error
+            .status()
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        error_message: error.to_string(),
+    }
+}
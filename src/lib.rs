@@ -2,26 +2,246 @@
 //!
 //! This library provides a client for interacting with the GitHub Copilot API. It handles token
 //! management, model retrieval, chat completions, and embeddings requests. The client uses
-//! [reqwest](https://crates.io/crates/reqwest) for HTTP requests and [serde](https://crates.io/crates/serde)
-//! for JSON serialization/deserialization.
+//! [reqwest](https://crates.io/crates/reqwest) for HTTP requests through the default-on `reqwest`
+//! feature and [serde](https://crates.io/crates/serde) for JSON serialization/deserialization.
 //!
 //! ## Features
 //!
-//! - Retrieve a GitHub token from the environment or configuration files.
+//! - Retrieve a GitHub token from the environment or configuration files, or sign in with the
+//!   device flow in [`auth`].
 //! - Fetch available Copilot models and agents.
-//! - Send chat completion requests and receive responses.
-//! - Request embeddings for provided input strings.
+//! - Send chat completion requests and receive responses, optionally streamed as a
+//!   [`ChatStream`].
+//! - Hold multi-turn chats with [`conversation::Conversation`] and persist them in a
+//!   [`session::SessionStore`].
+//! - Request embeddings for provided input strings, decoded incrementally into `f32` vectors
+//!   for large batches, and search a workspace with an [`index::VectorIndex`] built from them.
+//! - Import and export prompts in the OpenAI chat format with [`openai`], convert to and from
+//!   `async-openai` types with the `async-openai` feature, and answer Ollama clients with the
+//!   conversions in [`ollama`].
+//! - Plug into langchain-rust chains and agents with the `langchain` feature.
+//! - Assemble token-budgeted prompts with [`prompt::PromptBuilder`], and get a report of what
+//!   was included and cut with the response from [`CopilotClient::chat_prompt`].
+//! - Depend on the [`api::CopilotApi`] trait instead of the client to substitute mocks in tests,
+//!   or run integration tests against the mock Copilot server of the `test-util` feature and
+//!   inject delays, error statuses, and broken streams with its fault injection transport.
+//! - Record API traffic to cassette files and replay it offline with [`vcr`].
+//! - Observe every API call through `tracing` spans with the model, token counts, HTTP status,
+//!   and latency; credentials are never recorded. With the `otel` feature, chat and embeddings
+//!   calls are also reported as OpenTelemetry spans and metrics following the GenAI semantic
+//!   conventions.
+//! - Count requests, latency, and tokens through the `metrics` facade with the `metrics`
+//!   feature, for export to Prometheus.
+//! - Log sanitized request and response bodies with [`logging`] to debug rejected requests.
+//! - Audit requests, responses, retries, and token refreshes with [`hooks::Hooks`] callbacks that
+//!   receive sanitized metadata.
+//! - Add headers to or otherwise change every outgoing request with an ordered chain of
+//!   [`interceptor::Interceptor`]s.
+//! - Correlate requests with logs through the `X-Request-Id` each request carries and the ID
+//!   GitHub returns, both kept in responses, errors, and tracing spans; see [`request_id`].
+//! - Check credentials and API reachability with [`CopilotClient::health_check`].
+//! - Track cumulative token and premium-request usage with [`usage::UsageTracker`], per model
+//!   and per request [tag](ChatOptions::tags), such as the feature or user a request is for,
+//!   and optionally log every request to a JSON Lines history file.
+//! - Estimate and report spend in premium requests and token prices with a
+//!   [`pricing::PriceTable`], per request with [`CopilotClient::estimate_cost`] and in total
+//!   with [`usage::UsageTracker::cost`].
+//! - Cap the requests and tokens used per minute, hour, or day with a [`budget::Budget`], and
+//!   get called back as usage approaches a limit or the premium requests left run low.
+//! - Cache the Copilot token until it expires, with an injectable [`clock::Clock`] for tests.
+//! - Run many independent chat completions with bounded parallelism and ordered results with
+//!   [`CopilotClient::chat_completion_batch`].
+//! - Race several models with [`CopilotClient::chat_race`] and take the first answer.
+//! - Fall back to other models on rate limits, server errors, or content filtering with a
+//!   [`fallback::FallbackPolicy`].
+//! - Pick the model per request by prompt size, required features, or remaining premium budget
+//!   with a [`router::ModelRouter`].
+//! - Validate JSON replies and tool-call arguments against their schemas and have the model
+//!   repair them with the `schema` feature.
+//! - Let the model call tools in a loop with [`CopilotClient::run_tools`] and a
+//!   [`tools::Toolbox`], including a `search_web` tool on any [`tools::search::SearchProvider`],
+//!   such as the Brave Search API with the `brave-search` feature. With the `fetch` feature, a
+//!   `fetch_url` tool reads web pages as Markdown within a token budget and a domain allowlist.
+//!   With the `shell` feature, a `run_command` tool runs allowlisted programs in the workspace
+//!   with a timeout. With the `files` feature, file tools read workspace files and write them
+//!   only after an approval callback has seen a diff of the change.
+//! - Ask for changes as a unified diff with [`CopilotClient::chat_patch`], which places hunks
+//!   by their content rather than their line numbers and sends diffs that do not apply back
+//!   to the model, or apply diffs from any source with [`patch::Patch`].
+//! - Enforce output policies with [`guard::Guardrails`]: limit the length of replies, stop them
+//!   at a pattern, or truncate and reject them with custom filters, also mid-stream.
+//! - Keep credentials out of prompts with an opt-in [`secrets::SecretScanner`] that redacts or
+//!   rejects AWS keys, private keys, GitHub tokens, and high-entropy strings before sending.
+//! - Replace emails, phone numbers, and custom patterns in prompts with placeholders using a
+//!   [`pii::PiiFilter`], and optionally restore the original values in replies.
+//! - Clean up replies with [`postprocess::PostProcessors`] that strip code fences, trim
+//!   boilerplate preambles, and normalize line endings, for the client or per request.
+//! - Extract fenced code blocks with their language and position from replies, also while they
+//!   stream in, with [`code::extract_code_blocks`].
+//! - Build prompt context from a git repository with [`context::git`]: staged and unstaged
+//!   diffs, diffs between refs, and blame excerpts, fitted to a token budget.
+//! - Ground the model in editor state with a [`context::editor::ContextBuilder`] that formats
+//!   the active file, selection, diagnostics, symbols, and open files within a token budget.
+//! - Gather workspace files for context or indexing with the `FileCollector` of the `files`
+//!   feature, which honors `.gitignore`, `.copilotignore`, and `.aiexclude` and skips large and
+//!   binary files, so secrets and build artifacts are not uploaded.
+//! - Attach files, web pages, text, and images to prompts with [`attachment::Attachment`]s,
+//!   which are read, fetched, or encoded and added to a message within its token budget. Web
+//!   pages need the `fetch` feature.
+//! - Keep prompt text in MiniJinja templates with variables and partials, loaded from the
+//!   configuration directory, with the `templates` feature, which also brings a
+//!   [built-in prompt library](library) behind helpers such as [`CopilotClient::explain`].
+//! - Render Markdown replies as ANSI-styled terminal output with syntax-highlighted code blocks
+//!   with the `render` feature, also while they stream in.
+//! - Answer repeated chat completion and embeddings requests from a [`cache::ResponseCache`]
+//!   in memory or on disk instead of spending quota on them again, and coalesce identical
+//!   concurrent requests into one upstream call.
+//! - Keep default settings and named auth profiles in a [`config::Config`] file.
+//! - Share a gateway among a team with a [`tenant::TenantRegistry`] that checks the API keys
+//!   of incoming requests and maps each to an auth profile and a budget.
+//! - Call the API without async code through `blocking::CopilotClientBlocking` with the
+//!   `blocking` feature.
+//! - Embed the client in C, C++, or Swift through the C ABI of the `ffi` feature, or use it
+//!   from Python through the `python` feature.
+//! - Stay out of the executor's way: the client spawns no tasks and uses no timers, so the only
+//!   tokio requirement is the reactor that the default `reqwest` transport sends requests on.
+//!   Swap in another HTTP client with a [`transport::HttpTransport`], and drop reqwest and tokio
+//!   entirely with `default-features = false`, or wrap the transport in tower middleware with
+//!   the `tower` feature.
+//! - Run in the browser on `wasm32-unknown-unknown`, where requests go through `fetch` and the
+//!   GitHub token must be passed to the client explicitly.
 
-use reqwest::{
-    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT},
-    Client as HttpClient,
-};
+use http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{env, error::Error, fmt, fs, path::Path};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fmt, fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{Span, field::Empty};
+
+pub mod api;
+#[cfg(feature = "async-openai")]
+pub mod async_openai;
+pub mod attachment;
+pub mod auth;
+pub mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod budget;
+pub mod cache;
+pub mod clock;
+pub mod code;
+pub mod config;
+pub mod context;
+pub mod conversation;
+mod dedup;
+mod embeddings;
+pub mod fallback;
+#[cfg(feature = "test-util")]
+pub mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod guard;
+pub mod health;
+pub mod hooks;
+pub mod index;
+pub mod interceptor;
+#[cfg(feature = "langchain")]
+pub mod langchain;
+#[cfg(feature = "templates")]
+pub mod library;
+pub mod logging;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod ollama;
+pub mod openai;
+#[cfg(feature = "otel")]
+mod otel;
+pub mod patch;
+pub mod pii;
+pub mod postprocess;
+pub mod pricing;
+pub mod prompt;
+#[cfg(feature = "python")]
+pub mod python;
+mod race;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod request_id;
+pub mod router;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod secrets;
+pub mod session;
+pub mod sse;
+pub mod stream;
+#[cfg(feature = "templates")]
+pub mod templates;
+pub mod tenant;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+pub mod tools;
+#[cfg(feature = "tower")]
+pub mod tower;
+pub mod transport;
+pub mod usage;
+pub mod vcr;
+mod workspace;
+
+use batch::BatchRetry;
+use budget::Budget;
+use cache::ResponseCache;
+use clock::{Clock, SystemClock};
+use dedup::InFlight;
+use fallback::FallbackPolicy;
+use guard::Guardrails;
+use hooks::Hooks;
+use interceptor::Interceptor;
+use pii::PiiFilter;
+use postprocess::PostProcessors;
+use pricing::{Cost, PriceTable};
+use secrets::SecretScanner;
+pub use stream::{ChatStream, ChatStreamChunk};
+#[cfg(feature = "reqwest")]
+use transport::ReqwestTransport;
+use transport::{HttpRequest, HttpResponse, HttpTransport, Method};
+use usage::UsageTracker;
+
+/// Endpoint for chat completion requests.
+const CHAT_COMPLETIONS_URL: &str = "https://api.githubcopilot.com/chat/completions";
+
+/// Seconds before its expiry at which a cached Copilot token is replaced.
+const TOKEN_REFRESH_MARGIN: u64 = 60;
+
+/// Endpoint for listing models.
+const MODELS_URL: &str = "https://api.githubcopilot.com/models";
+
+/// Endpoint for embeddings requests.
+const EMBEDDINGS_URL: &str = "https://api.githubcopilot.com/embeddings";
+
+/// The length of the embedding vectors requested.
+const EMBEDDING_DIMENSIONS: u32 = 512;
+
+/// Placeholder for the bearer token in [`RequestPreview`] headers.
+const REDACTED: &str = "<redacted>";
+
+/// Keys of JSON fields that hold credentials, replaced with [`REDACTED`] wherever bodies are
+/// written out.
+const SECRET_KEYS: [&str; 3] = ["token", "access_token", "refresh_token"];
+
+/// The embedding model used by [`CopilotClient::get_embeddings`] when it is available or the
+/// models list has no other embeddings model. See [`CopilotClient::embedding_model`].
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
 
 /// Represents errors that can occur when interacting with the GitHub Copilot API.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum CopilotError {
     /// An invalid model was specified.
     InvalidModel(String),
@@ -29,10 +249,96 @@ pub enum CopilotError {
     TokenError(String),
     /// An HTTP error occurred during the API call.
     HttpError(String),
+    /// The API answered with an error status.
+    ApiError {
+        /// The HTTP status code.
+        status: u16,
+        /// The error message from the response body.
+        message: String,
+        /// Seconds to wait before retrying, from the `Retry-After` header.
+        retry_after: Option<u64>,
+        /// The client's ID for the request, from its `X-Request-Id` header.
+        request_id: Option<String>,
+        /// The ID GitHub assigned to the request, from the `X-GitHub-Request-Id` header.
+        server_request_id: Option<String>,
+    },
+    /// A client-side [budget](budget::Budget) limit was reached, and the request was not sent.
+    BudgetExceeded(budget::BudgetExceeded),
     /// Other errors.
     Other(String),
 }
 
+impl CopilotError {
+    /// Returns the HTTP status code for errors reported by the API.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            CopilotError::ApiError { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Returns the client's ID for the request, for errors reported by the API. See
+    /// [`request_id`].
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            CopilotError::ApiError { request_id, .. } => request_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the ID GitHub assigned to the request, for errors reported by the API. Quote it
+    /// when reporting a problem to GitHub.
+    pub fn server_request_id(&self) -> Option<&str> {
+        match self {
+            CopilotError::ApiError {
+                server_request_id, ..
+            } => server_request_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the request was rejected because of rate limiting.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(429)
+    }
+
+    /// Returns `true` if the request failed in a way that may not recur, such as a dropped
+    /// connection or a server error, so that sending it again right away is worthwhile.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, CopilotError::HttpError(_)) || matches!(self.status(), Some(500 | 502..=504))
+    }
+
+    /// Returns `true` if the error is caused by missing, invalid, or insufficient credentials.
+    pub fn is_auth_error(&self) -> bool {
+        matches!(self, CopilotError::TokenError(_)) || matches!(self.status(), Some(401 | 403))
+    }
+
+    /// Returns `true` if the requested model does not exist or is not available to the account.
+    pub fn is_model_not_found(&self) -> bool {
+        match self {
+            CopilotError::InvalidModel(_) => true,
+            CopilotError::ApiError {
+                status, message, ..
+            } => *status == 404 || (*status == 400 && message.contains("model_not_supported")),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the content filter rejected the prompt.
+    pub fn is_content_filtered(&self) -> bool {
+        match self {
+            CopilotError::ApiError {
+                status, message, ..
+            } => {
+                *status == 400
+                    && (message.contains("content_filter")
+                        || message.contains("ResponsibleAIPolicyViolation"))
+            }
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for CopilotError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -41,6 +347,20 @@ impl fmt::Display for CopilotError {
             }
             CopilotError::TokenError(msg) => write!(f, "Token error: {msg}"),
             CopilotError::HttpError(msg) => write!(f, "HTTP error: {msg}"),
+            CopilotError::ApiError {
+                status,
+                message,
+                request_id,
+                server_request_id,
+                ..
+            } => {
+                write!(f, "API error ({status}): {message}")?;
+                match server_request_id.as_ref().or(request_id.as_ref()) {
+                    Some(id) => write!(f, " (request ID {id})"),
+                    None => Ok(()),
+                }
+            }
+            CopilotError::BudgetExceeded(exceeded) => write!(f, "{exceeded}"),
             CopilotError::Other(msg) => write!(f, "{msg}"),
         }
     }
@@ -78,7 +398,7 @@ pub struct AgentsResponse {
 }
 
 /// Represents a model available for GitHub Copilot.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
     /// The model identifier.
     pub id: String,
@@ -92,6 +412,195 @@ pub struct Model {
     pub max_input_tokens: Option<u32>,
     /// Maximum number of output tokens allowed.
     pub max_output_tokens: Option<u32>,
+    /// Capabilities reported by the API, if available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<ModelCapabilities>,
+    /// Whether and how requests to the model count against the premium request quota, if
+    /// reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billing: Option<ModelBilling>,
+    /// Whether the model is a preview, if reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<bool>,
+    /// Whether the model is enabled for the user, if it is subject to a policy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ModelPolicy>,
+}
+
+impl Model {
+    /// Returns the maximum number of prompt tokens accepted by the model, if known.
+    pub fn prompt_token_limit(&self) -> Option<u32> {
+        self.max_input_tokens
+            .or_else(|| self.limits().and_then(|l| l.max_prompt_tokens))
+    }
+
+    /// Returns the maximum number of tokens the model can generate, if known.
+    pub fn output_token_limit(&self) -> Option<u32> {
+        self.max_output_tokens
+            .or_else(|| self.limits().and_then(|l| l.max_output_tokens))
+    }
+
+    /// Returns the size of the model's context window (prompt and output combined), if known.
+    pub fn context_window(&self) -> Option<u32> {
+        self.limits().and_then(|l| l.max_context_window_tokens)
+    }
+
+    /// Returns `true` if the model serves chat completions. Models that do not report their
+    /// type are assumed to be chat models.
+    pub fn is_chat_model(&self) -> bool {
+        self.capabilities
+            .as_ref()
+            .and_then(|c| c.kind.as_deref())
+            .is_none_or(|kind| kind == "chat")
+    }
+
+    /// Returns `true` if the model serves embeddings.
+    pub fn is_embeddings_model(&self) -> bool {
+        self.capabilities.as_ref().and_then(|c| c.kind.as_deref()) == Some("embeddings")
+    }
+
+    /// Returns the names of the optional features the model reports support for, such as
+    /// `"streaming"` or `"tool_calls"`.
+    pub fn supported_features(&self) -> Vec<&'static str> {
+        let Some(supports) = self.capabilities.as_ref().and_then(|c| c.supports.as_ref()) else {
+            return Vec::new();
+        };
+        [
+            ("streaming", supports.streaming),
+            ("tool_calls", supports.tool_calls),
+            ("parallel_tool_calls", supports.parallel_tool_calls),
+            ("vision", supports.vision),
+            ("structured_outputs", supports.structured_outputs),
+        ]
+        .into_iter()
+        .filter(|(_, supported)| *supported == Some(true))
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// Returns the premium requests a chat completion with the model consumes, if reported.
+    pub fn premium_multiplier(&self) -> Option<f64> {
+        self.billing.as_ref()?.multiplier
+    }
+
+    /// Returns `true` if requests to the model count against the premium request quota.
+    pub fn is_premium(&self) -> bool {
+        self.billing.as_ref().is_some_and(|billing| {
+            billing
+                .is_premium
+                .unwrap_or_else(|| billing.multiplier.is_some_and(|m| m > 0.0))
+        })
+    }
+
+    /// Returns `true` if the model is a preview.
+    pub fn is_preview(&self) -> bool {
+        self.preview == Some(true)
+    }
+
+    /// Returns `true` if the user may use the model: it is not subject to a policy, or the
+    /// policy enables it. Models whose terms have not been accepted yet are not enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.policy
+            .as_ref()
+            .is_none_or(|policy| policy.state == ModelPolicyState::Enabled)
+    }
+
+    fn limits(&self) -> Option<&ModelLimits> {
+        self.capabilities.as_ref().and_then(|c| c.limits.as_ref())
+    }
+}
+
+/// Billing information of a model as reported by the models endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelBilling {
+    /// Whether requests to the model count against the premium request quota.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_premium: Option<bool>,
+    /// The premium requests a chat completion consumes, such as `1` or `0.33`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<f64>,
+    /// The plans the model is available on, such as `"pro"` or `"business"`; empty if it is
+    /// not restricted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restricted_to: Vec<String>,
+}
+
+/// The policy that enables a model for the user, as reported by the models endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelPolicy {
+    /// Whether the model is enabled.
+    #[serde(default)]
+    pub state: ModelPolicyState,
+    /// The terms the user accepts by enabling the model, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms: Option<String>,
+}
+
+/// Whether a model subject to a [`ModelPolicy`] is enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelPolicyState {
+    /// The model is enabled.
+    Enabled,
+    /// The model was disabled, by the user or an organization policy.
+    Disabled,
+    /// The model has not been enabled yet; its terms have to be accepted first.
+    Unconfigured,
+    /// A state this version of the crate does not know.
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+/// Capabilities of a model as reported by the models endpoint.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// The model family (e.g. `"gpt-4o"`).
+    #[serde(default)]
+    pub family: Option<String>,
+    /// The kind of model: `"chat"`, `"embeddings"`, or `"completion"`.
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
+    /// Token limits of the model.
+    #[serde(default)]
+    pub limits: Option<ModelLimits>,
+    /// Optional features supported by the model.
+    #[serde(default)]
+    pub supports: Option<ModelSupports>,
+}
+
+/// Optional features a model reports support for.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelSupports {
+    /// Whether responses can be streamed.
+    #[serde(default)]
+    pub streaming: Option<bool>,
+    /// Whether the model can call tools.
+    #[serde(default)]
+    pub tool_calls: Option<bool>,
+    /// Whether the model can request several tool calls at once.
+    #[serde(default)]
+    pub parallel_tool_calls: Option<bool>,
+    /// Whether the model accepts images.
+    #[serde(default)]
+    pub vision: Option<bool>,
+    /// Whether the model supports structured (JSON schema) outputs.
+    #[serde(default)]
+    pub structured_outputs: Option<bool>,
+}
+
+/// Token limits of a model.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelLimits {
+    /// Size of the context window (prompt and output combined).
+    #[serde(default)]
+    pub max_context_window_tokens: Option<u32>,
+    /// Maximum number of prompt tokens.
+    #[serde(default)]
+    pub max_prompt_tokens: Option<u32>,
+    /// Maximum number of output tokens.
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
 }
 
 /// Response payload for retrieving models.
@@ -104,21 +613,358 @@ pub struct ModelsResponse {
 /// Represents a chat message.
 ///
 /// The `role` field typically contains values such as `"system"`, `"user"`, or `"assistant"`.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Assistant messages may carry [`ToolCall`]s instead of (or in addition to) content, and
+/// `"tool"` messages carry the result of a tool call identified by `tool_call_id`.
+///
+/// Messages with [`images`](Message::images) are serialized with a list of content parts, the
+/// text followed by the images, instead of a content string; both forms are read back.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "RawMessage")]
 pub struct Message {
     /// The role of the message sender.
     pub role: String,
-    /// The content of the message.
+    /// The content of the message. A `null` content (as sent with tool calls) is read as empty.
     pub content: String,
+    /// Images sent with the content, for models that support vision.
+    pub images: Vec<ImageUrl>,
+    /// Optional name of the participant.
+    pub name: Option<String>,
+    /// Tool calls requested by the assistant.
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// For `"tool"` messages, the id of the tool call this message answers.
+    pub tool_call_id: Option<String>,
+    /// Marks the end of a prompt prefix to cache. See [`Message::with_cache_breakpoint`].
+    pub cache_control: Option<CacheControl>,
+}
+
+impl Serialize for Message {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("role", &self.role)?;
+        if self.images.is_empty() {
+            map.serialize_entry("content", &self.content)?;
+        } else {
+            let text = (!self.content.is_empty()).then_some(ContentPartRef::Text {
+                text: &self.content,
+            });
+            let images = self
+                .images
+                .iter()
+                .map(|image_url| ContentPartRef::ImageUrl { image_url });
+            let parts: Vec<ContentPartRef<'_>> = text.into_iter().chain(images).collect();
+            map.serialize_entry("content", &parts)?;
+        }
+        if let Some(name) = &self.name {
+            map.serialize_entry("name", name)?;
+        }
+        if let Some(tool_calls) = &self.tool_calls {
+            map.serialize_entry("tool_calls", tool_calls)?;
+        }
+        if let Some(tool_call_id) = &self.tool_call_id {
+            map.serialize_entry("tool_call_id", tool_call_id)?;
+        }
+        if let Some(cache_control) = &self.cache_control {
+            map.serialize_entry("copilot_cache_control", cache_control)?;
+        }
+        map.end()
+    }
+}
+
+/// The serialized form of a [`Message`], whose content is a string or a list of parts.
+#[derive(Deserialize)]
+struct RawMessage {
+    role: String,
+    #[serde(default)]
+    content: Option<RawContent>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default)]
+    tool_call_id: Option<String>,
+    #[serde(default, rename = "copilot_cache_control", alias = "cache_control")]
+    cache_control: Option<CacheControl>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// A part of the content of a message.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+/// A borrowed [`ContentPart`], for serialization.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPartRef<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: &'a ImageUrl },
+}
+
+impl From<RawMessage> for Message {
+    fn from(raw: RawMessage) -> Self {
+        let (content, images) = match raw.content {
+            None => (String::new(), Vec::new()),
+            Some(RawContent::Text(text)) => (text, Vec::new()),
+            Some(RawContent::Parts(parts)) => {
+                let mut texts = Vec::new();
+                let mut images = Vec::new();
+                for part in parts {
+                    match part {
+                        ContentPart::Text { text } => texts.push(text),
+                        ContentPart::ImageUrl { image_url } => images.push(image_url),
+                    }
+                }
+                (texts.join("\n"), images)
+            }
+        };
+        Message {
+            role: raw.role,
+            content,
+            images,
+            name: raw.name,
+            tool_calls: raw.tool_calls,
+            tool_call_id: raw.tool_call_id,
+            cache_control: raw.cache_control,
+        }
+    }
+}
+
+/// An image attached to a [`Message`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageUrl {
+    /// An `https:` URL of the image, or a `data:` URL with the image encoded in base64.
+    pub url: String,
+    /// The resolution the model looks at the image in: `"low"`, `"high"`, or `"auto"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ImageUrl {
+    /// Creates an image from an `https:` or `data:` URL.
+    pub fn new(url: impl Into<String>) -> Self {
+        ImageUrl {
+            url: url.into(),
+            detail: None,
+        }
+    }
+
+    /// Creates an image from its encoded bytes as a `data:` URL, with the MIME type of the
+    /// format, such as `"image/png"`.
+    pub fn from_bytes(mime_type: &str, bytes: &[u8]) -> Self {
+        use base64::Engine;
+        let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+        Self::new(format!("data:{mime_type};base64,{data}"))
+    }
+}
+
+impl Message {
+    /// Creates a message with the given role and content.
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Message {
+            role: role.into(),
+            content: content.into(),
+            images: Vec::new(),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+            cache_control: None,
+        }
+    }
+
+    /// Creates a `"system"` message.
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new("system", content)
+    }
+
+    /// Creates a `"user"` message.
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new("user", content)
+    }
+
+    /// Creates an `"assistant"` message.
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new("assistant", content)
+    }
+
+    /// Creates a `"tool"` message carrying the result of the tool call `tool_call_id`.
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Message {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new("tool", content)
+        }
+    }
+
+    /// Attaches an image to the message, for models that support vision.
+    pub fn with_image(mut self, image: ImageUrl) -> Self {
+        self.images.push(image);
+        self
+    }
+
+    /// Marks this message as the end of a prompt prefix that the backend should cache.
+    ///
+    /// Models with explicit prompt caching, such as Claude, then reuse the processed prefix up to
+    /// and including this message in later requests that start with the same messages, which
+    /// cuts latency and the cost of the prompt tokens. Put static content (a long system prompt
+    /// or reference documents) first and mark its last message. Models that cache prefixes
+    /// automatically, or not at all, ignore the marker. Cache hits are reported in
+    /// [`TokenUsage::cached_tokens`].
+    pub fn with_cache_breakpoint(mut self) -> Self {
+        self.cache_control = Some(CacheControl::ephemeral());
+        self
+    }
+}
+
+/// A prompt caching directive attached to a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheControl {
+    /// The type of caching. Currently always `"ephemeral"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl CacheControl {
+    /// A short-lived cache entry, the only kind the API supports.
+    pub fn ephemeral() -> Self {
+        CacheControl {
+            kind: "ephemeral".to_string(),
+        }
+    }
+}
+
+/// A tool call requested by the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// The id of the tool call, referenced by the `"tool"` message answering it.
+    pub id: String,
+    /// The type of the tool. Currently always `"function"`.
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    /// The function to call.
+    pub function: FunctionCall,
+}
+
+/// The function name and arguments of a [`ToolCall`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    /// The name of the function.
+    pub name: String,
+    /// The arguments as a JSON-encoded string, exactly as generated by the model.
+    #[serde(default)]
+    pub arguments: String,
+}
+
+/// A tool the model may call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tool {
+    /// The type of the tool. Currently always `"function"`.
+    #[serde(rename = "type", default = "function_type")]
+    pub kind: String,
+    /// The function definition.
+    pub function: FunctionDefinition,
+}
+
+impl Tool {
+    /// Creates a function tool whose arguments are described by the JSON Schema `parameters`.
+    pub fn function(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: Value,
+    ) -> Self {
+        Tool {
+            kind: function_type(),
+            function: FunctionDefinition {
+                name: name.into(),
+                description: Some(description.into()),
+                parameters,
+            },
+        }
+    }
+}
+
+/// Predicted output of a chat completion. See [`ChatOptions::prediction`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prediction {
+    /// The type of the prediction. Currently always `"content"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The predicted content of the reply.
+    pub content: String,
+}
+
+impl Prediction {
+    /// Predicts that the reply will largely match `content`.
+    pub fn content(content: impl Into<String>) -> Self {
+        Prediction {
+            kind: "content".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// The definition of a function tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    /// The name of the function.
+    pub name: String,
+    /// A description of what the function does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON Schema describing the function arguments.
+    #[serde(default)]
+    pub parameters: Value,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+/// Deserializes `null` as the type's default value.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+}
+
+/// Deserializes a value that is either a single string or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::One(one)) => vec![one],
+        Some(OneOrMany::Many(many)) => many,
+        None => Vec::new(),
+    })
 }
 
 /// Request payload for a chat completion.
+///
+/// The messages may be borrowed, so that sending a long history does not copy it.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ChatRequest {
+pub struct ChatRequest<'a> {
     /// The model identifier to use.
     pub model: String,
     /// The list of messages to send.
-    pub messages: Vec<Message>,
+    pub messages: Cow<'a, [Message]>,
     /// Number of chat completions to generate.
     pub n: u32,
     /// Nucleus sampling probability.
@@ -130,10 +976,170 @@ pub struct ChatRequest {
     /// Optional maximum number of tokens to generate.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    /// Sequences at which generation stops. A single string is read as one sequence.
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub stop: Vec<String>,
+    /// Whether to return the log probability of each generated token.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub logprobs: bool,
+    /// The number of most likely alternatives to return for each token, up to 20. Requires
+    /// `logprobs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    /// Seed for best-effort deterministic sampling.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Biases added to the logits of token ids, from -100 to 100.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub logit_bias: BTreeMap<u32, i32>,
+    /// Predicted output, for speculative decoding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<Prediction>,
+    /// Tools the model may call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    /// Controls which (if any) tool is called, e.g. `"auto"`, `"none"`, or a specific function.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+}
+
+/// An HTTP request as it would be sent, with the credentials redacted.
+///
+/// Returned by [`CopilotClient::preview_chat_request`] for debugging prompt construction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestPreview {
+    /// The HTTP method.
+    pub method: String,
+    /// The request URL.
+    pub url: String,
+    /// The request headers, with the `authorization` value redacted.
+    pub headers: BTreeMap<String, String>,
+    /// The JSON request body.
+    pub body: Value,
+}
+
+/// Options controlling how a chat completion is generated.
+///
+/// The defaults match the values used by [`CopilotClient::chat_completion`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatOptions {
+    /// Number of chat completions to generate.
+    pub n: u32,
+    /// Nucleus sampling probability.
+    pub top_p: f64,
+    /// Sampling temperature.
+    pub temperature: f64,
+    /// Maximum number of tokens to generate. `None` leaves the limit to the server, unless
+    /// `auto_max_tokens` is set.
+    pub max_tokens: Option<u32>,
+    /// When `max_tokens` is `None`, derive a limit from the model's output limit and the context
+    /// remaining after the prompt instead of relying on server defaults, which vary by model.
+    pub auto_max_tokens: bool,
+    /// Sequences at which the model stops generating, such as `` "```" `` to end a reply after
+    /// a single code block. The stop sequence itself is not part of the reply.
+    pub stop: Vec<String>,
+    /// Return the log probability of each generated token in [`ChatChoice::logprobs`].
+    pub logprobs: bool,
+    /// Also return the given number (up to 20) of most likely alternatives for each token.
+    /// Implies `logprobs`.
+    pub top_logprobs: Option<u8>,
+    /// Sample deterministically with this seed, so that repeated requests with the same
+    /// parameters tend to return the same result. Determinism is best-effort: compare
+    /// [`ChatResponse::system_fingerprint`] between runs to detect backend changes.
+    pub seed: Option<i64>,
+    /// Biases from -100 to 100 added to the logits of the given token ids before sampling.
+    /// `-100` effectively bans a token and `100` forces it. Token ids are those of the model's
+    /// tokenizer (see [`Model::tokenizer`]); models that do not support biases ignore them.
+    pub logit_bias: BTreeMap<u32, i32>,
+    /// The expected output, such as the current version of a file that the reply will largely
+    /// repeat with small edits. Models that support predicted outputs skip ahead over the
+    /// matching parts, which cuts the latency of edit and refactoring requests.
+    pub prediction: Option<Prediction>,
+    /// Tools the model may call.
+    pub tools: Option<Vec<Tool>>,
+    /// Controls which (if any) tool is called.
+    pub tool_choice: Option<Value>,
+    /// The natural language to answer in, such as `"Japanese"`, overriding the client's
+    /// [`CopilotClient::with_response_language`]. See [`prompt::apply_response_language`].
+    pub response_language: Option<String>,
+    /// Post-processors run on the replies to this request, after those of the client. Not
+    /// applied to streamed responses, and never serialized.
+    #[serde(skip)]
+    pub post_processors: Option<PostProcessors>,
+    /// Key/value tags describing the request, such as the feature, user, or session it is
+    /// made for. Tags are not sent to the API: they are passed to [hooks](hooks::Hooks),
+    /// recorded in the `tags` field of the request's tracing span, and used to attribute usage
+    /// in the client's [`UsageTracker`].
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// The ID sent in the `X-Request-Id` header, such as the ID of the caller's own request
+    /// to correlate logs with. `None`, or an ID that is not a valid header value, sends a
+    /// generated one. Fallback attempts share the ID. See [`request_id`].
+    pub request_id: Option<String>,
+}
+
+impl Default for ChatOptions {
+    fn default() -> Self {
+        ChatOptions {
+            n: 1,
+            top_p: 1.0,
+            temperature: 0.5,
+            max_tokens: None,
+            auto_max_tokens: false,
+            stop: Vec::new(),
+            logprobs: false,
+            top_logprobs: None,
+            seed: None,
+            logit_bias: BTreeMap::new(),
+            prediction: None,
+            tools: None,
+            tool_choice: None,
+            response_language: None,
+            post_processors: None,
+            tags: BTreeMap::new(),
+            request_id: None,
+        }
+    }
+}
+
+impl ChatOptions {
+    /// Adds the tag `key` with `value`, replacing an earlier value of `key`.
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Resolves the `max_tokens` value to send for `messages` on `model`.
+    ///
+    /// Returns the explicit `max_tokens` if set. Otherwise, when `auto_max_tokens` is enabled,
+    /// returns the smaller of the model's output limit and the context window left after the
+    /// (estimated) prompt. Returns `None` if the limits are unknown or the prompt already fills
+    /// the context window.
+    pub fn resolve_max_tokens(&self, model: &Model, messages: &[Message]) -> Option<u32> {
+        if self.max_tokens.is_some() || !self.auto_max_tokens {
+            return self.max_tokens;
+        }
+        let output_limit = model.output_token_limit()?;
+        let Some(window) = model.context_window() else {
+            return Some(output_limit);
+        };
+        let prompt: u32 = messages.iter().map(prompt::estimate_message_tokens).sum();
+        // Token estimates are approximate, so keep a margin before the end of the window.
+        let remaining = window.saturating_sub(prompt + prompt / 10);
+        match output_limit.min(remaining) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
 }
 
 /// Represents a single choice in a chat completion response.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatChoice {
     /// The message generated by the model.
     pub message: Message,
@@ -141,20 +1147,147 @@ pub struct ChatChoice {
     pub finish_reason: Option<String>,
     /// Optional token usage information.
     pub usage: Option<TokenUsage>,
+    /// The log probabilities of the generated tokens, if requested with
+    /// [`ChatOptions::logprobs`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<ChoiceLogprobs>,
+}
+
+/// The log probabilities of the tokens of a choice.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChoiceLogprobs {
+    /// The generated tokens, in order.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub content: Vec<TokenLogprob>,
+}
+
+impl ChoiceLogprobs {
+    /// Returns the log probability of the whole generated text, the sum over its tokens.
+    pub fn total(&self) -> f64 {
+        self.content.iter().map(|token| token.logprob).sum()
+    }
+
+    /// Returns the mean log probability per token, or `None` if there are no tokens. Unlike
+    /// [`ChoiceLogprobs::total`], it can be compared between replies of different lengths.
+    pub fn mean(&self) -> Option<f64> {
+        (!self.content.is_empty()).then(|| self.total() / self.content.len() as f64)
+    }
+}
+
+/// A generated token with its log probability.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    /// The token text.
+    pub token: String,
+    /// The natural logarithm of the token's probability.
+    pub logprob: f64,
+    /// The UTF-8 bytes of the token, which may be part of a multi-byte character.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+    /// The most likely tokens at this position, if requested with
+    /// [`ChatOptions::top_logprobs`].
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+impl TokenLogprob {
+    /// Returns the probability of the token, between 0 and 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+/// A likely token at a position, whether or not it was generated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopLogprob {
+    /// The token text.
+    pub token: String,
+    /// The natural logarithm of the token's probability.
+    pub logprob: f64,
+    /// The UTF-8 bytes of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+}
+
+impl TopLogprob {
+    /// Returns the probability of the token, between 0 and 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
 }
 
 /// Information about token usage in a chat response.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenUsage {
+    /// Tokens in the prompt.
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    /// Tokens in the generated completion.
+    #[serde(default)]
+    pub completion_tokens: u32,
     /// Total tokens used.
     pub total_tokens: u32,
+    /// A breakdown of the prompt tokens, if reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    /// A breakdown of the completion tokens, if reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+impl TokenUsage {
+    /// Returns the number of prompt tokens that were read from the prompt cache, or 0 if not
+    /// reported.
+    pub fn cached_tokens(&self) -> u32 {
+        self.prompt_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.cached_tokens)
+    }
+}
+
+/// A breakdown of the prompt tokens of a response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptTokensDetails {
+    /// Prompt tokens that were read from the prompt cache rather than processed again.
+    #[serde(default)]
+    pub cached_tokens: u32,
+}
+
+/// A breakdown of the completion tokens of a response.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompletionTokensDetails {
+    /// Tokens of the [`ChatOptions::prediction`] that appeared in the reply.
+    #[serde(default)]
+    pub accepted_prediction_tokens: u32,
+    /// Tokens of the prediction that did not appear in the reply. They are still billed as
+    /// completion tokens.
+    #[serde(default)]
+    pub rejected_prediction_tokens: u32,
 }
 
 /// Response payload for a chat completion request.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
+    /// The id of the model the request was sent to. This differs from the requested model when
+    /// a [`FallbackPolicy`] sent the request to a fallback model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
     /// List of generated chat choices.
     pub choices: Vec<ChatChoice>,
+    /// Token usage for the whole request, if reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Identifies the backend configuration that generated the response. Responses to requests
+    /// with the same [`ChatOptions::seed`] are only expected to match if this matches too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
+    /// The client's ID for the request, sent in its `X-Request-Id` header. Responses from the
+    /// [response cache](CopilotClient::with_response_cache) keep the ID of the original request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// The ID GitHub assigned to the request, from the `X-GitHub-Request-Id` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_request_id: Option<String>,
 }
 
 /// Request payload for an embeddings request.
@@ -169,7 +1302,7 @@ pub struct EmbeddingRequest {
 }
 
 /// Represents an individual embedding.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Embedding {
     /// The index corresponding to the input.
     pub index: usize,
@@ -178,22 +1311,69 @@ pub struct Embedding {
 }
 
 /// Response payload for an embeddings request.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingResponse {
     /// List of embeddings.
     pub data: Vec<Embedding>,
+    /// Token usage for the request, if reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
 }
 
 /// Client for interacting with the GitHub Copilot API.
 ///
 /// This client handles GitHub token retrieval, fetching available models,
 /// and sending API requests for chat completions and embeddings.
+///
+/// The Copilot token is cached until shortly before it expires. Cloning a client is cheap:
+/// clones share the underlying transport, token cache, and usage tracker.
+#[derive(Clone)]
 pub struct CopilotClient {
-    http_client: HttpClient,
+    transport: Arc<dyn HttpTransport>,
     github_token: String,
     editor_version: String,
     /// List of available models.
     models: Vec<Model>,
+    /// Optional tracker that successful requests are reported into.
+    usage_tracker: Option<Arc<UsageTracker>>,
+    /// Optional limits on the requests and tokens used per window.
+    budget: Option<Arc<Budget>>,
+    /// The time source for token expiry.
+    clock: Arc<dyn Clock>,
+    /// The last Copilot token fetched, with the request headers built from it.
+    token: Arc<Mutex<Option<CachedToken>>>,
+    /// Optional cache that answers repeated chat completion and embeddings requests.
+    response_cache: Option<Arc<ResponseCache>>,
+    /// Identical requests in flight, if deduplication is enabled.
+    in_flight: Option<Arc<InFlight>>,
+    /// Optional policy that sends failed chat completions to other models.
+    fallback: Option<Arc<FallbackPolicy>>,
+    /// Optional policy that retries failed requests of batches.
+    batch_retry: Option<Arc<BatchRetry>>,
+    /// Optional guards applied to the output of chat completions.
+    guardrails: Option<Arc<Guardrails>>,
+    /// Optional post-processors applied to the replies of chat completions.
+    post_processors: Option<Arc<PostProcessors>>,
+    /// The natural language to answer in, unless a request sets its own.
+    response_language: Option<String>,
+    /// Optional scanner that redacts or rejects secrets in outgoing messages and embedding
+    /// inputs.
+    secret_scanner: Option<Arc<SecretScanner>>,
+    /// Optional filter that replaces personal data in outgoing messages with placeholders.
+    pii_filter: Option<Arc<PiiFilter>>,
+    /// Optional callbacks on request lifecycle events.
+    hooks: Option<Arc<Hooks>>,
+    /// Interceptors that change outgoing requests, in order.
+    interceptors: Vec<Arc<dyn Interceptor>>,
+}
+
+/// A cached Copilot token. The headers are built once per token rather than per request.
+#[derive(Debug)]
+struct CachedToken {
+    /// Unix timestamp at which the token expires.
+    expires_at: u64,
+    /// The request headers, including the token.
+    headers: HeaderMap,
 }
 
 impl CopilotClient {
@@ -207,6 +1387,7 @@ impl CopilotClient {
     /// # Errors
     ///
     /// Returns a `CopilotError` if the token retrieval or model fetching fails.
+    #[cfg(feature = "reqwest")]
     pub async fn from_env_with_models(editor_version: String) -> Result<Self, CopilotError> {
         let github_token =
             get_github_token().map_err(|e| CopilotError::TokenError(e.to_string()))?;
@@ -224,29 +1405,272 @@ impl CopilotClient {
     /// # Errors
     ///
     /// Returns a `CopilotError` if the model fetching fails.
+    #[cfg(feature = "reqwest")]
     pub async fn new_with_models(
         github_token: String,
         editor_version: String,
     ) -> Result<Self, CopilotError> {
-        let http_client = HttpClient::new();
-        let mut client = CopilotClient {
-            http_client,
+        Self::new_with_transport(github_token, editor_version, ReqwestTransport::default()).await
+    }
+
+    /// Like [`CopilotClient::new_with_models`], but sends every request, including the model
+    /// fetch, through `transport`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the model fetching fails.
+    pub async fn new_with_transport(
+        github_token: String,
+        editor_version: String,
+        transport: impl HttpTransport + 'static,
+    ) -> Result<Self, CopilotError> {
+        let mut client =
+            Self::with_models_and_transport(github_token, editor_version, Vec::new(), transport);
+        client.warm_up().await?;
+        Ok(client)
+    }
+
+    /// Creates a new `CopilotClient` with an already known list of models, without contacting the
+    /// API. Requests for models that are not in `models` fail with
+    /// `CopilotError::InvalidModel`.
+    #[cfg(feature = "reqwest")]
+    pub fn with_models(github_token: String, editor_version: String, models: Vec<Model>) -> Self {
+        Self::with_models_and_transport(
             github_token,
             editor_version,
-            models: Vec::new(),
-        };
-        // Fetch and store the available models.
-        let models = client.get_models().await?;
-        client.models = models;
-        Ok(client)
+            models,
+            ReqwestTransport::default(),
+        )
+    }
+
+    /// Like `CopilotClient::with_models`, but sends every request through `transport`.
+    /// Unlike `with_models(..).with_transport(..)`, no default transport is built and thrown
+    /// away, and it is available without the `reqwest` feature.
+    pub fn with_models_and_transport(
+        github_token: String,
+        editor_version: String,
+        models: Vec<Model>,
+        transport: impl HttpTransport + 'static,
+    ) -> Self {
+        CopilotClient {
+            transport: Arc::new(transport),
+            github_token,
+            editor_version,
+            models,
+            usage_tracker: None,
+            budget: None,
+            clock: Arc::new(SystemClock),
+            token: Arc::new(Mutex::new(None)),
+            response_cache: None,
+            in_flight: None,
+            fallback: None,
+            batch_retry: None,
+            guardrails: None,
+            post_processors: None,
+            response_language: None,
+            secret_scanner: None,
+            pii_filter: None,
+            hooks: None,
+            interceptors: Vec::new(),
+        }
+    }
+
+    /// Sends all further requests through `transport` instead of the default
+    /// `ReqwestTransport`.
+    pub fn with_transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Attaches a [`UsageTracker`] that records the usage of every successful chat completion
+    /// and embeddings request made by this client.
+    pub fn with_usage_tracker(mut self, tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = Some(tracker);
+        self
+    }
+
+    /// Refuses chat completion and embeddings requests with [`CopilotError::BudgetExceeded`]
+    /// once a limit of `budget` is reached. Requests count against the budget when they are
+    /// sent, and tokens when their response arrives. See the [`budget`] module.
+    pub fn with_budget(mut self, budget: Arc<Budget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Reads the time for Copilot token expiry from `clock` instead of the system time.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sends chat completions that fail as described by `policy` to the fallback models of the
+    /// requested model, in order. Streamed chat completions fall back only if the request fails
+    /// before the response starts.
+    pub fn with_fallback(mut self, policy: FallbackPolicy) -> Self {
+        self.fallback = Some(Arc::new(policy));
+        self
+    }
+
+    /// Applies `guardrails` to the output of every chat completion, truncating or rejecting
+    /// responses, and ending streamed responses as soon as a guard stops them.
+    pub fn with_guardrails(mut self, guardrails: Guardrails) -> Self {
+        self.guardrails = Some(Arc::new(guardrails));
+        self
+    }
+
+    /// Asks for answers in `language`, such as `"Japanese"`, in every chat completion whose
+    /// options do not set [`ChatOptions::response_language`]. The instruction is added to the
+    /// system prompt the same way for every request, so that it does not depend on the
+    /// language of the prompt.
+    pub fn with_response_language(mut self, language: impl Into<String>) -> Self {
+        self.response_language = Some(language.into());
+        self
+    }
+
+    /// Scans the messages of every chat completion request, including tool-call arguments, and
+    /// the inputs of every embeddings request with `scanner` before it is sent, redacting
+    /// possible secrets or failing the request. Previews from
+    /// [`CopilotClient::preview_chat_request`] show the redacted messages.
+    pub fn with_secret_scanner(mut self, scanner: SecretScanner) -> Self {
+        self.secret_scanner = Some(Arc::new(scanner));
+        self
+    }
+
+    /// Replaces personal data in the messages of every chat completion request with
+    /// placeholders before it is sent, and, if `filter` [restores](PiiFilter::with_restore)
+    /// them, puts the original values back into the reply, before any post-processors and
+    /// guardrails see it.
+    pub fn with_pii_filter(mut self, filter: PiiFilter) -> Self {
+        self.pii_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Runs `processors` on the replies of every chat completion that is not streamed, before
+    /// those in [`ChatOptions::post_processors`] and before any guardrails.
+    pub fn with_post_processors(mut self, processors: PostProcessors) -> Self {
+        self.post_processors = Some(Arc::new(processors));
+        self
+    }
+
+    /// Answers chat completion and embeddings requests identical to earlier ones from `cache`
+    /// instead of sending them. Streamed chat completions are not cached.
+    pub fn with_response_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// Coalesces chat completion and embeddings requests identical to one still in flight into
+    /// that request: instead of going upstream, they wait for it and receive a copy of its
+    /// response or error. Clones share the requests in flight. Streamed chat completions are
+    /// not deduplicated.
+    pub fn with_deduplication(mut self) -> Self {
+        self.in_flight = Some(Arc::new(InFlight::default()));
+        self
+    }
+
+    /// Retries requests of [`CopilotClient::chat_completion_batch`] that fail with a transient
+    /// error as described by `retry`, waiting with backoff between attempts. Without a policy,
+    /// failed batch requests are not retried.
+    pub fn with_batch_retry(mut self, retry: BatchRetry) -> Self {
+        self.batch_retry = Some(Arc::new(retry));
+        self
+    }
+
+    /// Calls `hooks` on the lifecycle events of all further requests: before each request,
+    /// after each response, before each retry, and after each Copilot token exchange. Replaces
+    /// hooks attached earlier.
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = Some(Arc::new(hooks));
+        self
+    }
+
+    /// Runs `interceptor` on every further request before it is sent, after the
+    /// interceptors added before it. See the [`interceptor`] module.
+    pub fn with_interceptor(mut self, interceptor: impl Interceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Returns the attached [`UsageTracker`], if any.
+    pub fn usage_tracker(&self) -> Option<&Arc<UsageTracker>> {
+        self.usage_tracker.as_ref()
+    }
+
+    /// Returns the attached [`Budget`], if any.
+    pub fn budget(&self) -> Option<&Arc<Budget>> {
+        self.budget.as_ref()
+    }
+
+    /// Returns the models fetched when the client was created.
+    pub fn models(&self) -> &[Model] {
+        &self.models
+    }
+
+    /// Returns `true` if `model_id` is among the available models.
+    pub fn has_model(&self, model_id: &str) -> bool {
+        self.models.iter().any(|m| m.id == model_id)
+    }
+
+    /// Returns the key identifying a request to `url` with `body`, if responses are cached or
+    /// identical requests are deduplicated.
+    fn request_key(&self, url: &str, body: &impl Serialize) -> Option<String> {
+        if self.response_cache.is_none() && self.in_flight.is_none() {
+            return None;
+        }
+        cache::key(url, body)
+    }
+
+    /// Looks up the response to the request identified by `key` in the response cache.
+    fn cached<T: serde::de::DeserializeOwned>(&self, key: Option<&str>) -> Option<T> {
+        let response = self.response_cache.as_ref()?.get(key?, self.clock.now());
+        if response.is_some() {
+            tracing::debug!("Response cache hit");
+        }
+        response
+    }
+
+    /// Runs `request`, or, with deduplication enabled, waits for an identical request in
+    /// flight instead.
+    async fn deduplicated<T: Clone + Send + Sync + 'static>(
+        &self,
+        key: Option<String>,
+        request: impl Future<Output = Result<T, CopilotError>>,
+    ) -> Result<T, CopilotError> {
+        match (&self.in_flight, key) {
+            (Some(in_flight), Some(key)) => in_flight.run(key, request).await,
+            _ => request.await,
+        }
+    }
+
+    /// Stores `response` in the response cache under `key`.
+    fn cache_response(&self, key: &str, response: &impl Serialize) {
+        if let Some(cache) = &self.response_cache {
+            cache.put(key, response, self.clock.now());
+        }
     }
 
     /// Constructs the HTTP headers required for GitHub Copilot API requests.
     ///
     /// This includes the authentication token, editor version information,
-    /// and other necessary headers.
+    /// and other necessary headers. The headers are cached with the Copilot token until it
+    /// expires; each call adds a new `X-Request-Id`.
     async fn get_headers(&self) -> Result<HeaderMap, CopilotError> {
-        let token = self.get_copilot_token().await?;
+        let now = self.clock.now().as_secs();
+        let cached = match &*self.token.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(token) if token.expires_at > now.saturating_add(TOKEN_REFRESH_MARGIN) => {
+                Some(token.headers.clone())
+            }
+            _ => None,
+        };
+        let mut headers = match cached {
+            Some(headers) => headers,
+            None => self.refresh_copilot_token().await?,
+        };
+        request_id::set(&mut headers, None);
+        Ok(headers)
+    }
+
+    fn headers_with_token(&self, token: &str) -> Result<HeaderMap, CopilotError> {
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
@@ -271,35 +1695,76 @@ impl CopilotClient {
         Ok(headers)
     }
 
-    /// Retrieves a GitHub Copilot token using the stored GitHub token.
+    /// Fetches a new Copilot token and caches it, whether or not the cached one has expired,
+    /// and returns the request headers built from it.
     ///
     /// # Errors
     ///
     /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
-    async fn get_copilot_token(&self) -> Result<String, CopilotError> {
-        let url = "https://api.github.com/copilot_internal/v2/token";
-        let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, HeaderValue::from_static("CopilotChat.nvim"));
-        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
-        headers.insert(
-            "Authorization",
-            HeaderValue::from_str(&format!("Token {}", self.github_token))
-                .map_err(|e| CopilotError::Other(e.to_string()))?,
+    async fn refresh_copilot_token(&self) -> Result<HeaderMap, CopilotError> {
+        let token_response =
+            auth::request_copilot_token(&*self.transport, &self.github_token).await;
+        if let Some(hooks) = &self.hooks {
+            hooks.token_refresh(&hooks::TokenRefreshEvent {
+                expires_at: token_response.as_ref().ok().map(|token| token.expires_at),
+                error: token_response.as_ref().err().map(ToString::to_string),
+            });
+        }
+        let token_response = token_response?;
+        tracing::debug!(
+            expires_at = token_response.expires_at,
+            "Refreshed Copilot token"
         );
-        let res = self
-            .http_client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?;
-        let token_response: CopilotTokenResponse = res
-            .json()
-            .await
-            .map_err(|e| CopilotError::Other(e.to_string()))?;
-        Ok(token_response.token)
+        let headers = self.headers_with_token(&token_response.token)?;
+        *self.token.lock().unwrap_or_else(|e| e.into_inner()) = Some(CachedToken {
+            expires_at: token_response.expires_at,
+            headers: headers.clone(),
+        });
+        Ok(headers)
+    }
+
+    /// Sends `request` through the client's transport with [`send`], after the interceptors
+    /// have changed it, calling the request and response hooks around it.
+    pub(crate) async fn send_request(
+        &self,
+        request: HttpRequest,
+    ) -> Result<HttpResponse, CopilotError> {
+        self.send_tagged(request, &BTreeMap::new()).await
+    }
+
+    /// Like [`CopilotClient::send_request`], passing `tags` to the hooks.
+    pub(crate) async fn send_tagged(
+        &self,
+        mut request: HttpRequest,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<HttpResponse, CopilotError> {
+        for interceptor in &self.interceptors {
+            interceptor.intercept(&mut request)?;
+        }
+        let Some(hooks) = &self.hooks else {
+            return send(&*self.transport, request).await;
+        };
+        let event = hooks::RequestEvent::new(&request, tags);
+        hooks.request(&event);
+        let start = unix_time();
+        let result = send(&*self.transport, request).await;
+        hooks.response(&hooks::ResponseEvent {
+            method: event.method,
+            url: event.url,
+            status: match &result {
+                Ok(response) => Some(response.status.as_u16()),
+                Err(e) => e.status(),
+            },
+            latency: unix_time().saturating_sub(start),
+            error: result.as_ref().err().map(ToString::to_string),
+            request_id: event.request_id,
+            server_request_id: match &result {
+                Ok(response) => request_id::server_id(&response.headers),
+                Err(e) => e.server_request_id().map(str::to_string),
+            },
+            tags: event.tags,
+        });
+        result
     }
 
     /// Fetches the list of agents from the GitHub Copilot API.
@@ -307,22 +1772,12 @@ impl CopilotClient {
     /// # Errors
     ///
     /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+    #[tracing::instrument(name = "copilot.get_agents", skip_all, err(Display, level = "debug"))]
     pub async fn get_agents(&self) -> Result<Vec<Agent>, CopilotError> {
         let url = "https://api.githubcopilot.com/agents";
         let headers = self.get_headers().await?;
-        let res = self
-            .http_client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?;
-        let agents_response: AgentsResponse = res
-            .json()
-            .await
-            .map_err(|e| CopilotError::Other(e.to_string()))?;
+        let request = HttpRequest::new(Method::GET, url).headers(headers);
+        let agents_response: AgentsResponse = self.send_request(request).await?.json().await?;
         Ok(agents_response.agents)
     }
 
@@ -331,30 +1786,36 @@ impl CopilotClient {
     /// # Errors
     ///
     /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+    #[tracing::instrument(name = "copilot.get_models", skip_all, err(Display, level = "debug"))]
     pub async fn get_models(&self) -> Result<Vec<Model>, CopilotError> {
-        let url = "https://api.githubcopilot.com/models";
         let headers = self.get_headers().await?;
-        let res = self
-            .http_client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?;
-        let models_response: ModelsResponse = res
-            .json()
-            .await
-            .map_err(|e| CopilotError::Other(e.to_string()))?;
+        let request = HttpRequest::new(Method::GET, MODELS_URL).headers(headers);
+        let models_response: ModelsResponse = self.send_request(request).await?.json().await?;
         Ok(models_response.data)
     }
 
+    /// Fetches the Copilot token and the list of available models, which the first chat
+    /// completion would otherwise wait for, and leaves a connection to the API open for it.
+    /// The client's model list is replaced with the fetched one.
+    ///
+    /// Clients created with [`CopilotClient::new_with_models`] are warmed up on creation; call
+    /// this at startup for clients created with [`CopilotClient::with_models`], for example from
+    /// a cached model list.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the token exchange or the model fetch fails.
+    #[tracing::instrument(name = "copilot.warm_up", skip_all, err(Display, level = "debug"))]
+    pub async fn warm_up(&mut self) -> Result<(), CopilotError> {
+        self.models = self.get_models().await?;
+        Ok(())
+    }
+
     /// Sends a chat completion request to the GitHub Copilot API.
     ///
     /// # Arguments
     ///
-    /// * `messages` - A vector of chat messages to send.
+    /// * `messages` - The chat messages to send, owned or borrowed.
     /// * `model_id` - The identifier of the model to use.
     ///
     /// # Errors
@@ -363,42 +1824,425 @@ impl CopilotClient {
     /// or another `CopilotError` if the HTTP request or response parsing fails.
     pub async fn chat_completion(
         &self,
-        messages: Vec<Message>,
+        messages: impl Into<Cow<'_, [Message]>>,
+        model_id: String,
+    ) -> Result<ChatResponse, CopilotError> {
+        self.chat_completion_with_options(messages, model_id, &ChatOptions::default())
+            .await
+    }
+
+    /// Sends a chat completion request using the given [`ChatOptions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The chat messages to send, owned or borrowed.
+    /// * `model_id` - The identifier of the model to use.
+    /// * `options` - Sampling and length options for the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available,
+    /// or another `CopilotError` if the HTTP request or response parsing fails.
+    pub async fn chat_completion_with_options(
+        &self,
+        messages: impl Into<Cow<'_, [Message]>>,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ChatResponse, CopilotError> {
+        let mut messages = messages.into();
+        let placeholders = self.redact_pii(&mut messages);
+        let mut response = match &self.fallback {
+            None => {
+                self.send_chat_completion(messages, model_id, options)
+                    .await?
+            }
+            Some(policy) => {
+                let messages = &*messages;
+                policy
+                    .run(
+                        model_id,
+                        self.hooks.as_deref(),
+                        &options.tags,
+                        |response| policy.falls_back_on_response(response),
+                        |model_id| self.send_chat_completion(messages.into(), model_id, options),
+                    )
+                    .await?
+            }
+        };
+        if let Some(placeholders) = placeholders {
+            placeholders.restore_response(&mut response);
+        }
+        let processors = [
+            self.post_processors.as_deref(),
+            options.post_processors.as_ref(),
+        ];
+        for processors in processors.into_iter().flatten() {
+            processors.apply(&mut response);
+        }
+        if let Some(guardrails) = &self.guardrails {
+            guardrails.apply(&mut response)?;
+        }
+        Ok(response)
+    }
+
+    /// Sends a chat completion request for a single model.
+    #[tracing::instrument(
+        name = "copilot.chat_completion",
+        skip_all,
+        fields(
+            model = %model_id,
+            stream = false,
+            tags = Empty,
+            prompt_tokens = Empty,
+            completion_tokens = Empty,
+            total_tokens = Empty,
+        ),
+        err(Display, level = "debug"),
+    )]
+    async fn send_chat_completion(
+        &self,
+        messages: Cow<'_, [Message]>,
         model_id: String,
+        options: &ChatOptions,
     ) -> Result<ChatResponse, CopilotError> {
+        #[cfg(feature = "otel")]
+        let operation = otel::Operation::chat(&model_id, options);
+        record_tags(&Span::current(), &options.tags);
+        let result = async {
+            let request_body = self.build_chat_request(messages, model_id, options, false)?;
+            let key = self.request_key(CHAT_COMPLETIONS_URL, &request_body);
+            if let Some(response) = self.cached(key.as_deref()) {
+                return Ok(response);
+            }
+            self.deduplicated(key.clone(), async {
+                let mut headers = self.get_headers().await?;
+                if options.request_id.is_some() {
+                    request_id::set(&mut headers, options.request_id.as_deref());
+                }
+                add_vision_header(&mut headers, &request_body.messages);
+                let id = request_id::client_id(&headers);
+                let request = HttpRequest::new(Method::POST, CHAT_COMPLETIONS_URL)
+                    .headers(headers)
+                    .json(&request_body)?;
+                self.acquire_budget()?;
+                let start = unix_time();
+                let response = self.send_tagged(request, &options.tags).await?;
+                let server_id = request_id::server_id(&response.headers);
+                let mut chat_response: ChatResponse = response.json().await?;
+                chat_response.model = Some(request_body.model.clone());
+                chat_response.request_id = id;
+                chat_response.server_request_id = server_id;
+                if let Some(key) = &key {
+                    self.cache_response(key, &chat_response);
+                }
+                record_usage(
+                    &Span::current(),
+                    &request_body.model,
+                    chat_response.usage.as_ref(),
+                );
+                if let Some(tracker) = &self.usage_tracker {
+                    tracker.record_chat_timed(
+                        &request_body.model,
+                        chat_response.usage.as_ref(),
+                        &options.tags,
+                        Some(unix_time().saturating_sub(start)),
+                    );
+                }
+                if let Some(budget) = &self.budget {
+                    budget.record_tokens(chat_response.usage.as_ref());
+                }
+                Ok(chat_response)
+            })
+            .await
+        }
+        .await;
+        #[cfg(feature = "otel")]
+        operation.end_chat(&result);
+        result
+    }
+
+    /// Sends a chat completion request and streams the response as it is generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The chat messages to send, owned or borrowed.
+    /// * `model_id` - The identifier of the model to use.
+    /// * `options` - Sampling and length options for the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available,
+    /// or another `CopilotError` if the request cannot be sent. Errors that occur while the
+    /// response is streaming are yielded by the returned [`ChatStream`].
+    pub async fn chat_completion_stream(
+        &self,
+        messages: impl Into<Cow<'_, [Message]>>,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ChatStream, CopilotError> {
+        let mut messages = messages.into();
+        let placeholders = self.redact_pii(&mut messages);
+        let mut stream = match &self.fallback {
+            None => {
+                self.send_chat_completion_stream(messages, model_id, options)
+                    .await?
+            }
+            Some(policy) => {
+                let messages = &*messages;
+                policy
+                    .run(
+                        model_id,
+                        self.hooks.as_deref(),
+                        &options.tags,
+                        |_| false,
+                        |model_id| {
+                            self.send_chat_completion_stream(messages.into(), model_id, options)
+                        },
+                    )
+                    .await?
+            }
+        };
+        if let Some(placeholders) = placeholders {
+            stream = stream.with_restored_placeholders(placeholders);
+        }
+        Ok(match &self.guardrails {
+            Some(guardrails) => stream.with_guardrails(Guardrails::clone(guardrails)),
+            None => stream,
+        })
+    }
+
+    /// Sends a streaming chat completion request for a single model.
+    #[tracing::instrument(
+        name = "copilot.chat_completion",
+        skip_all,
+        fields(
+            model = %model_id,
+            stream = true,
+            tags = Empty,
+            prompt_tokens = Empty,
+            completion_tokens = Empty,
+            total_tokens = Empty,
+        ),
+        err(Display, level = "debug"),
+    )]
+    async fn send_chat_completion_stream(
+        &self,
+        messages: Cow<'_, [Message]>,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ChatStream, CopilotError> {
+        #[cfg(feature = "otel")]
+        let operation = otel::Operation::chat(&model_id, options);
+        record_tags(&Span::current(), &options.tags);
+        let result = async {
+            let request_body = self.build_chat_request(messages, model_id, options, true)?;
+            let mut headers = self.get_headers().await?;
+            if options.request_id.is_some() {
+                request_id::set(&mut headers, options.request_id.as_deref());
+            }
+            headers.insert(ACCEPT, HeaderValue::from_static("text/event-stream"));
+            add_vision_header(&mut headers, &request_body.messages);
+            let id = request_id::client_id(&headers);
+            let request = HttpRequest::new(Method::POST, CHAT_COMPLETIONS_URL)
+                .headers(headers)
+                .json(&request_body)?;
+            self.acquire_budget()?;
+            let start = unix_time();
+            let res = self.send_tagged(request, &options.tags).await?;
+            Ok(ChatStream::from_response(
+                res,
+                request_body.model,
+                id,
+                stream::Accounting {
+                    tracker: self.usage_tracker.clone(),
+                    budget: self.budget.clone(),
+                    tags: options.tags.clone(),
+                    start,
+                },
+                Span::current(),
+            ))
+        }
+        .await;
+        #[cfg(feature = "otel")]
+        let result = match result {
+            Ok(stream) => Ok(stream.with_operation(operation)),
+            Err(e) => {
+                operation.end(Some(&e));
+                Err(e)
+            }
+        };
+        result
+    }
+
+    /// Returns the chat completion request that [`CopilotClient::chat_completion_with_options`]
+    /// (or, with `stream` set, [`CopilotClient::chat_completion_stream`]) would send, without
+    /// sending it or fetching a token.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available.
+    pub fn preview_chat_request<'a>(
+        &self,
+        messages: impl Into<Cow<'a, [Message]>>,
+        model_id: String,
+        options: &ChatOptions,
+        stream: bool,
+    ) -> Result<RequestPreview, CopilotError> {
+        let mut messages = messages.into();
+        self.redact_pii(&mut messages);
+        let request_body = self.build_chat_request(messages, model_id, options, stream)?;
+        let mut headers = self.headers_with_token(REDACTED)?;
+        if stream {
+            headers.insert(ACCEPT, HeaderValue::from_static("text/event-stream"));
+        }
+        add_vision_header(&mut headers, &request_body.messages);
+        // `json()` sets the content type when the request is sent.
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(RequestPreview {
+            method: "POST".to_string(),
+            url: CHAT_COMPLETIONS_URL.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+            body: serde_json::to_value(&request_body)
+                .map_err(|e| CopilotError::Other(e.to_string()))?,
+        })
+    }
+
+    /// Counts a request against the client's [`Budget`], failing if a limit is reached.
+    fn acquire_budget(&self) -> Result<(), CopilotError> {
+        match &self.budget {
+            Some(budget) => budget.acquire().map_err(CopilotError::BudgetExceeded),
+            None => Ok(()),
+        }
+    }
+
+    /// Estimates the cost of the chat completion that
+    /// [`CopilotClient::chat_completion_with_options`] would send, without sending it.
+    ///
+    /// The prompt tokens are estimated from the messages with [`prompt::estimate_tokens`], and
+    /// the completion tokens are taken to be the request's `max_tokens`, or 0 without a limit,
+    /// so that with a limit the estimate is an upper bound. Prices and multipliers come from
+    /// the usage tracker, as they would be recorded, or else from [`PriceTable::builtin`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::InvalidModel` error if the specified model is not available.
+    pub fn estimate_cost<'a>(
+        &self,
+        messages: impl Into<Cow<'a, [Message]>>,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<Cost, CopilotError> {
+        let mut messages = messages.into();
+        self.redact_pii(&mut messages);
+        let request = self.build_chat_request(messages, model_id, options, false)?;
+        let prompt_tokens = request
+            .messages
+            .iter()
+            .map(prompt::estimate_message_tokens)
+            .sum::<u32>();
+        let completion_tokens = request.max_tokens.unwrap_or(0);
+        let (prices, multiplier) = match &self.usage_tracker {
+            Some(tracker) => (
+                tracker.price_table().unwrap_or_else(PriceTable::builtin),
+                tracker.premium_multiplier(&request.model),
+            ),
+            None => {
+                let prices = PriceTable::builtin();
+                let multiplier = prices.premium_multiplier(&request.model).unwrap_or(1.0);
+                (prices, multiplier)
+            }
+        };
+        let usage = usage::ModelUsage {
+            requests: 1,
+            prompt_tokens: u64::from(prompt_tokens),
+            completion_tokens: u64::from(completion_tokens),
+            total_tokens: u64::from(prompt_tokens) + u64::from(completion_tokens),
+            premium_requests: multiplier,
+            ..usage::ModelUsage::default()
+        };
+        Ok(prices.cost(&request.model, &usage))
+    }
+
+    /// Redacts personal data in `messages` with the client's [`PiiFilter`], returning the
+    /// placeholders to restore in the reply, if any.
+    fn redact_pii(&self, messages: &mut Cow<'_, [Message]>) -> Option<pii::Placeholders> {
+        let filter = self.pii_filter.as_ref()?;
+        let placeholders = filter.redact_messages(messages);
+        (filter.restores() && !placeholders.is_empty()).then_some(placeholders)
+    }
+
+    /// Builds the request body for a chat completion, validating the model.
+    fn build_chat_request<'a>(
+        &self,
+        messages: Cow<'a, [Message]>,
+        model_id: String,
+        options: &ChatOptions,
+        stream: bool,
+    ) -> Result<ChatRequest<'a>, CopilotError> {
         // Check if the specified model is available.
-        if !self.models.iter().any(|m| m.id == model_id) {
+        let Some(model) = self.models.iter().find(|m| m.id == model_id) else {
             return Err(CopilotError::InvalidModel(model_id));
+        };
+        let mut messages = messages;
+        if let Some(scanner) = &self.secret_scanner {
+            scanner.apply(&mut messages)?;
         }
-        let url = "https://api.githubcopilot.com/chat/completions";
-        let headers = self.get_headers().await?;
-        let request_body = ChatRequest {
+        if let Some(language) = options
+            .response_language
+            .as_deref()
+            .or(self.response_language.as_deref())
+        {
+            prompt::apply_response_language(messages.to_mut(), language);
+        }
+        let max_tokens = options.resolve_max_tokens(model, &messages);
+        Ok(ChatRequest {
             model: model_id,
             messages,
-            n: 1,
-            top_p: 1.0,
-            stream: false,
-            temperature: 0.5,
-            max_tokens: None,
+            n: options.n,
+            top_p: options.top_p,
+            stream,
+            temperature: options.temperature,
+            max_tokens,
+            stop: options.stop.clone(),
+            logprobs: options.logprobs || options.top_logprobs.is_some(),
+            top_logprobs: options.top_logprobs,
+            seed: options.seed,
+            logit_bias: options.logit_bias.clone(),
+            prediction: options.prediction.clone(),
+            tools: options.tools.clone(),
+            tool_choice: options.tool_choice.clone(),
+        })
+    }
+
+    /// Returns the model that [`CopilotClient::get_embeddings`] sends requests to:
+    /// [`DEFAULT_EMBEDDING_MODEL`] if it is among the enabled embeddings models, or else the
+    /// first of them, so that embeddings keep working when GitHub replaces its embedding
+    /// models. Without such a model, for example in a client created with
+    /// [`CopilotClient::with_models`] and no models, it is [`DEFAULT_EMBEDDING_MODEL`].
+    pub fn embedding_model(&self) -> &str {
+        let embedding_models = || {
+            self.models
+                .iter()
+                .filter(|model| model.is_embeddings_model() && model.is_enabled())
         };
-        let res = self
-            .http_client
-            .post(url)
-            .headers(headers)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?;
-        let chat_response: ChatResponse = res
-            .json()
-            .await
-            .map_err(|e| CopilotError::Other(e.to_string()))?;
-        Ok(chat_response)
+        if embedding_models().any(|model| model.id == DEFAULT_EMBEDDING_MODEL) {
+            return DEFAULT_EMBEDDING_MODEL;
+        }
+        embedding_models()
+            .next()
+            .map_or(DEFAULT_EMBEDDING_MODEL, |model| model.id.as_str())
     }
 
-    /// Sends an embeddings request to the GitHub Copilot API.
+    /// Sends an embeddings request to the GitHub Copilot API, to the model returned by
+    /// [`CopilotClient::embedding_model`].
     ///
     /// # Arguments
     ///
@@ -407,45 +2251,248 @@ impl CopilotClient {
     /// # Errors
     ///
     /// Returns a `CopilotError` if the HTTP request fails or the response cannot be parsed.
+    #[tracing::instrument(
+        name = "copilot.get_embeddings",
+        skip_all,
+        fields(
+            model = self.embedding_model(),
+            inputs = inputs.len(),
+            prompt_tokens = Empty,
+            completion_tokens = Empty,
+            total_tokens = Empty,
+        ),
+        err(Display, level = "debug"),
+    )]
     pub async fn get_embeddings(
         &self,
         inputs: Vec<String>,
     ) -> Result<Vec<Embedding>, CopilotError> {
-        let url = "https://api.githubcopilot.com/embeddings";
-        let headers = self.get_headers().await?;
-        let request_body = EmbeddingRequest {
-            dimensions: 512,
-            input: inputs,
-            model: "text-embedding-3-small".to_string(),
-        };
-        let res = self
-            .http_client
-            .post(url)
-            .headers(headers)
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?
-            .error_for_status()
-            .map_err(|e| CopilotError::HttpError(e.to_string()))?;
-        let embedding_response: EmbeddingResponse = res
-            .json()
+        #[cfg(feature = "otel")]
+        let mut operation = otel::Operation::embeddings(self.embedding_model());
+        let result = async {
+            let url = EMBEDDINGS_URL;
+            let mut inputs = inputs;
+            if let Some(scanner) = &self.secret_scanner {
+                scanner.apply_to_inputs(&mut inputs)?;
+            }
+            let request_body = EmbeddingRequest {
+                dimensions: EMBEDDING_DIMENSIONS,
+                input: inputs,
+                model: self.embedding_model().to_string(),
+            };
+            let key = self.request_key(url, &request_body);
+            if let Some(response) = self.cached(key.as_deref()) {
+                return Ok(response);
+            }
+            self.deduplicated(key.clone(), async {
+                let headers = self.get_headers().await?;
+                let request = HttpRequest::new(Method::POST, url)
+                    .headers(headers)
+                    .json(&request_body)?;
+                self.acquire_budget()?;
+                let start = unix_time();
+                let embedding_response: EmbeddingResponse =
+                    self.send_request(request).await?.json().await?;
+                if let Some(key) = &key {
+                    self.cache_response(key, &embedding_response);
+                }
+                record_usage(
+                    &Span::current(),
+                    &request_body.model,
+                    embedding_response.usage.as_ref(),
+                );
+                if let Some(tracker) = &self.usage_tracker {
+                    tracker.record_embeddings_timed(
+                        &request_body.model,
+                        embedding_response.usage.as_ref(),
+                        Some(unix_time().saturating_sub(start)),
+                    );
+                }
+                if let Some(budget) = &self.budget {
+                    budget.record_tokens(embedding_response.usage.as_ref());
+                }
+                Ok(embedding_response)
+            })
             .await
-            .map_err(|e| CopilotError::Other(e.to_string()))?;
-        Ok(embedding_response.data)
+        }
+        .await;
+        #[cfg(feature = "otel")]
+        match &result {
+            Ok(response) => {
+                operation.record_usage(response.usage.as_ref());
+                operation.end(None);
+            }
+            Err(e) => operation.end(Some(e)),
+        }
+        result.map(|response| response.data)
+    }
+}
+
+/// Maximum number of characters of an error response body kept in [`CopilotError::ApiError`].
+const ERROR_BODY_LIMIT: usize = 500;
+
+/// Sends `request` through `transport` and checks the status of the response. Redirects are
+/// returned as responses if the request does not follow them.
+///
+/// Runs in a `copilot.http` span that records the status and the time until the response
+/// headers arrived.
+#[tracing::instrument(
+    name = "copilot.http",
+    level = "debug",
+    skip_all,
+    fields(
+        method = %request.method,
+        url = %request.url,
+        request_id = Empty,
+        server_request_id = Empty,
+        status = Empty,
+        latency_ms = Empty,
+    ),
+)]
+pub(crate) async fn send(
+    transport: &dyn HttpTransport,
+    request: HttpRequest,
+) -> Result<HttpResponse, CopilotError> {
+    let span = Span::current();
+    let id = request_id::client_id(&request.headers);
+    if let Some(id) = &id {
+        span.record("request_id", id.as_str());
+    }
+    tracing::trace!(headers = ?redacted_headers(&request.headers), "Sending request");
+    #[cfg(feature = "metrics")]
+    let url = request.url.clone();
+    let follow_redirects = request.follow_redirects;
+    let start = unix_time();
+    let response = transport.send(request).await;
+    let latency = unix_time().saturating_sub(start);
+    #[cfg(feature = "metrics")]
+    metrics::record_request(
+        &url,
+        response.as_ref().ok().map(|r| r.status.as_u16()),
+        latency,
+    );
+    let response = response?;
+    if let Some(server_id) = request_id::server_id(&response.headers) {
+        span.record("server_request_id", server_id.as_str());
+    }
+    span.record("status", response.status.as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    if !follow_redirects && response.status.is_redirection() {
+        return Ok(response);
+    }
+    check_status(response, id).await
+}
+
+/// Marks requests whose messages carry images as vision requests, which the API requires.
+fn add_vision_header(headers: &mut HeaderMap, messages: &[Message]) {
+    if messages.iter().any(|message| !message.images.is_empty()) {
+        headers.insert("Copilot-Vision-Request", HeaderValue::from_static("true"));
+    }
+}
+
+/// Returns the headers as text, with the credentials in `Authorization` and values marked as
+/// sensitive replaced.
+pub(crate) fn redacted_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if *name == AUTHORIZATION || value.is_sensitive() {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// Records non-empty request tags on a span with a `tags` field.
+fn record_tags(span: &Span, tags: &BTreeMap<String, String>) {
+    if !tags.is_empty() {
+        span.record("tags", tracing::field::debug(tags));
+    }
+}
+
+/// Records token counts on a span with `prompt_tokens`, `completion_tokens`, and `total_tokens`
+/// fields, and with the `metrics` feature in the token counter of `model`.
+pub(crate) fn record_usage(span: &Span, model: &str, usage: Option<&TokenUsage>) {
+    if let Some(usage) = usage {
+        #[cfg(feature = "metrics")]
+        metrics::record_tokens(model, usage);
+        #[cfg(not(feature = "metrics"))]
+        let _ = model;
+        span.record("prompt_tokens", usage.prompt_tokens);
+        span.record("completion_tokens", usage.completion_tokens);
+        span.record("total_tokens", usage.total_tokens);
     }
 }
 
+/// Passes successful responses through and turns error statuses into [`CopilotError::ApiError`],
+/// keeping the message from the response body and the IDs of the request.
+pub(crate) async fn check_status(
+    res: HttpResponse,
+    request_id: Option<String>,
+) -> Result<HttpResponse, CopilotError> {
+    let status = res.status;
+    if status.is_success() {
+        return Ok(res);
+    }
+    let retry_after = res
+        .headers
+        .get(http::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    let server_request_id = request_id::server_id(&res.headers);
+    let body = res
+        .bytes()
+        .await
+        .map(|body| String::from_utf8_lossy(&body).into_owned())
+        .unwrap_or_default();
+    // Error bodies are usually `{"error": {"message": ..., "code": ...}}` or `{"message": ...}`.
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|v| {
+            let message = v
+                .pointer("/error/message")
+                .or_else(|| v.get("message"))
+                .or_else(|| v.get("error"))
+                .and_then(|m| m.as_str())?;
+            Some(match v.pointer("/error/code").and_then(|c| c.as_str()) {
+                Some(code) => format!("{message} ({code})"),
+                None => message.to_string(),
+            })
+        })
+        .unwrap_or_else(|| body.trim().chars().take(ERROR_BODY_LIMIT).collect());
+    let message = if message.is_empty() {
+        status
+            .canonical_reason()
+            .unwrap_or("Request failed")
+            .to_string()
+    } else {
+        message
+    };
+    Err(CopilotError::ApiError {
+        status: status.as_u16(),
+        message,
+        retry_after,
+        request_id,
+        server_request_id,
+    })
+}
+
 /// Retrieves the GitHub token from the `GITHUB_TOKEN` environment variable or from a configuration file.
 ///
 /// # Errors
 ///
 /// Returns an error if the token is not found in the environment or configuration files.
 pub fn get_github_token() -> Result<String, Box<dyn Error>> {
-    if let Ok(token) = env::var("GITHUB_TOKEN") {
-        if env::var("CODESPACES").is_ok() {
-            return Ok(token);
-        }
+    if cfg!(target_arch = "wasm32") {
+        return Err("no token discovery on wasm32; pass the GitHub token to the client".into());
+    }
+    if let Ok(token) = env::var("GITHUB_TOKEN")
+        && env::var("CODESPACES").is_ok()
+    {
+        return Ok(token);
     }
     let config_dir = get_config_path()?;
     let file_paths = vec![
@@ -458,12 +2505,11 @@ pub fn get_github_token() -> Result<String, Box<dyn Error>> {
             let json_value: Value = serde_json::from_str(&content)?;
             if let Some(obj) = json_value.as_object() {
                 for (key, value) in obj {
-                    if key.contains("github.com") {
-                        if let Some(oauth_token) = value.get("oauth_token") {
-                            if let Some(token_str) = oauth_token.as_str() {
-                                return Ok(token_str.to_string());
-                            }
-                        }
+                    if key.contains("github.com")
+                        && let Some(token_str) =
+                            value.get("oauth_token").and_then(|token| token.as_str())
+                    {
+                        return Ok(token_str.to_string());
                     }
                 }
             }
@@ -472,6 +2518,21 @@ pub fn get_github_token() -> Result<String, Box<dyn Error>> {
     Err("Failed to find GitHub token".into())
 }
 
+/// Returns the time elapsed since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unix_time() -> Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Returns the time elapsed since the Unix epoch, read from the JavaScript clock because
+/// `SystemTime` is unavailable in the browser.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn unix_time() -> Duration {
+    Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
+
 /// Returns the user's configuration directory.
 ///
 /// On Unix systems, this is determined by the `XDG_CONFIG_HOME` environment variable or defaults
@@ -481,16 +2542,16 @@ pub fn get_github_token() -> Result<String, Box<dyn Error>> {
 ///
 /// Returns an error if the configuration directory cannot be determined.
 pub fn get_config_path() -> Result<String, Box<dyn Error>> {
-    if let Ok(xdg) = env::var("XDG_CONFIG_HOME") {
-        if !xdg.is_empty() {
-            return Ok(xdg);
-        }
+    if let Ok(xdg) = env::var("XDG_CONFIG_HOME")
+        && !xdg.is_empty()
+    {
+        return Ok(xdg);
     }
     if cfg!(target_os = "windows") {
-        if let Ok(local) = env::var("LOCALAPPDATA") {
-            if !local.is_empty() {
-                return Ok(local);
-            }
+        if let Ok(local) = env::var("LOCALAPPDATA")
+            && !local.is_empty()
+        {
+            return Ok(local);
         }
     } else if let Ok(home) = env::var("HOME") {
         return Ok(format!("{home}/.config"));
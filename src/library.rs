@@ -0,0 +1,232 @@
+//! Built-in prompts for common code tasks.
+//!
+//! The library holds [`PromptTemplates`] for explaining code, writing tests, refactoring,
+//! adding documentation, and finding bugs, modeled on the built-in prompts of CopilotChat.nvim,
+//! and helpers on [`CopilotClient`] that send them:
+//!
+//! ```no_run
+//! # async fn run(client: copilot_client::CopilotClient) -> Result<(), copilot_client::CopilotError> {
+//! let code = "fn add(a: i32, b: i32) -> i32 { a + b }";
+//! let explanation = client.explain(code, "rust", "gpt-4o".into()).await?;
+//! let tests = client.write_tests(code, "rust", "gpt-4o".into()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every task is a pair of a shared `system` template and a task template, rendered with the
+//! variables `code` and `language` (and `instructions` for `refactor`). To adjust the wording,
+//! override them with files of the same name and send them with
+//! [`CopilotClient::chat_template`]:
+//!
+//! ```no_run
+//! use copilot_client::templates::PromptTemplates;
+//! use serde_json::json;
+//!
+//! # async fn run(client: copilot_client::CopilotClient) -> Result<(), copilot_client::CopilotError> {
+//! let templates = PromptTemplates::builtin().with_dir(PromptTemplates::default_dir()?)?;
+//! let vars = json!({ "code": "SELECT 1", "language": "sql" });
+//! let reply = client.chat_template(&templates, "find_bugs", vars, "gpt-4o".into()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{ChatOptions, CopilotClient, CopilotError, templates::PromptTemplates};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::OnceLock;
+
+/// The system prompt shared by the tasks.
+const SYSTEM: &str = "You are a senior software engineer helping with {{ language }} code. \
+Answer in Markdown and put code in fenced code blocks tagged with the language. Be precise and \
+concise: do not restate the request or add pleasantries.";
+
+/// The code of a task, fenced and tagged with its language.
+const CODE: &str = "```{{ language }}\n{{ code }}\n```";
+
+const EXPLAIN: &str = "Explain what the following code does: its purpose, how it works step \
+by step, and anything a reader could miss, such as edge cases, side effects, or performance \
+characteristics.\n\n{% include \"partials/code\" %}";
+
+const WRITE_TESTS: &str = "Write unit tests for the following code with the usual test \
+framework for {{ language }}. Cover normal behavior, edge cases, and error handling, one \
+behavior per test with a descriptive name. Reply with the tests in a single code block, \
+followed by a short list of what they cover.\n\n{% include \"partials/code\" %}";
+
+const REFACTOR: &str = "Refactor the following code to make it easier to read and maintain \
+without changing its behavior.{% if instructions is defined and instructions %} \
+{{ instructions }}{% endif %} Reply with the complete refactored code in a single code block, \
+followed by a short list of the changes.\n\n{% include \"partials/code\" %}";
+
+const ADD_DOCS: &str = "Add documentation comments to the following code in the idiomatic \
+style for {{ language }}. Document every public item with its purpose, parameters, return \
+value, and errors, and leave the code itself unchanged. Reply with the complete documented \
+code in a single code block.\n\n{% include \"partials/code\" %}";
+
+const FIND_BUGS: &str = "Review the following code for bugs: logic errors, unhandled edge \
+cases, resource leaks, concurrency problems, and security issues. For each bug, name the line, \
+explain what goes wrong, and show a fix. If there are no bugs, say so instead of suggesting \
+style changes.\n\n{% include \"partials/code\" %}";
+
+/// The built-in templates by name.
+const BUILTIN: &[(&str, &str)] = &[
+    ("system", SYSTEM),
+    ("partials/code", CODE),
+    ("explain", EXPLAIN),
+    ("write_tests", WRITE_TESTS),
+    ("refactor", REFACTOR),
+    ("add_docs", ADD_DOCS),
+    ("find_bugs", FIND_BUGS),
+];
+
+impl PromptTemplates {
+    /// Returns the built-in prompt library: `system`, `partials/code`, `explain`,
+    /// `write_tests`, `refactor`, `add_docs`, and `find_bugs`.
+    ///
+    /// ```
+    /// use copilot_client::templates::PromptTemplates;
+    /// use serde_json::json;
+    ///
+    /// let prompt = PromptTemplates::builtin()
+    ///     .render("explain", json!({ "code": "x = 1", "language": "python" }))?;
+    /// assert!(prompt.ends_with("```python\nx = 1\n```"));
+    /// # Ok::<(), copilot_client::CopilotError>(())
+    /// ```
+    pub fn builtin() -> Self {
+        static BUILTIN_TEMPLATES: OnceLock<PromptTemplates> = OnceLock::new();
+        BUILTIN_TEMPLATES
+            .get_or_init(|| {
+                BUILTIN
+                    .iter()
+                    .try_fold(PromptTemplates::new(), |templates, (name, source)| {
+                        templates.with_template(*name, *source)
+                    })
+                    .expect("built-in prompt templates parse")
+            })
+            .clone()
+    }
+}
+
+impl CopilotClient {
+    /// Renders the `system` template and the template `name` of `templates` with `vars`,
+    /// sends them as a system and a user message, and returns the reply.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a template is missing or fails to render, or the request
+    /// fails.
+    pub async fn chat_template(
+        &self,
+        templates: &PromptTemplates,
+        name: &str,
+        vars: impl Serialize,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        let messages = vec![
+            templates.render_message("system", "system", &vars)?,
+            templates.render_message("user", name, &vars)?,
+        ];
+        let options = ChatOptions {
+            temperature: 0.2,
+            ..ChatOptions::default()
+        };
+        let response = self
+            .chat_completion_with_options(messages, model_id, &options)
+            .await?;
+        Ok(response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content)
+            .unwrap_or_default())
+    }
+
+    /// Explains what `code`, written in `language`, does.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the request fails.
+    pub async fn explain(
+        &self,
+        code: &str,
+        language: &str,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        self.builtin_task("explain", code, language, "", model_id)
+            .await
+    }
+
+    /// Writes unit tests for `code`, written in `language`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the request fails.
+    pub async fn write_tests(
+        &self,
+        code: &str,
+        language: &str,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        self.builtin_task("write_tests", code, language, "", model_id)
+            .await
+    }
+
+    /// Refactors `code`, written in `language`, without changing its behavior, following the
+    /// optional `instructions`, such as "Extract the parsing into its own function.".
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the request fails.
+    pub async fn refactor(
+        &self,
+        code: &str,
+        language: &str,
+        instructions: &str,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        self.builtin_task("refactor", code, language, instructions, model_id)
+            .await
+    }
+
+    /// Adds documentation comments to `code`, written in `language`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the request fails.
+    pub async fn add_docs(
+        &self,
+        code: &str,
+        language: &str,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        self.builtin_task("add_docs", code, language, "", model_id)
+            .await
+    }
+
+    /// Reviews `code`, written in `language`, for bugs.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the request fails.
+    pub async fn find_bugs(
+        &self,
+        code: &str,
+        language: &str,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        self.builtin_task("find_bugs", code, language, "", model_id)
+            .await
+    }
+
+    async fn builtin_task(
+        &self,
+        name: &str,
+        code: &str,
+        language: &str,
+        instructions: &str,
+        model_id: String,
+    ) -> Result<String, CopilotError> {
+        let vars = json!({ "code": code, "language": language, "instructions": instructions });
+        self.chat_template(&PromptTemplates::builtin(), name, vars, model_id)
+            .await
+    }
+}
@@ -0,0 +1,259 @@
+//! Logging of request and response bodies.
+//!
+//! A [`LoggingTransport`] writes the bodies that pass through it to `tracing` events with the
+//! `copilot_client::logging` target, which is useful for finding out why the API rejects a
+//! request with `400 Bad Request`. Logging is opt-in: enable it with
+//! [`CopilotClient::with_request_logging`] and a subscriber filter such as
+//! `copilot_client::logging=debug`:
+//!
+//! - At `DEBUG`, each request body, and a summary of each response: its top-level fields, with
+//!   arrays shortened to their length. Error responses are logged in full.
+//! - At `TRACE`, full response bodies. Streamed responses are logged when the stream ends.
+//!
+//! Credentials in bodies are always replaced. By default, message content, tool call arguments,
+//! and embedding inputs are replaced with their length; [`Redaction`] configures this.
+
+use crate::{
+    CopilotClient, CopilotError, REDACTED, SECRET_KEYS,
+    transport::{BoxStream, HeaderMap, HttpRequest, HttpResponse, HttpTransport, TransportFuture},
+};
+use bytes::Bytes;
+use futures_util::{StreamExt, stream};
+use http::header::CONTENT_TYPE;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Keys of JSON fields whose values are message content, redacted by [`Redaction::content`].
+const CONTENT_KEYS: [&str; 3] = ["content", "arguments", "input"];
+
+/// Which parts of logged bodies are hidden.
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    /// Replace message content, tool call arguments, and embedding inputs with their length.
+    pub content: bool,
+    /// Keys of further JSON fields whose values are replaced, at any depth.
+    pub fields: Vec<String>,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Redaction {
+            content: true,
+            fields: Vec::new(),
+        }
+    }
+}
+
+impl Redaction {
+    /// Hides nothing but credentials.
+    pub fn none() -> Self {
+        Redaction {
+            content: false,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Returns `body` as text with the redacted fields replaced. Bodies that are not JSON are
+    /// described by their length only, since form bodies carry device codes.
+    fn apply(&self, body: &[u8]) -> String {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut value) => {
+                self.redact(&mut value);
+                value.to_string()
+            }
+            Err(_) => format!("<{} bytes>", body.len()),
+        }
+    }
+
+    fn redact(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if SECRET_KEYS.contains(&key.as_str()) && value.is_string() {
+                        *value = Value::String(REDACTED.to_string());
+                    } else if (self.content && CONTENT_KEYS.contains(&key.as_str()))
+                        || self.fields.contains(key)
+                    {
+                        hide(value);
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            _ => {}
+        }
+    }
+
+    /// Applies the redaction to each event of a server-sent event stream.
+    fn apply_events(&self, body: &[u8]) -> String {
+        String::from_utf8_lossy(body)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.strip_prefix("data:") {
+                Some(data) if data.trim() != "[DONE]" => {
+                    format!("data: {}", self.apply(data.trim().as_bytes()))
+                }
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Replaces the strings in `value` with their length.
+fn hide(value: &mut Value) {
+    match value {
+        Value::String(s) => *value = Value::String(format!("<{} chars>", s.chars().count())),
+        Value::Array(items) => items.iter_mut().for_each(hide),
+        Value::Object(map) => map.values_mut().for_each(hide),
+        _ => {}
+    }
+}
+
+/// A transport that logs the bodies of the requests it sends through an inner transport.
+///
+/// When `DEBUG` events of the `copilot_client::logging` target are disabled, requests pass
+/// through untouched. Otherwise, non-streamed response bodies are read in full before they are
+/// returned.
+#[derive(Debug)]
+pub struct LoggingTransport<T> {
+    inner: T,
+    redaction: Arc<Redaction>,
+}
+
+impl<T: HttpTransport> LoggingTransport<T> {
+    /// Creates a transport that sends requests through `inner` and logs them with `redaction`.
+    pub fn new(inner: T, redaction: Redaction) -> Self {
+        LoggingTransport {
+            inner,
+            redaction: Arc::new(redaction),
+        }
+    }
+}
+
+impl<T: HttpTransport> HttpTransport for LoggingTransport<T> {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            if !tracing::enabled!(Level::DEBUG) {
+                return self.inner.send(request).await;
+            }
+            let body = request
+                .body
+                .as_deref()
+                .map(|body| self.redaction.apply(body));
+            tracing::debug!(
+                method = %request.method,
+                url = %request.url,
+                body = %body.as_deref().unwrap_or_default(),
+                "Sending request",
+            );
+
+            let response = self.inner.send(request).await?;
+            let status = response.status;
+            if is_event_stream(&response.headers) {
+                tracing::debug!(status = status.as_u16(), "Receiving streamed response");
+                if !tracing::enabled!(Level::TRACE) {
+                    return Ok(response);
+                }
+                let headers = response.headers.clone();
+                let body = trace_events(response.into_body(), self.redaction.clone());
+                return Ok(HttpResponse::new(status, headers, body));
+            }
+
+            let headers = response.headers.clone();
+            let body = response.bytes().await?;
+            if status.is_client_error() || status.is_server_error() {
+                tracing::debug!(
+                    status = status.as_u16(),
+                    body = %self.redaction.apply(&body),
+                    "Received error response",
+                );
+            } else if tracing::enabled!(Level::TRACE) {
+                tracing::trace!(
+                    status = status.as_u16(),
+                    body = %self.redaction.apply(&body),
+                    "Received response",
+                );
+            } else {
+                tracing::debug!(
+                    status = status.as_u16(),
+                    summary = %summarize(&body),
+                    "Received response",
+                );
+            }
+            Ok(HttpResponse::from_bytes(status, headers, body))
+        })
+    }
+}
+
+impl CopilotClient {
+    /// Logs the bodies of all further requests and responses, hiding the parts selected by
+    /// `redaction`. See the [`logging`](crate::logging) module for what is logged.
+    pub fn with_request_logging(mut self, redaction: Redaction) -> Self {
+        self.transport = Arc::new(LoggingTransport::new(self.transport.clone(), redaction));
+        self
+    }
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+/// Passes `body` through and logs it, redacted, once it has been read to the end or dropped.
+fn trace_events(
+    body: BoxStream<Result<Bytes, CopilotError>>,
+    redaction: Arc<Redaction>,
+) -> BoxStream<Result<Bytes, CopilotError>> {
+    let log = EventLog {
+        seen: Vec::new(),
+        redaction,
+    };
+    Box::pin(stream::unfold(
+        (body, log),
+        |(mut body, mut log)| async move {
+            let chunk = body.next().await?;
+            if let Ok(bytes) = &chunk {
+                log.seen.extend_from_slice(bytes);
+            }
+            Some((chunk, (body, log)))
+        },
+    ))
+}
+
+/// The events of a streamed response, logged when dropped. Chat streams stop reading at the
+/// `[DONE]` marker, so the end of the body is not always reached.
+struct EventLog {
+    seen: Vec<u8>,
+    redaction: Arc<Redaction>,
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        tracing::trace!(
+            body = %self.redaction.apply_events(&self.seen),
+            "Received streamed response",
+        );
+    }
+}
+
+/// Describes a JSON body by its top-level fields, with arrays and objects shortened.
+fn summarize(body: &[u8]) -> String {
+    let Ok(Value::Object(map)) = serde_json::from_slice::<Value>(body) else {
+        return format!("<{} bytes>", body.len());
+    };
+    map.iter()
+        .map(|(key, value)| match value {
+            Value::Array(items) => format!("{key}: [{} items]", items.len()),
+            Value::Object(_) if key == "usage" => format!("{key}: {value}"),
+            Value::Object(_) => format!("{key}: {{..}}"),
+            Value::String(_) if SECRET_KEYS.contains(&key.as_str()) => format!("{key}: {REDACTED}"),
+            _ => format!("{key}: {value}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
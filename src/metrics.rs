@@ -0,0 +1,70 @@
+//! Request and token metrics, enabled with the `metrics` feature.
+//!
+//! The client reports to the [metrics](https://crates.io/crates/metrics) facade, so any recorder
+//! installed by the application receives the measurements. With
+//! [metrics-exporter-prometheus](https://crates.io/crates/metrics-exporter-prometheus), a
+//! service built on the client exposes them for scraping:
+//!
+//! ```ignore
+//! let handle = metrics_exporter_prometheus::PrometheusBuilder::new().install_recorder()?;
+//! copilot_client::metrics::describe();
+//! // Serve `handle.render()` at `/metrics`.
+//! ```
+//!
+//! The client does not retry requests, so retries are not counted; retry middleware added with
+//! a custom transport shows up as additional requests.
+
+use crate::TokenUsage;
+use std::time::Duration;
+
+/// Counter of HTTP requests, labeled with the `endpoint` path and the response `status`, or
+/// `"error"` for requests that got no response.
+pub const REQUESTS_TOTAL: &str = "copilot_requests_total";
+
+/// Histogram of the seconds until the response headers arrived, labeled with the `endpoint`
+/// path.
+pub const REQUEST_DURATION_SECONDS: &str = "copilot_request_duration_seconds";
+
+/// Counter of tokens reported by the API, labeled with the `model` and the token `type`,
+/// `"prompt"` or `"completion"`.
+pub const TOKENS_TOTAL: &str = "copilot_tokens_total";
+
+/// Registers the units and descriptions of the client's metrics with the installed recorder.
+pub fn describe() {
+    ::metrics::describe_counter!(REQUESTS_TOTAL, "Copilot API requests");
+    ::metrics::describe_histogram!(
+        REQUEST_DURATION_SECONDS,
+        ::metrics::Unit::Seconds,
+        "Copilot API request latency"
+    );
+    ::metrics::describe_counter!(TOKENS_TOTAL, "Tokens consumed by Copilot API requests");
+}
+
+/// Records a request to `url` that was answered with `status` after `duration`.
+pub(crate) fn record_request(url: &str, status: Option<u16>, duration: Duration) {
+    let endpoint = endpoint(url);
+    let status = status.map_or_else(|| "error".to_string(), |status| status.to_string());
+    ::metrics::counter!(REQUESTS_TOTAL, "endpoint" => endpoint.clone(), "status" => status)
+        .increment(1);
+    ::metrics::histogram!(REQUEST_DURATION_SECONDS, "endpoint" => endpoint)
+        .record(duration.as_secs_f64());
+}
+
+/// Records the tokens used by a request to `model`.
+pub(crate) fn record_tokens(model: &str, usage: &TokenUsage) {
+    for (kind, count) in [
+        ("prompt", usage.prompt_tokens),
+        ("completion", usage.completion_tokens),
+    ] {
+        ::metrics::counter!(TOKENS_TOTAL, "model" => model.to_string(), "type" => kind)
+            .increment(u64::from(count));
+    }
+}
+
+/// Returns the path of `url`, without the host and query, to keep the label set small.
+fn endpoint(url: &str) -> String {
+    let path = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest.find('/').map_or("/", |i| &rest[i..]));
+    path.split(['?', '#']).next().unwrap_or(path).to_string()
+}
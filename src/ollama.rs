@@ -0,0 +1,337 @@
+//! Conversions between this crate's chat types and Ollama's wire format.
+//!
+//! Many editor plugins and desktop apps only speak the Ollama protocol. The functions here map
+//! the bodies of Ollama's `/api/chat` and `/api/generate` requests to [`ChatRequest`]s, and
+//! Copilot responses, stream chunks, and models back to the JSON Ollama clients expect, so that
+//! a server can answer those endpoints from the Copilot API.
+//!
+//! Streamed Ollama responses are newline-delimited JSON: one object per [`ChatStreamChunk`],
+//! followed by a final object with `"done": true` and the token counts.
+
+use crate::{
+    ChatOptions, ChatRequest, ChatResponse, CopilotError, FunctionCall, Message, Model, TokenUsage,
+    Tool, ToolCall, openai, stream::ChatStreamChunk, unix_time,
+};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+/// Parses the body of an Ollama `/api/chat` request.
+///
+/// Ollama streams by default, so a missing `stream` field means `true`. Sampling parameters are
+/// read from `options` (`temperature`, `top_p`, `stop`, `seed`, and `num_predict` as the token
+/// limit) and default to those of [`ChatOptions::default`]. The `format` field is ignored.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the model or messages are missing or invalid, or a message
+/// carries images.
+pub fn chat_request_from_ollama(value: &Value) -> Result<ChatRequest<'static>, CopilotError> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| invalid("request must be a JSON object"))?;
+    let messages = obj
+        .get("messages")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("request is missing messages"))?
+        .iter()
+        .map(message_from_ollama)
+        .collect::<Result<Vec<_>, _>>()?;
+    let tools = match obj.get("tools").filter(|v| !v.is_null()) {
+        Some(tools) => Some(
+            serde_json::from_value::<Vec<Tool>>(tools.clone())
+                .map_err(|e| invalid(&format!("invalid tools: {e}")))?,
+        ),
+        None => None,
+    };
+    let mut request = build_request(obj, messages)?;
+    request.tools = tools;
+    Ok(request)
+}
+
+/// Parses the body of an Ollama `/api/generate` request into a chat request with the optional
+/// `system` prompt followed by `prompt` as the user message.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the model or prompt is missing, or the request carries images.
+pub fn generate_request_from_ollama(value: &Value) -> Result<ChatRequest<'static>, CopilotError> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| invalid("request must be a JSON object"))?;
+    if obj.get("images").is_some_and(|v| !v.is_null()) {
+        return Err(invalid("images are not supported"));
+    }
+    let prompt = obj
+        .get("prompt")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("request is missing a prompt"))?;
+    let mut messages = Vec::with_capacity(2);
+    if let Some(system) = obj.get("system").and_then(Value::as_str) {
+        messages.push(Message::system(system));
+    }
+    messages.push(Message::user(prompt));
+    build_request(obj, messages)
+}
+
+/// Converts a chat response into an Ollama `/api/chat` response.
+pub fn chat_response_to_ollama(response: &ChatResponse, model: &str) -> Value {
+    let choice = response.choices.first();
+    let message = choice.map_or_else(
+        || json!({ "role": "assistant", "content": "" }),
+        |c| message_to_ollama(&c.message),
+    );
+    let mut obj = header(model);
+    obj.insert("message".into(), message);
+    finish(
+        &mut obj,
+        choice.and_then(|c| c.finish_reason.as_deref()),
+        response.usage.as_ref(),
+    );
+    Value::Object(obj)
+}
+
+/// Converts a chat response into an Ollama `/api/generate` response.
+pub fn generate_response_to_ollama(response: &ChatResponse, model: &str) -> Value {
+    let choice = response.choices.first();
+    let mut obj = header(model);
+    obj.insert(
+        "response".into(),
+        json!(choice.map_or("", |c| c.message.content.as_str())),
+    );
+    finish(
+        &mut obj,
+        choice.and_then(|c| c.finish_reason.as_deref()),
+        response.usage.as_ref(),
+    );
+    Value::Object(obj)
+}
+
+/// Converts a stream chunk into an intermediate line of a streamed `/api/chat` response.
+pub fn chat_chunk_to_ollama(chunk: &ChatStreamChunk, model: &str) -> Value {
+    let mut obj = header(model);
+    obj.insert(
+        "message".into(),
+        json!({ "role": "assistant", "content": chunk.content().unwrap_or("") }),
+    );
+    obj.insert("done".into(), json!(false));
+    Value::Object(obj)
+}
+
+/// Converts a stream chunk into an intermediate line of a streamed `/api/generate` response.
+pub fn generate_chunk_to_ollama(chunk: &ChatStreamChunk, model: &str) -> Value {
+    let mut obj = header(model);
+    obj.insert("response".into(), json!(chunk.content().unwrap_or("")));
+    obj.insert("done".into(), json!(false));
+    Value::Object(obj)
+}
+
+/// Returns the final line of a streamed `/api/chat` response.
+pub fn chat_done_to_ollama(
+    model: &str,
+    finish_reason: Option<&str>,
+    usage: Option<&TokenUsage>,
+) -> Value {
+    let mut obj = header(model);
+    obj.insert(
+        "message".into(),
+        json!({ "role": "assistant", "content": "" }),
+    );
+    finish(&mut obj, finish_reason, usage);
+    Value::Object(obj)
+}
+
+/// Returns the final line of a streamed `/api/generate` response.
+pub fn generate_done_to_ollama(
+    model: &str,
+    finish_reason: Option<&str>,
+    usage: Option<&TokenUsage>,
+) -> Value {
+    let mut obj = header(model);
+    obj.insert("response".into(), json!(""));
+    finish(&mut obj, finish_reason, usage);
+    Value::Object(obj)
+}
+
+/// Converts the chat models among `models` into an Ollama `/api/tags` response.
+///
+/// Ollama reports local files, so the size and digest are empty.
+pub fn tags_from_models(models: &[Model]) -> Value {
+    let modified_at = rfc3339_now();
+    let tags: Vec<Value> = models
+        .iter()
+        .filter(|m| m.is_chat_model())
+        .map(|m| {
+            let family = m
+                .capabilities
+                .as_ref()
+                .and_then(|c| c.family.as_deref())
+                .unwrap_or("");
+            json!({
+                "name": m.id,
+                "model": m.id,
+                "modified_at": modified_at,
+                "size": 0,
+                "digest": "",
+                "details": {
+                    "format": "",
+                    "family": family,
+                    "families": [family],
+                    "parameter_size": "",
+                    "quantization_level": "",
+                },
+            })
+        })
+        .collect();
+    json!({ "models": tags })
+}
+
+/// Builds a request from the fields shared by `/api/chat` and `/api/generate`.
+fn build_request(
+    obj: &Map<String, Value>,
+    messages: Vec<Message>,
+) -> Result<ChatRequest<'static>, CopilotError> {
+    let model = obj
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("request is missing a model"))?;
+    let defaults = ChatOptions::default();
+    let options = obj.get("options");
+    let option = |name: &str| options.and_then(|o| o.get(name)).and_then(Value::as_f64);
+    Ok(ChatRequest {
+        model: model.to_string(),
+        messages: messages.into(),
+        n: 1,
+        top_p: option("top_p").unwrap_or(defaults.top_p),
+        // Ollama streams unless asked not to.
+        stream: obj.get("stream").and_then(Value::as_bool).unwrap_or(true),
+        temperature: option("temperature").unwrap_or(defaults.temperature),
+        // A negative `num_predict` means no limit.
+        max_tokens: options
+            .and_then(|o| o.get("num_predict"))
+            .and_then(Value::as_u64)
+            .map(|v| v as u32),
+        stop: openai::stop_from_value(options.and_then(|o| o.get("stop")))?,
+        logprobs: false,
+        top_logprobs: None,
+        seed: options.and_then(|o| o.get("seed")).and_then(Value::as_i64),
+        logit_bias: BTreeMap::new(),
+        prediction: None,
+        tools: None,
+        tool_choice: None,
+    })
+}
+
+/// Parses a message of an `/api/chat` request. Tool call arguments are objects in Ollama's
+/// format and are re-encoded as JSON strings.
+fn message_from_ollama(value: &Value) -> Result<Message, CopilotError> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| invalid("message must be a JSON object"))?;
+    if obj
+        .get("images")
+        .and_then(Value::as_array)
+        .is_some_and(|images| !images.is_empty())
+    {
+        return Err(invalid("images are not supported"));
+    }
+    let role = obj
+        .get("role")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("message is missing a role"))?;
+    let content = obj.get("content").and_then(Value::as_str).unwrap_or("");
+    let mut message = Message::new(role, content);
+    if let Some(calls) = obj.get("tool_calls").and_then(Value::as_array) {
+        let calls = calls
+            .iter()
+            .enumerate()
+            .map(|(i, call)| {
+                let function = call
+                    .get("function")
+                    .ok_or_else(|| invalid("tool call is missing a function"))?;
+                let name = function
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid("tool call is missing a name"))?;
+                let arguments = match function.get("arguments") {
+                    Some(Value::String(arguments)) => arguments.clone(),
+                    Some(arguments) => arguments.to_string(),
+                    None => "{}".to_string(),
+                };
+                Ok(ToolCall {
+                    // Ollama does not assign ids; tool results are matched by order.
+                    id: format!("call_{i}"),
+                    kind: "function".to_string(),
+                    function: FunctionCall {
+                        name: name.to_string(),
+                        arguments,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, CopilotError>>()?;
+        message.tool_calls = Some(calls);
+    }
+    Ok(message)
+}
+
+/// Converts an assistant message, decoding tool call arguments into objects.
+fn message_to_ollama(message: &Message) -> Value {
+    let mut obj = Map::new();
+    obj.insert("role".into(), json!(message.role));
+    obj.insert("content".into(), json!(message.content));
+    if let Some(calls) = &message.tool_calls {
+        let calls: Vec<Value> = calls
+            .iter()
+            .map(|call| {
+                let arguments = serde_json::from_str(&call.function.arguments)
+                    .unwrap_or_else(|_| json!(call.function.arguments));
+                json!({ "function": { "name": call.function.name, "arguments": arguments } })
+            })
+            .collect();
+        obj.insert("tool_calls".into(), json!(calls));
+    }
+    Value::Object(obj)
+}
+
+/// Returns the fields every response line starts with.
+fn header(model: &str) -> Map<String, Value> {
+    let mut obj = Map::new();
+    obj.insert("model".into(), json!(model));
+    obj.insert("created_at".into(), json!(rfc3339_now()));
+    obj
+}
+
+/// Marks a response as done and adds the finish reason and token counts.
+fn finish(obj: &mut Map<String, Value>, finish_reason: Option<&str>, usage: Option<&TokenUsage>) {
+    obj.insert("done".into(), json!(true));
+    obj.insert("done_reason".into(), json!(finish_reason.unwrap_or("stop")));
+    if let Some(usage) = usage {
+        obj.insert("prompt_eval_count".into(), json!(usage.prompt_tokens));
+        obj.insert("eval_count".into(), json!(usage.completion_tokens));
+    }
+}
+
+/// Formats the current UTC time as an RFC 3339 timestamp.
+fn rfc3339_now() -> String {
+    let secs = unix_time().as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+fn invalid(msg: &str) -> CopilotError {
+    CopilotError::Other(format!("Invalid Ollama request: {msg}"))
+}
@@ -0,0 +1,321 @@
+//! Conversions between this crate's chat types and the OpenAI chat format.
+//!
+//! The Copilot chat API is largely OpenAI-compatible, but messages exported by other tools use
+//! variations this crate does not model directly: content given as an array of parts, the
+//! `developer` role, and the legacy `function_call`/`functions` fields. The functions here
+//! normalize those when importing and produce canonical OpenAI JSON when exporting, so prompts
+//! and transcripts can move between Copilot and other providers or be replayed from datasets.
+
+use crate::{
+    ChatRequest, CopilotError, FunctionCall, FunctionDefinition, ImageUrl, Message, Prediction,
+    Tool, ToolCall,
+};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+/// Converts a message into OpenAI chat JSON.
+///
+/// Assistant messages that only carry tool calls are exported with `"content": null`, and
+/// messages with images with a list of text and `image_url` parts.
+pub fn message_to_openai(message: &Message) -> Value {
+    let mut obj = Map::new();
+    obj.insert("role".into(), json!(message.role));
+    let has_tool_calls = message.tool_calls.as_ref().is_some_and(|c| !c.is_empty());
+    if !message.images.is_empty() {
+        let text = (!message.content.is_empty())
+            .then(|| json!({ "type": "text", "text": message.content }));
+        let images = message
+            .images
+            .iter()
+            .map(|image| json!({ "type": "image_url", "image_url": image }));
+        obj.insert("content".into(), text.into_iter().chain(images).collect());
+    } else if message.content.is_empty() && has_tool_calls {
+        obj.insert("content".into(), Value::Null);
+    } else {
+        obj.insert("content".into(), json!(message.content));
+    }
+    if let Some(name) = &message.name {
+        obj.insert("name".into(), json!(name));
+    }
+    if let Some(tool_calls) = &message.tool_calls {
+        obj.insert("tool_calls".into(), json!(tool_calls));
+    }
+    if let Some(id) = &message.tool_call_id {
+        obj.insert("tool_call_id".into(), json!(id));
+    }
+    Value::Object(obj)
+}
+
+/// Converts a list of messages into an OpenAI `messages` array.
+pub fn messages_to_openai(messages: &[Message]) -> Value {
+    Value::Array(messages.iter().map(message_to_openai).collect())
+}
+
+/// Parses a single OpenAI chat message.
+///
+/// Text content parts are joined with newlines, `image_url` parts become
+/// [`images`](Message::images), the `developer` role is mapped to `system`,
+/// legacy `function_call`/`function` messages are converted to tool calls and tool results, and
+/// `cache_control` markers are kept.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the message is malformed or contains content parts other than
+/// text and images.
+pub fn message_from_openai(value: &Value) -> Result<Message, CopilotError> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| invalid("message must be a JSON object"))?;
+    let role = obj
+        .get("role")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("message is missing a role"))?;
+    let (content, images) = parse_content(obj.get("content").unwrap_or(&Value::Null))?;
+    let name = obj.get("name").and_then(Value::as_str).map(str::to_string);
+
+    let mut message = match role {
+        "developer" => Message::system(content),
+        // Legacy function results are tool results keyed by the function name.
+        "function" => Message::tool_result(name.clone().unwrap_or_default(), content),
+        other => Message::new(other, content),
+    };
+    if role != "function" {
+        message.name = name;
+    }
+    message.images = images;
+    if let Some(id) = obj.get("tool_call_id").and_then(Value::as_str) {
+        message.tool_call_id = Some(id.to_string());
+    }
+    if let Some(cache_control) = obj
+        .get("copilot_cache_control")
+        .or_else(|| obj.get("cache_control"))
+        .filter(|v| !v.is_null())
+    {
+        message.cache_control = Some(
+            serde_json::from_value(cache_control.clone())
+                .map_err(|e| invalid(&format!("invalid cache_control: {e}")))?,
+        );
+    }
+    if let Some(calls) = obj.get("tool_calls").filter(|v| !v.is_null()) {
+        let calls: Vec<ToolCall> = serde_json::from_value(calls.clone())
+            .map_err(|e| invalid(&format!("invalid tool_calls: {e}")))?;
+        message.tool_calls = Some(calls);
+    } else if let Some(call) = obj.get("function_call").filter(|v| !v.is_null()) {
+        let function: FunctionCall = serde_json::from_value(call.clone())
+            .map_err(|e| invalid(&format!("invalid function_call: {e}")))?;
+        message.tool_calls = Some(vec![ToolCall {
+            id: function.name.clone(),
+            kind: "function".to_string(),
+            function,
+        }]);
+    }
+    Ok(message)
+}
+
+/// Parses an OpenAI `messages` array.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the value is not an array or any message is invalid.
+pub fn messages_from_openai(value: &Value) -> Result<Vec<Message>, CopilotError> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid("messages must be a JSON array"))?
+        .iter()
+        .map(message_from_openai)
+        .collect()
+}
+
+/// Converts a chat request into an OpenAI chat completion request body.
+pub fn request_to_openai(request: &ChatRequest<'_>) -> Value {
+    let mut obj = Map::new();
+    obj.insert("model".into(), json!(request.model));
+    obj.insert("messages".into(), messages_to_openai(&request.messages));
+    obj.insert("n".into(), json!(request.n));
+    obj.insert("top_p".into(), json!(request.top_p));
+    obj.insert("temperature".into(), json!(request.temperature));
+    obj.insert("stream".into(), json!(request.stream));
+    if let Some(max_tokens) = request.max_tokens {
+        obj.insert("max_tokens".into(), json!(max_tokens));
+    }
+    if !request.stop.is_empty() {
+        obj.insert("stop".into(), json!(request.stop));
+    }
+    if request.logprobs {
+        obj.insert("logprobs".into(), json!(true));
+    }
+    if let Some(top_logprobs) = request.top_logprobs {
+        obj.insert("top_logprobs".into(), json!(top_logprobs));
+    }
+    if let Some(seed) = request.seed {
+        obj.insert("seed".into(), json!(seed));
+    }
+    if !request.logit_bias.is_empty() {
+        obj.insert("logit_bias".into(), json!(request.logit_bias));
+    }
+    if let Some(prediction) = &request.prediction {
+        obj.insert("prediction".into(), json!(prediction));
+    }
+    if let Some(tools) = &request.tools {
+        obj.insert("tools".into(), json!(tools));
+    }
+    if let Some(choice) = &request.tool_choice {
+        obj.insert("tool_choice".into(), choice.clone());
+    }
+    Value::Object(obj)
+}
+
+/// Parses an OpenAI chat completion request body.
+///
+/// Missing sampling parameters take OpenAI's defaults (`n = 1`, `top_p = 1`,
+/// `temperature = 1`). `max_completion_tokens` is accepted as an alias of `max_tokens`, legacy
+/// `functions` are converted to tools, and predicted content given as parts is joined like
+/// message content.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if the model or messages are missing or invalid.
+pub fn request_from_openai(value: &Value) -> Result<ChatRequest<'static>, CopilotError> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| invalid("request must be a JSON object"))?;
+    let model = obj
+        .get("model")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("request is missing a model"))?;
+    let messages = messages_from_openai(
+        obj.get("messages")
+            .ok_or_else(|| invalid("request is missing messages"))?,
+    )?;
+    let tools = match (obj.get("tools"), obj.get("functions")) {
+        (Some(tools), _) if !tools.is_null() => Some(
+            serde_json::from_value::<Vec<Tool>>(tools.clone())
+                .map_err(|e| invalid(&format!("invalid tools: {e}")))?,
+        ),
+        (_, Some(functions)) if !functions.is_null() => Some(
+            serde_json::from_value::<Vec<FunctionDefinition>>(functions.clone())
+                .map_err(|e| invalid(&format!("invalid functions: {e}")))?
+                .into_iter()
+                .map(|function| Tool {
+                    kind: "function".to_string(),
+                    function,
+                })
+                .collect(),
+        ),
+        _ => None,
+    };
+    let max_tokens = obj
+        .get("max_tokens")
+        .or_else(|| obj.get("max_completion_tokens"))
+        .and_then(Value::as_u64)
+        .map(|v| v as u32);
+    Ok(ChatRequest {
+        model: model.to_string(),
+        messages: messages.into(),
+        n: obj.get("n").and_then(Value::as_u64).unwrap_or(1) as u32,
+        top_p: obj.get("top_p").and_then(Value::as_f64).unwrap_or(1.0),
+        stream: obj.get("stream").and_then(Value::as_bool).unwrap_or(false),
+        temperature: obj
+            .get("temperature")
+            .and_then(Value::as_f64)
+            .unwrap_or(1.0),
+        max_tokens,
+        stop: stop_from_value(obj.get("stop"))?,
+        logprobs: obj
+            .get("logprobs")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        top_logprobs: obj
+            .get("top_logprobs")
+            .and_then(Value::as_u64)
+            .map(|v| v.min(u64::from(u8::MAX)) as u8),
+        seed: obj.get("seed").and_then(Value::as_i64),
+        logit_bias: match obj.get("logit_bias") {
+            None | Some(Value::Null) => BTreeMap::new(),
+            Some(bias) => serde_json::from_value(bias.clone())
+                .map_err(|e| invalid(&format!("invalid logit_bias: {e}")))?,
+        },
+        prediction: obj
+            .get("prediction")
+            .filter(|v| !v.is_null())
+            .map(|prediction| parse_content(prediction.get("content").unwrap_or(&Value::Null)))
+            .transpose()?
+            .map(|(content, _)| Prediction::content(content)),
+        tools,
+        tool_choice: obj
+            .get("tool_choice")
+            .or_else(|| obj.get("function_call"))
+            .filter(|v| !v.is_null())
+            .cloned(),
+    })
+}
+
+/// Parses a `stop` field, which OpenAI accepts as a string or a list of strings.
+pub(crate) fn stop_from_value(value: Option<&Value>) -> Result<Vec<String>, CopilotError> {
+    match value {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(stop)) => Ok(vec![stop.clone()]),
+        Some(stop) => {
+            serde_json::from_value(stop.clone()).map_err(|e| invalid(&format!("invalid stop: {e}")))
+        }
+    }
+}
+
+/// Parses a JSONL dataset where each line is an object with a `messages` array, as used by
+/// OpenAI fine-tuning and evaluation files. Blank lines are skipped.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` naming the line number if a line cannot be parsed.
+pub fn conversations_from_jsonl(text: &str) -> Result<Vec<Vec<Message>>, CopilotError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let value: Value = serde_json::from_str(line)
+                .map_err(|e| invalid(&format!("line {}: {e}", number + 1)))?;
+            let messages = value
+                .get("messages")
+                .ok_or_else(|| invalid(&format!("line {}: missing messages", number + 1)))?;
+            messages_from_openai(messages)
+                .map_err(|e| CopilotError::Other(format!("line {}: {e}", number + 1)))
+        })
+        .collect()
+}
+
+/// Reads message content that may be a string, `null`, or an array of content parts.
+fn parse_content(content: &Value) -> Result<(String, Vec<ImageUrl>), CopilotError> {
+    match content {
+        Value::Null => Ok((String::new(), Vec::new())),
+        Value::String(text) => Ok((text.clone(), Vec::new())),
+        Value::Array(parts) => {
+            let mut texts = Vec::with_capacity(parts.len());
+            let mut images = Vec::new();
+            for part in parts {
+                match part.get("type").and_then(Value::as_str) {
+                    Some("text") => {
+                        texts.push(part.get("text").and_then(Value::as_str).unwrap_or(""))
+                    }
+                    Some("image_url") => {
+                        let image = part
+                            .get("image_url")
+                            .cloned()
+                            .and_then(|image| serde_json::from_value(image).ok())
+                            .ok_or_else(|| invalid("invalid image_url content part"))?;
+                        images.push(image);
+                    }
+                    Some(other) => {
+                        return Err(invalid(&format!("unsupported content part type '{other}'")));
+                    }
+                    None => return Err(invalid("content part is missing a type")),
+                }
+            }
+            Ok((texts.join("\n"), images))
+        }
+        _ => Err(invalid("content must be a string, null, or an array")),
+    }
+}
+
+fn invalid(msg: &str) -> CopilotError {
+    CopilotError::Other(format!("Invalid OpenAI chat format: {msg}"))
+}
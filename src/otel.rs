@@ -0,0 +1,214 @@
+//! OpenTelemetry spans and metrics, enabled with the `otel` feature.
+//!
+//! Chat and embeddings calls are reported to the global tracer and meter providers following
+//! the [GenAI semantic conventions](https://opentelemetry.io/docs/specs/semconv/gen-ai/):
+//!
+//! - A client span named `chat {model}` or `embeddings {model}`, a child of the current
+//!   OpenTelemetry context, with `gen_ai.operation.name`, `gen_ai.provider.name`,
+//!   `gen_ai.request.model`, the request parameters, `gen_ai.response.finish_reasons`,
+//!   `gen_ai.usage.input_tokens`, and `gen_ai.usage.output_tokens`. Failed calls set the span
+//!   status and `error.type`. Streaming calls end their span when the stream ends.
+//! - The `gen_ai.client.operation.duration` and `gen_ai.client.token.usage` histograms.
+//!
+//! Install an SDK tracer and meter provider with `opentelemetry::global` to export them; until
+//! then they are no-ops.
+
+use crate::{ChatOptions, ChatResponse, CopilotError, TokenUsage, unix_time};
+use opentelemetry::{
+    InstrumentationScope, KeyValue, StringValue, Value, global,
+    metrics::Meter,
+    trace::{Span, SpanKind, Status, Tracer},
+};
+use std::time::Duration;
+
+/// The `gen_ai.provider.name` of Copilot calls.
+const PROVIDER: &str = "github_copilot";
+
+/// The `server.address` of the Copilot API.
+const SERVER_ADDRESS: &str = "api.githubcopilot.com";
+
+/// Bucket boundaries for `gen_ai.client.operation.duration`, in seconds, as recommended by the
+/// semantic conventions.
+const DURATION_BOUNDARIES: [f64; 14] = [
+    0.01, 0.02, 0.04, 0.08, 0.16, 0.32, 0.64, 1.28, 2.56, 5.12, 10.24, 20.48, 40.96, 81.92,
+];
+
+/// Bucket boundaries for `gen_ai.client.token.usage`, as recommended by the semantic
+/// conventions.
+const TOKEN_BOUNDARIES: [f64; 14] = [
+    1.0, 4.0, 16.0, 64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+    16777216.0, 67108864.0,
+];
+
+/// An operation in progress, reported when it ends.
+pub(crate) struct Operation {
+    span: global::BoxedSpan,
+    /// Attributes shared by the span and the metrics.
+    attributes: Vec<KeyValue>,
+    start: Duration,
+    usage: Option<TokenUsage>,
+    finish_reasons: Vec<String>,
+}
+
+impl Operation {
+    /// Starts a chat operation for `model` with the request parameters in `options`.
+    pub(crate) fn chat(model: &str, options: &ChatOptions) -> Self {
+        let mut operation = Self::start("chat", model);
+        let mut request = vec![
+            KeyValue::new("gen_ai.request.temperature", options.temperature),
+            KeyValue::new("gen_ai.request.top_p", options.top_p),
+        ];
+        if options.n != 1 {
+            request.push(KeyValue::new(
+                "gen_ai.request.choice.count",
+                i64::from(options.n),
+            ));
+        }
+        if let Some(max_tokens) = options.max_tokens {
+            request.push(KeyValue::new(
+                "gen_ai.request.max_tokens",
+                i64::from(max_tokens),
+            ));
+        }
+        operation.span.set_attributes(request);
+        operation
+    }
+
+    /// Starts an embeddings operation for `model`.
+    pub(crate) fn embeddings(model: &str) -> Self {
+        Self::start("embeddings", model)
+    }
+
+    fn start(name: &'static str, model: &str) -> Self {
+        let attributes = vec![
+            KeyValue::new("gen_ai.operation.name", name),
+            KeyValue::new("gen_ai.provider.name", PROVIDER),
+            KeyValue::new("gen_ai.request.model", model.to_string()),
+            KeyValue::new("server.address", SERVER_ADDRESS),
+        ];
+        let tracer = global::tracer_with_scope(scope());
+        let span = tracer
+            .span_builder(format!("{name} {model}"))
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes.clone())
+            .start(&tracer);
+        Operation {
+            span,
+            attributes,
+            start: unix_time(),
+            usage: None,
+            finish_reasons: Vec::new(),
+        }
+    }
+
+    /// Notes the usage reported so far. Later reports replace earlier ones.
+    pub(crate) fn record_usage(&mut self, usage: Option<&TokenUsage>) {
+        if usage.is_some() {
+            self.usage = usage.cloned();
+        }
+    }
+
+    /// Notes the finish reason of a choice.
+    pub(crate) fn record_finish_reason(&mut self, reason: Option<&str>) {
+        if let Some(reason) = reason {
+            self.finish_reasons.push(reason.to_string());
+        }
+    }
+
+    /// Ends a chat operation with the outcome of the request.
+    pub(crate) fn end_chat(mut self, result: &Result<ChatResponse, CopilotError>) {
+        match result {
+            Ok(response) => {
+                self.record_usage(response.usage.as_ref());
+                for choice in &response.choices {
+                    self.record_finish_reason(choice.finish_reason.as_deref());
+                }
+                self.end(None);
+            }
+            Err(e) => self.end(Some(e)),
+        }
+    }
+
+    /// Ends the operation, reporting the span and metrics.
+    pub(crate) fn end(mut self, error: Option<&CopilotError>) {
+        let mut attributes = self.attributes.clone();
+        if let Some(error) = error {
+            let error_type = KeyValue::new("error.type", error_type(error));
+            self.span.set_attribute(error_type.clone());
+            self.span.set_status(Status::error(error.to_string()));
+            attributes.push(error_type);
+        }
+        if !self.finish_reasons.is_empty() {
+            let reasons = std::mem::take(&mut self.finish_reasons)
+                .into_iter()
+                .map(StringValue::from)
+                .collect::<Vec<_>>();
+            self.span.set_attribute(KeyValue::new(
+                "gen_ai.response.finish_reasons",
+                Value::Array(reasons.into()),
+            ));
+        }
+
+        let meter = global::meter_with_scope(scope());
+        let duration = unix_time().saturating_sub(self.start).as_secs_f64();
+        duration_histogram(&meter).record(duration, &attributes);
+        if let Some(usage) = self.usage.take() {
+            self.span.set_attributes([
+                KeyValue::new("gen_ai.usage.input_tokens", i64::from(usage.prompt_tokens)),
+                KeyValue::new(
+                    "gen_ai.usage.output_tokens",
+                    i64::from(usage.completion_tokens),
+                ),
+            ]);
+            let tokens = token_histogram(&meter);
+            for (token_type, count) in [
+                ("input", usage.prompt_tokens),
+                ("output", usage.completion_tokens),
+            ] {
+                let mut attributes = attributes.clone();
+                attributes.push(KeyValue::new("gen_ai.token.type", token_type));
+                tokens.record(u64::from(count), &attributes);
+            }
+        }
+        self.span.end();
+    }
+}
+
+fn scope() -> InstrumentationScope {
+    InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+        .with_version(env!("CARGO_PKG_VERSION"))
+        .build()
+}
+
+// Instruments are looked up on every call rather than cached, so that providers installed after
+// the first call still receive the measurements.
+fn duration_histogram(meter: &Meter) -> opentelemetry::metrics::Histogram<f64> {
+    meter
+        .f64_histogram("gen_ai.client.operation.duration")
+        .with_description("GenAI operation duration")
+        .with_unit("s")
+        .with_boundaries(DURATION_BOUNDARIES.to_vec())
+        .build()
+}
+
+fn token_histogram(meter: &Meter) -> opentelemetry::metrics::Histogram<u64> {
+    meter
+        .u64_histogram("gen_ai.client.token.usage")
+        .with_description("Measures number of input and output tokens used")
+        .with_unit("{token}")
+        .with_boundaries(TOKEN_BOUNDARIES.to_vec())
+        .build()
+}
+
+/// Returns the `error.type` of an error: the status code for API errors, otherwise a name for
+/// the kind of error.
+fn error_type(error: &CopilotError) -> String {
+    match error {
+        CopilotError::ApiError { status, .. } => status.to_string(),
+        CopilotError::InvalidModel(_) => "invalid_model".to_string(),
+        CopilotError::TokenError(_) => "token_error".to_string(),
+        CopilotError::HttpError(_) => "http_error".to_string(),
+        CopilotError::BudgetExceeded(_) => "budget_exceeded".to_string(),
+        CopilotError::Other(_) => "_OTHER".to_string(),
+    }
+}
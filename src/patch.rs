@@ -0,0 +1,625 @@
+//! Unified diff patches.
+//!
+//! Asking a model for changes as a unified diff keeps replies short, but the diffs it writes
+//! are often slightly wrong: hunk headers with made-up line numbers and counts, missing spaces
+//! on blank context lines, or trailing whitespace that differs from the file. [`Patch::parse`]
+//! reads such diffs without trusting the counts, and [`FilePatch::apply`] places each hunk by
+//! finding its context and removed lines in the file, nearest to the line the header names,
+//! so that only hunks whose lines do not exist fail:
+//!
+//! ```
+//! use copilot_client::patch::Patch;
+//!
+//! let patch = Patch::parse(
+//!     "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -10,3 +10,3 @@\n fn main() {\n-    println!(\"hi\");\n+    println!(\"hello\");\n }\n",
+//! )?;
+//! let updated = patch.files[0].apply(Some("fn main() {\n    println!(\"hi\");\n}\n"))?;
+//! assert_eq!(updated.as_deref(), Some("fn main() {\n    println!(\"hello\");\n}\n"));
+//! # Ok::<(), copilot_client::CopilotError>(())
+//! ```
+//!
+//! [`Patch::check`] applies a patch to the files of a workspace in memory and returns the
+//! results, and [`Patch::apply`] writes them once every file applies cleanly.
+//! [`CopilotClient::chat_patch`] asks the model for a diff, checks it, and asks again with the
+//! errors and the current file contents when it does not apply.
+
+use crate::{
+    ChatOptions, ChatResponse, CopilotClient, CopilotError, Message,
+    code::extract_code_blocks,
+    context::{decode, fenced, fit, language},
+    prompt::append_system_instruction,
+    workspace::{check_relative, resolve, slash_path},
+};
+use regex::Regex;
+use std::{fs, path::Path, sync::LazyLock};
+
+/// Instructions sent with [`CopilotClient::chat_patch`] requests.
+const PATCH_INSTRUCTIONS: &str = "Answer with the changes as a unified diff in a single \
+    ```diff code block. Use paths relative to the workspace root with `a/` and `b/` prefixes, \
+    and `/dev/null` as the old path of new files and the new path of deleted files. Give each \
+    hunk at least three unchanged context lines, and copy context and removed lines exactly \
+    from the current files.";
+
+/// Tokens of each file's content sent back to the model when its diff does not apply.
+const REPAIR_FILE_TOKENS: u32 = 4_000;
+
+/// Matches hunk headers such as `@@ -12,7 +12,8 @@`, capturing the old start line and the old
+/// and new line counts.
+static HUNK_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+\d+(?:,(\d+))? @@").expect("valid pattern")
+});
+
+/// A line of a [`Hunk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchLine {
+    /// A line that stays.
+    Context(String),
+    /// A line that is removed.
+    Removed(String),
+    /// A line that is added.
+    Added(String),
+}
+
+/// A group of changes to neighboring lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hunk {
+    /// The line of the old file where the hunk starts according to its header, counted from 1.
+    /// Only a hint: the hunk is placed where its lines are.
+    pub old_start: Option<usize>,
+    /// The lines of the hunk.
+    pub lines: Vec<PatchLine>,
+}
+
+impl Hunk {
+    /// Returns the lines the hunk expects in the old file: the context and removed lines.
+    pub fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                PatchLine::Context(text) | PatchLine::Removed(text) => Some(text.as_str()),
+                PatchLine::Added(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns the lines of the hunk in the new file: the context and added lines.
+    pub fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                PatchLine::Context(text) | PatchLine::Added(text) => Some(text.as_str()),
+                PatchLine::Removed(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// The changes to one file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilePatch {
+    /// The path of the file before the change, without the `a/` prefix, or `None` for a new
+    /// file.
+    pub old_path: Option<String>,
+    /// The path of the file after the change, without the `b/` prefix, or `None` for a deleted
+    /// file.
+    pub new_path: Option<String>,
+    /// The hunks, in order.
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// Returns the path of the file after the change, or before it for a deleted file.
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Applies the hunks to `original`, the content of the file or `None` if it does not
+    /// exist, and returns the new content, or `None` if the file is deleted.
+    ///
+    /// Each hunk is placed where its context and removed lines occur, preferably after the
+    /// previous hunk, nearest to the line its header names. Lines are first compared exactly, then ignoring
+    /// trailing whitespace, then ignoring surrounding whitespace; the file keeps its own
+    /// version of context lines. Line endings and the final newline of the file are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` naming the hunk if its lines are not found in the file.
+    pub fn apply(&self, original: Option<&str>) -> Result<Option<String>, CopilotError> {
+        if self.new_path.is_none() {
+            return Ok(None);
+        }
+        let original = original.unwrap_or_default();
+        let newline = if original.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let lines: Vec<&str> = original.lines().collect();
+        // Place every hunk first, preferring places after the previous hunk, then apply them
+        // in file order, since models do not always list hunks in order.
+        let mut placed: Vec<(usize, &Hunk)> = Vec::with_capacity(self.hunks.len());
+        let mut cursor = 0;
+        let mut offset: isize = 0;
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            let old = hunk.old_lines();
+            let hint = hunk
+                .old_start
+                .map(|start| (start.saturating_sub(1) as isize + offset).max(0) as usize);
+            let position = if old.is_empty() {
+                // A pure insertion: the header names the line to insert after.
+                hunk.old_start
+                    .map_or(lines.len(), |start| {
+                        (start as isize + offset).max(0) as usize
+                    })
+                    .min(lines.len())
+            } else {
+                find_hunk(&lines, &old, cursor, hint)
+                    .or_else(|| find_hunk(&lines, &old, 0, hint))
+                    .ok_or_else(|| {
+                        CopilotError::Other(format!(
+                            "Hunk {} of {} does not apply: these lines are not in the file:\n{}",
+                            index + 1,
+                            self.path(),
+                            old.join("\n")
+                        ))
+                    })?
+            };
+            if let Some(start) = hunk.old_start {
+                offset = position as isize - start.saturating_sub(1) as isize;
+            }
+            cursor = position + old.len();
+            placed.push((position, hunk));
+        }
+        placed.sort_by_key(|&(position, _)| position);
+        let mut output: Vec<&str> = Vec::with_capacity(lines.len());
+        let mut cursor = 0;
+        for (position, hunk) in placed {
+            if position < cursor {
+                return Err(CopilotError::Other(format!(
+                    "Hunks of {} overlap at line {}",
+                    self.path(),
+                    position + 1
+                )));
+            }
+            output.extend(&lines[cursor..position]);
+            let mut line = position;
+            for patch_line in &hunk.lines {
+                match patch_line {
+                    PatchLine::Context(_) => {
+                        output.push(lines[line]);
+                        line += 1;
+                    }
+                    PatchLine::Removed(_) => line += 1,
+                    PatchLine::Added(text) => output.push(text),
+                }
+            }
+            cursor = line;
+        }
+        output.extend(&lines[cursor..]);
+        let mut content = output.join(newline);
+        if !content.is_empty() && (original.is_empty() || original.ends_with('\n')) {
+            content.push_str(newline);
+        }
+        Ok(Some(content))
+    }
+}
+
+/// A file as changed by a [`Patch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchedFile {
+    /// The path of the file, relative to the workspace root, with `/` separators.
+    pub path: String,
+    /// The content before the patch, or `None` if the file is created.
+    pub old: Option<String>,
+    /// The content after the patch, or `None` if the file is deleted.
+    pub new: Option<String>,
+}
+
+/// A unified diff, possibly changing several files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patch {
+    /// The changed files, in order.
+    pub files: Vec<FilePatch>,
+}
+
+impl Patch {
+    /// Parses a unified diff, as written by `diff -u` or `git diff`.
+    ///
+    /// Models often get the line counts of hunk headers wrong, so a hunk ends at the next hunk
+    /// or file header, or at a line that is not part of a hunk. The counts are only used to
+    /// read removed and added lines that look like file headers, such as `-- x` followed by
+    /// `++ y`, as hunk lines while the hunk has lines left and they are not followed by a hunk
+    /// header the counts do not account for. Empty lines in hunks are read as empty context
+    /// lines, and headers without line numbers, such as a bare `@@`, are accepted.
+    ///
+    /// ```
+    /// use copilot_client::patch::{Patch, PatchLine};
+    ///
+    /// let patch = Patch::parse("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n--- x\n+++ y\n")?;
+    /// assert_eq!(patch.files.len(), 1);
+    /// assert_eq!(
+    ///     patch.files[0].hunks[0].lines,
+    ///     [
+    ///         PatchLine::Context("a".into()),
+    ///         PatchLine::Removed("-- x".into()),
+    ///         PatchLine::Added("++ y".into()),
+    ///     ]
+    /// );
+    /// # Ok::<(), copilot_client::CopilotError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the text contains no file headers, a hunk comes before the
+    /// first file header, or a changed file has no hunks.
+    pub fn parse(text: &str) -> Result<Patch, CopilotError> {
+        let mut files: Vec<FilePatch> = Vec::new();
+        let mut in_hunk = false;
+        // The old and new lines the current hunk's header says are left, if it has counts.
+        let mut remaining: Option<(usize, usize)> = None;
+        let mut lines = text.lines().peekable();
+        while let Some(line) = lines.next() {
+            // A `---` and `+++` pair is a removed and an added line while the hunk has room for
+            // both, unless a hunk header follows that the counts do not end the hunk before.
+            let hunk_lines_left = in_hunk
+                && remaining.is_some_and(|(old, new)| {
+                    old > 0
+                        && new > 0
+                        && ((old, new) == (1, 1)
+                            || !lines.clone().nth(1).is_some_and(|l| l.starts_with("@@")))
+                });
+            if !hunk_lines_left
+                && let Some(old) = line.strip_prefix("--- ")
+                && let Some(new) = lines.peek().and_then(|next| next.strip_prefix("+++ "))
+            {
+                files.push(FilePatch {
+                    old_path: parse_path(old, "a/"),
+                    new_path: parse_path(new, "b/"),
+                    hunks: Vec::new(),
+                });
+                lines.next();
+                in_hunk = false;
+                continue;
+            }
+            if line.starts_with("@@") {
+                let file = files.last_mut().ok_or_else(|| {
+                    CopilotError::Other("Hunk before the first file header".to_string())
+                })?;
+                let caps = HUNK_HEADER.captures(line);
+                file.hunks.push(Hunk {
+                    old_start: caps.as_ref().and_then(|caps| caps[1].parse().ok()),
+                    lines: Vec::new(),
+                });
+                // An omitted count means one line.
+                remaining = caps.map(|caps| {
+                    let count = |group| {
+                        caps.get(group)
+                            .map_or(1, |m| m.as_str().parse().unwrap_or(0))
+                    };
+                    (count(2), count(3))
+                });
+                in_hunk = true;
+                continue;
+            }
+            if !in_hunk {
+                continue;
+            }
+            let Some(hunk) = files.last_mut().and_then(|file| file.hunks.last_mut()) else {
+                continue;
+            };
+            let patch_line = match line.chars().next() {
+                None => PatchLine::Context(String::new()),
+                Some(' ') => PatchLine::Context(line[1..].to_string()),
+                Some('-') => PatchLine::Removed(line[1..].to_string()),
+                Some('+') => PatchLine::Added(line[1..].to_string()),
+                // "\ No newline at end of file"
+                Some('\\') => continue,
+                Some(_) => {
+                    in_hunk = false;
+                    continue;
+                }
+            };
+            if let Some((old, new)) = &mut remaining {
+                if !matches!(patch_line, PatchLine::Added(_)) {
+                    *old = old.saturating_sub(1);
+                }
+                if !matches!(patch_line, PatchLine::Removed(_)) {
+                    *new = new.saturating_sub(1);
+                }
+            }
+            hunk.lines.push(patch_line);
+        }
+        if files.is_empty() {
+            return Err(CopilotError::Other("No unified diff found".to_string()));
+        }
+        for file in &mut files {
+            // Blank lines between hunks or after the diff are not context.
+            for hunk in &mut file.hunks {
+                while hunk.lines.last() == Some(&PatchLine::Context(String::new())) {
+                    hunk.lines.pop();
+                }
+            }
+            if file.hunks.is_empty() && file.new_path.is_some() && file.old_path == file.new_path {
+                return Err(CopilotError::Other(format!(
+                    "The diff of {} has no hunks",
+                    file.path()
+                )));
+            }
+        }
+        Ok(Patch { files })
+    }
+
+    /// Parses the diff in a model reply: the `diff` or `patch` code blocks, or the whole
+    /// content if it has none.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Patch::parse`].
+    pub fn from_reply(content: &str) -> Result<Patch, CopilotError> {
+        let blocks: Vec<String> = extract_code_blocks(content)
+            .into_iter()
+            .filter(|block| matches!(block.language.as_deref(), Some("diff" | "patch")))
+            .map(|block| block.content)
+            .collect();
+        if blocks.is_empty() {
+            Patch::parse(content)
+        } else {
+            Patch::parse(&blocks.join("\n"))
+        }
+    }
+
+    /// Applies the patch in memory to the files under `root` and returns the files it changes,
+    /// in the order they are first changed. Several diffs of the same file apply in turn, and
+    /// renames show as the deletion of the old path and the creation of the new one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a path leaves the workspace, a changed file does not exist,
+    /// a created file already exists, a file is not text, or a hunk does not apply.
+    pub fn check(&self, root: impl AsRef<Path>) -> Result<Vec<PatchedFile>, CopilotError> {
+        let root = root.as_ref();
+        let mut changed: Vec<PatchedFile> = Vec::new();
+        for file in &self.files {
+            let old_path = file.old_path.as_deref().map(normalize).transpose()?;
+            let new_path = file.new_path.as_deref().map(normalize).transpose()?;
+            let current = match &old_path {
+                Some(path) => Some(
+                    current_content(root, path, &mut changed)?
+                        .ok_or_else(|| CopilotError::Other(format!("{path} does not exist")))?,
+                ),
+                None => None,
+            };
+            let updated = file.apply(current.as_deref())?;
+            match (&old_path, &new_path) {
+                (_, None) => set_content(root, old_path.as_deref(), None, &mut changed)?,
+                (Some(old), Some(new)) if old != new => {
+                    set_content(root, Some(old), None, &mut changed)?;
+                    create(root, new, updated, &mut changed)?;
+                }
+                (Some(_), Some(_)) => {
+                    set_content(root, new_path.as_deref(), updated, &mut changed)?
+                }
+                (None, Some(new)) => create(root, new, updated, &mut changed)?,
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Checks the patch like [`Patch::check`] and, if every file applies, writes the changes
+    /// under `root`, creating missing directories and removing deleted files.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Patch::check`], in which case no file is changed, or a
+    /// `CopilotError` if a file cannot be written.
+    pub fn apply(&self, root: impl AsRef<Path>) -> Result<Vec<PatchedFile>, CopilotError> {
+        let root = root.as_ref();
+        let changed = self.check(root)?;
+        for file in &changed {
+            let path = resolve(root, Path::new(&file.path))?;
+            let result = match &file.new {
+                Some(content) => path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|()| fs::write(&path, content)),
+                None if file.old.is_some() => fs::remove_file(&path),
+                None => Ok(()),
+            };
+            result
+                .map_err(|e| CopilotError::Other(format!("Failed to write {}: {e}", file.path)))?;
+        }
+        Ok(changed)
+    }
+}
+
+/// The result of [`CopilotClient::chat_patch`].
+#[derive(Debug, Clone)]
+pub struct PatchResponse {
+    /// The response whose diff applies.
+    pub response: ChatResponse,
+    /// The parsed diff.
+    pub patch: Patch,
+    /// The files as changed by the diff, not yet written.
+    pub files: Vec<PatchedFile>,
+}
+
+impl CopilotClient {
+    /// Asks the model for the changes as a unified diff against the files under `root`, and
+    /// checks that the diff applies with [`Patch::check`]. While it does not, the reply, the
+    /// error, and the current content of the files it changes are appended to the conversation
+    /// and the request is sent again, up to `max_repairs` times.
+    ///
+    /// Nothing is written; call [`Patch::apply`] on the returned patch to write the changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a request fails, or the diff still does not apply after
+    /// `max_repairs` repairs.
+    pub async fn chat_patch(
+        &self,
+        mut messages: Vec<Message>,
+        root: impl AsRef<Path>,
+        model_id: String,
+        options: &ChatOptions,
+        max_repairs: u32,
+    ) -> Result<PatchResponse, CopilotError> {
+        let root = root.as_ref();
+        append_system_instruction(&mut messages, PATCH_INSTRUCTIONS);
+        let mut repairs = 0;
+        loop {
+            let response = self
+                .chat_completion_with_options(&*messages, model_id.clone(), options)
+                .await?;
+            let content = response
+                .choices
+                .first()
+                .map(|choice| choice.message.content.clone())
+                .unwrap_or_default();
+            let checked = Patch::from_reply(&content).and_then(|patch| {
+                let files = patch.check(root)?;
+                Ok((patch, files))
+            });
+            let error = match checked {
+                Ok((patch, files)) => {
+                    return Ok(PatchResponse {
+                        response,
+                        patch,
+                        files,
+                    });
+                }
+                Err(error) => error,
+            };
+            if repairs == max_repairs {
+                return Err(CopilotError::Other(format!(
+                    "Diff does not apply after {repairs} repairs: {error}"
+                )));
+            }
+            repairs += 1;
+            tracing::debug!(repairs, %error, "Diff does not apply");
+            let mut feedback = format!(
+                "Your diff does not apply to the current files: {error}\n\nReply again with a \
+                 corrected diff."
+            );
+            let patch = Patch::from_reply(&content).unwrap_or_default();
+            for path in patch
+                .files
+                .iter()
+                .filter_map(|file| file.old_path.as_deref())
+            {
+                let Ok(relative) = check_relative(path) else {
+                    continue;
+                };
+                let Ok(full) = resolve(root, &relative) else {
+                    continue;
+                };
+                if let Some(content) = fs::read(&full).ok().and_then(|bytes| decode(&bytes)) {
+                    feedback.push_str(&format!(
+                        "\n\nCurrent content of `{path}`:\n{}",
+                        fenced(&fit(&content, REPAIR_FILE_TOKENS), language(&relative))
+                    ));
+                }
+            }
+            messages.push(Message::assistant(content));
+            messages.push(Message::user(feedback));
+        }
+    }
+}
+
+/// Reads a path from a `---` or `+++` header: the part before a tab, without `prefix`, or
+/// `None` for `/dev/null`.
+fn parse_path(header: &str, prefix: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    let path = path.trim_matches('"');
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// Checks that `path` stays in the workspace and returns it with `/` separators.
+fn normalize(path: &str) -> Result<String, CopilotError> {
+    check_relative(path).map(|relative| slash_path(&relative))
+}
+
+/// Returns the content of `path`: as changed so far, or as on disk, or `None` if it does not
+/// exist.
+fn current_content(
+    root: &Path,
+    path: &str,
+    changed: &mut [PatchedFile],
+) -> Result<Option<String>, CopilotError> {
+    if let Some(file) = changed.iter().find(|file| file.path == path) {
+        return Ok(file.new.clone());
+    }
+    read_file(root, path)
+}
+
+/// Reads the file at `path`, or returns `None` if it does not exist.
+fn read_file(root: &Path, path: &str) -> Result<Option<String>, CopilotError> {
+    let full = resolve(root, Path::new(path))?;
+    match fs::read(&full) {
+        Ok(bytes) => decode(&bytes)
+            .map(Some)
+            .ok_or_else(|| CopilotError::Other(format!("{path} is not a text file"))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(CopilotError::Other(format!("Failed to read {path}: {e}"))),
+    }
+}
+
+/// Records the new content of `path`.
+fn set_content(
+    root: &Path,
+    path: Option<&str>,
+    content: Option<String>,
+    changed: &mut Vec<PatchedFile>,
+) -> Result<(), CopilotError> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    match changed.iter_mut().find(|file| file.path == path) {
+        Some(file) => file.new = content,
+        None => changed.push(PatchedFile {
+            path: path.to_string(),
+            old: read_file(root, path)?,
+            new: content,
+        }),
+    }
+    Ok(())
+}
+
+/// Records the creation of `path`, which must not exist.
+fn create(
+    root: &Path,
+    path: &str,
+    content: Option<String>,
+    changed: &mut Vec<PatchedFile>,
+) -> Result<(), CopilotError> {
+    if current_content(root, path, changed)?.is_some() {
+        return Err(CopilotError::Other(format!("{path} already exists")));
+    }
+    set_content(root, Some(path), content, changed)
+}
+
+/// Returns the position of `old` in `lines` at or after `from`, nearest to `hint`, comparing
+/// lines exactly, then without trailing whitespace, then without surrounding whitespace.
+fn find_hunk(lines: &[&str], old: &[&str], from: usize, hint: Option<usize>) -> Option<usize> {
+    let comparisons: [fn(&str, &str) -> bool; 3] = [
+        |a, b| a == b,
+        |a, b| a.trim_end() == b.trim_end(),
+        |a, b| a.trim() == b.trim(),
+    ];
+    let last = lines.len().checked_sub(old.len())?;
+    let target = hint.unwrap_or(from);
+    comparisons.iter().find_map(|same| {
+        (from..=last)
+            .filter(|&start| {
+                old.iter()
+                    .zip(&lines[start..])
+                    .all(|(expected, actual)| same(actual, expected))
+            })
+            .min_by_key(|&start| start.abs_diff(target))
+    })
+}
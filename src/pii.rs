@@ -0,0 +1,341 @@
+//! PII redaction.
+//!
+//! A [`PiiFilter`] replaces personal data in outgoing messages, such as email addresses, phone
+//! numbers, or anything matched by your own patterns or [`Redactor`]s, with placeholders like
+//! `[EMAIL_1]`, so that the data never reaches the service. Attached to a client with
+//! [`CopilotClient::with_pii_filter`], it applies to every chat completion request, and with
+//! [`PiiFilter::with_restore`] the placeholders in the reply are turned back into the original
+//! values, so callers see a complete answer:
+//!
+//! ```
+//! use copilot_client::pii::{PiiFilter, Placeholders};
+//! use regex::Regex;
+//!
+//! let filter = PiiFilter::new()
+//!     .emails()
+//!     .phone_numbers()
+//!     .with_pattern("ACCOUNT", Regex::new(r"\bACC-\d{8}\b").unwrap());
+//! let mut placeholders = Placeholders::new();
+//! let redacted = filter.redact("Mail jane@example.com about ACC-12345678.", &mut placeholders);
+//! assert_eq!(redacted, "Mail [EMAIL_1] about [ACCOUNT_1].");
+//! assert_eq!(
+//!     placeholders.restore("I wrote to [EMAIL_1]."),
+//!     "I wrote to jane@example.com."
+//! );
+//! ```
+//!
+//! The same value gets the same placeholder everywhere in a request, so the model can still
+//! tell values apart and refer to them. Only message content is redacted and restored; tool
+//! call arguments are sent and returned as they are.
+//!
+//! [`CopilotClient::with_pii_filter`]: crate::CopilotClient::with_pii_filter
+
+use crate::{
+    ChatResponse, Message,
+    stream::{ChatStream, ChatStreamChoice, ChatStreamChunk, MessageDelta},
+};
+use futures_util::{StreamExt, stream};
+use regex::Regex;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, LazyLock},
+};
+
+/// Matches placeholders such as `[EMAIL_1]`.
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[[A-Z][A-Z0-9_]*_\d+\]").expect("valid pattern"));
+
+/// Matches email addresses.
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").expect("valid pattern")
+});
+
+/// Matches phone numbers written with separators, with an optional country code.
+static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{2,4}\)[ .-]?|\b\d{2,4}[ .-])\d{3,4}[ .-]?\d{3,4}\b")
+        .expect("valid pattern")
+});
+
+/// The placeholders of a request and the values they stand for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Placeholders {
+    values: HashMap<String, String>,
+    placeholders: HashMap<(String, String), String>,
+    counts: HashMap<String, usize>,
+}
+
+impl Placeholders {
+    /// Creates an empty set of placeholders.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the placeholder for `value` of the given `kind`, such as `[EMAIL_2]`, creating
+    /// one if `value` has none yet. `kind` should be upper case letters, digits, and
+    /// underscores.
+    pub fn insert(&mut self, kind: &str, value: &str) -> String {
+        let key = (kind.to_string(), value.to_string());
+        if let Some(placeholder) = self.placeholders.get(&key) {
+            return placeholder.clone();
+        }
+        let count = self.counts.entry(kind.to_string()).or_default();
+        *count += 1;
+        let placeholder = format!("[{kind}_{count}]");
+        self.values.insert(placeholder.clone(), value.to_string());
+        self.placeholders.insert(key, placeholder.clone());
+        placeholder
+    }
+
+    /// Returns the value a placeholder stands for.
+    pub fn get(&self, placeholder: &str) -> Option<&str> {
+        self.values.get(placeholder).map(String::as_str)
+    }
+
+    /// Returns the number of placeholders.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if nothing was redacted.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Replaces the placeholders in `text` with their values. Unknown placeholders are kept.
+    pub fn restore<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        PLACEHOLDER.replace_all(text, |caps: &regex::Captures<'_>| {
+            self.get(&caps[0]).unwrap_or(&caps[0]).to_string()
+        })
+    }
+
+    /// Replaces the placeholders in the content of every choice of `response`.
+    pub fn restore_response(&self, response: &mut ChatResponse) {
+        for choice in &mut response.choices {
+            if let Cow::Owned(restored) = self.restore(&choice.message.content) {
+                choice.message.content = restored;
+            }
+        }
+    }
+
+    /// Returns the length of the longest placeholder.
+    fn max_len(&self) -> usize {
+        self.values.keys().map(String::len).max().unwrap_or(0)
+    }
+}
+
+/// A redaction stage.
+///
+/// Redactors replace the personal data they find in `text` with placeholders obtained from
+/// [`Placeholders::insert`]. Closures taking a `&str` and `&mut Placeholders` and returning a
+/// `String` are redactors.
+pub trait Redactor: Send + Sync {
+    /// Returns `text` with personal data replaced by placeholders.
+    fn redact(&self, text: &str, placeholders: &mut Placeholders) -> String;
+}
+
+impl<F> Redactor for F
+where
+    F: Fn(&str, &mut Placeholders) -> String + Send + Sync,
+{
+    fn redact(&self, text: &str, placeholders: &mut Placeholders) -> String {
+        self(text, placeholders)
+    }
+}
+
+/// A set of redactors applied to outgoing messages in order, optionally reversed on the reply.
+#[derive(Clone, Default)]
+pub struct PiiFilter {
+    redactors: Vec<Arc<dyn Redactor>>,
+    restore: bool,
+}
+
+impl fmt::Debug for PiiFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PiiFilter")
+            .field("redactors", &self.redactors.len())
+            .field("restore", &self.restore)
+            .finish()
+    }
+}
+
+impl PiiFilter {
+    /// Creates a filter without redactors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a redactor.
+    pub fn with_redactor(mut self, redactor: impl Redactor + 'static) -> Self {
+        self.redactors.push(Arc::new(redactor));
+        self
+    }
+
+    /// Replaces every match of `pattern` with a placeholder of the given `kind`, such as
+    /// `"ACCOUNT"`.
+    pub fn with_pattern(self, kind: impl Into<String>, pattern: Regex) -> Self {
+        let kind = kind.into();
+        self.with_redactor(move |text: &str, placeholders: &mut Placeholders| {
+            pattern
+                .replace_all(text, |caps: &regex::Captures<'_>| {
+                    placeholders.insert(&kind, &caps[0])
+                })
+                .into_owned()
+        })
+    }
+
+    /// Replaces email addresses with `[EMAIL_n]` placeholders.
+    pub fn emails(self) -> Self {
+        self.with_pattern("EMAIL", EMAIL.clone())
+    }
+
+    /// Replaces phone numbers written with separators, such as `+1 415-555-0123` or
+    /// `(03) 1234 5678`, with `[PHONE_n]` placeholders.
+    pub fn phone_numbers(self) -> Self {
+        self.with_pattern("PHONE", PHONE.clone())
+    }
+
+    /// Sets whether placeholders in replies are replaced with the original values. Off by
+    /// default, so that the personal data stays out of the reply too.
+    pub fn with_restore(mut self, restore: bool) -> Self {
+        self.restore = restore;
+        self
+    }
+
+    /// Returns `true` if placeholders in replies are restored.
+    pub fn restores(&self) -> bool {
+        self.restore
+    }
+
+    /// Runs the redactors on `text` in order.
+    pub fn redact(&self, text: &str, placeholders: &mut Placeholders) -> String {
+        self.redactors
+            .iter()
+            .fold(text.to_string(), |text, redactor| {
+                redactor.redact(&text, placeholders)
+            })
+    }
+
+    /// Redacts the content of `messages` and returns the placeholders used. Messages are only
+    /// copied if something is redacted.
+    pub fn redact_messages(&self, messages: &mut Cow<'_, [Message]>) -> Placeholders {
+        let mut placeholders = Placeholders::new();
+        for i in 0..messages.len() {
+            let redacted = self.redact(&messages[i].content, &mut placeholders);
+            if redacted != messages[i].content {
+                messages.to_mut()[i].content = redacted;
+            }
+        }
+        placeholders
+    }
+}
+
+/// The text of each choice of a stream held back because it may end in a placeholder.
+struct RestoreState {
+    placeholders: Placeholders,
+    max_len: usize,
+    pending: Vec<String>,
+    done: bool,
+}
+
+impl RestoreState {
+    /// Appends `delta` to the pending text of choice `index` and returns what can be restored
+    /// and emitted, holding back a trailing `[` that may start a placeholder unless `flush`.
+    fn take(&mut self, index: usize, delta: &str, flush: bool) -> String {
+        if self.pending.len() <= index {
+            self.pending.resize(index + 1, String::new());
+        }
+        let pending = &mut self.pending[index];
+        pending.push_str(delta);
+        let split = match pending.rfind('[') {
+            Some(open)
+                if !flush
+                    && !pending[open..].contains(']')
+                    && pending.len() - open < self.max_len =>
+            {
+                open
+            }
+            _ => pending.len(),
+        };
+        let ready: String = pending.drain(..split).collect();
+        self.placeholders.restore(&ready).into_owned()
+    }
+}
+
+impl ChatStream {
+    /// Replaces the `placeholders` in the content of every choice as it streams in. Text that
+    /// may be the start of a placeholder is held back until the placeholder is complete or the
+    /// choice finishes.
+    pub fn with_restored_placeholders(self, placeholders: Placeholders) -> Self {
+        if placeholders.is_empty() {
+            return self;
+        }
+        let state = RestoreState {
+            max_len: placeholders.max_len(),
+            placeholders,
+            pending: Vec::new(),
+            done: false,
+        };
+        self.map_inner(|inner| {
+            Box::pin(stream::unfold(
+                (inner, state),
+                |(mut inner, mut state)| async move {
+                    if state.done {
+                        return None;
+                    }
+                    let Some(chunk) = inner.next().await else {
+                        state.done = true;
+                        let chunk = flush_chunk(&mut state)?;
+                        return Some((Ok(chunk), (inner, state)));
+                    };
+                    let mut chunk = match chunk {
+                        Ok(chunk) => chunk,
+                        Err(e) => return Some((Err(e), (inner, state))),
+                    };
+                    for choice in &mut chunk.choices {
+                        let flush = choice.finish_reason.is_some();
+                        let index = choice.index as usize;
+                        let delta = choice.delta.content.as_deref().unwrap_or_default();
+                        if delta.is_empty() && !flush {
+                            continue;
+                        }
+                        let ready = state.take(index, delta, flush);
+                        if choice.delta.content.is_some() || !ready.is_empty() {
+                            choice.delta.content = Some(ready);
+                        }
+                    }
+                    Some((Ok(chunk), (inner, state)))
+                },
+            ))
+        })
+    }
+}
+
+/// Returns a chunk with the text still held back when a stream ends without finishing every
+/// choice, or `None` if nothing is left.
+fn flush_chunk(state: &mut RestoreState) -> Option<ChatStreamChunk> {
+    let indices: Vec<usize> = (0..state.pending.len())
+        .filter(|&index| !state.pending[index].is_empty())
+        .collect();
+    let choices: Vec<ChatStreamChoice> = indices
+        .into_iter()
+        .map(|index| ChatStreamChoice {
+            index: index as u32,
+            delta: MessageDelta {
+                content: Some(state.take(index, "", true)),
+                ..MessageDelta::default()
+            },
+            finish_reason: None,
+            logprobs: None,
+        })
+        .collect();
+    (!choices.is_empty()).then_some(ChatStreamChunk {
+        choices,
+        usage: None,
+        system_fingerprint: None,
+    })
+}
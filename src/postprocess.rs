@@ -0,0 +1,161 @@
+//! Post-processing of chat completion replies.
+//!
+//! [`PostProcessors`] rewrite the content of every choice of a response, in the order they were
+//! added. Attach them to a client with [`CopilotClient::with_post_processors`] to process every
+//! chat completion, or set [`ChatOptions::post_processors`] to process the replies to a single
+//! request, after those of the client:
+//!
+//! ```
+//! use copilot_client::postprocess::PostProcessors;
+//!
+//! let processors = PostProcessors::new()
+//!     .normalize_line_endings()
+//!     .trim_preamble()
+//!     .strip_code_fence();
+//! let reply = "Sure, here is the script:\r\n\r\n```sh\r\necho hi\r\n```";
+//! assert_eq!(processors.process(reply.to_string()), "echo hi");
+//! ```
+//!
+//! Streamed chat completions are not processed; apply [`PostProcessors::process`] to the
+//! collected content instead.
+//!
+//! [`CopilotClient::with_post_processors`]: crate::CopilotClient::with_post_processors
+//! [`ChatOptions::post_processors`]: crate::ChatOptions::post_processors
+
+use crate::ChatResponse;
+use std::{fmt, sync::Arc};
+
+/// A rewrite of the content of a reply.
+///
+/// Closures taking and returning a `String` are post-processors.
+pub trait PostProcessor: Send + Sync {
+    /// Returns the processed `content`.
+    fn process(&self, content: String) -> String;
+}
+
+impl<F> PostProcessor for F
+where
+    F: Fn(String) -> String + Send + Sync,
+{
+    fn process(&self, content: String) -> String {
+        self(content)
+    }
+}
+
+/// An ordered list of post-processors.
+#[derive(Clone, Default)]
+pub struct PostProcessors {
+    processors: Vec<Arc<dyn PostProcessor>>,
+}
+
+impl fmt::Debug for PostProcessors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostProcessors")
+            .field("processors", &self.processors.len())
+            .finish()
+    }
+}
+
+impl PostProcessors {
+    /// Creates an empty list of post-processors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a post-processor.
+    pub fn with_processor(mut self, processor: impl PostProcessor + 'static) -> Self {
+        self.processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Removes a code fence around the whole reply, keeping its contents.
+    pub fn strip_code_fence(self) -> Self {
+        self.with_processor(|content: String| match strip_code_fence(&content) {
+            stripped if stripped.len() == content.len() => content,
+            stripped => stripped.to_string(),
+        })
+    }
+
+    /// Removes a boilerplate first line such as "Sure, here is the updated function:" and the
+    /// blank lines after it.
+    pub fn trim_preamble(self) -> Self {
+        self.with_processor(|content: String| match trim_preamble(&content) {
+            trimmed if trimmed.len() == content.len() => content,
+            trimmed => trimmed.to_string(),
+        })
+    }
+
+    /// Replaces `\r\n` and lone `\r` line endings with `\n`.
+    pub fn normalize_line_endings(self) -> Self {
+        self.with_processor(|content: String| {
+            if content.contains('\r') {
+                content.replace("\r\n", "\n").replace('\r', "\n")
+            } else {
+                content
+            }
+        })
+    }
+
+    /// Runs the post-processors on `content`.
+    pub fn process(&self, content: String) -> String {
+        self.processors
+            .iter()
+            .fold(content, |content, processor| processor.process(content))
+    }
+
+    /// Runs the post-processors on the content of every choice of `response`.
+    pub fn apply(&self, response: &mut ChatResponse) {
+        for choice in &mut response.choices {
+            let content = std::mem::take(&mut choice.message.content);
+            choice.message.content = self.process(content);
+        }
+    }
+}
+
+/// Returns the contents of a code fence around the whole of `content`, without the info string,
+/// or `content` itself if it is not fenced.
+pub fn strip_code_fence(content: &str) -> &str {
+    let trimmed = content.trim();
+    trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .filter(|inner| !inner.contains("\n```"))
+        .map_or(content, |inner| {
+            inner
+                .split_once('\n')
+                .map_or("", |(_, body)| body.trim_end_matches(['\n', '\r']))
+        })
+}
+
+/// Openings of boilerplate first lines, in lowercase.
+const PREAMBLE_OPENINGS: &[&str] = &[
+    "sure",
+    "certainly",
+    "of course",
+    "absolutely",
+    "okay",
+    "ok,",
+    "here is",
+    "here's",
+    "here are",
+    "below is",
+    "below are",
+];
+
+/// Returns `content` without a boilerplate first line and the blank lines after it. Only lines
+/// that start with a stock opening and end with `:`, `.`, or `!` count as preamble, and only if
+/// something follows them.
+pub fn trim_preamble(content: &str) -> &str {
+    let Some((first, rest)) = content.trim_start().split_once('\n') else {
+        return content;
+    };
+    let first = first.trim_end().to_lowercase();
+    let rest = rest.trim_start_matches(['\n', '\r']);
+    let is_preamble = PREAMBLE_OPENINGS.iter().any(|opening| {
+        first
+            .strip_prefix(opening)
+            .is_some_and(|after| !after.starts_with(char::is_alphanumeric))
+    }) && first.ends_with([':', '.', '!'])
+        && !rest.trim().is_empty();
+    if is_preamble { rest } else { content }
+}
@@ -0,0 +1,236 @@
+//! Premium-request multipliers and token prices, for reporting spend.
+//!
+//! A [`PriceTable`] holds, per model, the number of premium requests a chat completion
+//! consumes and the price of its tokens. [`PriceTable::builtin`] ships the multipliers of
+//! Copilot's paid plans and the providers' list prices for API tokens at the time of this
+//! release; override entries that have changed, or load a whole table from JSON:
+//!
+//! ```
+//! use copilot_client::{
+//!     pricing::{ModelPrice, PriceTable},
+//!     usage::UsageTracker,
+//! };
+//!
+//! let prices = PriceTable::builtin()
+//!     .with_premium_request_price(0.04)
+//!     .with_model("my-fine-tune", ModelPrice::new(1.0, 3.0, 12.0));
+//! let tracker = UsageTracker::new();
+//! tracker.set_price_table(prices);
+//! // ... make requests ...
+//! let cost = tracker.cost();
+//! println!(
+//!     "{} premium requests, ${:.2}; ${:.2} at API token prices",
+//!     cost.premium_requests, cost.premium_cost, cost.token_cost
+//! );
+//! ```
+//!
+//! The two costs answer different questions: [`Cost::premium_cost`] is what the premium
+//! requests cost beyond a plan's allowance, and [`Cost::token_cost`] what the same tokens would
+//! cost through the providers' APIs, for charging usage back to teams. Both are estimates.
+
+use crate::usage::ModelUsage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The price of a premium request beyond a plan's allowance, in US dollars.
+pub const PREMIUM_REQUEST_PRICE: f64 = 0.04;
+
+/// Multipliers and token prices in US dollars per million tokens, built into
+/// [`PriceTable::builtin`]: model id, premium multiplier, input, cached input, and output.
+const BUILTIN_PRICES: &[(&str, f64, f64, f64, f64)] = &[
+    ("gpt-4o", 0.0, 2.5, 1.25, 10.0),
+    ("gpt-4o-mini", 0.0, 0.15, 0.075, 0.6),
+    ("gpt-4.1", 0.0, 2.0, 0.5, 8.0),
+    ("gpt-4.1-mini", 0.0, 0.4, 0.1, 1.6),
+    ("gpt-5", 1.0, 1.25, 0.125, 10.0),
+    ("gpt-5-mini", 0.0, 0.25, 0.025, 2.0),
+    ("o3", 1.0, 2.0, 0.5, 8.0),
+    ("o3-mini", 0.33, 1.1, 0.55, 4.4),
+    ("o4-mini", 0.33, 1.1, 0.275, 4.4),
+    ("claude-3.5-sonnet", 1.0, 3.0, 0.3, 15.0),
+    ("claude-3.7-sonnet", 1.0, 3.0, 0.3, 15.0),
+    ("claude-3.7-sonnet-thought", 1.25, 3.0, 0.3, 15.0),
+    ("claude-sonnet-4", 1.0, 3.0, 0.3, 15.0),
+    ("claude-sonnet-4.5", 1.0, 3.0, 0.3, 15.0),
+    ("claude-haiku-4.5", 0.33, 1.0, 0.1, 5.0),
+    ("claude-opus-4", 10.0, 15.0, 1.5, 75.0),
+    ("claude-opus-4.1", 10.0, 15.0, 1.5, 75.0),
+    ("gemini-2.0-flash", 0.25, 0.1, 0.025, 0.4),
+    ("gemini-2.5-pro", 1.0, 1.25, 0.31, 10.0),
+    ("text-embedding-3-small", 0.0, 0.02, 0.02, 0.0),
+];
+
+/// The premium multiplier and token prices of a model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Premium requests consumed by one chat completion, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub premium_multiplier: Option<f64>,
+    /// US dollars per million prompt tokens.
+    #[serde(default)]
+    pub input: f64,
+    /// US dollars per million prompt tokens read from the prompt cache, if they are priced
+    /// differently from other prompt tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_input: Option<f64>,
+    /// US dollars per million completion tokens.
+    #[serde(default)]
+    pub output: f64,
+}
+
+impl ModelPrice {
+    /// Creates a price with a premium multiplier and input and output prices in US dollars per
+    /// million tokens.
+    pub fn new(premium_multiplier: f64, input: f64, output: f64) -> Self {
+        ModelPrice {
+            premium_multiplier: Some(premium_multiplier),
+            input,
+            cached_input: None,
+            output,
+        }
+    }
+
+    /// Sets the price of cached prompt tokens in US dollars per million tokens.
+    pub fn with_cached_input(mut self, cached_input: f64) -> Self {
+        self.cached_input = Some(cached_input);
+        self
+    }
+
+    /// Returns the price of `usage`'s tokens in US dollars.
+    pub fn token_cost(&self, usage: &ModelUsage) -> f64 {
+        let cached = usage.cached_tokens.min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        (uncached as f64 * self.input
+            + cached as f64 * self.cached_input.unwrap_or(self.input)
+            + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Premium requests and their price, with the price of the tokens used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cost {
+    /// Premium requests consumed.
+    pub premium_requests: f64,
+    /// The price of the premium requests beyond a plan's allowance, in US dollars.
+    pub premium_cost: f64,
+    /// The price of the tokens at the providers' API prices, in US dollars. Zero for models
+    /// without token prices.
+    pub token_cost: f64,
+}
+
+impl Cost {
+    /// Adds `other` to this cost.
+    pub fn add(&mut self, other: &Cost) {
+        self.premium_requests += other.premium_requests;
+        self.premium_cost += other.premium_cost;
+        self.token_cost += other.token_cost;
+    }
+}
+
+/// Premium multipliers and token prices by model id. See the [module documentation](self).
+///
+/// Model ids are looked up exactly, and then without a snapshot suffix, so that the entry for
+/// `gpt-4o` also prices snapshots such as `gpt-4o-2024-11-20` and `gpt-4o-0513`. Variants such
+/// as `gpt-4o-mini` are separate models and need their own entries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceTable {
+    /// The price of a premium request beyond a plan's allowance, in US dollars.
+    #[serde(default = "default_premium_request_price")]
+    pub premium_request_price: f64,
+    /// Prices by model id.
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelPrice>,
+}
+
+/// Returns `model` without a trailing snapshot suffix: a date such as `-2024-11-20`, or a
+/// version of at least four digits such as `-0613` or `-20241022`.
+fn strip_snapshot(model: &str) -> Option<&str> {
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if let Some(date_start) = model.len().checked_sub(11)
+        && let Some((base, date)) = model.split_at_checked(date_start)
+        && let Some(date) = date.strip_prefix('-')
+        && let [year, month, day] = date.split('-').collect::<Vec<_>>()[..]
+        && year.len() == 4
+        && month.len() == 2
+        && day.len() == 2
+        && [year, month, day].into_iter().all(is_digits)
+    {
+        return Some(base);
+    }
+    let (base, version) = model.rsplit_once('-')?;
+    (version.len() >= 4 && is_digits(version)).then_some(base)
+}
+
+fn default_premium_request_price() -> f64 {
+    PREMIUM_REQUEST_PRICE
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PriceTable {
+    /// Creates a table without models, with the default premium request price.
+    pub fn new() -> Self {
+        PriceTable {
+            premium_request_price: PREMIUM_REQUEST_PRICE,
+            models: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the table shipped with this release. Prices change; check them against
+    /// GitHub's and the providers' current prices before relying on them for billing.
+    pub fn builtin() -> Self {
+        let models = BUILTIN_PRICES
+            .iter()
+            .map(|&(id, multiplier, input, cached_input, output)| {
+                let price =
+                    ModelPrice::new(multiplier, input, output).with_cached_input(cached_input);
+                (id.to_string(), price)
+            })
+            .collect();
+        PriceTable {
+            premium_request_price: PREMIUM_REQUEST_PRICE,
+            models,
+        }
+    }
+
+    /// Sets the price of `model`, replacing its entry if any.
+    pub fn with_model(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.models.insert(model.into(), price);
+        self
+    }
+
+    /// Sets the price of a premium request in US dollars.
+    pub fn with_premium_request_price(mut self, price: f64) -> Self {
+        self.premium_request_price = price;
+        self
+    }
+
+    /// Returns the price of `model`, if the table has one.
+    pub fn price(&self, model: &str) -> Option<&ModelPrice> {
+        self.models
+            .get(model)
+            .or_else(|| self.models.get(strip_snapshot(model)?))
+    }
+
+    /// Returns the premium multiplier of `model`, if the table has one.
+    pub fn premium_multiplier(&self, model: &str) -> Option<f64> {
+        self.price(model)?.premium_multiplier
+    }
+
+    /// Returns the cost of `usage` recorded for `model`. The premium requests are taken from
+    /// `usage`, as counted when the requests were recorded.
+    pub fn cost(&self, model: &str, usage: &ModelUsage) -> Cost {
+        Cost {
+            premium_requests: usage.premium_requests,
+            premium_cost: usage.premium_requests * self.premium_request_price,
+            token_cost: self
+                .price(model)
+                .map_or(0.0, |price| price.token_cost(usage)),
+        }
+    }
+}
@@ -0,0 +1,599 @@
+//! Token-budgeted prompt assembly.
+//!
+//! [`PromptBuilder`] collects the pieces of a chat prompt (system prompt, retrieved context,
+//! conversation history, and the user message) and fits them into a token budget. When the
+//! prompt does not fit, the lowest-priority section is trimmed first and everything that was
+//! removed is recorded in a [`PromptReport`], along with what was included and the tokens of
+//! each section. [`CopilotClient::chat_prompt`] returns the report with the response, so that
+//! callers can tell why the model did not see something.
+//!
+//! Token counts are estimated with [`estimate_tokens`], which is intentionally conservative and
+//! does not depend on a model-specific tokenizer.
+
+use crate::{ChatOptions, ChatResponse, CopilotClient, CopilotError, Message};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Approximate number of characters per token used by [`estimate_tokens`].
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Fixed per-message overhead (role, separators) added by the chat format.
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// Heading of the system message that carries retrieved context.
+const CONTEXT_HEADING: &str = "Context:\n\n";
+
+/// Estimates the number of tokens in `text`.
+///
+/// The estimate assumes roughly four characters per token, which is close to the behavior of
+/// the tokenizers used by Copilot chat models for English text and source code.
+pub fn estimate_tokens(text: &str) -> u32 {
+    let chars = text.chars().count();
+    chars.div_ceil(CHARS_PER_TOKEN) as u32
+}
+
+/// Estimates the number of tokens a single message occupies, including formatting overhead.
+pub fn estimate_message_tokens(message: &Message) -> u32 {
+    estimate_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Truncates `text` so that it fits in approximately `max_tokens` tokens.
+///
+/// The text is cut on a character boundary; no marker is appended.
+pub fn truncate_to_tokens(text: &str, max_tokens: u32) -> &str {
+    let max_chars = max_tokens as usize * CHARS_PER_TOKEN;
+    match text.char_indices().nth(max_chars) {
+        Some((idx, _)) => &text[..idx],
+        None => text,
+    }
+}
+
+/// Asks the model to answer in `language`, such as `"Japanese"` or `"en-US"`, whatever the
+/// language of the prompt. The instruction is appended to the leading system message, or sent
+/// as a system message of its own if there is none.
+pub fn apply_response_language(messages: &mut Vec<Message>, language: &str) {
+    let instruction = format!(
+        "Always answer in {language}, even if the question or the context is written in another \
+         language. Keep code, identifiers, and quoted text unchanged."
+    );
+    append_system_instruction(messages, &instruction);
+}
+
+/// Appends `instruction` to the leading system message, or inserts a system message with it if
+/// there is none.
+pub(crate) fn append_system_instruction(messages: &mut Vec<Message>, instruction: &str) {
+    match messages.first_mut() {
+        Some(system) if system.role == "system" => {
+            if !system.content.is_empty() {
+                system.content.push_str("\n\n");
+            }
+            system.content.push_str(instruction);
+        }
+        _ => messages.insert(0, Message::system(instruction)),
+    }
+}
+
+/// Tokens used by the system message that wraps the retrieved context.
+fn context_wrapper_tokens() -> u32 {
+    estimate_tokens(CONTEXT_HEADING) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// A section of a prompt managed by [`PromptBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptSection {
+    /// The system prompt.
+    System,
+    /// Retrieved context (files, search results, documentation).
+    Context,
+    /// Previous turns of the conversation.
+    History,
+    /// The current user message.
+    User,
+}
+
+impl fmt::Display for PromptSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PromptSection::System => "system",
+            PromptSection::Context => "context",
+            PromptSection::History => "history",
+            PromptSection::User => "user",
+        })
+    }
+}
+
+/// A piece of retrieved context with a label describing its origin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextItem {
+    /// A short label shown to the model, such as a file path or URL.
+    pub label: String,
+    /// The context content.
+    pub content: String,
+}
+
+/// Describes content that was removed or shortened to fit the token budget.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedContent {
+    /// The section the content belonged to.
+    pub section: PromptSection,
+    /// A label identifying the content (context label, history index, etc.).
+    pub label: String,
+    /// Estimated tokens before trimming.
+    pub original_tokens: u32,
+    /// Estimated tokens kept after trimming. `0` means the content was dropped entirely.
+    pub kept_tokens: u32,
+}
+
+impl DroppedContent {
+    /// Returns `true` if the content was removed entirely rather than truncated.
+    pub fn is_dropped(&self) -> bool {
+        self.kept_tokens == 0
+    }
+}
+
+/// Describes content that made it into the prompt, whole or truncated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncludedContent {
+    /// The section the content belongs to.
+    pub section: PromptSection,
+    /// A label identifying the content (context label, history index, etc.).
+    pub label: String,
+    /// Estimated tokens of the content in the prompt.
+    pub tokens: u32,
+    /// `true` if the content was truncated to fit.
+    pub truncated: bool,
+}
+
+/// Token counts of one section of a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionReport {
+    /// The section.
+    pub section: PromptSection,
+    /// Number of items (context items or history messages) given to the builder.
+    pub items: usize,
+    /// Number of items kept, whole or truncated.
+    pub kept_items: usize,
+    /// Estimated tokens of the section before trimming.
+    pub original_tokens: u32,
+    /// Estimated tokens of the section in the prompt.
+    pub kept_tokens: u32,
+}
+
+/// Summary of how a prompt was fitted into its token budget.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptReport {
+    /// The token budget the prompt was built against.
+    pub budget: u32,
+    /// Estimated tokens used by the final prompt.
+    pub total_tokens: u32,
+    /// Content that was dropped or truncated, in the order it was trimmed.
+    pub dropped: Vec<DroppedContent>,
+    /// Content in the prompt, in prompt order.
+    #[serde(default)]
+    pub included: Vec<IncludedContent>,
+    /// Token counts of the sections given to the builder, in prompt order.
+    #[serde(default)]
+    pub sections: Vec<SectionReport>,
+}
+
+impl PromptReport {
+    /// Returns `true` if nothing had to be trimmed.
+    pub fn is_complete(&self) -> bool {
+        self.dropped.is_empty()
+    }
+
+    /// Returns the token counts of `section`, if the prompt had any content in it.
+    pub fn section(&self, section: PromptSection) -> Option<&SectionReport> {
+        self.sections.iter().find(|s| s.section == section)
+    }
+}
+
+/// Formats the report as a short human-readable summary: the tokens of each section, then what
+/// was cut.
+impl fmt::Display for PromptReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Prompt: {} of {} tokens", self.total_tokens, self.budget)?;
+        for section in &self.sections {
+            write!(f, "\n  {}: {}", section.section, section.kept_tokens)?;
+            if section.kept_tokens < section.original_tokens {
+                write!(f, " of {}", section.original_tokens)?;
+            }
+            write!(f, " tokens")?;
+            if matches!(
+                section.section,
+                PromptSection::Context | PromptSection::History
+            ) {
+                write!(f, " ({} of {} items)", section.kept_items, section.items)?;
+            }
+        }
+        if !self.dropped.is_empty() {
+            write!(f, "\nCut to fit:")?;
+        }
+        for dropped in &self.dropped {
+            write!(f, "\n  - {}: ", dropped.label)?;
+            if dropped.is_dropped() {
+                write!(f, "dropped, {} tokens", dropped.original_tokens)?;
+            } else {
+                write!(
+                    f,
+                    "truncated from {} to {} tokens",
+                    dropped.original_tokens, dropped.kept_tokens
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A chat completion response together with the report on how its prompt was built.
+#[derive(Debug, Clone)]
+pub struct PromptResponse {
+    /// The response.
+    pub response: ChatResponse,
+    /// What was included in the prompt and what was cut to fit the budget.
+    pub report: PromptReport,
+}
+
+/// A prompt assembled by [`PromptBuilder::build`].
+#[derive(Debug, Clone)]
+pub struct BuiltPrompt {
+    /// The messages to send, in order.
+    pub messages: Vec<Message>,
+    /// Details on what was trimmed to fit the budget.
+    pub report: PromptReport,
+}
+
+/// Builds chat prompts that fit into a token budget.
+///
+/// Each section has a priority; when the prompt exceeds the budget, sections are trimmed from the
+/// lowest priority upwards. The defaults trim history first (oldest turns first), then retrieved
+/// context (last item first), then the system prompt, and finally the user message.
+///
+/// ```
+/// use copilot_client::prompt::PromptBuilder;
+///
+/// let prompt = PromptBuilder::new(4096)
+///     .system("You are a helpful assistant.")
+///     .context("README.md", "# Example project")
+///     .user("What does this project do?")
+///     .build();
+/// assert!(prompt.report.is_complete());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PromptBuilder {
+    budget: u32,
+    system: Option<String>,
+    context: Vec<ContextItem>,
+    history: Vec<Message>,
+    user: Option<String>,
+    priorities: [(PromptSection, u8); 4],
+    cache_system: bool,
+}
+
+/// A unit of prompt content that can be trimmed independently.
+struct Unit {
+    section: PromptSection,
+    index: usize,
+    tokens: u32,
+    truncatable: bool,
+}
+
+impl PromptBuilder {
+    /// Creates a builder for a prompt of at most `budget` estimated tokens.
+    pub fn new(budget: u32) -> Self {
+        PromptBuilder {
+            budget,
+            system: None,
+            context: Vec::new(),
+            history: Vec::new(),
+            user: None,
+            priorities: [
+                (PromptSection::History, 0),
+                (PromptSection::Context, 1),
+                (PromptSection::System, 2),
+                (PromptSection::User, 3),
+            ],
+            cache_system: false,
+        }
+    }
+
+    /// Sets the system prompt.
+    pub fn system(mut self, content: impl Into<String>) -> Self {
+        self.system = Some(content.into());
+        self
+    }
+
+    /// Adds a piece of retrieved context. Items added first are considered most relevant and are
+    /// kept longest.
+    pub fn context(mut self, label: impl Into<String>, content: impl Into<String>) -> Self {
+        self.context.push(ContextItem {
+            label: label.into(),
+            content: content.into(),
+        });
+        self
+    }
+
+    /// Adds several context items at once.
+    pub fn context_items(mut self, items: impl IntoIterator<Item = ContextItem>) -> Self {
+        self.context.extend(items);
+        self
+    }
+
+    /// Sets the conversation history, oldest message first.
+    pub fn history(mut self, messages: Vec<Message>) -> Self {
+        self.history = messages;
+        self
+    }
+
+    /// Sets the current user message.
+    pub fn user(mut self, content: impl Into<String>) -> Self {
+        self.user = Some(content.into());
+        self
+    }
+
+    /// Marks the system prompt as a cache breakpoint (see [`Message::with_cache_breakpoint`]).
+    ///
+    /// The system prompt is always the first message, ahead of the context and history that
+    /// change between requests, so a long static system prompt can be cached across all prompts
+    /// built with it. The marker is left out if the system prompt had to be truncated, since
+    /// the truncated text would not match later prompts anyway.
+    pub fn cache_system_prompt(mut self) -> Self {
+        self.cache_system = true;
+        self
+    }
+
+    /// Overrides the priority of a section. Lower values are trimmed first.
+    pub fn priority(mut self, section: PromptSection, priority: u8) -> Self {
+        for entry in self.priorities.iter_mut() {
+            if entry.0 == section {
+                entry.1 = priority;
+            }
+        }
+        self
+    }
+
+    /// Assembles the prompt, trimming content until it fits into the budget.
+    pub fn build(self) -> BuiltPrompt {
+        let mut units = self.units();
+        let mut total: u32 = units.iter().map(|u| u.tokens).sum();
+        let mut kept: Vec<u32> = units.iter().map(|u| u.tokens).collect();
+        let mut report = PromptReport {
+            budget: self.budget,
+            ..PromptReport::default()
+        };
+
+        // Trim from the lowest-priority section upwards. `units` is already sorted in trim order.
+        for (slot, unit) in units.iter_mut().enumerate() {
+            if total <= self.budget {
+                break;
+            }
+            let overflow = total - self.budget;
+            let keep = if unit.truncatable && overflow < unit.tokens {
+                unit.tokens - overflow
+            } else {
+                0
+            };
+            total -= unit.tokens - keep;
+            kept[slot] = keep;
+            report.dropped.push(DroppedContent {
+                section: unit.section,
+                label: self.label(unit.section, unit.index),
+                original_tokens: unit.tokens,
+                kept_tokens: keep,
+            });
+        }
+        report.total_tokens = total;
+        report.included = self
+            .prompt_order(&units)
+            .into_iter()
+            .filter(|&slot| kept[slot] > 0)
+            .map(|slot| IncludedContent {
+                section: units[slot].section,
+                label: self.label(units[slot].section, units[slot].index),
+                tokens: kept[slot],
+                truncated: kept[slot] < units[slot].tokens,
+            })
+            .collect();
+        for section in [
+            PromptSection::System,
+            PromptSection::Context,
+            PromptSection::History,
+            PromptSection::User,
+        ] {
+            let slots: Vec<usize> = (0..units.len())
+                .filter(|&slot| units[slot].section == section)
+                .collect();
+            if slots.is_empty() {
+                continue;
+            }
+            report.sections.push(SectionReport {
+                section,
+                items: slots.len(),
+                kept_items: slots.iter().filter(|&&slot| kept[slot] > 0).count(),
+                original_tokens: slots.iter().map(|&slot| units[slot].tokens).sum(),
+                kept_tokens: slots.iter().map(|&slot| kept[slot]).sum(),
+            });
+        }
+
+        let lookup = |section: PromptSection, index: usize| {
+            units
+                .iter()
+                .zip(&kept)
+                .find(|(u, _)| u.section == section && u.index == index)
+                .map(|(_, k)| *k)
+                .unwrap_or(0)
+        };
+
+        let mut messages = Vec::new();
+        if let Some(system) = &self.system {
+            let keep = lookup(PromptSection::System, 0);
+            if keep > 0 {
+                let text = truncate_to_tokens(system, keep.saturating_sub(MESSAGE_OVERHEAD_TOKENS));
+                let mut message = Message::system(text);
+                if self.cache_system && text.len() == system.len() {
+                    message = message.with_cache_breakpoint();
+                }
+                messages.push(message);
+            }
+        }
+        let mut context_block = String::new();
+        for (index, item) in self.context.iter().enumerate() {
+            let keep = lookup(PromptSection::Context, index);
+            if keep == 0 {
+                continue;
+            }
+            let header = format!("### {}\n", item.label);
+            let mut budget = keep.saturating_sub(estimate_tokens(&header));
+            if index == 0 {
+                budget = budget.saturating_sub(context_wrapper_tokens());
+            }
+            context_block.push_str(&header);
+            context_block.push_str(truncate_to_tokens(&item.content, budget));
+            context_block.push_str("\n\n");
+        }
+        if !context_block.is_empty() {
+            messages.push(Message::system(format!(
+                "{CONTEXT_HEADING}{}",
+                context_block.trim_end()
+            )));
+        }
+        for (index, message) in self.history.iter().enumerate() {
+            if lookup(PromptSection::History, index) > 0 {
+                messages.push(message.clone());
+            }
+        }
+        if let Some(user) = &self.user {
+            let keep = lookup(PromptSection::User, 0);
+            if keep > 0 {
+                let text = truncate_to_tokens(user, keep.saturating_sub(MESSAGE_OVERHEAD_TOKENS));
+                messages.push(Message::user(text));
+            }
+        }
+
+        BuiltPrompt { messages, report }
+    }
+
+    /// Splits the prompt into trimmable units, sorted in the order they should be trimmed.
+    fn units(&self) -> Vec<Unit> {
+        let mut units = Vec::new();
+        if let Some(system) = &self.system {
+            units.push(Unit {
+                section: PromptSection::System,
+                index: 0,
+                tokens: estimate_tokens(system) + MESSAGE_OVERHEAD_TOKENS,
+                truncatable: true,
+            });
+        }
+        // Least relevant context (added last) is trimmed first.
+        // The most relevant item also carries the cost of the message wrapping all context.
+        for (index, item) in self.context.iter().enumerate().rev() {
+            let header = format!("### {}\n", item.label);
+            let mut tokens = estimate_tokens(&header) + estimate_tokens(&item.content);
+            if index == 0 {
+                tokens += context_wrapper_tokens();
+            }
+            units.push(Unit {
+                section: PromptSection::Context,
+                index,
+                tokens,
+                truncatable: true,
+            });
+        }
+        // Oldest history is trimmed first; whole turns are dropped rather than truncated.
+        for (index, message) in self.history.iter().enumerate() {
+            units.push(Unit {
+                section: PromptSection::History,
+                index,
+                tokens: estimate_message_tokens(message),
+                truncatable: false,
+            });
+        }
+        if let Some(user) = &self.user {
+            units.push(Unit {
+                section: PromptSection::User,
+                index: 0,
+                tokens: estimate_tokens(user) + MESSAGE_OVERHEAD_TOKENS,
+                truncatable: true,
+            });
+        }
+        // The sort is stable, so the per-section order chosen above is preserved.
+        units.sort_by_key(|unit| self.priority_of(unit.section));
+        units
+    }
+
+    /// Returns the positions in `units` in the order their content appears in the prompt.
+    fn prompt_order(&self, units: &[Unit]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..units.len()).collect();
+        order.sort_by_key(|&slot| {
+            let section = match units[slot].section {
+                PromptSection::System => 0,
+                PromptSection::Context => 1,
+                PromptSection::History => 2,
+                PromptSection::User => 3,
+            };
+            (section, units[slot].index)
+        });
+        order
+    }
+
+    fn priority_of(&self, section: PromptSection) -> u8 {
+        self.priorities
+            .iter()
+            .find(|(s, _)| *s == section)
+            .map(|(_, p)| *p)
+            .unwrap_or(0)
+    }
+
+    fn label(&self, section: PromptSection, index: usize) -> String {
+        match section {
+            PromptSection::System => "system prompt".to_string(),
+            PromptSection::Context => self.context[index].label.clone(),
+            PromptSection::History => {
+                format!("history[{index}] ({})", self.history[index].role)
+            }
+            PromptSection::User => "user message".to_string(),
+        }
+    }
+}
+
+impl CopilotClient {
+    /// Sends a prompt built by [`PromptBuilder`] and returns the response together with the
+    /// prompt's [`PromptReport`].
+    ///
+    /// ```no_run
+    /// use copilot_client::{ChatOptions, prompt::PromptBuilder};
+    ///
+    /// # async fn run(client: copilot_client::CopilotClient, notes: String) -> Result<(), copilot_client::CopilotError> {
+    /// let prompt = PromptBuilder::new(8_000)
+    ///     .context("notes.md", notes)
+    ///     .user("Summarize my notes.")
+    ///     .build();
+    /// let reply = client
+    ///     .chat_prompt(prompt, "gpt-4o".into(), &ChatOptions::default())
+    ///     .await?;
+    /// if !reply.report.is_complete() {
+    ///     eprintln!("{}", reply.report);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the request fails.
+    pub async fn chat_prompt(
+        &self,
+        prompt: BuiltPrompt,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<PromptResponse, CopilotError> {
+        let response = self
+            .chat_completion_with_options(prompt.messages, model_id, options)
+            .await?;
+        Ok(PromptResponse {
+            response,
+            report: prompt.report,
+        })
+    }
+}
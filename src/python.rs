@@ -0,0 +1,370 @@
+//! Python bindings, enabled with the `python` feature.
+//!
+//! Build the extension module with `maturin develop` (see `pyproject.toml`) and use it as
+//! `copilot_client`:
+//!
+//! ```python
+//! from copilot_client import CopilotClient, Message
+//!
+//! client = CopilotClient()
+//! print(client.chat([Message.user("Hello")], "gpt-4o"))
+//! for delta in client.stream([Message.user("Tell me a story")], "gpt-4o"):
+//!     print(delta, end="")
+//! ```
+//!
+//! Every blocking method has an `_async` counterpart returning an awaitable, and
+//! `stream_async` returns an async iterator. Requests run on a shared tokio runtime and release
+//! the GIL while waiting.
+
+use crate::{ChatOptions, ChatStream, CopilotClient, CopilotError, Message, get_github_token};
+use futures_util::StreamExt;
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyStopAsyncIteration},
+    prelude::*,
+};
+use pyo3_async_runtimes::tokio::{future_into_py, get_runtime};
+use std::sync::{Arc, Mutex};
+
+/// Editor version reported when none is given.
+const DEFAULT_EDITOR_VERSION: &str = "Neovim/0.9.0";
+
+create_exception!(
+    copilot_client,
+    CopilotException,
+    PyException,
+    "Raised when a Copilot API request fails."
+);
+
+impl From<CopilotError> for PyErr {
+    fn from(e: CopilotError) -> Self {
+        CopilotException::new_err(e.to_string())
+    }
+}
+
+/// A chat message.
+#[pyclass(name = "Message", module = "copilot_client", from_py_object)]
+#[derive(Clone)]
+pub struct PyMessage {
+    inner: Message,
+}
+
+#[pymethods]
+impl PyMessage {
+    #[new]
+    fn new(role: String, content: String) -> Self {
+        PyMessage {
+            inner: Message::new(role, content),
+        }
+    }
+
+    /// Creates a system message.
+    #[staticmethod]
+    fn system(content: String) -> Self {
+        PyMessage {
+            inner: Message::system(content),
+        }
+    }
+
+    /// Creates a user message.
+    #[staticmethod]
+    fn user(content: String) -> Self {
+        PyMessage {
+            inner: Message::user(content),
+        }
+    }
+
+    /// Creates an assistant message.
+    #[staticmethod]
+    fn assistant(content: String) -> Self {
+        PyMessage {
+            inner: Message::assistant(content),
+        }
+    }
+
+    #[getter]
+    fn role(&self) -> &str {
+        &self.inner.role
+    }
+
+    #[getter]
+    fn content(&self) -> &str {
+        &self.inner.content
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Message(role={:?}, content={:?})",
+            self.inner.role, self.inner.content
+        )
+    }
+}
+
+/// Client for the GitHub Copilot API.
+#[pyclass(name = "CopilotClient", module = "copilot_client")]
+pub struct PyCopilotClient {
+    client: CopilotClient,
+}
+
+#[pymethods]
+impl PyCopilotClient {
+    /// Creates a client and fetches the available models. Without `github_token`, the token is
+    /// discovered from the environment.
+    #[new]
+    #[pyo3(signature = (github_token = None, editor_version = DEFAULT_EDITOR_VERSION.to_string()))]
+    fn new(py: Python<'_>, github_token: Option<String>, editor_version: String) -> PyResult<Self> {
+        let github_token = match github_token {
+            Some(token) => token,
+            None => get_github_token().map_err(|e| CopilotError::TokenError(e.to_string()))?,
+        };
+        let client = py.detach(|| {
+            get_runtime().block_on(CopilotClient::new_with_models(github_token, editor_version))
+        })?;
+        Ok(PyCopilotClient { client })
+    }
+
+    /// Returns the ids of the available models.
+    fn models(&self) -> Vec<String> {
+        self.client.models().iter().map(|m| m.id.clone()).collect()
+    }
+
+    /// Sends a chat request and returns the reply text.
+    #[pyo3(signature = (messages, model, temperature = None, max_tokens = None))]
+    fn chat(
+        &self,
+        py: Python<'_>,
+        messages: Vec<PyMessage>,
+        model: String,
+        temperature: Option<f64>,
+        max_tokens: Option<u32>,
+    ) -> PyResult<String> {
+        let client = self.client.clone();
+        let options = options(temperature, max_tokens);
+        py.detach(|| get_runtime().block_on(chat(client, messages, model, options)))
+            .map_err(Into::into)
+    }
+
+    /// Like `chat`, but returns an awaitable.
+    #[pyo3(signature = (messages, model, temperature = None, max_tokens = None))]
+    fn chat_async<'py>(
+        &self,
+        py: Python<'py>,
+        messages: Vec<PyMessage>,
+        model: String,
+        temperature: Option<f64>,
+        max_tokens: Option<u32>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = self.client.clone();
+        let options = options(temperature, max_tokens);
+        future_into_py(py, async move {
+            Ok(chat(client, messages, model, options).await?)
+        })
+    }
+
+    /// Sends a chat request and returns an iterator over the pieces of the reply.
+    #[pyo3(signature = (messages, model, temperature = None, max_tokens = None))]
+    fn stream(
+        &self,
+        py: Python<'_>,
+        messages: Vec<PyMessage>,
+        model: String,
+        temperature: Option<f64>,
+        max_tokens: Option<u32>,
+    ) -> PyResult<ChatIterator> {
+        let messages = into_messages(messages);
+        let options = options(temperature, max_tokens);
+        let stream = py.detach(|| {
+            get_runtime().block_on(
+                self.client
+                    .chat_completion_stream(messages, model, &options),
+            )
+        })?;
+        Ok(ChatIterator {
+            stream: Mutex::new(Some(stream)),
+        })
+    }
+
+    /// Like `stream`, but returns an async iterator. The request is sent on the first
+    /// iteration.
+    #[pyo3(signature = (messages, model, temperature = None, max_tokens = None))]
+    fn stream_async(
+        &self,
+        messages: Vec<PyMessage>,
+        model: String,
+        temperature: Option<f64>,
+        max_tokens: Option<u32>,
+    ) -> AsyncChatIterator {
+        let request = StreamRequest {
+            client: self.client.clone(),
+            messages: into_messages(messages),
+            model,
+            options: options(temperature, max_tokens),
+        };
+        AsyncChatIterator {
+            state: Arc::new(tokio::sync::Mutex::new(StreamState::Pending(Box::new(
+                request,
+            )))),
+        }
+    }
+
+    /// Returns an embedding vector for each input string.
+    fn embeddings(&self, py: Python<'_>, inputs: Vec<String>) -> PyResult<Vec<Vec<f64>>> {
+        let client = self.client.clone();
+        py.detach(|| get_runtime().block_on(embeddings(client, inputs)))
+            .map_err(Into::into)
+    }
+
+    /// Like `embeddings`, but returns an awaitable.
+    fn embeddings_async<'py>(
+        &self,
+        py: Python<'py>,
+        inputs: Vec<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = self.client.clone();
+        future_into_py(py, async move { Ok(embeddings(client, inputs).await?) })
+    }
+}
+
+/// Iterator over the pieces of a streamed reply.
+#[pyclass(module = "copilot_client")]
+pub struct ChatIterator {
+    stream: Mutex<Option<ChatStream>>,
+}
+
+#[pymethods]
+impl ChatIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<String>> {
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(inner) = stream.as_mut() else {
+            return Ok(None);
+        };
+        let next = py.detach(|| get_runtime().block_on(next_content(inner)));
+        if !matches!(next, Ok(Some(_))) {
+            *stream = None;
+        }
+        Ok(next?)
+    }
+}
+
+/// Async iterator over the pieces of a streamed reply.
+#[pyclass(module = "copilot_client")]
+pub struct AsyncChatIterator {
+    state: Arc<tokio::sync::Mutex<StreamState>>,
+}
+
+struct StreamRequest {
+    client: CopilotClient,
+    messages: Vec<Message>,
+    model: String,
+    options: ChatOptions,
+}
+
+enum StreamState {
+    Pending(Box<StreamRequest>),
+    Streaming(ChatStream),
+    Done,
+}
+
+#[pymethods]
+impl AsyncChatIterator {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let state = self.state.clone();
+        future_into_py(py, async move {
+            let mut state = state.lock().await;
+            if let StreamState::Pending(_) = &*state
+                && let StreamState::Pending(request) =
+                    std::mem::replace(&mut *state, StreamState::Done)
+            {
+                let stream = request
+                    .client
+                    .chat_completion_stream(request.messages, request.model, &request.options)
+                    .await?;
+                *state = StreamState::Streaming(stream);
+            }
+            let StreamState::Streaming(stream) = &mut *state else {
+                return Err(PyStopAsyncIteration::new_err(()));
+            };
+            match next_content(stream).await {
+                Ok(Some(content)) => Ok(content),
+                Ok(None) => {
+                    *state = StreamState::Done;
+                    Err(PyStopAsyncIteration::new_err(()))
+                }
+                Err(e) => {
+                    *state = StreamState::Done;
+                    Err(e.into())
+                }
+            }
+        })
+    }
+}
+
+/// The `copilot_client` Python module.
+#[pymodule]
+fn copilot_client(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyCopilotClient>()?;
+    m.add_class::<PyMessage>()?;
+    m.add_class::<ChatIterator>()?;
+    m.add_class::<AsyncChatIterator>()?;
+    m.add("CopilotException", m.py().get_type::<CopilotException>())?;
+    Ok(())
+}
+
+fn options(temperature: Option<f64>, max_tokens: Option<u32>) -> ChatOptions {
+    let mut options = ChatOptions::default();
+    if let Some(temperature) = temperature {
+        options.temperature = temperature;
+    }
+    options.max_tokens = max_tokens;
+    options
+}
+
+fn into_messages(messages: Vec<PyMessage>) -> Vec<Message> {
+    messages.into_iter().map(|m| m.inner).collect()
+}
+
+async fn chat(
+    client: CopilotClient,
+    messages: Vec<PyMessage>,
+    model: String,
+    options: ChatOptions,
+) -> Result<String, CopilotError> {
+    let response = client
+        .chat_completion_with_options(into_messages(messages), model, &options)
+        .await?;
+    Ok(response
+        .choices
+        .into_iter()
+        .next()
+        .map(|c| c.message.content)
+        .unwrap_or_default())
+}
+
+async fn embeddings(
+    client: CopilotClient,
+    inputs: Vec<String>,
+) -> Result<Vec<Vec<f64>>, CopilotError> {
+    let mut embeddings = client.get_embeddings(inputs).await?;
+    embeddings.sort_by_key(|e| e.index);
+    Ok(embeddings.into_iter().map(|e| e.embedding).collect())
+}
+
+/// Returns the next non-empty piece of content, or `None` at the end of the stream.
+async fn next_content(stream: &mut ChatStream) -> Result<Option<String>, CopilotError> {
+    while let Some(chunk) = stream.next().await {
+        if let Some(content) = chunk?.content()
+            && !content.is_empty()
+        {
+            return Ok(Some(content.to_string()));
+        }
+    }
+    Ok(None)
+}
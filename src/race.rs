@@ -0,0 +1,67 @@
+//! Racing several models for the fastest answer.
+//!
+//! [`CopilotClient::chat_race`] sends the same request to several models at once and returns the
+//! first successful completion. The other requests are dropped as soon as one succeeds, which
+//! cancels them: the client spawns no tasks, so nothing keeps running in the background.
+
+use crate::{ChatOptions, ChatResponse, CopilotClient, CopilotError, Message};
+use futures_util::future::{FutureExt, select_ok};
+use std::borrow::Cow;
+use tracing::field::Empty;
+
+impl CopilotClient {
+    /// Sends the same chat completion request to every model in `model_ids` concurrently and
+    /// returns the id of the first model to answer successfully, with its response. The
+    /// requests to the other models are cancelled.
+    ///
+    /// This hides models that are intermittently slow, at the cost of one request (and, for
+    /// premium models, one premium request) per model.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last model to fail if none succeeds, or a `CopilotError` if
+    /// `model_ids` is empty.
+    pub async fn chat_race(
+        &self,
+        messages: impl Into<Cow<'_, [Message]>>,
+        model_ids: &[&str],
+    ) -> Result<(String, ChatResponse), CopilotError> {
+        self.chat_race_with_options(messages, model_ids, &ChatOptions::default())
+            .await
+    }
+
+    /// Like [`CopilotClient::chat_race`], with explicit options for every request.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last model to fail if none succeeds, or a `CopilotError` if
+    /// `model_ids` is empty.
+    #[tracing::instrument(
+        name = "copilot.chat_race",
+        skip_all,
+        fields(models = model_ids.len(), winner = Empty),
+        err(Display, level = "debug"),
+    )]
+    pub async fn chat_race_with_options(
+        &self,
+        messages: impl Into<Cow<'_, [Message]>>,
+        model_ids: &[&str],
+        options: &ChatOptions,
+    ) -> Result<(String, ChatResponse), CopilotError> {
+        if model_ids.is_empty() {
+            return Err(CopilotError::Other(
+                "chat_race needs at least one model".to_string(),
+            ));
+        }
+        let messages = messages.into();
+        let requests = model_ids.iter().map(|&model_id| {
+            Box::pin(
+                self.chat_completion_with_options(&*messages, model_id.to_string(), options)
+                    .map(move |result| result.map(|response| (model_id.to_string(), response))),
+            )
+        });
+        let ((model_id, response), _) = select_ok(requests).await?;
+        tracing::Span::current().record("winner", model_id.as_str());
+        Ok((model_id, response))
+    }
+}
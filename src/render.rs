@@ -0,0 +1,282 @@
+//! Streaming Markdown to ANSI rendering for terminal output.
+//!
+//! The renderer works line by line: text is buffered until a newline arrives, then the complete
+//! line is styled and returned. This keeps streamed output responsive while still allowing
+//! block-level constructs (headings, lists, fenced code) to be recognized. Fenced code is
+//! highlighted with [syntect](https://docs.rs/syntect) by the language of its info string.
+//!
+//! ```
+//! use copilot_client::render::{MarkdownRenderer, render_markdown};
+//!
+//! let mut renderer = MarkdownRenderer::new();
+//! let mut out = renderer.push("# Title\nSome **bold** te");
+//! out.push_str(&renderer.push("xt\n"));
+//! out.push_str(&renderer.finish());
+//! assert_eq!(out, render_markdown("# Title\nSome **bold** text\n"));
+//! ```
+//!
+//! The output always contains escape codes; callers decide whether the terminal supports them,
+//! for example by checking `NO_COLOR` and whether stdout is a terminal.
+
+use std::{fmt, sync::OnceLock};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::as_24_bit_terminal_escaped,
+};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_OFF: &str = "\x1b[22m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const ITALIC_OFF: &str = "\x1b[23m";
+const UNDERLINE: &str = "\x1b[4m";
+const UNDERLINE_OFF: &str = "\x1b[24m";
+const HEADING: &str = "\x1b[1;35m";
+const INLINE_CODE: &str = "\x1b[36m";
+const FG_DEFAULT: &str = "\x1b[39m";
+
+/// Name of the syntect theme used for code blocks.
+const THEME: &str = "base16-ocean.dark";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    &THEMES.get_or_init(ThemeSet::load_defaults).themes[THEME]
+}
+
+/// State of an open fenced code block.
+struct CodeBlock {
+    fence: String,
+    highlighter: Option<HighlightLines<'static>>,
+}
+
+/// Renders a complete Markdown text into ANSI-styled text.
+pub fn render_markdown(text: &str) -> String {
+    let mut renderer = MarkdownRenderer::new();
+    let mut out = renderer.push(text);
+    out.push_str(&renderer.finish());
+    out
+}
+
+/// Incrementally renders Markdown into ANSI-styled text.
+pub struct MarkdownRenderer {
+    pending: String,
+    code: Option<CodeBlock>,
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MarkdownRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MarkdownRenderer")
+            .field("pending", &self.pending)
+            .field("in_code_block", &self.code.is_some())
+            .finish()
+    }
+}
+
+impl MarkdownRenderer {
+    /// Creates a renderer outside of any block.
+    pub fn new() -> Self {
+        MarkdownRenderer {
+            pending: String::new(),
+            code: None,
+        }
+    }
+
+    /// Feeds streamed text and returns the rendering of every line it completed.
+    pub fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+        let mut out = String::new();
+        while let Some(pos) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=pos).collect();
+            out.push_str(&self.render_line(line.trim_end_matches(['\n', '\r'])));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Renders any buffered partial line and resets the renderer.
+    pub fn finish(&mut self) -> String {
+        let line = std::mem::take(&mut self.pending);
+        let mut out = if line.is_empty() {
+            String::new()
+        } else {
+            self.render_line(&line)
+        };
+        if self.code.take().is_some() {
+            out.push_str(RESET);
+        }
+        out
+    }
+
+    fn render_line(&mut self, line: &str) -> String {
+        let trimmed = line.trim_start();
+        if let Some(code) = &mut self.code {
+            if trimmed.starts_with(code.fence.as_str()) && trimmed.trim_end() == code.fence {
+                self.code = None;
+                return format!("{DIM}{line}{RESET}");
+            }
+            return highlight(code, line);
+        }
+        if let Some(fence) = fence_marker(trimmed) {
+            let lang = trimmed[fence.len()..].trim();
+            let syntaxes = syntax_set();
+            let syntax = (!lang.is_empty())
+                .then(|| syntaxes.find_syntax_by_token(lang))
+                .flatten();
+            self.code = Some(CodeBlock {
+                fence: fence.to_string(),
+                highlighter: syntax.map(|s| HighlightLines::new(s, theme())),
+            });
+            return format!("{DIM}{line}{RESET}");
+        }
+        render_block_line(line)
+    }
+}
+
+/// Returns the fence (three or more backticks or tildes) opening a code block, if any.
+fn fence_marker(line: &str) -> Option<&str> {
+    for ch in ['`', '~'] {
+        let len = line.chars().take_while(|&c| c == ch).count();
+        if len >= 3 {
+            return Some(&line[..len]);
+        }
+    }
+    None
+}
+
+fn highlight(code: &mut CodeBlock, line: &str) -> String {
+    let Some(highlighter) = &mut code.highlighter else {
+        return line.to_string();
+    };
+    // The "newlines" syntax set expects every line to end with '\n'.
+    let with_newline = format!("{line}\n");
+    match highlighter.highlight_line(&with_newline, syntax_set()) {
+        Ok(ranges) => {
+            let escaped = as_24_bit_terminal_escaped(&ranges, false);
+            format!("{}{RESET}", escaped.trim_end_matches('\n'))
+        }
+        Err(_) => line.to_string(),
+    }
+}
+
+/// Renders a line outside of code blocks.
+fn render_block_line(line: &str) -> String {
+    let indent_len = line.len() - line.trim_start().len();
+    let (indent, rest) = line.split_at(indent_len);
+
+    let hashes = rest.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && rest[hashes..].starts_with(' ') {
+        return format!(
+            "{indent}{HEADING}{}{RESET}",
+            render_inline(rest[hashes..].trim())
+        );
+    }
+    if is_rule(rest) {
+        return format!("{DIM}{}{RESET}", "─".repeat(40));
+    }
+    if let Some(quote) = rest.strip_prefix('>') {
+        return format!(
+            "{indent}{DIM}│{RESET} {ITALIC}{}{RESET}",
+            render_inline(quote.trim_start())
+        );
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = rest.strip_prefix(bullet) {
+            return format!("{indent}• {}", render_inline(item));
+        }
+    }
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && rest[digits..].starts_with(". ") {
+        return format!(
+            "{indent}{BOLD}{}{BOLD_OFF} {}",
+            &rest[..digits + 1],
+            render_inline(&rest[digits + 2..])
+        );
+    }
+    format!("{indent}{}", render_inline(rest))
+}
+
+/// Returns `true` for a thematic break such as `---`, `***`, or `___`.
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&ch| compact.chars().all(|c| c == ch))
+}
+
+/// Renders inline emphasis, code spans, and links.
+fn render_inline(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut bold = false;
+    let mut italic = false;
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        if ch == '`'
+            && let Some(end) = rest[1..].find('`')
+        {
+            out.push_str(INLINE_CODE);
+            out.push_str(&rest[1..=end]);
+            out.push_str(FG_DEFAULT);
+            rest = &rest[end + 2..];
+            continue;
+        }
+        if rest.starts_with("**") || rest.starts_with("__") {
+            bold = !bold;
+            out.push_str(if bold { BOLD } else { BOLD_OFF });
+            rest = &rest[2..];
+            continue;
+        }
+        if ch == '*' && (italic || !rest[1..].starts_with(' ')) {
+            italic = !italic;
+            out.push_str(if italic { ITALIC } else { ITALIC_OFF });
+            rest = &rest[1..];
+            continue;
+        }
+        if ch == '['
+            && let Some((label, url, len)) = parse_link(rest)
+        {
+            out.push_str(UNDERLINE);
+            out.push_str(label);
+            out.push_str(UNDERLINE_OFF);
+            out.push_str(&format!(" {DIM}({url}){RESET}"));
+            if bold {
+                out.push_str(BOLD);
+            }
+            if italic {
+                out.push_str(ITALIC);
+            }
+            rest = &rest[len..];
+            continue;
+        }
+        out.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    if bold || italic {
+        out.push_str(RESET);
+    }
+    out
+}
+
+/// Parses `[label](url)` at the start of `text`, returning the label, url, and consumed length.
+fn parse_link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let label = &text[1..close];
+    let url_start = close + 2;
+    let url_len = text[url_start..].find(')')?;
+    let url = &text[url_start..url_start + url_len];
+    Some((label, url, url_start + url_len + 1))
+}
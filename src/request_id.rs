@@ -0,0 +1,96 @@
+//! Correlation IDs for requests.
+//!
+//! Every request to the Copilot API carries an `X-Request-Id` header, generated by the client
+//! or set per chat completion with [`ChatOptions::request_id`]. The ID, and the ID GitHub
+//! assigns to the request in its `X-GitHub-Request-Id` response header, are recorded in the
+//! `copilot.http` tracing span, passed to [hooks](crate::hooks), and returned in
+//! [`ChatResponse::request_id`], [`ChatStream::request_id`], and
+//! [`CopilotError::ApiError`], so that an intermittent failure can be matched with the logs
+//! on both sides:
+//!
+//! ```
+//! use copilot_client::{ChatOptions, CopilotError, request_id::new_request_id};
+//!
+//! let options = ChatOptions {
+//!     request_id: Some(new_request_id()),
+//!     ..ChatOptions::default()
+//! };
+//! # let error = CopilotError::Other(String::new());
+//! if let Some(id) = error.server_request_id() {
+//!     eprintln!("Quote {id} when reporting this to GitHub");
+//! }
+//! ```
+//!
+//! [`ChatOptions::request_id`]: crate::ChatOptions::request_id
+//! [`ChatResponse::request_id`]: crate::ChatResponse::request_id
+//! [`ChatStream::request_id`]: crate::ChatStream::request_id
+//! [`CopilotError::ApiError`]: crate::CopilotError::ApiError
+
+use crate::unix_time;
+use http::header::{HeaderMap, HeaderValue};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The request header carrying the client's request ID.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The response header carrying the ID GitHub assigned to the request.
+pub const GITHUB_REQUEST_ID_HEADER: &str = "x-github-request-id";
+
+/// Returns a new random request ID in the format of a version 4 UUID.
+pub fn new_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    // `RandomState` is seeded randomly per process; the time and a counter keep IDs unique
+    // where it is not.
+    let state = RandomState::new();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let time = unix_time().as_nanos() as u64;
+    let [high, low] = [0u64, 1].map(|half| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(half);
+        hasher.write_u64(count);
+        hasher.write_u64(time);
+        hasher.finish()
+    });
+    let bits = (u128::from(high) << 64 | u128::from(low)) & !(0xf000 << 64) & !(0xc << 60);
+    let bits = bits | (0x4000 << 64) | (0x8 << 60);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Sets the `X-Request-Id` header to `id`, or to a new ID if `id` is `None`.
+pub(crate) fn set(headers: &mut HeaderMap, id: Option<&str>) {
+    let value = id
+        .and_then(|id| HeaderValue::from_str(id).ok())
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&new_request_id()).expect("request IDs are valid header values")
+        });
+    headers.insert(REQUEST_ID_HEADER, value);
+}
+
+/// Returns the client's request ID from request headers.
+pub(crate) fn client_id(headers: &HeaderMap) -> Option<String> {
+    header(headers, REQUEST_ID_HEADER)
+}
+
+/// Returns the ID GitHub assigned to a request from response headers.
+pub(crate) fn server_id(headers: &HeaderMap) -> Option<String> {
+    header(headers, GITHUB_REQUEST_ID_HEADER)
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
@@ -0,0 +1,189 @@
+//! Rule-based model selection.
+//!
+//! A [`ModelRouter`] picks the model for each chat completion from an ordered list of routes,
+//! so an application can send short prompts to a cheap model, long ones to a model with a large
+//! context window, and switch to included models once its premium-request budget runs low:
+//!
+//! ```
+//! use copilot_client::router::{Condition, ModelRouter};
+//!
+//! let router = ModelRouter::new("gpt-4o-mini")
+//!     .with_premium_budget(300.0)
+//!     .route([Condition::PremiumRemainingBelow(30.0)], "gpt-4o-mini")
+//!     .route([Condition::MinPromptTokens(32_000)], "gpt-4.1")
+//!     .route([Condition::Requires("tool_calls".into())], "gpt-4o");
+//! ```
+//!
+//! A route only applies if the client knows its model, the model supports the features the
+//! request needs, and the prompt fits into the model's prompt limit. Requests no route applies
+//! to go to the default model.
+
+use crate::{
+    ChatOptions, ChatResponse, CopilotClient, CopilotError, Message, Model,
+    prompt::estimate_message_tokens,
+};
+use std::borrow::Cow;
+
+/// What a chat completion needs from a model, as seen by a [`ModelRouter`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteRequest {
+    /// The estimated number of prompt tokens.
+    pub prompt_tokens: u32,
+    /// Features the model must support, named as in [`Model::supported_features`].
+    pub features: Vec<String>,
+}
+
+impl RouteRequest {
+    /// Describes a request for `messages` with `options`. Requests that offer tools require
+    /// the `"tool_calls"` feature.
+    pub fn new(messages: &[Message], options: &ChatOptions) -> Self {
+        let mut features = Vec::new();
+        if options
+            .tools
+            .as_ref()
+            .is_some_and(|tools| !tools.is_empty())
+        {
+            features.push("tool_calls".to_string());
+        }
+        RouteRequest {
+            prompt_tokens: messages.iter().map(estimate_message_tokens).sum(),
+            features,
+        }
+    }
+
+    /// Adds a feature the model must support, such as `"vision"`.
+    pub fn requiring(mut self, feature: impl Into<String>) -> Self {
+        let feature = feature.into();
+        if !self.features.contains(&feature) {
+            self.features.push(feature);
+        }
+        self
+    }
+}
+
+/// A condition of a route. A route applies when all of its conditions hold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// The estimated prompt has at least this many tokens.
+    MinPromptTokens(u32),
+    /// The estimated prompt has at most this many tokens.
+    MaxPromptTokens(u32),
+    /// The request requires this feature, such as `"tool_calls"` or `"vision"`.
+    Requires(String),
+    /// Fewer than this many premium-request units of the budget set with
+    /// [`ModelRouter::with_premium_budget`] remain, as counted by the client's usage tracker.
+    /// Never holds without a budget or a tracker.
+    PremiumRemainingBelow(f64),
+}
+
+/// Picks a model per request from an ordered list of routes.
+#[derive(Debug, Clone)]
+pub struct ModelRouter {
+    routes: Vec<(Vec<Condition>, String)>,
+    default_model: String,
+    premium_budget: Option<f64>,
+}
+
+impl ModelRouter {
+    /// Creates a router that sends every request to `default_model` until routes are added.
+    pub fn new(default_model: impl Into<String>) -> Self {
+        ModelRouter {
+            routes: Vec::new(),
+            default_model: default_model.into(),
+            premium_budget: None,
+        }
+    }
+
+    /// Sends requests for which all `conditions` hold to `model`. Routes are tried in the order
+    /// they were added.
+    pub fn route(
+        mut self,
+        conditions: impl IntoIterator<Item = Condition>,
+        model: impl Into<String>,
+    ) -> Self {
+        self.routes
+            .push((conditions.into_iter().collect(), model.into()));
+        self
+    }
+
+    /// Sets the number of premium-request units available, for
+    /// [`Condition::PremiumRemainingBelow`].
+    pub fn with_premium_budget(mut self, units: f64) -> Self {
+        self.premium_budget = Some(units);
+        self
+    }
+
+    /// Returns the model for `request` on `client`: that of the first route whose conditions
+    /// hold and whose model can serve the request, or the default model.
+    pub fn select(&self, client: &CopilotClient, request: &RouteRequest) -> &str {
+        let premium_remaining = self.premium_budget.and_then(|budget| {
+            let tracker = client.usage_tracker()?;
+            Some(budget - tracker.totals().premium_requests)
+        });
+        self.routes
+            .iter()
+            .find(|(conditions, model)| {
+                conditions
+                    .iter()
+                    .all(|condition| holds(condition, request, premium_remaining))
+                    && client
+                        .models()
+                        .iter()
+                        .find(|m| m.id == *model)
+                        .is_some_and(|m| can_serve(m, request))
+            })
+            .map_or(&self.default_model, |(_, model)| model)
+    }
+}
+
+fn holds(condition: &Condition, request: &RouteRequest, premium_remaining: Option<f64>) -> bool {
+    match condition {
+        Condition::MinPromptTokens(tokens) => request.prompt_tokens >= *tokens,
+        Condition::MaxPromptTokens(tokens) => request.prompt_tokens <= *tokens,
+        Condition::Requires(feature) => request.features.contains(feature),
+        Condition::PremiumRemainingBelow(units) => {
+            premium_remaining.is_some_and(|remaining| remaining < *units)
+        }
+    }
+}
+
+/// Returns `true` if `model` fits the prompt and supports the required features. Models that do
+/// not report their features are assumed to support them.
+fn can_serve(model: &Model, request: &RouteRequest) -> bool {
+    let fits = model
+        .prompt_token_limit()
+        .is_none_or(|limit| request.prompt_tokens <= limit);
+    let reports_features = model
+        .capabilities
+        .as_ref()
+        .is_some_and(|c| c.supports.is_some());
+    let supported = model.supported_features();
+    fits && (!reports_features
+        || request
+            .features
+            .iter()
+            .all(|feature| supported.contains(&feature.as_str())))
+}
+
+impl CopilotClient {
+    /// Sends a chat completion to the model `router` selects for it. [`ChatResponse::model`]
+    /// reports the model that answered.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the selected model is not available or the request fails.
+    pub async fn chat_completion_routed(
+        &self,
+        router: &ModelRouter,
+        messages: impl Into<Cow<'_, [Message]>>,
+        options: &ChatOptions,
+    ) -> Result<ChatResponse, CopilotError> {
+        let messages = messages.into();
+        let model = router
+            .select(self, &RouteRequest::new(&messages, options))
+            .to_string();
+        tracing::debug!(%model, "Routed chat completion");
+        self.chat_completion_with_options(messages, model, options)
+            .await
+    }
+}
@@ -0,0 +1,173 @@
+//! Validation of JSON replies and tool-call arguments, with automatic repair.
+//!
+//! Models do not always produce JSON that matches the schema they were given. With the `schema`
+//! feature, [`CopilotClient::chat_completion_validated`] checks the reply against an output
+//! schema, or each tool call against the `parameters` of its tool, and on failure sends the
+//! conversation back with the validation errors, asking the model to correct itself, up to a
+//! number of repairs.
+//!
+//! ```no_run
+//! use copilot_client::{ChatOptions, Message};
+//! use serde_json::json;
+//!
+//! # async fn run(client: copilot_client::CopilotClient) -> Result<(), copilot_client::CopilotError> {
+//! let schema = json!({
+//!     "type": "object",
+//!     "properties": { "title": { "type": "string" }, "tags": { "type": "array" } },
+//!     "required": ["title", "tags"],
+//! });
+//! let messages = vec![Message::user(format!(
+//!     "Summarize the release notes as JSON matching this schema: {schema}"
+//! ))];
+//! let response = client
+//!     .chat_completion_validated(messages, "gpt-4o".into(), &ChatOptions::default(), Some(&schema), 2)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    ChatOptions, ChatResponse, CopilotClient, CopilotError, Message, Tool, ToolCall, postprocess,
+};
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Validates `instance` against the JSON Schema `schema` and returns the errors, each as the
+/// JSON pointer of the invalid value and a message. An empty list means `instance` is valid.
+///
+/// # Errors
+///
+/// Returns a `CopilotError` if `schema` is not a valid JSON Schema.
+pub fn validation_errors(schema: &Value, instance: &Value) -> Result<Vec<String>, CopilotError> {
+    let validator = jsonschema::validator_for(schema)
+        .map_err(|e| CopilotError::Other(format!("Invalid JSON schema: {e}")))?;
+    Ok(validator
+        .iter_errors(instance)
+        .map(|error| match error.instance_path().as_str() {
+            "" => error.to_string(),
+            path => format!("{path}: {error}"),
+        })
+        .collect())
+}
+
+/// Parses the JSON in a reply, ignoring a surrounding Markdown code fence.
+pub fn parse_json_reply(content: &str) -> Result<Value, serde_json::Error> {
+    serde_json::from_str(postprocess::strip_code_fence(content))
+}
+
+impl CopilotClient {
+    /// Sends a chat completion and checks the first choice: its tool calls against the
+    /// `parameters` of the tools in `options`, or, if it calls no tools, its content against
+    /// `output_schema`. While the check fails, the reply and a message listing the errors are
+    /// appended to the conversation and the request is sent again, up to `max_repairs` times.
+    ///
+    /// Replies may wrap their JSON in a code fence. Tools without `parameters` accept any JSON
+    /// arguments, and the content is not checked if `output_schema` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a request fails, a schema is invalid, or the reply still
+    /// does not validate after `max_repairs` repairs.
+    #[tracing::instrument(
+        name = "copilot.chat_completion_validated",
+        skip_all,
+        fields(model = %model_id, max_repairs),
+        err(Display, level = "debug"),
+    )]
+    pub async fn chat_completion_validated(
+        &self,
+        messages: impl Into<Cow<'_, [Message]>>,
+        model_id: String,
+        options: &ChatOptions,
+        output_schema: Option<&Value>,
+        max_repairs: u32,
+    ) -> Result<ChatResponse, CopilotError> {
+        let mut messages = messages.into();
+        let mut repairs = 0;
+        loop {
+            let response = self
+                .chat_completion_with_options(&*messages, model_id.clone(), options)
+                .await?;
+            let Some(message) = response.choices.first().map(|choice| &choice.message) else {
+                return Ok(response);
+            };
+            let calls = message
+                .tool_calls
+                .as_deref()
+                .filter(|calls| !calls.is_empty());
+            let errors = match (calls, output_schema) {
+                (Some(calls), _) => {
+                    tool_call_errors(calls, options.tools.as_deref().unwrap_or_default())?
+                }
+                (None, Some(schema)) => match parse_json_reply(&message.content) {
+                    Ok(value) => validation_errors(schema, &value)?,
+                    Err(e) => vec![format!("not valid JSON: {e}")],
+                },
+                (None, None) => Vec::new(),
+            };
+            if errors.is_empty() {
+                return Ok(response);
+            }
+            if repairs == max_repairs {
+                return Err(CopilotError::Other(format!(
+                    "Reply does not match its schema after {repairs} repairs: {}",
+                    errors.join("; ")
+                )));
+            }
+            repairs += 1;
+            tracing::debug!(repairs, errors = errors.len(), "Reply failed validation");
+            let messages = messages.to_mut();
+            match calls {
+                Some(calls) => {
+                    let calls: Vec<String> = calls
+                        .iter()
+                        .map(|call| {
+                            format!("`{}` with {}", call.function.name, call.function.arguments)
+                        })
+                        .collect();
+                    messages.push(Message::user(format!(
+                        "None of your tool calls were made:\n{}\n\nThe arguments have these \
+                         errors:\n{}\n\nCall the tools again with corrected arguments.",
+                        bullets(&calls),
+                        bullets(&errors)
+                    )));
+                }
+                None => {
+                    messages.push(Message::assistant(message.content.clone()));
+                    messages.push(Message::user(format!(
+                        "Your reply does not match the required JSON schema:\n{}\n\nReply again \
+                         with only the corrected JSON.",
+                        bullets(&errors)
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Checks tool call arguments against the `parameters` of their tools.
+fn tool_call_errors(calls: &[ToolCall], tools: &[Tool]) -> Result<Vec<String>, CopilotError> {
+    let mut errors = Vec::new();
+    for call in calls {
+        let name = &call.function.name;
+        let Some(tool) = tools.iter().find(|tool| tool.function.name == *name) else {
+            errors.push(format!("`{name}`: no such tool"));
+            continue;
+        };
+        let call_errors = match serde_json::from_str::<Value>(&call.function.arguments) {
+            Ok(_) if tool.function.parameters.is_null() => Vec::new(),
+            Ok(arguments) => validation_errors(&tool.function.parameters, &arguments)?,
+            Err(e) => vec![format!("not valid JSON: {e}")],
+        };
+        errors.extend(call_errors.into_iter().map(|e| format!("`{name}`: {e}")));
+    }
+    Ok(errors)
+}
+
+fn bullets(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|line| format!("- {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
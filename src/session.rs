@@ -0,0 +1,320 @@
+//! On-disk storage of saved conversations.
+//!
+//! A [`SessionStore`] keeps one JSON file per conversation (in the format of
+//! [`ConversationRecord`]) plus an `index.json` file with the id, title, model, and timestamps of
+//! every session, so sessions can be listed without parsing every transcript.
+
+use crate::{
+    CopilotClient, CopilotError,
+    conversation::{Conversation, ConversationRecord},
+    get_config_path, unix_time,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Name of the index file inside the store directory.
+const INDEX_FILE: &str = "index.json";
+
+/// Version of the index file format.
+const INDEX_VERSION: u32 = 1;
+
+/// Summary of a saved session, as stored in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// The conversation id.
+    pub id: String,
+    /// The conversation title, if any.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The model used by the conversation.
+    pub model: String,
+    /// Number of messages in the history.
+    pub message_count: usize,
+    /// The id of the conversation this one was forked from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// When the session was first saved, as a Unix timestamp.
+    pub created_at: u64,
+    /// When the session was last saved, as a Unix timestamp.
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    version: u32,
+    sessions: Vec<SessionInfo>,
+}
+
+/// A directory of saved conversations.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// Creates a store backed by `dir`. The directory is created on first save.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SessionStore { dir: dir.into() }
+    }
+
+    /// Creates a store in the default location, `<config dir>/copilot-client/sessions`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the configuration directory cannot be determined.
+    pub fn default_location() -> Result<Self, CopilotError> {
+        let config_dir = get_config_path().map_err(|e| CopilotError::Other(e.to_string()))?;
+        Ok(Self::new(
+            Path::new(&config_dir)
+                .join("copilot-client")
+                .join("sessions"),
+        ))
+    }
+
+    /// Returns the directory backing the store.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Lists saved sessions, most recently updated first.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the index cannot be read.
+    pub fn list(&self) -> Result<Vec<SessionInfo>, CopilotError> {
+        let mut sessions = self.read_index()?.sessions;
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(sessions)
+    }
+
+    /// Returns the index entry for `id`, if the session exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the index cannot be read.
+    pub fn get(&self, id: &str) -> Result<Option<SessionInfo>, CopilotError> {
+        Ok(self.read_index()?.sessions.into_iter().find(|s| s.id == id))
+    }
+
+    /// Resolves a full session id from an unambiguous prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if no session or more than one session matches.
+    pub fn resolve_id(&self, prefix: &str) -> Result<String, CopilotError> {
+        let sessions = self.read_index()?.sessions;
+        if let Some(exact) = sessions.iter().find(|s| s.id == prefix) {
+            return Ok(exact.id.clone());
+        }
+        let mut matches = sessions.iter().filter(|s| s.id.starts_with(prefix));
+        match (matches.next(), matches.next()) {
+            (Some(session), None) => Ok(session.id.clone()),
+            (None, _) => Err(CopilotError::Other(format!(
+                "No session matches '{prefix}'"
+            ))),
+            (Some(_), Some(_)) => Err(CopilotError::Other(format!(
+                "Session id prefix '{prefix}' is ambiguous"
+            ))),
+        }
+    }
+
+    /// Saves `conversation`, replacing any previous version with the same id.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the session file or index cannot be written.
+    pub fn save(&self, conversation: &Conversation) -> Result<SessionInfo, CopilotError> {
+        let record = conversation.to_record();
+        let path = self.session_path(&record.id)?;
+        fs::create_dir_all(&self.dir).map_err(io_error)?;
+        let json = serde_json::to_string_pretty(&record).map_err(json_error)?;
+        write_atomic(&path, &json)?;
+
+        let now = unix_now();
+        let mut index = self.read_index()?;
+        let created_at = index
+            .sessions
+            .iter()
+            .find(|s| s.id == record.id)
+            .map(|s| s.created_at)
+            .unwrap_or(now);
+        index.sessions.retain(|s| s.id != record.id);
+        let info = SessionInfo {
+            id: record.id,
+            title: record.title,
+            model: record.model,
+            message_count: record.messages.len(),
+            parent_id: record.parent.map(|p| p.conversation_id),
+            created_at,
+            updated_at: now,
+        };
+        index.sessions.push(info.clone());
+        self.write_index(&index)?;
+        Ok(info)
+    }
+
+    /// Loads the session `id` as a conversation using `client`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the session does not exist or cannot be parsed.
+    pub fn load(&self, id: &str, client: CopilotClient) -> Result<Conversation, CopilotError> {
+        Conversation::from_record(client, self.load_record(id)?)
+    }
+
+    /// Loads the saved record of session `id` without attaching a client, e.g. for export.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the session does not exist or cannot be parsed.
+    pub fn load_record(&self, id: &str) -> Result<ConversationRecord, CopilotError> {
+        let path = self.session_path(id)?;
+        let content = fs::read_to_string(&path).map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                CopilotError::Other(format!("Session '{id}' not found"))
+            } else {
+                io_error(e)
+            }
+        })?;
+        serde_json::from_str(&content).map_err(json_error)
+    }
+
+    /// Loads the most recently updated session, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the index or the session cannot be read.
+    pub fn resume_latest(
+        &self,
+        client: CopilotClient,
+    ) -> Result<Option<Conversation>, CopilotError> {
+        match self.list()?.first() {
+            Some(info) => self.load(&info.id, client).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the session `id`. Returns `false` if it did not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the session file or index cannot be updated.
+    pub fn delete(&self, id: &str) -> Result<bool, CopilotError> {
+        let path = self.session_path(id)?;
+        let existed = match fs::remove_file(&path) {
+            Ok(()) => true,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => false,
+            Err(e) => return Err(io_error(e)),
+        };
+        let mut index = self.read_index()?;
+        let before = index.sessions.len();
+        index.sessions.retain(|s| s.id != id);
+        if index.sessions.len() != before {
+            self.write_index(&index)?;
+        }
+        Ok(existed || index.sessions.len() != before)
+    }
+
+    /// Rebuilds the index by scanning the session files in the store directory.
+    ///
+    /// Useful when the index was lost or session files were copied in by hand. Timestamps are
+    /// taken from the file modification times.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the directory cannot be read or the index cannot be written.
+    pub fn rebuild_index(&self) -> Result<Vec<SessionInfo>, CopilotError> {
+        let mut index = Index {
+            version: INDEX_VERSION,
+            sessions: Vec::new(),
+        };
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(e)),
+        };
+        for entry in entries {
+            let path = entry.map_err(io_error)?.path();
+            if path.extension().is_none_or(|ext| ext != "json")
+                || path.file_name().is_some_and(|name| name == INDEX_FILE)
+            {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(record) = serde_json::from_str::<ConversationRecord>(&content) else {
+                continue;
+            };
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or_else(unix_now);
+            index.sessions.push(SessionInfo {
+                id: record.id,
+                title: record.title,
+                model: record.model,
+                message_count: record.messages.len(),
+                parent_id: record.parent.map(|p| p.conversation_id),
+                created_at: modified,
+                updated_at: modified,
+            });
+        }
+        self.write_index(&index)?;
+        Ok(index.sessions)
+    }
+
+    fn session_path(&self, id: &str) -> Result<PathBuf, CopilotError> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(CopilotError::Other(format!("Invalid session id '{id}'")));
+        }
+        Ok(self.dir.join(format!("{id}.json")))
+    }
+
+    fn read_index(&self) -> Result<Index, CopilotError> {
+        match fs::read_to_string(self.dir.join(INDEX_FILE)) {
+            Ok(content) => serde_json::from_str(&content).map_err(json_error),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Index {
+                version: INDEX_VERSION,
+                sessions: Vec::new(),
+            }),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    fn write_index(&self, index: &Index) -> Result<(), CopilotError> {
+        fs::create_dir_all(&self.dir).map_err(io_error)?;
+        let json = serde_json::to_string_pretty(index).map_err(json_error)?;
+        write_atomic(&self.dir.join(INDEX_FILE), &json)
+    }
+}
+
+/// Writes `content` to a temporary file next to `path` and renames it into place, so readers
+/// never observe a partially written file.
+fn write_atomic(path: &Path, content: &str) -> Result<(), CopilotError> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content).map_err(io_error)?;
+    fs::rename(&tmp, path).map_err(io_error)
+}
+
+fn unix_now() -> u64 {
+    unix_time().as_secs()
+}
+
+fn io_error(e: io::Error) -> CopilotError {
+    CopilotError::Other(e.to_string())
+}
+
+fn json_error(e: serde_json::Error) -> CopilotError {
+    CopilotError::Other(e.to_string())
+}
@@ -0,0 +1,126 @@
+//! Incremental decoding of server-sent events.
+//!
+//! The chat completions endpoint streams responses as `text/event-stream`. [`SseDecoder`] is fed
+//! raw body chunks as they arrive and yields the `data` payload of every complete event. Chunks
+//! may split events (and even UTF-8 sequences) at arbitrary byte positions.
+//!
+//! [`SseDecoder::push_bytes`] works on [`Bytes`] without copying: an event that lies within one
+//! chunk, which is the usual case, is returned as a slice of that chunk. Only the tail of a chunk
+//! that ends mid-event is buffered, and only events with several `data` lines are joined into
+//! new buffers.
+
+use bytes::{Bytes, BytesMut};
+
+/// Decodes a stream of server-sent events from arbitrary byte chunks.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    /// The start of an event whose end has not arrived yet.
+    buffer: BytesMut,
+    /// The number of bytes at the start of `buffer` known not to contain the end of an event.
+    searched: usize,
+}
+
+impl SseDecoder {
+    /// Creates an empty decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a chunk of the response body and returns the data of every event completed by it.
+    ///
+    /// Events without a `data` field (such as keep-alive comments) are skipped. Multiple `data`
+    /// lines in one event are joined with newlines, as specified by the SSE format. Invalid UTF-8
+    /// is replaced; use [`SseDecoder::push_bytes`] to get the data without conversion.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        self.push_bytes(Bytes::copy_from_slice(chunk), &mut events);
+        events.iter().map(|data| to_string(data)).collect()
+    }
+
+    /// Feeds a chunk of the response body and adds the data of every event completed by it to
+    /// `events`, as slices of the chunk where possible.
+    pub fn push_bytes(&mut self, mut chunk: Bytes, events: &mut impl Extend<Bytes>) {
+        if !self.buffer.is_empty() {
+            // Complete the buffered event first. Events that span chunks are the only ones copied.
+            self.buffer.extend_from_slice(&chunk);
+            let Some((end, separator_len)) = find_event_end(&self.buffer, self.searched) else {
+                // A separator may begin in the last three bytes and end in the next chunk.
+                self.searched = self.buffer.len().saturating_sub(3);
+                return;
+            };
+            let raw = self.buffer.split_to(end + separator_len).freeze();
+            events.extend(parse_event(raw.slice(..end)));
+            chunk = self.buffer.split().freeze();
+            self.searched = 0;
+        }
+        while let Some((end, separator_len)) = find_event_end(&chunk, 0) {
+            let raw = chunk.split_to(end + separator_len);
+            events.extend(parse_event(raw.slice(..end)));
+        }
+        if !chunk.is_empty() {
+            self.buffer.extend_from_slice(&chunk);
+            self.searched = chunk.len().saturating_sub(3);
+        }
+    }
+
+    /// Returns the data of a trailing event that was not terminated by a blank line, if any.
+    ///
+    /// Call this once the body has ended; some servers omit the final separator.
+    pub fn finish(&mut self) -> Option<String> {
+        self.finish_bytes().map(|data| to_string(&data))
+    }
+
+    /// Like [`SseDecoder::finish`], but returns the data without conversion.
+    pub fn finish_bytes(&mut self) -> Option<Bytes> {
+        self.searched = 0;
+        parse_event(self.buffer.split().freeze())
+    }
+}
+
+/// Finds the end of the first complete event, starting the search at `from`, and returns its
+/// length and the separator length.
+fn find_event_end(buffer: &[u8], from: usize) -> Option<(usize, usize)> {
+    let mut i = from;
+    while let Some(offset) = buffer[i..].iter().position(|&b| b == b'\n' || b == b'\r') {
+        i += offset;
+        match buffer[i..] {
+            [b'\r', b'\n', b'\r', b'\n', ..] => return Some((i, 4)),
+            [b'\n', b'\n', ..] | [b'\r', b'\r', ..] => return Some((i, 2)),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Extracts the `data` field from a single raw event.
+fn parse_event(raw: Bytes) -> Option<Bytes> {
+    let mut data: Option<Bytes> = None;
+    let mut joined: Option<BytesMut> = None;
+    for line in raw.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let Some(value) = line.strip_prefix(b"data:") else {
+            // `event:`, `id:`, `retry:` and `:` comments carry nothing we need.
+            continue;
+        };
+        let value = value.strip_prefix(b" ").unwrap_or(value);
+        match (&data, &mut joined) {
+            (None, _) => data = Some(raw.slice_ref(value)),
+            (Some(first), None) => {
+                let mut buffer = BytesMut::with_capacity(first.len() + 1 + value.len());
+                buffer.extend_from_slice(first);
+                buffer.extend_from_slice(b"\n");
+                buffer.extend_from_slice(value);
+                joined = Some(buffer);
+            }
+            (Some(_), Some(buffer)) => {
+                buffer.extend_from_slice(b"\n");
+                buffer.extend_from_slice(value);
+            }
+        }
+    }
+    joined.map(BytesMut::freeze).or(data)
+}
+
+fn to_string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
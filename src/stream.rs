@@ -0,0 +1,351 @@
+//! Streaming chat completions.
+//!
+//! [`CopilotClient::chat_completion_stream`] returns a [`ChatStream`] that yields
+//! [`ChatStreamChunk`]s as the model generates them, decoded from the server-sent events of the
+//! chat completions endpoint.
+//!
+//! [`CopilotClient::chat_completion_stream`]: crate::CopilotClient::chat_completion_stream
+
+use crate::{
+    ChoiceLogprobs, CopilotError, TokenUsage,
+    budget::Budget,
+    record_usage,
+    sse::SseDecoder,
+    transport::{BoxStream, HttpResponse},
+    unix_time,
+    usage::UsageTracker,
+};
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tracing::Span;
+
+/// A chunk of a streamed chat completion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatStreamChunk {
+    /// Incremental updates for each choice.
+    #[serde(default)]
+    pub choices: Vec<ChatStreamChoice>,
+    /// Token usage for the whole request, usually only present on the final chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Identifies the backend configuration that generated the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
+}
+
+impl ChatStreamChunk {
+    /// Returns the content delta of the first choice, if any.
+    pub fn content(&self) -> Option<&str> {
+        self.choices
+            .first()
+            .and_then(|choice| choice.delta.content.as_deref())
+    }
+}
+
+/// An incremental update to a single choice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatStreamChoice {
+    /// The index of the choice this update belongs to.
+    #[serde(default)]
+    pub index: u32,
+    /// The new content of the message.
+    #[serde(default)]
+    pub delta: MessageDelta,
+    /// The reason why the generation finished, on the last update of the choice.
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    /// The log probabilities of the tokens in this update, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<ChoiceLogprobs>,
+}
+
+/// The part of a message generated since the previous chunk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageDelta {
+    /// The role, usually only sent on the first chunk.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Newly generated content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Fragments of tool calls being generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// A fragment of a streamed tool call. Fragments with the same `index` belong to the same call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+    /// Position of the tool call in the message.
+    pub index: u32,
+    /// The tool call id, sent with the first fragment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Function name and argument fragments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<FunctionCallDelta>,
+}
+
+/// Function name and argument fragments of a [`ToolCallDelta`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FunctionCallDelta {
+    /// The function name, sent with the first fragment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The next piece of the JSON-encoded arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
+}
+
+/// A stream of [`ChatStreamChunk`]s from a streaming chat completion.
+///
+/// The stream ends after the server's `[DONE]` marker. If a usage tracker is attached to the
+/// client, the request is recorded when the stream completes.
+pub struct ChatStream {
+    inner: BoxStream<Result<ChatStreamChunk, CopilotError>>,
+    model: Option<String>,
+    request_id: Option<String>,
+    server_request_id: Option<String>,
+}
+
+impl fmt::Debug for ChatStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChatStream")
+            .field("model", &self.model)
+            .field("request_id", &self.request_id)
+            .field("server_request_id", &self.server_request_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for ChatStream {
+    type Item = Result<ChatStreamChunk, CopilotError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl ChatStream {
+    /// Creates a stream that decodes the body of a streaming chat completion response.
+    pub(crate) fn from_response(
+        response: HttpResponse,
+        model: String,
+        request_id: Option<String>,
+        accounting: Accounting,
+        span: Span,
+    ) -> Self {
+        let server_request_id = crate::request_id::server_id(&response.headers);
+        let state = StreamState {
+            body: response.into_body(),
+            decoder: SseDecoder::new(),
+            pending: VecDeque::new(),
+            body_done: false,
+            finished: false,
+            model: model.clone(),
+            usage: None,
+            accounting,
+            span,
+        };
+        let inner = stream::unfold(state, |mut state| async move {
+            state.next_chunk().await.map(|item| (item, state))
+        });
+        ChatStream {
+            inner: Box::pin(inner),
+            model: Some(model),
+            request_id,
+            server_request_id,
+        }
+    }
+
+    /// Creates a stream that yields `chunks`, for example to stand in for a response in tests.
+    pub fn from_chunks(chunks: Vec<Result<ChatStreamChunk, CopilotError>>) -> Self {
+        ChatStream {
+            inner: Box::pin(stream::iter(chunks)),
+            model: None,
+            request_id: None,
+            server_request_id: None,
+        }
+    }
+
+    /// Returns the id of the model the request was sent to, which differs from the requested
+    /// model when a [`FallbackPolicy`](crate::fallback::FallbackPolicy) sent it to a fallback
+    /// model. `None` for streams created with [`ChatStream::from_chunks`].
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Returns the client's ID for the request, sent in its `X-Request-Id` header. `None` for
+    /// streams created with [`ChatStream::from_chunks`].
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Returns the ID GitHub assigned to the request, from the `X-GitHub-Request-Id` header.
+    pub fn server_request_id(&self) -> Option<&str> {
+        self.server_request_id.as_deref()
+    }
+
+    /// Replaces the chunks with those of `f` applied to them, keeping the model and IDs.
+    pub(crate) fn map_inner(
+        self,
+        f: impl FnOnce(
+            BoxStream<Result<ChatStreamChunk, CopilotError>>,
+        ) -> BoxStream<Result<ChatStreamChunk, CopilotError>>,
+    ) -> Self {
+        ChatStream {
+            inner: f(self.inner),
+            model: self.model,
+            request_id: self.request_id,
+            server_request_id: self.server_request_id,
+        }
+    }
+
+    /// Reports the chunks to `operation` and ends it when the stream ends or fails.
+    #[cfg(feature = "otel")]
+    pub(crate) fn with_operation(self, operation: crate::otel::Operation) -> Self {
+        let inner = stream::unfold(
+            (self.inner, Some(operation)),
+            |(mut inner, mut operation)| async move {
+                let item = inner.next().await;
+                if let Some(mut op) = operation.take() {
+                    match &item {
+                        Some(Ok(chunk)) => {
+                            op.record_usage(chunk.usage.as_ref());
+                            for choice in &chunk.choices {
+                                op.record_finish_reason(choice.finish_reason.as_deref());
+                            }
+                            operation = Some(op);
+                        }
+                        Some(Err(e)) => op.end(Some(e)),
+                        None => op.end(None),
+                    }
+                }
+                item.map(|item| (item, (inner, operation)))
+            },
+        );
+        ChatStream {
+            inner: Box::pin(inner),
+            model: self.model,
+            request_id: self.request_id,
+            server_request_id: self.server_request_id,
+        }
+    }
+
+    /// Consumes the stream and concatenates the content of the first choice.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered while streaming.
+    pub async fn collect_content(mut self) -> Result<String, CopilotError> {
+        let mut content = String::new();
+        while let Some(chunk) = self.next().await {
+            if let Some(delta) = chunk?.content() {
+                content.push_str(delta);
+            }
+        }
+        Ok(content)
+    }
+}
+
+/// Where a stream reports the usage of its request when it ends.
+pub(crate) struct Accounting {
+    pub(crate) tracker: Option<Arc<UsageTracker>>,
+    /// The budget that the tokens of the response count against.
+    pub(crate) budget: Option<Arc<Budget>>,
+    /// The tags of the request, for the usage tracker.
+    pub(crate) tags: BTreeMap<String, String>,
+    /// The time the request was sent, for its latency.
+    pub(crate) start: Duration,
+}
+
+struct StreamState {
+    body: BoxStream<Result<Bytes, CopilotError>>,
+    decoder: SseDecoder,
+    pending: VecDeque<Bytes>,
+    body_done: bool,
+    finished: bool,
+    model: String,
+    usage: Option<TokenUsage>,
+    accounting: Accounting,
+    /// The span of the request, kept open until the stream ends so the usage can be recorded.
+    span: Span,
+}
+
+impl StreamState {
+    async fn next_chunk(&mut self) -> Option<Result<ChatStreamChunk, CopilotError>> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            if let Some(data) = self.pending.pop_front() {
+                if data.trim_ascii() == b"[DONE]" {
+                    self.finish();
+                    return None;
+                }
+                let chunk = parse_chunk(&data);
+                if let Ok(chunk) = &chunk
+                    && chunk.usage.is_some()
+                {
+                    self.usage.clone_from(&chunk.usage);
+                }
+                return Some(chunk);
+            }
+            if self.body_done {
+                self.finish();
+                return None;
+            }
+            match self.body.next().await {
+                Some(Ok(bytes)) => self.decoder.push_bytes(bytes, &mut self.pending),
+                Some(Err(e)) => {
+                    // The connection is unusable after a transport error.
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.body_done = true;
+                    self.pending.extend(self.decoder.finish_bytes());
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+        record_usage(&self.span, &self.model, self.usage.as_ref());
+        self.span
+            .in_scope(|| tracing::debug!(model = %self.model, "Chat stream finished"));
+        let accounting = &self.accounting;
+        if let Some(tracker) = &accounting.tracker {
+            tracker.record_chat_timed(
+                &self.model,
+                self.usage.as_ref(),
+                &accounting.tags,
+                Some(unix_time().saturating_sub(accounting.start)),
+            );
+        }
+        if let Some(budget) = &accounting.budget {
+            budget.record_tokens(self.usage.as_ref());
+        }
+    }
+}
+
+/// Parses the data of one event. The data is parsed in place; invalid UTF-8, which JSON
+/// rejects, is replaced before a second attempt.
+fn parse_chunk(data: &[u8]) -> Result<ChatStreamChunk, CopilotError> {
+    serde_json::from_slice(data)
+        .or_else(|e| match std::str::from_utf8(data) {
+            Ok(_) => Err(e),
+            Err(_) => serde_json::from_str(&String::from_utf8_lossy(data)),
+        })
+        .map_err(|e| CopilotError::Other(format!("Invalid stream chunk: {e}")))
+}
@@ -0,0 +1,203 @@
+//! Prompt templates.
+//!
+//! [`PromptTemplates`] holds named [MiniJinja](https://docs.rs/minijinja) templates, so prompt
+//! text can live in files next to the configuration and be edited without recompiling.
+//! Templates take named variables and can include each other as partials:
+//!
+//! ```
+//! use copilot_client::templates::PromptTemplates;
+//! use serde_json::json;
+//!
+//! let templates = PromptTemplates::new()
+//!     .with_template("partials/tone", "Answer briefly.")?
+//!     .with_template("explain", "Explain this {{ language }} code. {% include \"partials/tone\" %}")?;
+//! let prompt = templates.render("explain", json!({ "language": "Rust" }))?;
+//! assert_eq!(prompt, "Explain this Rust code. Answer briefly.");
+//! # Ok::<(), copilot_client::CopilotError>(())
+//! ```
+//!
+//! Templates loaded with [`PromptTemplates::from_dir`] are named by their path relative to the
+//! directory without the extension, so `prompts/partials/tone.j2` becomes `partials/tone`.
+//! Rendering fails if a template uses a variable that was not given.
+
+use crate::{CopilotError, Message, get_config_path};
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A set of named prompt templates.
+#[derive(Debug, Clone)]
+pub struct PromptTemplates {
+    env: Environment<'static>,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PromptTemplates {
+    /// Creates an empty set of templates.
+    pub fn new() -> Self {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        PromptTemplates { env }
+    }
+
+    /// Returns the default template directory, `<config dir>/copilot-client/prompts`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the configuration directory cannot be determined.
+    pub fn default_dir() -> Result<PathBuf, CopilotError> {
+        let config_dir = get_config_path().map_err(|e| CopilotError::Other(e.to_string()))?;
+        Ok(Path::new(&config_dir)
+            .join("copilot-client")
+            .join("prompts"))
+    }
+
+    /// Loads the templates in the default directory. A missing directory yields no templates.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the directory cannot be determined or read, or a template
+    /// does not parse.
+    pub fn load() -> Result<Self, CopilotError> {
+        let dir = Self::default_dir()?;
+        if !dir.exists() {
+            return Ok(Self::new());
+        }
+        Self::from_dir(dir)
+    }
+
+    /// Loads every file in `dir` and its subdirectories as a template, skipping hidden files.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the directory cannot be read or a template does not parse.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        Self::new().with_dir(dir)
+    }
+
+    /// Adds every file in `dir` and its subdirectories as a template, like
+    /// [`PromptTemplates::from_dir`], replacing templates of the same name. Use it to override
+    /// the [built-in templates](PromptTemplates::builtin).
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the directory cannot be read or a template does not parse.
+    pub fn with_dir(mut self, dir: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        self.add_dir(dir.as_ref(), dir.as_ref())?;
+        Ok(self)
+    }
+
+    fn add_dir(&mut self, root: &Path, dir: &Path) -> Result<(), CopilotError> {
+        let read_error = |e: std::io::Error| {
+            CopilotError::Other(format!("Failed to read {}: {e}", dir.display()))
+        };
+        let mut entries = fs::read_dir(dir)
+            .map_err(read_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(read_error)?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                self.add_dir(root, &path)?;
+                continue;
+            }
+            let source = fs::read_to_string(&path).map_err(|e| {
+                CopilotError::Other(format!("Failed to read {}: {e}", path.display()))
+            })?;
+            let name = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .with_extension("")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            self.add(name, source)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the template `name`, replacing a template of the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the template does not parse.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Result<(), CopilotError> {
+        let name = name.into();
+        self.env
+            .add_template_owned(name.clone(), source.into())
+            .map_err(|e| CopilotError::Other(format!("Invalid prompt template {name}: {e}")))
+    }
+
+    /// Adds the template `name`, like [`PromptTemplates::add`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the template does not parse.
+    pub fn with_template(
+        mut self,
+        name: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Result<Self, CopilotError> {
+        self.add(name, source)?;
+        Ok(self)
+    }
+
+    /// Returns `true` if a template named `name` exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.env.get_template(name).is_ok()
+    }
+
+    /// Returns the names of the templates, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.env.templates().map(|(name, _)| name).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Renders the template `name` with the variables in `vars`, such as a `serde_json` object
+    /// or a struct deriving `Serialize`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if there is no such template or rendering fails, for example
+    /// because a variable is missing.
+    pub fn render(&self, name: &str, vars: impl Serialize) -> Result<String, CopilotError> {
+        self.env
+            .get_template(name)
+            .and_then(|template| template.render(vars))
+            .map_err(|e| {
+                CopilotError::Other(format!("Failed to render prompt template {name}: {e}"))
+            })
+    }
+
+    /// Renders the template `name` into a message with the given role.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if there is no such template or rendering fails.
+    pub fn render_message(
+        &self,
+        role: impl Into<String>,
+        name: &str,
+        vars: impl Serialize,
+    ) -> Result<Message, CopilotError> {
+        Ok(Message::new(role, self.render(name, vars)?))
+    }
+}
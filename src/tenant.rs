@@ -0,0 +1,183 @@
+//! API keys for a shared gateway, each mapped to an auth profile and a budget.
+//!
+//! A server that lets a small team share Copilot access needs to know who sent each request,
+//! which GitHub account to send it as, and how much that caller may spend. A [`TenantRegistry`]
+//! maps the API keys handed out to callers to [`Tenant`]s, each with a [`Profile`] and an
+//! optional [`Budget`], and checks the key of an incoming request:
+//!
+//! ```
+//! use copilot_client::{
+//!     budget::{Budget, BudgetWindow},
+//!     config::Profile,
+//!     tenant::{Tenant, TenantRegistry},
+//!     transport::HeaderMap,
+//! };
+//! use std::sync::Arc;
+//!
+//! let tenants = TenantRegistry::new()
+//!     .with_tenant(
+//!         "key-for-alice",
+//!         Tenant::new("alice", Profile::default())
+//!             .with_budget(Arc::new(Budget::new().with_request_limit(BudgetWindow::Day, 300))),
+//!     )
+//!     .with_tenant("key-for-ci", Tenant::new("ci", Profile::default()));
+//!
+//! let mut headers = HeaderMap::new();
+//! headers.insert("authorization", "Bearer key-for-ci".parse().unwrap());
+//! assert_eq!(tenants.authenticate(&headers).map(|t| t.name.as_str()), Some("ci"));
+//! assert!(tenants.verify("key-for-bob").is_none());
+//! ```
+//!
+//! Create one client per tenant at startup, with the tenant's GitHub token and its budget
+//! attached by [`Tenant::apply`], and answer each request with the client of the tenant its key
+//! belongs to. Keys are compared in constant time, and keys and tokens are left out of `Debug`
+//! output.
+
+use crate::{
+    CopilotClient, CopilotError, budget::Budget, config::Profile, get_github_token,
+    transport::HeaderMap,
+};
+use std::{fmt, sync::Arc};
+
+/// The header carrying the API key, for clients that do not send it as a bearer token.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// A caller of a shared gateway. `Debug` output shows the name and whether a budget is set,
+/// but not the credentials.
+#[derive(Clone)]
+pub struct Tenant {
+    /// A name for the tenant, for logs and usage tags.
+    pub name: String,
+    /// The credentials the tenant's requests are sent with.
+    pub profile: Profile,
+    /// The limits of the tenant's requests, if any.
+    pub budget: Option<Arc<Budget>>,
+}
+
+impl fmt::Debug for Tenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenant")
+            .field("name", &self.name)
+            .field("budget", &self.budget.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tenant {
+    /// Creates a tenant without a budget.
+    pub fn new(name: impl Into<String>, profile: Profile) -> Self {
+        Tenant {
+            name: name.into(),
+            profile,
+            budget: None,
+        }
+    }
+
+    /// Limits the tenant's requests with `budget`. Share the `Arc` to give several tenants one
+    /// budget.
+    pub fn with_budget(mut self, budget: Arc<Budget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Returns the GitHub token of the tenant's profile, or the token discovered by
+    /// [`get_github_token`] if the profile has none.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError::TokenError` if no token can be found.
+    pub fn github_token(&self) -> Result<String, CopilotError> {
+        match &self.profile.github_token {
+            Some(token) => Ok(token.clone()),
+            None => get_github_token().map_err(|e| CopilotError::TokenError(e.to_string())),
+        }
+    }
+
+    /// Attaches the tenant's budget, if it has one, to `client`.
+    pub fn apply(&self, client: CopilotClient) -> CopilotClient {
+        match &self.budget {
+            Some(budget) => client.with_budget(budget.clone()),
+            None => client,
+        }
+    }
+}
+
+/// Tenants by API key. See the [module documentation](self).
+#[derive(Clone, Default)]
+pub struct TenantRegistry {
+    tenants: Vec<(String, Tenant)>,
+}
+
+impl fmt::Debug for TenantRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.tenants.iter().map(|(_, tenant)| &tenant.name))
+            .finish()
+    }
+}
+
+impl TenantRegistry {
+    /// Creates a registry without tenants, which accepts no key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps `api_key` to `tenant`, replacing the tenant the key was mapped to. Empty keys are
+    /// never accepted.
+    pub fn with_tenant(mut self, api_key: impl Into<String>, tenant: Tenant) -> Self {
+        let api_key = api_key.into();
+        self.tenants.retain(|(key, _)| *key != api_key);
+        self.tenants.push((api_key, tenant));
+        self
+    }
+
+    /// Returns the number of keys.
+    pub fn len(&self) -> usize {
+        self.tenants.len()
+    }
+
+    /// Returns `true` if no key has been added.
+    pub fn is_empty(&self) -> bool {
+        self.tenants.is_empty()
+    }
+
+    /// Returns the tenant `api_key` belongs to, or `None` if it is not a known key.
+    pub fn verify(&self, api_key: &str) -> Option<&Tenant> {
+        if api_key.is_empty() {
+            return None;
+        }
+        // Compare against every key, so that the time taken does not tell which one matched.
+        self.tenants.iter().fold(None, |found, (key, tenant)| {
+            if constant_time_eq(key.as_bytes(), api_key.as_bytes()) {
+                Some(tenant)
+            } else {
+                found
+            }
+        })
+    }
+
+    /// Returns the tenant whose key a request carries as an `Authorization: Bearer` token or in
+    /// the [`API_KEY_HEADER`], or `None` if it carries no known key.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<&Tenant> {
+        let bearer = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                v.split_once(' ')
+                    .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            })
+            .map(|(_, key)| key.trim());
+        let key = bearer.or_else(|| {
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+        })?;
+        self.verify(key)
+    }
+}
+
+/// Compares `a` and `b` in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
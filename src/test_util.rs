@@ -0,0 +1,322 @@
+//! A mock Copilot server for integration tests, enabled with the `test-util` feature.
+//!
+//! [`MockCopilot`] runs a [wiremock](https://crates.io/crates/wiremock) server that answers the
+//! token and model endpoints out of the box, and hands out clients whose requests go to it
+//! instead of GitHub. Chat and embeddings responses are mounted per test with the fixture
+//! builders in this module:
+//!
+//! ```
+//! use copilot_client::{Message, test_util::{self, MockCopilot}};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), copilot_client::CopilotError> {
+//! let mock = MockCopilot::start().await;
+//! mock.mock_chat(test_util::chat_response("Hi there")).await;
+//! mock.mock_chat_stream(test_util::chat_chunks(&["Hi", " there"])).await;
+//!
+//! let client = mock.client().await?;
+//! let response = client
+//!     .chat_completion(vec![Message::user("Hello")], test_util::MODEL.to_string())
+//!     .await?;
+//! assert_eq!(response.choices[0].message.content, "Hi there");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Mounting a response again replaces the earlier one. Use [`MockCopilot::server`] to mount
+//! mocks for other endpoints or to inspect the received requests. Mount them with wiremock's
+//! default priority: the replaced mocks are tracked by position, which other priorities upset.
+
+use crate::{
+    ChatChoice, ChatResponse, CopilotClient, CopilotError, CopilotTokenResponse, Embedding,
+    EmbeddingResponse, Message, Model, ModelsResponse,
+    stream::{ChatStreamChoice, ChatStreamChunk, MessageDelta},
+    transport::{HttpRequest, HttpTransport, ReqwestTransport, TransportFuture},
+};
+use serde_json::json;
+use std::{fmt, sync::Mutex};
+use wiremock::{
+    Mock, MockBuilder, MockGuard, MockServer, ResponseTemplate,
+    matchers::{body_partial_json, method, path},
+};
+
+/// The model served by [`MockCopilot`] until other models are mounted.
+pub const MODEL: &str = "gpt-4o";
+
+/// The GitHub token of clients created by [`MockCopilot::client`].
+pub const GITHUB_TOKEN: &str = "test-github-token";
+
+/// The Copilot token returned by the mock token endpoint.
+pub const COPILOT_TOKEN: &str = "test-copilot-token";
+
+/// Hosts whose requests are sent to the mock server.
+const HOSTS: [&str; 3] = [
+    "https://github.com",
+    "https://api.github.com",
+    "https://api.githubcopilot.com",
+];
+
+/// A running mock of the Copilot API.
+pub struct MockCopilot {
+    server: MockServer,
+    mounted: Mutex<Vec<Mounted>>,
+}
+
+impl fmt::Debug for MockCopilot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockCopilot")
+            .field("uri", &self.server.uri())
+            .finish()
+    }
+}
+
+impl MockCopilot {
+    /// Starts a server that issues Copilot tokens and lists a single model, [`MODEL`].
+    pub async fn start() -> Self {
+        let mock = MockCopilot {
+            server: MockServer::start().await,
+            mounted: Mutex::new(Vec::new()),
+        };
+        mock.mock_token(CopilotTokenResponse {
+            token: COPILOT_TOKEN.to_string(),
+            expires_at: u64::from(u32::MAX),
+        })
+        .await;
+        mock.mock_models(vec![model(MODEL)]).await;
+        mock
+    }
+
+    /// Returns the underlying wiremock server.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Creates a client that sends its requests to this server and has fetched its models.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the model fetching fails, for example because an error was
+    /// mounted for it.
+    pub async fn client(&self) -> Result<CopilotClient, CopilotError> {
+        CopilotClient::new_with_transport(
+            GITHUB_TOKEN.to_string(),
+            "test-editor/1.0".to_string(),
+            self.transport(),
+        )
+        .await
+    }
+
+    /// Returns a transport that sends requests for GitHub and Copilot hosts to this server, for
+    /// building clients in other ways.
+    pub fn transport(&self) -> MockTransport {
+        MockTransport {
+            uri: self.server.uri(),
+            inner: ReqwestTransport::default(),
+        }
+    }
+
+    /// Answers Copilot token requests with `token`.
+    ///
+    /// Clients cache the token until shortly before its `expires_at`, so a client that already
+    /// has one picks up the new token only then. Give the client a
+    /// [`ManualClock`](crate::clock::ManualClock) to reach the expiry without waiting.
+    pub async fn mock_token(&self, token: CopilotTokenResponse) {
+        self.mount(
+            "/copilot_internal/v2/token",
+            None,
+            Mock::given(method("GET")).and(path("/copilot_internal/v2/token")),
+            ResponseTemplate::new(200).set_body_json(token),
+        )
+        .await;
+    }
+
+    /// Lists `models` at the models endpoint.
+    pub async fn mock_models(&self, models: Vec<Model>) {
+        self.mount(
+            "/models",
+            None,
+            Mock::given(method("GET")).and(path("/models")),
+            ResponseTemplate::new(200).set_body_json(ModelsResponse { data: models }),
+        )
+        .await;
+    }
+
+    /// Answers non-streaming chat requests with `response`.
+    pub async fn mock_chat(&self, response: ChatResponse) {
+        self.mount(
+            "/chat/completions",
+            Some(false),
+            Mock::given(method("POST"))
+                .and(path("/chat/completions"))
+                .and(body_partial_json(json!({ "stream": false }))),
+            ResponseTemplate::new(200).set_body_json(response),
+        )
+        .await;
+    }
+
+    /// Answers streaming chat requests with `chunks` as server-sent events, followed by the
+    /// `[DONE]` marker.
+    pub async fn mock_chat_stream(&self, chunks: Vec<ChatStreamChunk>) {
+        let mut body = String::new();
+        for chunk in &chunks {
+            let data = serde_json::to_string(chunk).unwrap_or_default();
+            body.push_str(&format!("data: {data}\n\n"));
+        }
+        body.push_str("data: [DONE]\n\n");
+        self.mount(
+            "/chat/completions",
+            Some(true),
+            Mock::given(method("POST"))
+                .and(path("/chat/completions"))
+                .and(body_partial_json(json!({ "stream": true }))),
+            ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"),
+        )
+        .await;
+    }
+
+    /// Answers embeddings requests with `embeddings`.
+    pub async fn mock_embeddings(&self, embeddings: Vec<Embedding>) {
+        self.mount(
+            "/embeddings",
+            None,
+            Mock::given(method("POST")).and(path("/embeddings")),
+            ResponseTemplate::new(200).set_body_json(EmbeddingResponse {
+                data: embeddings,
+                usage: None,
+            }),
+        )
+        .await;
+    }
+
+    /// Answers requests to `endpoint`, such as `"/chat/completions"` or `"/models"`, with an
+    /// API error.
+    pub async fn mock_error(&self, endpoint: &str, status: u16, message: &str) {
+        self.mount(
+            endpoint,
+            None,
+            Mock::given(path(endpoint)),
+            ResponseTemplate::new(status).set_body_json(json!({ "error": { "message": message } })),
+        )
+        .await;
+    }
+
+    /// Mounts `mock` for `endpoint`, replacing the mocks for the same endpoint and streaming
+    /// mode. A mock without a mode replaces, and is replaced by, those of both modes.
+    async fn mount(
+        &self,
+        endpoint: &str,
+        stream: Option<bool>,
+        mock: MockBuilder,
+        response: ResponseTemplate,
+    ) {
+        let replaced: Vec<Mounted> = {
+            let mut mounted = self.mounted.lock().unwrap_or_else(|e| e.into_inner());
+            let (replaced, kept) = mounted.drain(..).partition(|m| {
+                m.endpoint == endpoint
+                    && (m.stream.is_none() || stream.is_none() || m.stream == stream)
+            });
+            *mounted = kept;
+            replaced
+        };
+        drop(replaced);
+        let guard = self
+            .server
+            .register_as_scoped(mock.respond_with(response))
+            .await;
+        self.mounted
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Mounted {
+                endpoint: endpoint.to_string(),
+                stream,
+                _guard: guard,
+            });
+    }
+}
+
+/// A mock mounted through a [`MockCopilot`] method. Dropping it unmounts the mock.
+struct Mounted {
+    endpoint: String,
+    stream: Option<bool>,
+    _guard: MockGuard,
+}
+
+/// A transport that sends requests for GitHub and Copilot hosts to a [`MockCopilot`] server.
+#[derive(Debug, Clone)]
+pub struct MockTransport {
+    uri: String,
+    inner: ReqwestTransport,
+}
+
+impl HttpTransport for MockTransport {
+    fn send(&self, mut request: HttpRequest) -> TransportFuture<'_> {
+        if let Some(rest) = HOSTS.iter().find_map(|host| request.url.strip_prefix(host)) {
+            request.url = format!("{}{rest}", self.uri);
+        }
+        self.inner.send(request)
+    }
+}
+
+/// Creates a model with `id` and no limits or capabilities.
+pub fn model(id: &str) -> Model {
+    Model {
+        id: id.to_string(),
+        name: id.to_string(),
+        version: None,
+        tokenizer: None,
+        max_input_tokens: None,
+        max_output_tokens: None,
+        capabilities: None,
+        billing: None,
+        preview: None,
+        policy: None,
+    }
+}
+
+/// Creates a chat response with a single assistant reply.
+pub fn chat_response(content: &str) -> ChatResponse {
+    ChatResponse {
+        model: None,
+        choices: vec![ChatChoice {
+            message: Message::assistant(content),
+            finish_reason: Some("stop".to_string()),
+            usage: None,
+            logprobs: None,
+        }],
+        usage: None,
+        system_fingerprint: None,
+        request_id: None,
+        server_request_id: None,
+    }
+}
+
+/// Creates stream chunks that deliver `pieces` in order, the last one with a `"stop"` finish
+/// reason.
+pub fn chat_chunks(pieces: &[&str]) -> Vec<ChatStreamChunk> {
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(i, piece)| ChatStreamChunk {
+            choices: vec![ChatStreamChoice {
+                index: 0,
+                delta: MessageDelta {
+                    role: (i == 0).then(|| "assistant".to_string()),
+                    content: Some(piece.to_string()),
+                    tool_calls: None,
+                },
+                finish_reason: (i + 1 == pieces.len()).then(|| "stop".to_string()),
+                logprobs: None,
+            }],
+            usage: None,
+            system_fingerprint: None,
+        })
+        .collect()
+}
+
+/// Creates an embedding for the input at `index`.
+pub fn embedding(index: usize, vector: Vec<f64>) -> Embedding {
+    Embedding {
+        index,
+        embedding: vector,
+    }
+}
@@ -0,0 +1,256 @@
+//! Tools the model can call, and a loop that runs them.
+//!
+//! A [`ToolHandler`] pairs the definition of a tool with the code that runs it. Handlers are
+//! collected in a [`Toolbox`], and [`CopilotClient::run_tools`] sends a chat completion with
+//! their definitions, runs the tool calls of each reply, sends the results back, and repeats
+//! until the model answers without calling tools:
+//!
+//! ```no_run
+//! use copilot_client::{ChatOptions, Message, tools::Toolbox};
+//! use serde_json::json;
+//!
+//! # async fn run(client: copilot_client::CopilotClient) -> Result<(), copilot_client::CopilotError> {
+//! let toolbox = Toolbox::new()
+//!     .with_function(
+//!         "current_time",
+//!         "Returns the current UTC time.",
+//!         json!({ "type": "object", "properties": {} }),
+//!         |_| Ok("2025-01-01T12:00:00Z".to_string()),
+//!     );
+//! let run = client
+//!     .run_tools(
+//!         vec![Message::user("What time is it in UTC?")],
+//!         &toolbox,
+//!         "gpt-4o".into(),
+//!         &ChatOptions::default(),
+//!     )
+//!     .await?;
+//! println!("{}", run.response.choices[0].message.content);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Tool failures, such as invalid arguments or an unknown tool, are sent to the model as the
+//! result of the call, so that it can correct itself.
+//!
+//! Built-in tools are in the submodules: [`search`], `fetch` with the `fetch` feature, `files`
+//! with the `files` feature, and `shell` with the `shell` feature.
+
+#[cfg(feature = "fetch")]
+pub mod fetch;
+#[cfg(feature = "files")]
+pub mod files;
+pub mod search;
+#[cfg(all(feature = "shell", not(target_arch = "wasm32")))]
+pub mod shell;
+
+use crate::{
+    ChatOptions, ChatResponse, CopilotClient, CopilotError, Message, Tool, ToolCall, api::ApiFuture,
+};
+use futures_util::future::join_all;
+use serde_json::Value;
+use std::{fmt, sync::Arc};
+
+/// The default maximum number of chat completions sent by [`CopilotClient::run_tools`].
+pub const DEFAULT_MAX_ROUNDS: usize = 10;
+
+/// A tool the model can call.
+pub trait ToolHandler: Send + Sync {
+    /// Returns the definition of the tool sent to the model.
+    fn definition(&self) -> Tool;
+
+    /// Runs the tool with the `arguments` generated by the model and returns the result sent
+    /// back to it. `client` can be used to send HTTP requests through the client's transport.
+    fn call<'a>(&'a self, client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String>;
+}
+
+/// A tool that runs a synchronous function.
+struct FunctionTool<F> {
+    tool: Tool,
+    function: F,
+}
+
+impl<F> ToolHandler for FunctionTool<F>
+where
+    F: Fn(Value) -> Result<String, CopilotError> + Send + Sync,
+{
+    fn definition(&self) -> Tool {
+        self.tool.clone()
+    }
+
+    fn call<'a>(&'a self, _client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        let result = (self.function)(arguments);
+        Box::pin(async move { result })
+    }
+}
+
+/// A set of tools, run by [`CopilotClient::run_tools`].
+#[derive(Clone)]
+pub struct Toolbox {
+    handlers: Vec<Arc<dyn ToolHandler>>,
+    max_rounds: usize,
+}
+
+impl Default for Toolbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Toolbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self
+            .handlers
+            .iter()
+            .map(|handler| handler.definition().function.name)
+            .collect();
+        f.debug_struct("Toolbox")
+            .field("tools", &names)
+            .field("max_rounds", &self.max_rounds)
+            .finish()
+    }
+}
+
+impl Toolbox {
+    /// Creates an empty toolbox.
+    pub fn new() -> Self {
+        Toolbox {
+            handlers: Vec::new(),
+            max_rounds: DEFAULT_MAX_ROUNDS,
+        }
+    }
+
+    /// Adds a tool. A tool with the same name as one added before replaces it.
+    pub fn with_tool(mut self, handler: impl ToolHandler + 'static) -> Self {
+        let name = handler.definition().function.name;
+        self.handlers
+            .retain(|existing| existing.definition().function.name != name);
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// Adds a tool that calls `function` with the arguments, described by the JSON Schema
+    /// `parameters`.
+    pub fn with_function<F>(
+        self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        parameters: Value,
+        function: F,
+    ) -> Self
+    where
+        F: Fn(Value) -> Result<String, CopilotError> + Send + Sync + 'static,
+    {
+        self.with_tool(FunctionTool {
+            tool: Tool::function(name, description, parameters),
+            function,
+        })
+    }
+
+    /// Sets the maximum number of chat completions sent by [`CopilotClient::run_tools`].
+    /// Defaults to [`DEFAULT_MAX_ROUNDS`].
+    pub fn with_max_rounds(mut self, rounds: usize) -> Self {
+        self.max_rounds = rounds;
+        self
+    }
+
+    /// Returns the definitions of the tools.
+    pub fn definitions(&self) -> Vec<Tool> {
+        self.handlers
+            .iter()
+            .map(|handler| handler.definition())
+            .collect()
+    }
+
+    /// Returns the tool named `name`.
+    pub fn get(&self, name: &str) -> Option<&dyn ToolHandler> {
+        self.handlers
+            .iter()
+            .find(|handler| handler.definition().function.name == name)
+            .map(|handler| &**handler)
+    }
+
+    /// Runs `call` and returns the `"tool"` message with its result. Errors are reported in the
+    /// message.
+    pub async fn call(&self, client: &CopilotClient, call: &ToolCall) -> Message {
+        let name = &call.function.name;
+        let result = match self.get(name) {
+            None => Err(CopilotError::Other(format!("No such tool: {name}"))),
+            Some(handler) => match parse_arguments(&call.function.arguments) {
+                Ok(arguments) => handler.call(client, arguments).await,
+                Err(e) => Err(e),
+            },
+        };
+        let content = result.unwrap_or_else(|e| {
+            tracing::debug!(tool = %name, error = %e, "Tool call failed");
+            format!("Error: {e}")
+        });
+        Message::tool_result(&call.id, content)
+    }
+}
+
+/// Parses tool call arguments, reading empty arguments as an empty object.
+fn parse_arguments(arguments: &str) -> Result<Value, CopilotError> {
+    if arguments.trim().is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+    serde_json::from_str(arguments)
+        .map_err(|e| CopilotError::Other(format!("Invalid tool arguments: {e}")))
+}
+
+/// The result of [`CopilotClient::run_tools`].
+#[derive(Debug, Clone)]
+pub struct ToolRun {
+    /// The final response, which calls no tools.
+    pub response: ChatResponse,
+    /// The messages sent, followed by the replies that called tools, the tool results, and the
+    /// final reply, ready to continue the chat.
+    pub messages: Vec<Message>,
+}
+
+impl CopilotClient {
+    /// Sends a chat completion request with the tools of `toolbox`, runs the tools the reply
+    /// calls, and sends their results until the model replies without calling tools.
+    ///
+    /// The tools are added to the tools of `options`. Calls made in the same reply run
+    /// concurrently.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if a request fails, or if the model still calls tools after
+    /// the maximum number of rounds.
+    pub async fn run_tools(
+        &self,
+        mut messages: Vec<Message>,
+        toolbox: &Toolbox,
+        model_id: String,
+        options: &ChatOptions,
+    ) -> Result<ToolRun, CopilotError> {
+        let mut options = options.clone();
+        options
+            .tools
+            .get_or_insert_with(Vec::new)
+            .extend(toolbox.definitions());
+        for _ in 0..toolbox.max_rounds {
+            let response = self
+                .chat_completion_with_options(messages.clone(), model_id.clone(), &options)
+                .await?;
+            let Some(choice) = response.choices.first() else {
+                return Err(CopilotError::Other("Response has no choices".to_string()));
+            };
+            let reply = choice.message.clone();
+            let calls = reply.tool_calls.clone().unwrap_or_default();
+            messages.push(reply);
+            if calls.is_empty() {
+                return Ok(ToolRun { response, messages });
+            }
+            tracing::debug!(count = calls.len(), "Running tool calls");
+            let results = join_all(calls.iter().map(|call| toolbox.call(self, call))).await;
+            messages.extend(results);
+        }
+        Err(CopilotError::Other(format!(
+            "Model still called tools after {} rounds",
+            toolbox.max_rounds
+        )))
+    }
+}
@@ -0,0 +1,313 @@
+//! A tool that fetches web pages.
+//!
+//! [`FetchTool`] lets the model read a URL: HTML pages are converted to Markdown, without
+//! scripts, styles, and navigation, and the text is truncated to a token budget before it is
+//! returned. Domains can be limited with an allowlist and a denylist:
+//!
+//! ```
+//! use copilot_client::tools::fetch::{FetchTool, html_to_markdown};
+//!
+//! let tool = FetchTool::new()
+//!     .with_allowed_domain("docs.rs")
+//!     .with_allowed_domain("github.com")
+//!     .with_denied_domain("gist.github.com")
+//!     .with_max_tokens(2_000);
+//! assert!(tool.allows("https://docs.rs/regex/latest/regex/"));
+//! assert!(!tool.allows("https://gist.github.com/someone"));
+//! assert!(!tool.allows("https://example.com/"));
+//!
+//! assert_eq!(html_to_markdown("<h1>Title</h1><p>Some <b>bold</b> text.</p>"), "# Title\n\nSome **bold** text.");
+//! ```
+//!
+//! The lists are checked against the requested URL and every redirect the tool follows.
+//! Loopback, link-local, and private addresses, such as `localhost` or the cloud metadata
+//! address `169.254.169.254`, are denied unless [`FetchTool::with_private_networks`] allows
+//! them. Only hosts given as such addresses are recognized: a domain that resolves to a private
+//! address is reached, so use an allowlist where that matters. Bodies are read up to
+//! [`DEFAULT_MAX_BYTES`], and the rest is not downloaded.
+
+use super::ToolHandler;
+use crate::{
+    CopilotClient, CopilotError, Tool,
+    api::ApiFuture,
+    context::{decode, fit},
+    transport::{HttpRequest, HttpResponse, Method},
+};
+use http::header::{CONTENT_TYPE, LOCATION};
+use serde_json::{Value, json};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use url::{Host, Url};
+
+/// The default maximum size of a fetched page, in tokens.
+pub const DEFAULT_MAX_TOKENS: u32 = 4_000;
+
+/// The default maximum size of a fetched body, in bytes.
+pub const DEFAULT_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// The maximum number of redirects followed for one fetch.
+const MAX_REDIRECTS: usize = 10;
+
+/// Elements left out when converting HTML to Markdown.
+const SKIPPED_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "iframe", "nav", "footer",
+];
+
+/// Fetches URLs for the model, as a [`ToolHandler`] named `fetch_url`.
+#[derive(Debug, Clone)]
+pub struct FetchTool {
+    allowed: Vec<String>,
+    denied: Vec<String>,
+    max_tokens: u32,
+    max_bytes: usize,
+    private_networks: bool,
+}
+
+impl Default for FetchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FetchTool {
+    /// Creates a tool that fetches any `http` or `https` URL outside private networks and
+    /// returns at most [`DEFAULT_MAX_TOKENS`] tokens.
+    pub fn new() -> Self {
+        FetchTool {
+            allowed: Vec::new(),
+            denied: Vec::new(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            max_bytes: DEFAULT_MAX_BYTES,
+            private_networks: false,
+        }
+    }
+
+    /// Allows `domain` and its subdomains. Once a domain is allowed, all others are denied.
+    pub fn with_allowed_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed.push(normalize_domain(domain.into()));
+        self
+    }
+
+    /// Denies `domain` and its subdomains, even if an allowed domain contains them.
+    pub fn with_denied_domain(mut self, domain: impl Into<String>) -> Self {
+        self.denied.push(normalize_domain(domain.into()));
+        self
+    }
+
+    /// Truncates fetched text to about `tokens` tokens. Defaults to [`DEFAULT_MAX_TOKENS`].
+    pub fn with_max_tokens(mut self, tokens: u32) -> Self {
+        self.max_tokens = tokens;
+        self
+    }
+
+    /// Reads at most `bytes` bytes of each body. Defaults to [`DEFAULT_MAX_BYTES`].
+    pub fn with_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
+    /// Allows loopback, link-local, and private addresses if `allowed`, for example to read
+    /// documentation served on the local machine. They are denied by default.
+    pub fn with_private_networks(mut self, allowed: bool) -> Self {
+        self.private_networks = allowed;
+        self
+    }
+
+    /// Returns `true` if `url` is an `http` or `https` URL whose host is allowed.
+    pub fn allows(&self, url: &str) -> bool {
+        self.check(url).is_ok()
+    }
+
+    /// Fetches `url` and returns its text, converted to Markdown if it is HTML and truncated
+    /// to the token budget. Redirects are followed if their targets are allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the URL or a redirect target is not allowed, the request
+    /// fails, or the response is not text.
+    pub async fn fetch(&self, client: &CopilotClient, url: &str) -> Result<String, CopilotError> {
+        let mut url = self.check(url)?;
+        let mut redirects = 0;
+        let response = loop {
+            let request = HttpRequest::new(Method::GET, url.as_str()).follow_redirects(false);
+            let response = client
+                .send_request(request)
+                .await
+                .map_err(|e| CopilotError::Other(format!("Failed to fetch {url}: {e}")))?;
+            if !response.status.is_redirection() {
+                break response;
+            }
+            let location = response
+                .headers
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| {
+                    CopilotError::Other(format!("{url} redirected without a location"))
+                })?;
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                return Err(CopilotError::Other(format!(
+                    "Too many redirects fetching {url}"
+                )));
+            }
+            let target = url.join(location).map_err(|e| {
+                CopilotError::Other(format!("{url} redirected to an invalid URL: {e}"))
+            })?;
+            url = self.check(target.as_str())?;
+        };
+        let (content_type, bytes) = read(response, self.max_bytes).await?;
+        let text = decode(&bytes)
+            .ok_or_else(|| CopilotError::Other(format!("{url} is not a text document")))?;
+        let text = if content_type == "text/html" || content_type == "application/xhtml+xml" {
+            html_to_markdown(&text)
+        } else {
+            text
+        };
+        Ok(fit(&text, self.max_tokens))
+    }
+
+    /// Parses `url` and checks that it may be fetched.
+    fn check(&self, url: &str) -> Result<Url, CopilotError> {
+        let parsed =
+            Url::parse(url).map_err(|e| CopilotError::Other(format!("Invalid URL {url}: {e}")))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(CopilotError::Other(format!(
+                "Only http and https URLs can be fetched: {url}"
+            )));
+        }
+        let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+        let matches = |domain: &String| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        };
+        if self.denied.iter().any(matches)
+            || (!self.allowed.is_empty() && !self.allowed.iter().any(matches))
+        {
+            return Err(CopilotError::Other(format!(
+                "Fetching from {host} is not allowed"
+            )));
+        }
+        if !self.private_networks && is_private_host(&parsed) {
+            return Err(CopilotError::Other(format!(
+                "Fetching from {host} is not allowed: it is a private network address"
+            )));
+        }
+        Ok(parsed)
+    }
+}
+
+impl ToolHandler for FetchTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "fetch_url",
+            "Fetches a web page or text document and returns its content, with HTML converted \
+             to Markdown. Long documents are truncated.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The http or https URL to fetch."
+                    }
+                },
+                "required": ["url"]
+            }),
+        )
+    }
+
+    fn call<'a>(&'a self, client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        Box::pin(async move {
+            let url = arguments
+                .get("url")
+                .and_then(Value::as_str)
+                .ok_or_else(|| CopilotError::Other("Missing `url` argument".to_string()))?;
+            self.fetch(client, url).await
+        })
+    }
+}
+
+/// Converts HTML to Markdown, leaving out scripts, styles, navigation, and other elements that
+/// carry no content. Returns the HTML unchanged if it cannot be converted.
+pub fn html_to_markdown(html: &str) -> String {
+    htmd::HtmlToMarkdown::builder()
+        .skip_tags(SKIPPED_TAGS.to_vec())
+        .build()
+        .convert(html)
+        .map(|markdown| markdown.trim().to_string())
+        .unwrap_or_else(|_| html.to_string())
+}
+
+/// Fetches `url` through the client's transport and returns its MIME type, in lower case and
+/// without parameters, and its body, read up to `max_bytes` bytes.
+pub(crate) async fn get(
+    client: &CopilotClient,
+    url: &str,
+    max_bytes: usize,
+) -> Result<(String, Vec<u8>), CopilotError> {
+    let response = client
+        .send_request(HttpRequest::new(Method::GET, url))
+        .await
+        .map_err(|e| CopilotError::Other(format!("Failed to fetch {url}: {e}")))?;
+    read(response, max_bytes).await
+}
+
+/// Returns the MIME type of `response`, in lower case and without parameters, and its body,
+/// read up to `max_bytes` bytes. A truncated body ends at the last complete UTF-8 character
+/// if it is text.
+async fn read(response: HttpResponse, max_bytes: usize) -> Result<(String, Vec<u8>), CopilotError> {
+    let content_type = response
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let (mut bytes, truncated) = response.bytes_up_to(max_bytes).await?;
+    if truncated
+        && let Err(e) = std::str::from_utf8(&bytes)
+        && e.error_len().is_none()
+    {
+        bytes.truncate(e.valid_up_to());
+    }
+    Ok((content_type, bytes))
+}
+
+/// Returns `true` if the host of `url` is `localhost` or a loopback, link-local, private,
+/// shared, or unspecified address.
+fn is_private_host(url: &Url) -> bool {
+    fn private_v4(ip: Ipv4Addr) -> bool {
+        let [a, b, ..] = ip.octets();
+        ip.is_loopback()
+            || ip.is_private()
+            || ip.is_link_local()
+            || ip.is_unspecified()
+            || ip.is_broadcast()
+            || a == 0
+            // 100.64.0.0/10, shared address space behind carrier-grade NAT.
+            || (a == 100 && (64..128).contains(&b))
+    }
+    fn private_v6(ip: Ipv6Addr) -> bool {
+        let first = ip.segments()[0];
+        ip.is_loopback()
+            || ip.is_unspecified()
+            // fc00::/7, unique local addresses, and fe80::/10, link-local addresses.
+            || (first & 0xfe00) == 0xfc00
+            || (first & 0xffc0) == 0xfe80
+            || ip.to_ipv4_mapped().is_some_and(private_v4)
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(ip)) => private_v4(ip),
+        Some(Host::Ipv6(ip)) => private_v6(ip),
+        None => false,
+    }
+}
+
+fn normalize_domain(domain: String) -> String {
+    domain.trim().trim_start_matches('.').to_ascii_lowercase()
+}
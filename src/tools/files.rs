@@ -0,0 +1,415 @@
+//! Tools that read and write workspace files.
+//!
+//! These are the primitives of an editing agent. [`ListFilesTool`] and [`ReadFileTool`] show
+//! the model the files of a workspace, within the same rules as a
+//! [`FileCollector`]: files matched by `.gitignore`, `.copilotignore`, or `.aiexclude`,
+//! hidden files, and large and binary files stay out of reach. [`WriteFileTool`] lets the
+//! model create and replace files, but every write first goes to an approval callback with a
+//! unified diff of the change, and only happens if the callback approves it:
+//!
+//! ```no_run
+//! use copilot_client::tools::{
+//!     Toolbox,
+//!     files::{ListFilesTool, ReadFileTool, WriteFileTool},
+//! };
+//!
+//! let toolbox = Toolbox::new()
+//!     .with_tool(ListFilesTool::new("."))
+//!     .with_tool(ReadFileTool::new("."))
+//!     .with_tool(WriteFileTool::new(".", |change| {
+//!         println!("{}", change.diff);
+//!         let mut answer = String::new();
+//!         std::io::stdin().read_line(&mut answer).is_ok() && answer.trim() == "y"
+//!     }));
+//! ```
+//!
+//! Paths are relative to the workspace root. Absolute paths, `..` components, paths into
+//! `.git`, and symbolic links leading out of the workspace are rejected.
+
+use super::ToolHandler;
+use crate::{
+    CopilotClient, CopilotError, Tool,
+    api::ApiFuture,
+    context::{decode, fenced, files::FileCollector, fit, language},
+    workspace::{check_relative, resolve, slash_path},
+};
+use serde_json::{Value, json};
+use similar::TextDiff;
+use std::{fmt, fs, path::PathBuf, sync::Arc};
+
+/// The default maximum size of a file returned by [`ReadFileTool`], in tokens.
+pub const DEFAULT_MAX_READ_TOKENS: u32 = 8_000;
+
+/// The default maximum number of paths returned by [`ListFilesTool`].
+pub const DEFAULT_MAX_LISTED_FILES: usize = 500;
+
+/// Lists the files of a workspace for the model, as a [`ToolHandler`] named `list_files`.
+#[derive(Debug, Clone)]
+pub struct ListFilesTool {
+    collector: FileCollector,
+    max_files: usize,
+}
+
+impl ListFilesTool {
+    /// Creates a tool that lists the files under `root` that a [`FileCollector`] would
+    /// collect.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::from_collector(FileCollector::new(root))
+    }
+
+    /// Creates a tool that lists the files `collector` would collect.
+    pub fn from_collector(collector: FileCollector) -> Self {
+        ListFilesTool {
+            collector,
+            max_files: DEFAULT_MAX_LISTED_FILES,
+        }
+    }
+
+    /// Lists at most `count` paths. Defaults to [`DEFAULT_MAX_LISTED_FILES`].
+    pub fn with_max_files(mut self, count: usize) -> Self {
+        self.max_files = count;
+        self
+    }
+
+    /// Returns the paths, relative to the root, of the files in `dir`, a directory relative
+    /// to the root, or in the whole workspace.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `dir` is not a valid workspace path, or a directory or an
+    /// ignore file cannot be read.
+    pub fn list(&self, dir: Option<&str>) -> Result<Vec<String>, CopilotError> {
+        let prefix = match dir {
+            Some(dir) if !matches!(dir.trim(), "" | ".") => Some(check_relative(dir)?),
+            _ => None,
+        };
+        let root = self.collector.root();
+        Ok(self
+            .collector
+            .paths()?
+            .into_iter()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(root).ok()?;
+                prefix
+                    .as_ref()
+                    .is_none_or(|prefix| relative.starts_with(prefix))
+                    .then(|| slash_path(relative))
+            })
+            .collect())
+    }
+}
+
+impl ToolHandler for ListFilesTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "list_files",
+            "Lists the paths of the files in the workspace, or in a directory of it.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "dir": {
+                        "type": "string",
+                        "description": "A directory relative to the workspace root. Defaults to the whole workspace."
+                    }
+                }
+            }),
+        )
+    }
+
+    fn call<'a>(&'a self, _client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        let result = self.list(arguments.get("dir").and_then(Value::as_str));
+        Box::pin(async move {
+            let paths = result?;
+            if paths.is_empty() {
+                return Ok("No files.".to_string());
+            }
+            let mut listed = paths[..paths.len().min(self.max_files)].join("\n");
+            if paths.len() > self.max_files {
+                listed.push_str(&format!(
+                    "\n[... and {} more files]",
+                    paths.len() - self.max_files
+                ));
+            }
+            Ok(listed)
+        })
+    }
+}
+
+/// Reads workspace files for the model, as a [`ToolHandler`] named `read_file`.
+#[derive(Debug, Clone)]
+pub struct ReadFileTool {
+    collector: FileCollector,
+    max_tokens: u32,
+}
+
+impl ReadFileTool {
+    /// Creates a tool that reads the files under `root` that a [`FileCollector`] would
+    /// collect.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::from_collector(FileCollector::new(root))
+    }
+
+    /// Creates a tool that reads the files `collector` would collect.
+    pub fn from_collector(collector: FileCollector) -> Self {
+        ReadFileTool {
+            collector,
+            max_tokens: DEFAULT_MAX_READ_TOKENS,
+        }
+    }
+
+    /// Truncates what is read to about `tokens` tokens. Defaults to
+    /// [`DEFAULT_MAX_READ_TOKENS`].
+    pub fn with_max_tokens(mut self, tokens: u32) -> Self {
+        self.max_tokens = tokens;
+        self
+    }
+
+    /// Returns the lines `start..=end` of the file at `path`, relative to the root, counted
+    /// from 1, or the whole file, as a fenced block under a label.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `path` is not a valid workspace path, the file is excluded
+    /// from the workspace, or it cannot be read as text.
+    pub fn read(
+        &self,
+        path: &str,
+        start: Option<usize>,
+        end: Option<usize>,
+    ) -> Result<String, CopilotError> {
+        let relative = check_relative(path)?;
+        let path = slash_path(&relative);
+        if !self.collector.includes(&relative)? {
+            return Err(CopilotError::Other(format!(
+                "{path} does not exist or is excluded from the workspace"
+            )));
+        }
+        let full = resolve(self.collector.root(), &relative)?;
+        let bytes = fs::read(&full)
+            .map_err(|e| CopilotError::Other(format!("Failed to read {path}: {e}")))?;
+        let content = decode(&bytes)
+            .ok_or_else(|| CopilotError::Other(format!("{path} is not a text file")))?;
+        let lines: Vec<&str> = content.lines().collect();
+        let (label, text) = if start.is_some() || end.is_some() {
+            let start = start.unwrap_or(1).max(1);
+            let end = end.unwrap_or(lines.len()).min(lines.len());
+            if start > end {
+                return Err(CopilotError::Other(format!(
+                    "{path} has {} lines, so lines {start}-{end} are empty",
+                    lines.len()
+                )));
+            }
+            (
+                format!("`{path}` lines {start}-{end} of {}:", lines.len()),
+                lines[start - 1..end].join("\n"),
+            )
+        } else {
+            (format!("`{path}` ({} lines):", lines.len()), content)
+        };
+        Ok(format!(
+            "{label}\n{}",
+            fenced(&fit(&text, self.max_tokens), language(&relative))
+        ))
+    }
+}
+
+impl ToolHandler for ReadFileTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "read_file",
+            "Reads a text file of the workspace, or a range of its lines. Long files are \
+             truncated; read them in ranges.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The path of the file, relative to the workspace root."
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "The first line to read, counted from 1.",
+                        "minimum": 1
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "description": "The last line to read.",
+                        "minimum": 1
+                    }
+                },
+                "required": ["path"]
+            }),
+        )
+    }
+
+    fn call<'a>(&'a self, _client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        let line = |key: &str| {
+            arguments
+                .get(key)
+                .and_then(Value::as_u64)
+                .map(|line| line as usize)
+        };
+        let result = required_str(&arguments, "path")
+            .and_then(|path| self.read(path, line("start_line"), line("end_line")));
+        Box::pin(async move { result })
+    }
+}
+
+/// A write proposed by the model, passed to the approval callback of a [`WriteFileTool`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// The path of the file, relative to the workspace root, with `/` separators.
+    pub path: String,
+    /// The current content, or `None` if the file does not exist yet.
+    pub old: Option<String>,
+    /// The proposed content.
+    pub new: String,
+    /// The change as a unified diff.
+    pub diff: String,
+}
+
+/// Writes workspace files for the model after approval, as a [`ToolHandler`] named
+/// `write_file`.
+#[derive(Clone)]
+pub struct WriteFileTool {
+    root: PathBuf,
+    approve: Arc<dyn Fn(&FileChange) -> bool + Send + Sync>,
+}
+
+impl fmt::Debug for WriteFileTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteFileTool")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WriteFileTool {
+    /// Creates a tool that writes files under `root` when `approve` returns `true` for the
+    /// change. The callback runs on the task that runs the tool, so it may block on user
+    /// input.
+    pub fn new(
+        root: impl Into<PathBuf>,
+        approve: impl Fn(&FileChange) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        WriteFileTool {
+            root: root.into(),
+            approve: Arc::new(approve),
+        }
+    }
+
+    /// Proposes writing `content` to the file at `path`, relative to the root, and writes it,
+    /// creating missing directories, if the change is approved. Returns a summary of the
+    /// change.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if `path` is not a valid workspace path, the existing file is
+    /// not text, the change is declined, or the file cannot be written.
+    pub fn write(&self, path: &str, content: &str) -> Result<String, CopilotError> {
+        let relative = check_relative(path)?;
+        let full = resolve(&self.root, &relative)?;
+        let path = slash_path(&relative);
+        let old = match fs::read(&full) {
+            Ok(bytes) => Some(
+                decode(&bytes)
+                    .ok_or_else(|| CopilotError::Other(format!("{path} is not a text file")))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(CopilotError::Other(format!("Failed to read {path}: {e}"))),
+        };
+        if old.as_deref() == Some(content) {
+            return Ok(format!("{path} already has this content"));
+        }
+        let change = FileChange {
+            diff: unified_diff(&path, old.as_deref(), content),
+            path,
+            old,
+            new: content.to_string(),
+        };
+        if !(self.approve)(&change) {
+            return Err(CopilotError::Other(format!(
+                "The user declined the change to {}",
+                change.path
+            )));
+        }
+        if let Some(parent) = full.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                CopilotError::Other(format!("Failed to create {}: {e}", parent.display()))
+            })?;
+        }
+        fs::write(&full, content)
+            .map_err(|e| CopilotError::Other(format!("Failed to write {}: {e}", change.path)))?;
+        let (added, removed) = count_changes(&change.diff);
+        Ok(match change.old {
+            Some(_) => format!("Updated {} (+{added} -{removed} lines)", change.path),
+            None => format!("Created {} ({added} lines)", change.path),
+        })
+    }
+}
+
+impl ToolHandler for WriteFileTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "write_file",
+            "Creates a file of the workspace or replaces its whole content. The user reviews \
+             each change and may decline it.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The path of the file, relative to the workspace root."
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "The complete new content of the file."
+                    }
+                },
+                "required": ["path", "content"]
+            }),
+        )
+    }
+
+    fn call<'a>(&'a self, _client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        let result = required_str(&arguments, "path").and_then(|path| {
+            let content = required_str(&arguments, "content")?;
+            self.write(path, content)
+        });
+        Box::pin(async move { result })
+    }
+}
+
+/// Returns the change from `old` to `new` of the file at `path` as a unified diff with three
+/// lines of context, with `/dev/null` as the old file if it does not exist.
+pub fn unified_diff(path: &str, old: Option<&str>, new: &str) -> String {
+    let from = match old {
+        Some(_) => format!("a/{path}"),
+        None => "/dev/null".to_string(),
+    };
+    TextDiff::from_lines(old.unwrap_or_default(), new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&from, &format!("b/{path}"))
+        .to_string()
+}
+
+/// Counts the added and removed lines of a unified diff.
+fn count_changes(diff: &str) -> (usize, usize) {
+    diff.lines()
+        .filter(|line| !line.starts_with("+++") && !line.starts_with("---"))
+        .fold((0, 0), |(added, removed), line| {
+            match line.as_bytes().first() {
+                Some(b'+') => (added + 1, removed),
+                Some(b'-') => (added, removed + 1),
+                _ => (added, removed),
+            }
+        })
+}
+
+fn required_str<'a>(arguments: &'a Value, key: &str) -> Result<&'a str, CopilotError> {
+    arguments
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| CopilotError::Other(format!("Missing `{key}` argument")))
+}
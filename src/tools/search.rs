@@ -0,0 +1,304 @@
+//! A web search tool.
+//!
+//! A [`SearchProvider`] runs web searches, and a [`SearchTool`] exposes a provider to the
+//! model as a `search_web` tool, so that chats can look up information beyond the local
+//! workspace. Combined with the `FetchTool` of the `fetch` feature, the model can search, then
+//! read the pages it finds.
+//!
+//! With the `brave-search` feature, `BraveSearch` implements a provider on the
+//! [Brave Search API](https://brave.com/search/api/):
+//!
+//! ```ignore
+//! use copilot_client::tools::{Toolbox, fetch::FetchTool, search::{BraveSearch, SearchTool}};
+//!
+//! let toolbox = Toolbox::new()
+//!     .with_tool(SearchTool::new(BraveSearch::new(std::env::var("BRAVE_API_KEY")?)))
+//!     .with_tool(FetchTool::new());
+//! ```
+//!
+//! Other search engines are added by implementing [`SearchProvider`]:
+//!
+//! ```
+//! use copilot_client::{
+//!     CopilotClient,
+//!     api::ApiFuture,
+//!     tools::search::{SearchProvider, SearchResult, SearchTool},
+//! };
+//!
+//! struct Docs;
+//!
+//! impl SearchProvider for Docs {
+//!     fn search<'a>(
+//!         &'a self,
+//!         _client: &'a CopilotClient,
+//!         query: &'a str,
+//!         _count: usize,
+//!     ) -> ApiFuture<'a, Vec<SearchResult>> {
+//!         let url = format!("https://docs.example.com/search?q={query}");
+//!         Box::pin(async move { Ok(vec![SearchResult::new("Search results", url, "")]) })
+//!     }
+//! }
+//!
+//! let tool = SearchTool::new(Docs).with_max_results(3);
+//! ```
+
+use super::ToolHandler;
+use crate::{CopilotClient, CopilotError, Tool, api::ApiFuture};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{fmt, sync::Arc};
+
+#[cfg(feature = "brave-search")]
+pub use brave::BraveSearch;
+
+/// The default number of results returned by a [`SearchTool`].
+pub const DEFAULT_MAX_RESULTS: usize = 5;
+
+/// A web search result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// The title of the page.
+    pub title: String,
+    /// The URL of the page.
+    pub url: String,
+    /// An excerpt of the page matching the query, as plain text or Markdown.
+    pub snippet: String,
+}
+
+impl SearchResult {
+    /// Creates a search result.
+    pub fn new(
+        title: impl Into<String>,
+        url: impl Into<String>,
+        snippet: impl Into<String>,
+    ) -> Self {
+        SearchResult {
+            title: title.into(),
+            url: url.into(),
+            snippet: snippet.into(),
+        }
+    }
+}
+
+/// A web search engine.
+pub trait SearchProvider: Send + Sync {
+    /// Returns at most `count` results for `query`, best first. `client` can be used to send
+    /// HTTP requests through the client's transport.
+    fn search<'a>(
+        &'a self,
+        client: &'a CopilotClient,
+        query: &'a str,
+        count: usize,
+    ) -> ApiFuture<'a, Vec<SearchResult>>;
+}
+
+/// Exposes a [`SearchProvider`] to the model, as a [`ToolHandler`] named `search_web`.
+#[derive(Clone)]
+pub struct SearchTool {
+    provider: Arc<dyn SearchProvider>,
+    max_results: usize,
+}
+
+impl fmt::Debug for SearchTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SearchTool")
+            .field("max_results", &self.max_results)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SearchTool {
+    /// Creates a tool that searches with `provider` and returns at most
+    /// [`DEFAULT_MAX_RESULTS`] results.
+    pub fn new(provider: impl SearchProvider + 'static) -> Self {
+        SearchTool {
+            provider: Arc::new(provider),
+            max_results: DEFAULT_MAX_RESULTS,
+        }
+    }
+
+    /// Sets the maximum number of results, which also caps the number the model asks for.
+    /// Defaults to [`DEFAULT_MAX_RESULTS`].
+    pub fn with_max_results(mut self, count: usize) -> Self {
+        self.max_results = count;
+        self
+    }
+}
+
+impl ToolHandler for SearchTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "search_web",
+            "Searches the web and returns the title, URL, and an excerpt of the best matching \
+             pages.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query."
+                    },
+                    "count": {
+                        "type": "integer",
+                        "description": format!("The number of results, at most {}.", self.max_results),
+                        "minimum": 1
+                    }
+                },
+                "required": ["query"]
+            }),
+        )
+    }
+
+    fn call<'a>(&'a self, client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        Box::pin(async move {
+            let query = arguments
+                .get("query")
+                .and_then(Value::as_str)
+                .filter(|query| !query.trim().is_empty())
+                .ok_or_else(|| CopilotError::Other("Missing `query` argument".to_string()))?;
+            let count = arguments
+                .get("count")
+                .and_then(Value::as_u64)
+                .map_or(self.max_results, |count| count as usize)
+                .clamp(1, self.max_results.max(1));
+            let mut results = self.provider.search(client, query, count).await?;
+            results.truncate(count);
+            Ok(format_results(&results))
+        })
+    }
+}
+
+/// Formats results as a numbered Markdown list.
+fn format_results(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return "No results.".to_string();
+    }
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let mut item = format!("{}. [{}]({})", i + 1, result.title, result.url);
+            if !result.snippet.is_empty() {
+                item.push_str("\n   ");
+                item.push_str(&result.snippet);
+            }
+            item
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(feature = "brave-search")]
+mod brave {
+    use super::{SearchProvider, SearchResult};
+    use crate::{
+        CopilotClient, CopilotError,
+        api::ApiFuture,
+        tools::fetch::html_to_markdown,
+        transport::{HttpRequest, Method},
+    };
+    use http::header::{ACCEPT, HeaderMap, HeaderValue};
+    use serde::Deserialize;
+    use std::fmt;
+
+    /// The endpoint of the Brave web search API.
+    const BRAVE_SEARCH_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+
+    /// The most results the API returns for one request.
+    const MAX_COUNT: usize = 20;
+
+    /// A [`SearchProvider`] on the Brave Search API.
+    #[derive(Clone)]
+    pub struct BraveSearch {
+        api_key: String,
+        url: String,
+    }
+
+    impl fmt::Debug for BraveSearch {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("BraveSearch")
+                .field("url", &self.url)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl BraveSearch {
+        /// Creates a provider that authenticates with the subscription token `api_key`.
+        pub fn new(api_key: impl Into<String>) -> Self {
+            BraveSearch {
+                api_key: api_key.into(),
+                url: BRAVE_SEARCH_URL.to_string(),
+            }
+        }
+
+        /// Sends searches to `url` instead of the Brave endpoint, such as a proxy or a mock
+        /// server.
+        pub fn with_url(mut self, url: impl Into<String>) -> Self {
+            self.url = url.into();
+            self
+        }
+
+        async fn run(
+            &self,
+            client: &CopilotClient,
+            query: &str,
+            count: usize,
+        ) -> Result<Vec<SearchResult>, CopilotError> {
+            let params = serde_urlencoded::to_string([
+                ("q", query),
+                ("count", &count.clamp(1, MAX_COUNT).to_string()),
+            ])
+            .map_err(|e| CopilotError::Other(e.to_string()))?;
+            let mut token = HeaderValue::from_str(&self.api_key)
+                .map_err(|e| CopilotError::Other(format!("Invalid Brave API key: {e}")))?;
+            token.set_sensitive(true);
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+            headers.insert("X-Subscription-Token", token);
+            let request =
+                HttpRequest::new(Method::GET, format!("{}?{params}", self.url)).headers(headers);
+            let response: BraveResponse = client.send_request(request).await?.json().await?;
+            Ok(response
+                .web
+                .map(|web| web.results)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|result| SearchResult {
+                    title: result.title,
+                    url: result.url,
+                    snippet: html_to_markdown(&result.description),
+                })
+                .collect())
+        }
+    }
+
+    impl SearchProvider for BraveSearch {
+        fn search<'a>(
+            &'a self,
+            client: &'a CopilotClient,
+            query: &'a str,
+            count: usize,
+        ) -> ApiFuture<'a, Vec<SearchResult>> {
+            Box::pin(self.run(client, query, count))
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct BraveResponse {
+        web: Option<BraveWeb>,
+    }
+
+    #[derive(Deserialize)]
+    struct BraveWeb {
+        #[serde(default)]
+        results: Vec<BraveResult>,
+    }
+
+    #[derive(Deserialize)]
+    struct BraveResult {
+        title: String,
+        url: String,
+        #[serde(default)]
+        description: String,
+    }
+}
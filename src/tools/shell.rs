@@ -0,0 +1,309 @@
+//! A tool that runs commands.
+//!
+//! [`ShellTool`] lets the model run commands in a workspace, so that it can build the code, run
+//! the tests, and see why they fail. Execution stays under control:
+//!
+//! - Only programs on an allowlist run. The allowlist is empty by default, so nothing runs
+//!   until programs are allowed.
+//! - Commands are not passed to a shell. They are split into a program and its arguments, with
+//!   `'…'` and `"…"` quoting, so pipes, redirections, `;`, `&&`, and substitutions have no
+//!   effect and cannot chain a program that is not allowed.
+//! - Commands run in the workspace root or a directory under it.
+//! - Commands that run longer than a timeout are killed.
+//! - The output is truncated to a token budget, keeping its start and its end, where test
+//!   failures and error summaries usually are.
+//!
+//! ```ignore
+//! use copilot_client::tools::{Toolbox, shell::ShellTool};
+//! use std::time::Duration;
+//!
+//! let toolbox = Toolbox::new().with_tool(
+//!     ShellTool::new(".")
+//!         .with_allowed_program("cargo")
+//!         .with_allowed_program("git")
+//!         .with_timeout(Duration::from_secs(300)),
+//! );
+//! let run = client
+//!     .run_tools(vec![Message::user("Run the tests and fix the failure.")], &toolbox, "gpt-4o".into(), &ChatOptions::default())
+//!     .await?;
+//! ```
+//!
+//! The tool spawns processes and times them with tokio, so it needs a runtime with the I/O and
+//! time drivers enabled. An allowed program can still do anything its arguments tell it to,
+//! such as `cargo run` or `git push`, so allow only programs whose every use is acceptable.
+
+use super::ToolHandler;
+use crate::{
+    CopilotClient, CopilotError, Tool,
+    api::ApiFuture,
+    prompt::{estimate_tokens, truncate_to_tokens},
+};
+use serde_json::{Value, json};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+use tokio::process::Command;
+
+/// The default time after which a command is killed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The default maximum size of the output returned to the model, in tokens.
+pub const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 4_000;
+
+/// Runs allowed programs in a workspace for the model, as a [`ToolHandler`] named
+/// `run_command`.
+#[derive(Debug, Clone)]
+pub struct ShellTool {
+    root: PathBuf,
+    allowed: BTreeSet<String>,
+    timeout: Duration,
+    max_output_tokens: u32,
+}
+
+/// The result of a command run by a [`ShellTool`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// The exit code, or `None` if the process was killed by a signal or the timeout.
+    pub exit_code: Option<i32>,
+    /// Whether the command was killed because it ran longer than the timeout.
+    pub timed_out: bool,
+    /// The standard output, decoded lossily.
+    pub stdout: String,
+    /// The standard error, decoded lossily.
+    pub stderr: String,
+}
+
+impl ShellTool {
+    /// Creates a tool that runs commands in `root` and the directories under it. No program is
+    /// allowed until [`ShellTool::with_allowed_program`] allows it.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ShellTool {
+            root: root.into(),
+            allowed: BTreeSet::new(),
+            timeout: DEFAULT_TIMEOUT,
+            max_output_tokens: DEFAULT_MAX_OUTPUT_TOKENS,
+        }
+    }
+
+    /// Allows running `program`, such as `"cargo"`. Commands must name the program exactly as
+    /// allowed, so `/tmp/cargo` does not match `cargo`.
+    pub fn with_allowed_program(mut self, program: impl Into<String>) -> Self {
+        self.allowed.insert(program.into());
+        self
+    }
+
+    /// Kills commands that run longer than `timeout`. Defaults to [`DEFAULT_TIMEOUT`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Truncates the output returned to the model to about `tokens` tokens. Defaults to
+    /// [`DEFAULT_MAX_OUTPUT_TOKENS`].
+    pub fn with_max_output_tokens(mut self, tokens: u32) -> Self {
+        self.max_output_tokens = tokens;
+        self
+    }
+
+    /// Runs `command` in `dir`, a directory relative to the root, or in the root itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the command cannot be parsed, its program is not allowed,
+    /// `dir` is outside the root, or the process cannot be started.
+    pub async fn run(
+        &self,
+        command: &str,
+        dir: Option<&str>,
+    ) -> Result<CommandOutput, CopilotError> {
+        let args = split_command(command)?;
+        let Some((program, args)) = args.split_first() else {
+            return Err(CopilotError::Other("Empty command".to_string()));
+        };
+        if !self.allowed.contains(program) {
+            return Err(CopilotError::Other(format!(
+                "`{program}` is not an allowed program. Allowed programs: {}",
+                self.allowed.iter().cloned().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        let dir = self.resolve_dir(dir.unwrap_or("."))?;
+        tracing::debug!(command, dir = %dir.display(), "Running command");
+        let child = Command::new(program)
+            .args(args)
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| CopilotError::Other(format!("Failed to run `{program}`: {e}")))?;
+        match tokio::time::timeout(self.timeout, child.wait_with_output()).await {
+            Ok(output) => {
+                let output = output
+                    .map_err(|e| CopilotError::Other(format!("Failed to run `{program}`: {e}")))?;
+                Ok(CommandOutput {
+                    exit_code: output.status.code(),
+                    timed_out: false,
+                    stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                })
+            }
+            // Dropping the future kills the process.
+            Err(_) => Ok(CommandOutput {
+                exit_code: None,
+                timed_out: true,
+                stdout: String::new(),
+                stderr: String::new(),
+            }),
+        }
+    }
+
+    /// Returns `dir` resolved against the root, if it is the root or under it.
+    fn resolve_dir(&self, dir: &str) -> Result<PathBuf, CopilotError> {
+        let root = canonicalize(&self.root)?;
+        let resolved = canonicalize(&root.join(dir))?;
+        if !resolved.starts_with(&root) {
+            return Err(CopilotError::Other(format!(
+                "{dir} is outside the workspace"
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// Formats `output` for the model within the token budget.
+    fn format(&self, output: &CommandOutput) -> String {
+        let status = match (output.timed_out, output.exit_code) {
+            (true, _) => format!("Killed after {:?}", self.timeout),
+            (false, Some(code)) => format!("Exit code: {code}"),
+            (false, None) => "Killed by a signal".to_string(),
+        };
+        let streams: Vec<(&str, &str)> = [("stdout", &output.stdout), ("stderr", &output.stderr)]
+            .into_iter()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(name, text)| (name, text.as_str()))
+            .collect();
+        let mut sections = vec![status];
+        let budget = self.max_output_tokens / streams.len().max(1) as u32;
+        for (name, text) in streams {
+            sections.push(format!("{name}:\n{}", keep_ends(text.trim_end(), budget)));
+        }
+        sections.join("\n\n")
+    }
+}
+
+impl ToolHandler for ShellTool {
+    fn definition(&self) -> Tool {
+        let allowed = self.allowed.iter().cloned().collect::<Vec<_>>().join(", ");
+        Tool::function(
+            "run_command",
+            format!(
+                "Runs a command in the workspace and returns its exit code and output. The \
+                 command is not run by a shell: pipes, redirections, and chaining are not \
+                 supported. Allowed programs: {allowed}."
+            ),
+            json!({
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The program and its arguments, such as `cargo test --lib`."
+                    },
+                    "cwd": {
+                        "type": "string",
+                        "description": "The directory to run in, relative to the workspace root. Defaults to the root."
+                    }
+                },
+                "required": ["command"]
+            }),
+        )
+    }
+
+    fn call<'a>(&'a self, _client: &'a CopilotClient, arguments: Value) -> ApiFuture<'a, String> {
+        Box::pin(async move {
+            let command = arguments
+                .get("command")
+                .and_then(Value::as_str)
+                .ok_or_else(|| CopilotError::Other("Missing `command` argument".to_string()))?;
+            let dir = arguments.get("cwd").and_then(Value::as_str);
+            let output = self.run(command, dir).await?;
+            Ok(self.format(&output))
+        })
+    }
+}
+
+/// Splits `command` into words at whitespace. Single quotes keep their content as it is, and
+/// double quotes and backslashes escape whitespace and quotes.
+fn split_command(command: &str) -> Result<Vec<String>, CopilotError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(unclosed_quote()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(unclosed_quote()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(unclosed_quote()),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+                word.push(chars.next().unwrap_or('\\'));
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn unclosed_quote() -> CopilotError {
+    CopilotError::Other("Unclosed quote in command".to_string())
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, CopilotError> {
+    path.canonicalize()
+        .map_err(|e| CopilotError::Other(format!("Invalid directory {}: {e}", path.display())))
+}
+
+/// Truncates `text` to about `budget` tokens, keeping a third of it from the start and the rest
+/// from the end.
+fn keep_ends(text: &str, budget: u32) -> String {
+    let tokens = estimate_tokens(text);
+    if tokens <= budget {
+        return text.to_string();
+    }
+    let head = truncate_to_tokens(text, budget / 3);
+    let reversed: String = text.chars().rev().collect();
+    let tail: String = truncate_to_tokens(&reversed, budget - budget / 3)
+        .chars()
+        .rev()
+        .collect();
+    let dropped = tokens - estimate_tokens(head) - estimate_tokens(&tail);
+    format!("{head}\n[... {dropped} tokens of output truncated ...]\n{tail}")
+}
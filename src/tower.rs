@@ -0,0 +1,131 @@
+//! Tower integration, enabled with the `tower` feature.
+//!
+//! [`CopilotClient::with_layer`] wraps the client's transport in any `tower::Layer`, so retry,
+//! rate-limit, timeout, and telemetry middleware from the tower ecosystem apply to every request
+//! the client sends:
+//!
+//! ```ignore
+//! use copilot_client::CopilotClient;
+//! use tower::limit::ConcurrencyLimitLayer;
+//!
+//! let client = CopilotClient::new_with_models(github_token, editor_version)
+//!     .await?
+//!     .with_layer(ConcurrencyLimitLayer::new(4));
+//! ```
+//!
+//! The building blocks are also available on their own: [`TransportService`] exposes a
+//! transport as a `tower::Service`, and [`ServiceTransport`] turns a service back into an
+//! [`HttpTransport`].
+
+use crate::{
+    CopilotClient, CopilotError,
+    transport::{HttpRequest, HttpResponse, HttpTransport, TransportFuture},
+};
+use ::tower::{BoxError, Layer, Service};
+use futures_util::future;
+use std::{
+    fmt,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+impl CopilotClient {
+    /// Sends all further requests through `layer` wrapped around the current transport.
+    ///
+    /// Errors produced by the middleware itself, such as timeouts, are reported as
+    /// `CopilotError::HttpError`.
+    pub fn with_layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<TransportService>,
+        L::Service: Service<HttpRequest, Response = HttpResponse> + Clone + Send + Sync + 'static,
+        <L::Service as Service<HttpRequest>>::Error: Into<BoxError>,
+        <L::Service as Service<HttpRequest>>::Future: Send,
+    {
+        let service = layer.layer(TransportService {
+            transport: self.transport.clone(),
+        });
+        self.transport = Arc::new(ServiceTransport::new(service));
+        self
+    }
+}
+
+/// An [`HttpTransport`] as a `tower::Service`. It is always ready.
+#[derive(Clone)]
+pub struct TransportService {
+    transport: Arc<dyn HttpTransport>,
+}
+
+impl TransportService {
+    /// Creates a service that sends requests through `transport`.
+    pub fn new(transport: impl HttpTransport + 'static) -> Self {
+        TransportService {
+            transport: Arc::new(transport),
+        }
+    }
+}
+
+impl fmt::Debug for TransportService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransportService").finish_non_exhaustive()
+    }
+}
+
+impl Service<HttpRequest> for TransportService {
+    type Response = HttpResponse;
+    type Error = CopilotError;
+    type Future = TransportFuture<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: HttpRequest) -> Self::Future {
+        let transport = self.transport.clone();
+        Box::pin(async move { transport.send(request).await })
+    }
+}
+
+/// A `tower::Service` as an [`HttpTransport`]. Each request is sent on a clone of the service.
+#[derive(Clone)]
+pub struct ServiceTransport<S> {
+    service: S,
+}
+
+impl<S> ServiceTransport<S> {
+    /// Creates a transport that sends requests through `service`.
+    pub fn new(service: S) -> Self {
+        ServiceTransport { service }
+    }
+}
+
+impl<S> fmt::Debug for ServiceTransport<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceTransport").finish_non_exhaustive()
+    }
+}
+
+impl<S> HttpTransport for ServiceTransport<S>
+where
+    S: Service<HttpRequest, Response = HttpResponse> + Clone + Send + Sync,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        let mut service = self.service.clone();
+        Box::pin(async move {
+            future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(into_copilot_error)?;
+            service.call(request).await.map_err(into_copilot_error)
+        })
+    }
+}
+
+/// Keeps errors from the inner transport as they are and reports middleware errors as HTTP
+/// errors.
+fn into_copilot_error(error: impl Into<BoxError>) -> CopilotError {
+    match error.into().downcast::<CopilotError>() {
+        Ok(error) => *error,
+        Err(error) => CopilotError::HttpError(error.to_string()),
+    }
+}
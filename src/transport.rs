@@ -0,0 +1,361 @@
+//! The HTTP layer behind [`CopilotClient`].
+//!
+//! Every request the client makes goes through an [`HttpTransport`]. The default,
+//! `ReqwestTransport`, sends requests with `reqwest` and needs the `reqwest` feature, which is
+//! on by default; supply another implementation with [`CopilotClient::with_transport`] or
+//! [`CopilotClient::with_models_and_transport`] to use hyper, ureq, a runtime other than tokio,
+//! or canned responses in tests. Requests and responses use the types of the [`http`] crate,
+//! so without the `reqwest` feature neither reqwest nor tokio is compiled:
+//!
+//! ```
+//! use copilot_client::transport::{
+//!     HttpRequest, HttpResponse, HttpTransport, StatusCode, TransportFuture,
+//! };
+//!
+//! struct Canned(&'static str);
+//!
+//! impl HttpTransport for Canned {
+//!     fn send(&self, _request: HttpRequest) -> TransportFuture<'_> {
+//!         let response = HttpResponse::from_bytes(StatusCode::OK, Default::default(), self.0);
+//!         Box::pin(async move { Ok(response) })
+//!     }
+//! }
+//! ```
+//!
+//! [`CopilotClient`]: crate::CopilotClient
+//! [`CopilotClient::with_transport`]: crate::CopilotClient::with_transport
+//! [`CopilotClient::with_models_and_transport`]: crate::CopilotClient::with_models_and_transport
+
+use crate::CopilotError;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, stream};
+use http::header::CONTENT_TYPE;
+use serde::{Serialize, de::DeserializeOwned};
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+use std::time::Duration;
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+pub use http::{self, HeaderMap, HeaderValue, Method, StatusCode};
+
+/// A boxed stream that is `Send` wherever the HTTP client allows it. Response bodies in the
+/// browser are backed by JavaScript objects, which cannot leave their thread.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type BoxStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+#[cfg(target_arch = "wasm32")]
+pub(crate) type BoxStream<T> = Pin<Box<dyn Stream<Item = T>>>;
+
+/// The future returned by [`HttpTransport::send`]. It is `Send` except on `wasm32`.
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HttpResponse, CopilotError>> + Send + 'a>>;
+/// The future returned by [`HttpTransport::send`]. It is `Send` except on `wasm32`.
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HttpResponse, CopilotError>> + 'a>>;
+
+/// Sends HTTP requests for a [`CopilotClient`](crate::CopilotClient).
+pub trait HttpTransport: Send + Sync {
+    /// Sends `request` and returns the response once its headers have arrived.
+    ///
+    /// Error statuses are returned as responses; only failures to get a response at all should
+    /// be reported as errors, preferably as `CopilotError::HttpError`.
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_>;
+}
+
+impl<T: HttpTransport + ?Sized> HttpTransport for Arc<T> {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        (**self).send(request)
+    }
+}
+
+/// An HTTP request.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    /// The request body, or `None` for requests without one.
+    pub body: Option<Bytes>,
+    /// Whether redirects are followed. If `false`, transports that follow redirects should
+    /// return redirect responses as they are, or fail if they cannot.
+    pub follow_redirects: bool,
+}
+
+impl HttpRequest {
+    /// Creates a request without headers or a body.
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        HttpRequest {
+            method,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            body: None,
+            follow_redirects: true,
+        }
+    }
+
+    /// Sets whether redirects are followed. They are by default.
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    /// Adds `headers`, replacing existing values of the same names.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        if self.headers.is_empty() {
+            self.headers = headers;
+        } else {
+            self.headers.extend(headers);
+        }
+        self
+    }
+
+    /// Sets a JSON body.
+    pub(crate) fn json(mut self, body: &impl Serialize) -> Result<Self, CopilotError> {
+        let body = serde_json::to_vec(body).map_err(|e| CopilotError::Other(e.to_string()))?;
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.body = Some(body.into());
+        Ok(self)
+    }
+
+    /// Sets a URL-encoded form body.
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn form(mut self, body: &impl Serialize) -> Result<Self, CopilotError> {
+        let body =
+            serde_urlencoded::to_string(body).map_err(|e| CopilotError::Other(e.to_string()))?;
+        self.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        self.body = Some(body.into());
+        Ok(self)
+    }
+}
+
+/// An HTTP response whose body is read as it arrives.
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    body: BoxStream<Result<Bytes, CopilotError>>,
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpResponse {
+    /// Creates a response whose body is read from `body`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: impl Stream<Item = Result<Bytes, CopilotError>> + Send + 'static,
+    ) -> Self {
+        HttpResponse {
+            status,
+            headers,
+            body: Box::pin(body),
+        }
+    }
+
+    /// Creates a response whose body is read from `body`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(
+        status: StatusCode,
+        headers: HeaderMap,
+        body: impl Stream<Item = Result<Bytes, CopilotError>> + 'static,
+    ) -> Self {
+        HttpResponse {
+            status,
+            headers,
+            body: Box::pin(body),
+        }
+    }
+
+    /// Creates a response with a body that is already complete.
+    pub fn from_bytes(status: StatusCode, headers: HeaderMap, body: impl Into<Bytes>) -> Self {
+        Self::new(status, headers, stream::iter([Ok(body.into())]))
+    }
+
+    /// Returns the body as a stream of chunks.
+    pub fn into_body(self) -> BoxStream<Result<Bytes, CopilotError>> {
+        self.body
+    }
+
+    /// Reads the whole body.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error reported by the body stream.
+    pub async fn bytes(self) -> Result<Vec<u8>, CopilotError> {
+        let mut body = Vec::new();
+        let mut chunks = self.body;
+        while let Some(chunk) = chunks.next().await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(body)
+    }
+
+    /// Reads the body up to `limit` bytes and returns them, with `true` if the body was
+    /// longer. The rest of the body is not downloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error reported by the body stream.
+    pub async fn bytes_up_to(self, limit: usize) -> Result<(Vec<u8>, bool), CopilotError> {
+        let mut body = Vec::new();
+        let mut chunks = self.body;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if body.len() + chunk.len() > limit {
+                body.extend_from_slice(&chunk[..limit - body.len()]);
+                return Ok((body, true));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok((body, false))
+    }
+
+    /// Reads the whole body and decodes it as JSON.
+    pub(crate) async fn json<T: DeserializeOwned>(self) -> Result<T, CopilotError> {
+        let body = self.bytes().await?;
+        serde_json::from_slice(&body).map_err(|e| CopilotError::Other(e.to_string()))
+    }
+}
+
+/// The default transport, backed by a `reqwest::Client`. Needs the `reqwest` feature.
+///
+/// The client keeps a pool of connections, so the token exchange and the API calls of a
+/// [`CopilotClient`](crate::CopilotClient) reuse warm connections instead of paying for a TCP
+/// and TLS handshake each time. Connections to the Copilot API use HTTP/2 where the server
+/// offers it, multiplexing concurrent requests over one connection.
+///
+/// Requests whose [`HttpRequest::follow_redirects`] is `false` are sent with a second client
+/// that does not follow redirects. In the browser, which always follows them, such requests
+/// fail when they are redirected.
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+    /// The client for requests that must not follow redirects, if there is one.
+    no_redirects: Option<reqwest::Client>,
+}
+
+#[cfg(feature = "reqwest")]
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::from_builder(Self::client_builder)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    /// Creates a transport that sends requests with `client`, for example one configured with a
+    /// proxy or timeouts.
+    ///
+    /// The redirect policy of `client` applies to every request. Requests that must not follow
+    /// redirects, such as those of [`FetchTool`](crate::tools::fetch::FetchTool), fail when they
+    /// are redirected, unless `client` is built with `redirect::Policy::none()`.
+    pub fn new(client: reqwest::Client) -> Self {
+        ReqwestTransport {
+            client,
+            no_redirects: None,
+        }
+    }
+
+    /// Builds a transport from the builders returned by `builder`, the second one without
+    /// following redirects.
+    fn from_builder(builder: impl Fn() -> reqwest::ClientBuilder) -> Self {
+        let build = |builder: reqwest::ClientBuilder| {
+            builder.build().unwrap_or_else(|_| reqwest::Client::new())
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let no_redirects = Some(build(builder().redirect(reqwest::redirect::Policy::none())));
+        #[cfg(target_arch = "wasm32")]
+        let no_redirects = None;
+        ReqwestTransport {
+            client: build(builder()),
+            no_redirects,
+        }
+    }
+
+    /// Returns a `reqwest::ClientBuilder` with the connection settings of the default
+    /// transport, to add a proxy or timeouts to before passing the client to
+    /// [`ReqwestTransport::new`].
+    ///
+    /// Small chat requests are dominated by connection setup, so the settings keep connections
+    /// open and responsive: `TCP_NODELAY`, TCP keepalive, HTTP/2 pings while idle, and a longer
+    /// idle timeout for pooled connections. Responses are requested with gzip or brotli
+    /// compression, which shrinks model lists and embeddings several times over, and
+    /// decompressed transparently.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn client_builder() -> reqwest::ClientBuilder {
+        reqwest::Client::builder()
+            .gzip(true)
+            .brotli(true)
+            .tcp_nodelay(true)
+            .tcp_keepalive(Duration::from_secs(60))
+            .pool_idle_timeout(Duration::from_secs(300))
+            .http2_adaptive_window(true)
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .http2_keep_alive_timeout(Duration::from_secs(10))
+            .http2_keep_alive_while_idle(true)
+    }
+
+    /// Returns a `reqwest::ClientBuilder` for the default transport. The browser manages
+    /// connections and compression itself, so there is nothing to tune.
+    #[cfg(target_arch = "wasm32")]
+    pub fn client_builder() -> reqwest::ClientBuilder {
+        reqwest::Client::builder()
+    }
+
+    /// Creates a default transport that asks for compressed responses only if `enabled`.
+    /// Compression costs CPU time to save bandwidth, which may not pay off on a fast local
+    /// link or behind a proxy that compresses itself.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_compression(enabled: bool) -> Self {
+        Self::from_builder(|| Self::client_builder().gzip(enabled).brotli(enabled))
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let client = match &self.no_redirects {
+                Some(no_redirects) if !request.follow_redirects => no_redirects,
+                _ => &self.client,
+            };
+            let mut builder = client
+                .request(request.method, &request.url)
+                .headers(request.headers);
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+            let response = builder
+                .send()
+                .await
+                .map_err(|e| CopilotError::HttpError(e.to_string()))?;
+            if !request.follow_redirects
+                && reqwest::Url::parse(&request.url).ok().as_ref() != Some(response.url())
+            {
+                return Err(CopilotError::HttpError(format!(
+                    "{} redirected to {}, but redirects were not to be followed",
+                    request.url,
+                    response.url()
+                )));
+            }
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(|e| CopilotError::HttpError(e.to_string())));
+            Ok(HttpResponse::new(status, headers, body))
+        })
+    }
+}
@@ -0,0 +1,561 @@
+//! Cumulative usage tracking.
+//!
+//! A [`UsageTracker`] accumulates per-model request counts, token usage, and premium-request
+//! units. Attach one to a client with [`CopilotClient::with_usage_tracker`] and query it at any
+//! time, for example to stop a batch job before it exceeds a spending ceiling.
+//!
+//! With [`UsageTracker::with_history_file`], the tracker also appends a [`UsageRecord`] per
+//! request to a JSON Lines file, which accumulates across runs for analysis with
+//! [`read_history`] or tools such as `jq`:
+//!
+//! ```no_run
+//! use copilot_client::usage::{UsageTracker, read_history};
+//! use std::sync::Arc;
+//!
+//! let tracker = Arc::new(UsageTracker::new().with_history_file("usage.jsonl"));
+//! // ... attach it with `CopilotClient::with_usage_tracker` and make requests ...
+//! let history = read_history("usage.jsonl")?;
+//! let slow = history.iter().filter(|r| r.latency_ms > Some(10_000)).count();
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! A long-running service can also be warned before its premium requests run out: set the
+//! premium requests left, for example from [`copilot_user`], and register a callback for when
+//! fewer than a given number remain:
+//!
+//! ```
+//! use copilot_client::usage::UsageTracker;
+//!
+//! let tracker = UsageTracker::new().on_premium_remaining_below(30.0, |alert| {
+//!     eprintln!("only {} of {} premium requests left", alert.remaining, alert.allowance);
+//! });
+//! tracker.set_premium_allowance(300.0);
+//! ```
+//!
+//! [`CopilotClient::with_usage_tracker`]: crate::CopilotClient::with_usage_tracker
+//! [`copilot_user`]: crate::auth::copilot_user
+
+use crate::{
+    Model, TokenUsage,
+    pricing::{Cost, PriceTable},
+    unix_time,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Usage accumulated for a single model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    /// Number of successful requests.
+    pub requests: u64,
+    /// Prompt tokens reported by the API.
+    pub prompt_tokens: u64,
+    /// Prompt tokens that were read from the prompt cache.
+    #[serde(default)]
+    pub cached_tokens: u64,
+    /// Completion tokens reported by the API.
+    pub completion_tokens: u64,
+    /// Total tokens reported by the API.
+    pub total_tokens: u64,
+    /// Premium-request units consumed.
+    pub premium_requests: f64,
+}
+
+impl ModelUsage {
+    fn add(&mut self, other: &ModelUsage) {
+        self.requests += other.requests;
+        self.prompt_tokens += other.prompt_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.premium_requests += other.premium_requests;
+    }
+}
+
+/// A request recorded in the history file of a [`UsageTracker`], one JSON object per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// The Unix timestamp, in seconds, at which the request was recorded.
+    pub timestamp: u64,
+    /// The model the request was sent to.
+    pub model: String,
+    /// Prompt tokens reported by the API.
+    pub prompt_tokens: u64,
+    /// Prompt tokens that were read from the prompt cache.
+    #[serde(default)]
+    pub cached_tokens: u64,
+    /// Completion tokens reported by the API.
+    pub completion_tokens: u64,
+    /// Total tokens reported by the API.
+    pub total_tokens: u64,
+    /// Premium-request units consumed.
+    pub premium_requests: f64,
+    /// The time from sending the request until the response was read, in milliseconds, if
+    /// the request was made by the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// The [tags](crate::ChatOptions::tags) of the request.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Reads the records of a history file written by a [`UsageTracker`], oldest first. Lines that
+/// are not valid records, such as a line cut short by a crash, are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read.
+pub fn read_history(path: impl AsRef<Path>) -> io::Result<Vec<UsageRecord>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Premium requests running low, passed to the callbacks of
+/// [`UsageTracker::on_premium_remaining_below`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PremiumAlert {
+    /// The premium requests set with [`UsageTracker::set_premium_allowance`].
+    pub allowance: f64,
+    /// The premium requests recorded since the allowance was set.
+    pub used: f64,
+    /// The premium requests left.
+    pub remaining: f64,
+    /// The number of remaining premium requests that was passed.
+    pub threshold: f64,
+}
+
+/// A callback of [`UsageTracker::on_premium_remaining_below`].
+struct PremiumHook {
+    threshold: f64,
+    callback: Arc<dyn Fn(&PremiumAlert) + Send + Sync>,
+}
+
+impl fmt::Debug for PremiumHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PremiumHook")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Thread-safe accumulator of API usage, keyed by model id.
+///
+/// Chat requests consume premium-request units according to the model's multiplier (see
+/// [`UsageTracker::set_premium_multiplier`]); embeddings requests never do. Usage of requests
+/// with [tags](crate::ChatOptions::tags) is also summed per tag, for attributing usage to
+/// features or users with [`UsageTracker::tag_usage`].
+#[derive(Debug)]
+pub struct UsageTracker {
+    inner: Mutex<TrackerState>,
+    /// The file each request is appended to, if any.
+    history: Option<PathBuf>,
+    premium_alerts: Vec<PremiumHook>,
+}
+
+#[derive(Debug)]
+struct TrackerState {
+    models: BTreeMap<String, ModelUsage>,
+    /// Usage by tag key and value, summed over all models.
+    tags: BTreeMap<String, BTreeMap<String, ModelUsage>>,
+    multipliers: HashMap<String, f64>,
+    default_multiplier: f64,
+    prices: Option<PriceTable>,
+    /// The premium requests available, and the total recorded when they were set.
+    premium_allowance: Option<(f64, f64)>,
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UsageTracker {
+    /// Creates an empty tracker where every chat request counts as one premium-request unit.
+    pub fn new() -> Self {
+        UsageTracker {
+            inner: Mutex::new(TrackerState {
+                models: BTreeMap::new(),
+                tags: BTreeMap::new(),
+                multipliers: HashMap::new(),
+                default_multiplier: 1.0,
+                prices: None,
+                premium_allowance: None,
+            }),
+            history: None,
+            premium_alerts: Vec::new(),
+        }
+    }
+
+    /// Appends a [`UsageRecord`] for every further request to the JSON Lines file at `path`,
+    /// creating it if needed. Records are appended to what earlier runs wrote. Write errors are
+    /// ignored: the history is informational and never fails a request.
+    pub fn with_history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history = Some(path.into());
+        self
+    }
+
+    /// Calls `alert` when a recorded request leaves fewer than `threshold` of the premium
+    /// requests set with [`UsageTracker::set_premium_allowance`]. Each callback is called once
+    /// per allowance. Callbacks run synchronously on the task making the request.
+    pub fn on_premium_remaining_below(
+        mut self,
+        threshold: f64,
+        alert: impl Fn(&PremiumAlert) + Send + Sync + 'static,
+    ) -> Self {
+        self.premium_alerts.push(PremiumHook {
+            threshold,
+            callback: Arc::new(alert),
+        });
+        self
+    }
+
+    /// Sets the premium requests available from now on, such as the
+    /// [remaining](crate::auth::QuotaSnapshot::remaining) premium quota of the account, for
+    /// [`UsageTracker::premium_remaining`] and the callbacks of
+    /// [`UsageTracker::on_premium_remaining_below`].
+    pub fn set_premium_allowance(&self, units: f64) {
+        let mut state = self.lock();
+        let used = state.premium_total();
+        state.premium_allowance = Some((units, used));
+    }
+
+    /// Returns the premium requests left of the allowance, if one is set. Negative once the
+    /// allowance is exceeded.
+    pub fn premium_remaining(&self) -> Option<f64> {
+        let state = self.lock();
+        let (allowance, baseline) = state.premium_allowance?;
+        Some(allowance - (state.premium_total() - baseline))
+    }
+
+    /// Returns the path of the history file, if any.
+    pub fn history_file(&self) -> Option<&Path> {
+        self.history.as_deref()
+    }
+
+    /// Sets the premium-request multiplier for `model`.
+    pub fn set_premium_multiplier(&self, model: impl Into<String>, multiplier: f64) {
+        self.lock().multipliers.insert(model.into(), multiplier);
+    }
+
+    /// Sets the premium-request multipliers of the `models` that report one in their
+    /// [billing](crate::Model::billing) information, such as those of
+    /// [`CopilotClient::models`](crate::CopilotClient::models).
+    pub fn set_model_multipliers(&self, models: &[Model]) {
+        let mut state = self.lock();
+        for model in models {
+            if let Some(multiplier) = model.premium_multiplier() {
+                state.multipliers.insert(model.id.clone(), multiplier);
+            }
+        }
+    }
+
+    /// Sets the multiplier used for models without an explicit one.
+    pub fn set_default_multiplier(&self, multiplier: f64) {
+        self.lock().default_multiplier = multiplier;
+    }
+
+    /// Prices usage with `table` in [`UsageTracker::cost`], and takes the premium-request
+    /// multipliers of models without an explicit one from it.
+    pub fn set_price_table(&self, table: PriceTable) {
+        self.lock().prices = Some(table);
+    }
+
+    /// Returns the price table set with [`UsageTracker::set_price_table`], if any.
+    pub fn price_table(&self) -> Option<PriceTable> {
+        self.lock().prices.clone()
+    }
+
+    /// Returns the premium-request multiplier applied to chat requests for `model`: the one
+    /// set for it, or else the one in the price table, or else the default multiplier.
+    pub fn premium_multiplier(&self, model: &str) -> f64 {
+        let state = self.lock();
+        state
+            .multipliers
+            .get(model)
+            .copied()
+            .or_else(|| state.prices.as_ref()?.premium_multiplier(model))
+            .unwrap_or(state.default_multiplier)
+    }
+
+    /// Records a successful chat completion request.
+    pub fn record_chat(&self, model: &str, usage: Option<&TokenUsage>) {
+        let premium = self.premium_multiplier(model);
+        self.record(model, usage, premium);
+    }
+
+    /// Records a successful chat completion request made with `tags`, counting it for the model
+    /// and for each tag.
+    pub fn record_chat_tagged(
+        &self,
+        model: &str,
+        usage: Option<&TokenUsage>,
+        tags: &BTreeMap<String, String>,
+    ) {
+        self.record_chat_timed(model, usage, tags, None);
+    }
+
+    /// Records a successful chat completion request that took `latency`.
+    pub(crate) fn record_chat_timed(
+        &self,
+        model: &str,
+        usage: Option<&TokenUsage>,
+        tags: &BTreeMap<String, String>,
+        latency: Option<Duration>,
+    ) {
+        let premium = self.premium_multiplier(model);
+        self.record_entry(model, usage, premium, tags, latency);
+    }
+
+    /// Records a successful embeddings request.
+    pub fn record_embeddings(&self, model: &str, usage: Option<&TokenUsage>) {
+        self.record_embeddings_timed(model, usage, None);
+    }
+
+    /// Records a successful embeddings request that took `latency`.
+    pub(crate) fn record_embeddings_timed(
+        &self,
+        model: &str,
+        usage: Option<&TokenUsage>,
+        latency: Option<Duration>,
+    ) {
+        self.record_entry(model, usage, 0.0, &BTreeMap::new(), latency);
+    }
+
+    /// Records a request with an explicit number of premium-request units.
+    pub fn record(&self, model: &str, usage: Option<&TokenUsage>, premium_requests: f64) {
+        self.record_tagged(model, usage, premium_requests, &BTreeMap::new());
+    }
+
+    /// Records a request made with `tags` with an explicit number of premium-request units.
+    pub fn record_tagged(
+        &self,
+        model: &str,
+        usage: Option<&TokenUsage>,
+        premium_requests: f64,
+        tags: &BTreeMap<String, String>,
+    ) {
+        self.record_entry(model, usage, premium_requests, tags, None);
+    }
+
+    fn record_entry(
+        &self,
+        model: &str,
+        usage: Option<&TokenUsage>,
+        premium_requests: f64,
+        tags: &BTreeMap<String, String>,
+        latency: Option<Duration>,
+    ) {
+        let mut entry = ModelUsage {
+            requests: 1,
+            premium_requests,
+            ..ModelUsage::default()
+        };
+        if let Some(usage) = usage {
+            entry.prompt_tokens = u64::from(usage.prompt_tokens);
+            entry.cached_tokens = u64::from(usage.cached_tokens());
+            entry.completion_tokens = u64::from(usage.completion_tokens);
+            entry.total_tokens = u64::from(usage.total_tokens);
+        }
+        let mut state = self.lock();
+        let before = state.premium_total();
+        state
+            .models
+            .entry(model.to_string())
+            .or_default()
+            .add(&entry);
+        for (key, value) in tags {
+            state
+                .tags
+                .entry(key.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_default()
+                .add(&entry);
+        }
+        let allowance = state.premium_allowance;
+        drop(state);
+        if let Some((allowance, baseline)) = allowance {
+            self.alert_premium(allowance, before - baseline, premium_requests);
+        }
+        if let Some(path) = &self.history {
+            let record = UsageRecord {
+                timestamp: unix_time().as_secs(),
+                model: model.to_string(),
+                prompt_tokens: entry.prompt_tokens,
+                cached_tokens: entry.cached_tokens,
+                completion_tokens: entry.completion_tokens,
+                total_tokens: entry.total_tokens,
+                premium_requests,
+                latency_ms: latency.map(|latency| latency.as_millis() as u64),
+                tags: tags.clone(),
+            };
+            let _ = append_record(path, &record);
+        }
+    }
+
+    /// Returns the usage recorded for `model`, if any.
+    pub fn model_usage(&self, model: &str) -> Option<ModelUsage> {
+        self.lock().models.get(model).cloned()
+    }
+
+    /// Returns the usage of every model, sorted by model id.
+    pub fn snapshot(&self) -> BTreeMap<String, ModelUsage> {
+        self.lock().models.clone()
+    }
+
+    /// Returns the usage of requests tagged with `key`, summed over all models, by tag value.
+    /// For example, `tag_usage("feature")` returns the usage of each feature.
+    pub fn tag_usage(&self, key: &str) -> BTreeMap<String, ModelUsage> {
+        self.lock().tags.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Returns the usage summed over all models.
+    pub fn totals(&self) -> ModelUsage {
+        let mut totals = ModelUsage::default();
+        for usage in self.lock().models.values() {
+            totals.add(usage);
+        }
+        totals
+    }
+
+    /// Returns the cost of the usage of every model, sorted by model id, priced with the price
+    /// table, or with [`PriceTable::builtin`] if none is set.
+    pub fn costs(&self) -> BTreeMap<String, Cost> {
+        let state = self.lock();
+        let builtin;
+        let prices = match &state.prices {
+            Some(prices) => prices,
+            None => {
+                builtin = PriceTable::builtin();
+                &builtin
+            }
+        };
+        state
+            .models
+            .iter()
+            .map(|(model, usage)| (model.clone(), prices.cost(model, usage)))
+            .collect()
+    }
+
+    /// Returns the cost of the usage summed over all models. See [`UsageTracker::costs`].
+    pub fn cost(&self) -> Cost {
+        let mut total = Cost::default();
+        for cost in self.costs().values() {
+            total.add(cost);
+        }
+        total
+    }
+
+    /// Serializes the per-model usage, the per-tag usage, and totals as pretty-printed JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        #[derive(Serialize)]
+        struct Export {
+            models: BTreeMap<String, ModelUsage>,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            tags: BTreeMap<String, BTreeMap<String, ModelUsage>>,
+            totals: ModelUsage,
+        }
+        let tags = self.lock().tags.clone();
+        serde_json::to_string_pretty(&Export {
+            models: self.snapshot(),
+            tags,
+            totals: self.totals(),
+        })
+    }
+
+    /// Creates a tracker holding the per-model and per-tag usage from JSON written by
+    /// [`UsageTracker::to_json`], so that usage can be accumulated across processes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `json` is not valid tracker output.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct Import {
+            models: BTreeMap<String, ModelUsage>,
+            #[serde(default)]
+            tags: BTreeMap<String, BTreeMap<String, ModelUsage>>,
+        }
+        let import: Import = serde_json::from_str(json)?;
+        let tracker = Self::new();
+        let mut state = tracker.lock();
+        state.models = import.models;
+        state.tags = import.tags;
+        drop(state);
+        Ok(tracker)
+    }
+
+    /// Clears all recorded usage. Multipliers, prices, and the premium allowance are kept, and
+    /// the whole allowance is available again.
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        state.models.clear();
+        state.tags.clear();
+        if let Some((_, baseline)) = &mut state.premium_allowance {
+            *baseline = 0.0;
+        }
+    }
+
+    /// Calls the callbacks whose threshold `premium_requests` more brought the remaining
+    /// premium requests below.
+    fn alert_premium(&self, allowance: f64, used: f64, premium_requests: f64) {
+        let before = allowance - used;
+        let remaining = before - premium_requests;
+        for alert in &self.premium_alerts {
+            if before >= alert.threshold && remaining < alert.threshold {
+                (alert.callback)(&PremiumAlert {
+                    allowance,
+                    used: used + premium_requests,
+                    remaining,
+                    threshold: alert.threshold,
+                });
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TrackerState> {
+        // A panic while holding the lock cannot leave the counters in an invalid state.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TrackerState {
+    /// Returns the premium requests recorded for all models.
+    fn premium_total(&self) -> f64 {
+        self.models
+            .values()
+            .map(|usage| usage.premium_requests)
+            .sum()
+    }
+}
+
+/// Appends `record` to the file at `path` as one line. The line is written with a single
+/// write to a file opened for appending, so that processes sharing the file do not interleave
+/// their records.
+fn append_record(path: &Path, record: &UsageRecord) -> io::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)
+}
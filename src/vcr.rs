@@ -0,0 +1,310 @@
+//! Recording and replaying of API traffic.
+//!
+//! A [`RecordingTransport`] sends requests through another transport and writes each exchange
+//! to a cassette file; a [`ReplayTransport`] answers requests from that file without touching
+//! the network. Recording once against the real API and replaying in CI makes tests of code
+//! built on the client deterministic:
+//!
+//! ```no_run
+//! use copilot_client::{
+//!     CopilotClient,
+//!     transport::ReqwestTransport,
+//!     vcr::{RecordingTransport, ReplayTransport},
+//! };
+//!
+//! # async fn run(github_token: String) -> Result<(), copilot_client::CopilotError> {
+//! let path = "tests/cassettes/chat.json";
+//! let client = if std::env::var_os("RECORD").is_some() {
+//!     let transport = RecordingTransport::new(ReqwestTransport::default(), path);
+//!     CopilotClient::new_with_transport(github_token, "Neovim/0.9.0".to_string(), transport)
+//!         .await?
+//! } else {
+//!     let transport = ReplayTransport::load(path)?;
+//!     CopilotClient::new_with_transport(github_token, "Neovim/0.9.0".to_string(), transport)
+//!         .await?
+//! };
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Request headers are not recorded, so the GitHub and Copilot tokens sent in them never reach
+//! the cassette. Tokens in response bodies, such as the one returned by the token endpoint, are
+//! replaced with a placeholder, and `Set-Cookie` headers are dropped along with the framing
+//! headers that no longer apply to the scrubbed body.
+
+use crate::{
+    CopilotError, REDACTED, SECRET_KEYS,
+    transport::{HeaderMap, HttpRequest, HttpResponse, HttpTransport, StatusCode, TransportFuture},
+};
+use http::header::{CONTENT_LENGTH, HeaderName, HeaderValue, SET_COOKIE, TRANSFER_ENCODING};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Version of the cassette file format.
+const CASSETTE_VERSION: u32 = 1;
+
+/// A recorded sequence of request/response exchanges, stored as JSON.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    /// The file format version.
+    pub version: u32,
+    /// The recorded exchanges, in the order they happened.
+    pub interactions: Vec<Interaction>,
+}
+
+/// One recorded exchange.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The request, without its headers.
+    pub request: RecordedRequest,
+    /// The response, with secrets scrubbed.
+    pub response: RecordedResponse,
+}
+
+/// A recorded request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// The HTTP method.
+    pub method: String,
+    /// The request URL.
+    pub url: String,
+    /// The request body, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+/// A recorded response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response headers.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The complete response body. Streamed responses are recorded in full.
+    pub body: String,
+}
+
+impl Cassette {
+    /// Reads a cassette from `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be read or is not a cassette.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| {
+            CopilotError::Other(format!("Failed to read cassette {}: {e}", path.display()))
+        })?;
+        serde_json::from_str(&content).map_err(json_error)
+    }
+
+    /// Writes the cassette to `path`, creating parent directories as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CopilotError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(json_error)?;
+        fs::write(path, json).map_err(io_error)
+    }
+}
+
+/// A transport that records every exchange of an inner transport to a cassette file.
+///
+/// The file is rewritten after each exchange, so it is complete even if the process does not
+/// shut down cleanly. Responses are read in full before they are returned, so streamed
+/// responses arrive in one piece.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl<T: HttpTransport> RecordingTransport<T> {
+    /// Creates a transport that sends requests through `inner` and records them to `path`,
+    /// replacing any cassette already there.
+    pub fn new(inner: T, path: impl Into<PathBuf>) -> Self {
+        RecordingTransport {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette {
+                version: CASSETTE_VERSION,
+                interactions: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl<T: HttpTransport> HttpTransport for RecordingTransport<T> {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let recorded_request = RecordedRequest::from(&request);
+            let response = self.inner.send(request).await?;
+            let status = response.status;
+            let headers = response.headers.clone();
+            let body = response.bytes().await?;
+            let interaction = Interaction {
+                request: recorded_request,
+                response: RecordedResponse {
+                    status: status.as_u16(),
+                    headers: record_headers(&headers),
+                    body: scrub_body(&body),
+                },
+            };
+            let cassette = {
+                let mut cassette = self.cassette.lock().unwrap_or_else(|e| e.into_inner());
+                cassette.interactions.push(interaction);
+                cassette.clone()
+            };
+            cassette.save(&self.path)?;
+            Ok(HttpResponse::from_bytes(status, headers, body))
+        })
+    }
+}
+
+/// A transport that answers requests from a cassette.
+///
+/// A request is answered with the first unused interaction with the same method, URL, and body.
+/// Once all matching interactions have been used, the last one is repeated. Requests without a
+/// matching interaction fail with `CopilotError::HttpError`.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    /// Creates a transport that replays `cassette`.
+    pub fn new(cassette: Cassette) -> Self {
+        ReplayTransport {
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+        }
+    }
+
+    /// Creates a transport that replays the cassette at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CopilotError` if the cassette cannot be loaded.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CopilotError> {
+        Cassette::load(path).map(Self::new)
+    }
+
+    fn find(&self, request: &RecordedRequest) -> Option<&RecordedResponse> {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let matching: Vec<usize> = (0..self.interactions.len())
+            .filter(|&i| self.interactions[i].request == *request)
+            .collect();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&i| !used[i])
+            .or_else(|| matching.last().copied())?;
+        used[index] = true;
+        Some(&self.interactions[index].response)
+    }
+}
+
+impl HttpTransport for ReplayTransport {
+    fn send(&self, request: HttpRequest) -> TransportFuture<'_> {
+        let request = RecordedRequest::from(&request);
+        let response = self
+            .find(&request)
+            .ok_or_else(|| {
+                CopilotError::HttpError(format!(
+                    "No recorded response for {} {}",
+                    request.method, request.url
+                ))
+            })
+            .and_then(replay_response);
+        Box::pin(async move { response })
+    }
+}
+
+impl From<&HttpRequest> for RecordedRequest {
+    fn from(request: &HttpRequest) -> Self {
+        RecordedRequest {
+            method: request.method.to_string(),
+            url: request.url.clone(),
+            body: request
+                .body
+                .as_ref()
+                .map(|body| String::from_utf8_lossy(body).into_owned()),
+        }
+    }
+}
+
+fn record_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| ![SET_COOKIE, CONTENT_LENGTH, TRANSFER_ENCODING].contains(name))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn replay_response(response: &RecordedResponse) -> Result<HttpResponse, CopilotError> {
+    let status = StatusCode::from_u16(response.status)
+        .map_err(|e| CopilotError::Other(format!("Invalid recorded status: {e}")))?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in &response.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    Ok(HttpResponse::from_bytes(
+        status,
+        headers,
+        response.body.clone(),
+    ))
+}
+
+/// Returns the body as text, with secret fields of JSON bodies replaced.
+fn scrub_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            scrub_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+fn scrub_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && value.is_string() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    scrub_value(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub_value),
+        _ => {}
+    }
+}
+
+fn io_error(e: io::Error) -> CopilotError {
+    CopilotError::Other(e.to_string())
+}
+
+fn json_error(e: serde_json::Error) -> CopilotError {
+    CopilotError::Other(e.to_string())
+}
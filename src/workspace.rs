@@ -0,0 +1,61 @@
+//! Paths inside a workspace, shared by the file tools and patches.
+
+use crate::CopilotError;
+use std::path::{Component, Path, PathBuf};
+
+/// Checks that `path` is relative and stays inside the workspace, and returns it without `.`
+/// components.
+pub(crate) fn check_relative(path: &str) -> Result<PathBuf, CopilotError> {
+    let mut relative = PathBuf::new();
+    for component in Path::new(path.trim()).components() {
+        match component {
+            Component::Normal(name) if name == ".git" => {
+                return Err(CopilotError::Other(format!(
+                    "{path} is inside the .git directory"
+                )));
+            }
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(CopilotError::Other(format!(
+                    "{path} must be relative to the workspace root, without `..`"
+                )));
+            }
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        return Err(CopilotError::Other("Empty path".to_string()));
+    }
+    Ok(relative)
+}
+
+/// Joins `relative` to `root`, checking that symbolic links, including dangling ones, do not
+/// lead out of the root.
+pub(crate) fn resolve(root: &Path, relative: &Path) -> Result<PathBuf, CopilotError> {
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| CopilotError::Other(format!("Invalid workspace {}: {e}", root.display())))?;
+    let full = root.join(relative);
+    let existing = full
+        .ancestors()
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .unwrap_or(root);
+    let canonical = existing.canonicalize().map_err(|e| {
+        CopilotError::Other(format!("Failed to resolve {}: {e}", existing.display()))
+    })?;
+    if !canonical.starts_with(&canonical_root) {
+        return Err(CopilotError::Other(format!(
+            "{} leads out of the workspace",
+            slash_path(relative)
+        )));
+    }
+    Ok(full)
+}
+
+/// Returns `path` with `/` separators.
+pub(crate) fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}